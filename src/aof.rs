@@ -0,0 +1,644 @@
+//! The append-only file, laid out like Redis 7 does in `appenddirname`:
+//!
+//! - a base file, the dataset as of the last rewrite: an RDB payload (`.base.rdb`) or the
+//!   commands rebuilding it (`.base.aof`),
+//! - incremental files, the write commands executed since then in RESP form (`.incr.aof`),
+//! - a manifest listing them in the order they are replayed.
+//!
+//! A rewrite switches appends to a new incremental file, writes a new base from a snapshot of
+//! the dataset taken at that point, then drops the files the new base replaces.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use thiserror::Error;
+
+use crate::{
+    config::AppendFsync,
+    error::RedisError,
+    rdb::{self, Rdb, RdbError},
+    resp::{self, ProtocolVersion, RespDataType},
+    types::RedisType,
+    utils,
+};
+
+/// Elements per command when a collection is written out as commands.
+const ITEMS_PER_COMMAND: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum AofError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Rdb(#[from] RdbError),
+    #[error("Bad RDB preamble in the append only file: {0}")]
+    Preamble(RdbError),
+    #[error("Bad file format reading the append only file at offset {0}: make a backup of your AOF file, then use ./redis-check-aof --fix <filename>")]
+    BadFormat(usize),
+    #[error("Unexpected end of file reading the append only file. You can: 1) Make a backup of your AOF file, then use ./redis-check-aof --fix <filename>. 2) Alternatively you can set the 'aof-load-truncated' configuration option to yes and restart the server.")]
+    Truncated,
+    #[error("Invalid command in the append only file: {0}")]
+    Command(RedisError),
+    #[error("Invalid AOF manifest file format: {0}")]
+    Manifest(String),
+    #[error("The AOF file {0} doesn't exist")]
+    Missing(String),
+}
+
+/// Content of an AOF file.
+#[derive(Debug, Default)]
+pub struct AofContent {
+    pub preamble: Option<Rdb>,
+    pub commands: Vec<RespDataType>,
+    /// Bytes up to the end of the last complete command.
+    pub valid_len: usize,
+    /// Whether the file ends in the middle of a command, e.g. after a crash during a write.
+    pub truncated: bool,
+}
+
+/// Splits an AOF file into its RDB preamble, if it starts with one, and its commands. A
+/// transaction without its `EXEC` counts as truncated: it is dropped from the content, like
+/// Redis reverting it.
+pub fn parse(data: &[u8]) -> Result<AofContent, AofError> {
+    let mut content = AofContent::default();
+    if data.starts_with(b"REDIS") {
+        let (rdb, len) = rdb::parse_prefix(data).map_err(AofError::Preamble)?;
+        content.preamble = Some(rdb);
+        content.valid_len = len;
+    }
+    // Commands and bytes before the open `MULTI`.
+    let mut multi = None;
+    while content.valid_len < data.len() {
+        match RespDataType::decode(&data[content.valid_len..]) {
+            Ok(Some((command @ RespDataType::Array(_), len))) => {
+                match command_name(&command).as_deref() {
+                    Some("MULTI") => multi = Some((content.commands.len(), content.valid_len)),
+                    Some("EXEC") => multi = None,
+                    _ => {}
+                }
+                content.commands.push(command);
+                content.valid_len += len;
+            }
+            Ok(None) => {
+                content.truncated = true;
+                break;
+            }
+            Ok(Some(_)) | Err(_) => return Err(AofError::BadFormat(content.valid_len)),
+        }
+    }
+    if let Some((commands, valid_len)) = multi {
+        content.commands.truncate(commands);
+        content.valid_len = valid_len;
+        content.truncated = true;
+    }
+    Ok(content)
+}
+
+fn command_name(command: &RespDataType) -> Option<String> {
+    match command {
+        RespDataType::Array(args) => args
+            .first()
+            .and_then(RespDataType::to_string)
+            .map(|name| name.to_ascii_uppercase()),
+        _ => None,
+    }
+}
+
+fn encode_command(argv: &[Bytes]) -> Vec<u8> {
+    RespDataType::Array(argv.iter().map(RespDataType::bulk_string).collect())
+        .encode(ProtocolVersion::Resp2)
+}
+
+/// Content of a new base file and the extension of its name: an RDB payload with
+/// `aof-use-rdb-preamble`, commands otherwise. Sets and hashes have no command rebuilding them
+/// yet, so a dataset holding any is written as RDB either way. Function libraries come first.
+pub fn base_content(
+    data: &HashMap<String, RedisType>,
+    functions: &[String],
+    now_millis: u128,
+    use_rdb_preamble: bool,
+) -> (Vec<u8>, &'static str) {
+    if !use_rdb_preamble {
+        if let Some(commands) = rewrite_commands(data, functions, now_millis) {
+            return (commands, "aof");
+        }
+    }
+    (rdb::encode(data, functions, now_millis), "rdb")
+}
+
+fn rewrite_commands(
+    data: &HashMap<String, RedisType>,
+    functions: &[String],
+    now_millis: u128,
+) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    for code in functions {
+        let argv = [
+            Bytes::from("FUNCTION"),
+            Bytes::from("LOAD"),
+            Bytes::from(code.clone()),
+        ];
+        out.extend(encode_command(&argv));
+    }
+    for (key, value) in data {
+        let key = Bytes::from(key.clone());
+        let mut batched = |command: &'static str, items: Vec<Bytes>, per_item: usize| {
+            for chunk in items.chunks(ITEMS_PER_COMMAND * per_item) {
+                let mut argv = vec![Bytes::from(command), key.clone()];
+                argv.extend_from_slice(chunk);
+                out.extend(encode_command(&argv));
+            }
+        };
+        match value {
+            RedisType::None => {}
+            RedisType::String(kv) => {
+                let mut argv = vec![Bytes::from("SET"), key.clone(), kv.value.clone()];
+                match kv.expired_at_millis {
+                    Some(at) if at <= now_millis => continue,
+                    Some(at) => argv.extend([Bytes::from("PXAT"), Bytes::from(at.to_string())]),
+                    None => {}
+                }
+                out.extend(encode_command(&argv));
+            }
+            RedisType::List(list) => batched("RPUSH", list.iter().cloned().collect(), 1),
+            RedisType::ZSet(zset) => {
+                let items = zset
+                    .iter()
+                    .flat_map(|value| {
+                        [
+                            Bytes::from(resp::format_double(value.score)),
+                            value.member.clone(),
+                        ]
+                    })
+                    .collect();
+                batched("ZADD", items, 2);
+            }
+            RedisType::Stream(stream) => {
+                for (id, entry) in stream.entries() {
+                    let mut argv = vec![Bytes::from("XADD"), key.clone(), Bytes::from(id.clone())];
+                    for (field, value) in entry.values() {
+                        argv.push(Bytes::from(field.clone()));
+                        argv.push(Bytes::from(value.clone()));
+                    }
+                    out.extend(encode_command(&argv));
+                }
+            }
+            RedisType::Set(_) | RedisType::Hash(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+/// A file listed in the manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AofFile {
+    pub name: String,
+    pub seq: u64,
+}
+
+/// The files making up the AOF, in replay order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Manifest {
+    pub base: Option<AofFile>,
+    pub incrs: Vec<AofFile>,
+}
+
+impl Manifest {
+    pub fn path(dir: &Path, name: &str) -> PathBuf {
+        dir.join(format!("{}.manifest", name))
+    }
+
+    /// Reads the manifest of the AOF called `name` in `dir`, `None` if there is none.
+    pub fn load(dir: &Path, name: &str) -> Result<Option<Self>, AofError> {
+        match fs::read_to_string(Self::path(dir, name)) {
+            Ok(content) => Self::parse(&content).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// One `file <name> seq <n> type <b|h|i>` line per file. History files (`h`) are left over
+    /// from a rewrite and aren't loaded.
+    pub fn parse(content: &str) -> Result<Self, AofError> {
+        let mut manifest = Manifest::default();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = || AofError::Manifest(line.to_string());
+            let args = resp::split_inline_args(line.as_bytes()).map_err(|_| invalid())?;
+            let fields: HashMap<String, String> = args
+                .chunks_exact(2)
+                .map(|pair| {
+                    (
+                        String::from_utf8_lossy(&pair[0]).into_owned(),
+                        String::from_utf8_lossy(&pair[1]).into_owned(),
+                    )
+                })
+                .collect();
+            let file = AofFile {
+                name: fields.get("file").ok_or_else(invalid)?.clone(),
+                seq: fields
+                    .get("seq")
+                    .and_then(|seq| seq.parse().ok())
+                    .ok_or_else(invalid)?,
+            };
+            match fields.get("type").map(String::as_str) {
+                Some("b") if manifest.base.is_none() => manifest.base = Some(file),
+                Some("i") => manifest.incrs.push(file),
+                Some("h") => {}
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn persist(&self, dir: &Path, name: &str) -> io::Result<()> {
+        utils::write_atomically(&Self::path(dir, name), self.to_string().as_bytes())
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &AofFile> {
+        self.base.iter().chain(&self.incrs)
+    }
+
+    /// The next base file, `ext` being `rdb` or `aof`.
+    pub fn next_base(&self, name: &str, ext: &str) -> AofFile {
+        let seq = self.base.as_ref().map_or(1, |base| base.seq + 1);
+        AofFile {
+            name: format!("{}.{}.base.{}", name, seq, ext),
+            seq,
+        }
+    }
+
+    fn next_incr(&self, name: &str) -> AofFile {
+        let seq = self.incrs.last().map_or(1, |incr| incr.seq + 1);
+        AofFile {
+            name: format!("{}.{}.incr.aof", name, seq),
+            seq,
+        }
+    }
+
+    /// Makes `base` the base file, keeping the incremental files from `first_incr` on (none
+    /// without one), saves the manifest and deletes the files it no longer lists.
+    pub fn replace_base(
+        &mut self,
+        dir: &Path,
+        name: &str,
+        base: AofFile,
+        first_incr: Option<u64>,
+    ) -> io::Result<()> {
+        let old: Vec<AofFile> = self.files().cloned().collect();
+        self.base = Some(base);
+        self.incrs
+            .retain(|incr| first_incr.is_some_and(|first| incr.seq >= first));
+        self.persist(dir, name)?;
+        for file in old
+            .iter()
+            .filter(|file| !self.files().any(|kept| kept == *file))
+        {
+            let _ = fs::remove_file(dir.join(&file.name));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(base) = &self.base {
+            writeln!(f, "file {} seq {} type b", base.name, base.seq)?;
+        }
+        for incr in &self.incrs {
+            writeln!(f, "file {} seq {} type i", incr.name, incr.seq)?;
+        }
+        Ok(())
+    }
+}
+
+/// The AOF while it is on: its manifest and the incremental file commands are appended to.
+#[derive(Debug)]
+pub struct Aof {
+    dir: PathBuf,
+    name: String,
+    manifest: Manifest,
+    file: File,
+    last_fsync: Instant,
+    /// Bytes in the files, now and after the last rewrite, for `auto-aof-rewrite-percentage`.
+    pub size: u64,
+    pub base_size: u64,
+}
+
+impl Aof {
+    /// Opens the last incremental file of `manifest` for appending, starting one if there is
+    /// none. `size` is what the listed files hold.
+    pub fn open(dir: &Path, name: &str, mut manifest: Manifest, size: u64) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let incr = match manifest.incrs.last() {
+            Some(incr) => incr.clone(),
+            None => {
+                let incr = manifest.next_incr(name);
+                manifest.incrs.push(incr.clone());
+                manifest.persist(dir, name)?;
+                incr
+            }
+        };
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            file: open_append(&dir.join(&incr.name))?,
+            manifest,
+            last_fsync: Instant::now(),
+            size,
+            base_size: size,
+        })
+    }
+
+    /// Starts an AOF with an incremental file after those of `manifest`. The manifest is only
+    /// saved by `finish_rewrite`, once a base holds the dataset the commands apply to.
+    pub fn create(dir: &Path, name: &str, mut manifest: Manifest) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let incr = manifest.next_incr(name);
+        let file = open_append(&dir.join(&incr.name))?;
+        manifest.incrs.push(incr);
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            manifest,
+            file,
+            last_fsync: Instant::now(),
+            size: 0,
+            base_size: 0,
+        })
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Sequence of the incremental file commands are appended to.
+    pub fn incr_seq(&self) -> u64 {
+        self.manifest.incrs.last().map_or(0, |incr| incr.seq)
+    }
+
+    /// Appends a command. With `always` it is on disk when this returns, otherwise it is left
+    /// to the OS or to the periodic fsync.
+    pub fn append(&mut self, argv: &[Bytes], fsync: AppendFsync) -> io::Result<()> {
+        let command = encode_command(argv);
+        self.file.write_all(&command)?;
+        self.size += command.len() as u64;
+        if fsync == AppendFsync::Always {
+            self.file.sync_data()?;
+            self.last_fsync = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// For `everysec`: a handle to fsync from a blocking thread, once a second has passed since
+    /// the last one.
+    pub fn fsync_due(&mut self) -> Option<io::Result<File>> {
+        if self.last_fsync.elapsed() < Duration::from_secs(1) {
+            return None;
+        }
+        self.last_fsync = Instant::now();
+        Some(self.file.try_clone())
+    }
+
+    pub fn fsync(&mut self) -> io::Result<()> {
+        self.last_fsync = Instant::now();
+        self.file.sync_data()
+    }
+
+    /// Switches appends to a new incremental file, listed in the manifest right away: until
+    /// the rewrite completes, the old files followed by this one still hold the whole dataset.
+    /// Returns its sequence, to pass to `finish_rewrite`.
+    pub fn start_incr(&mut self) -> io::Result<u64> {
+        let incr = self.manifest.next_incr(&self.name);
+        let file = open_append(&self.dir.join(&incr.name))?;
+        self.fsync()?;
+        self.file = file;
+        let seq = incr.seq;
+        self.manifest.incrs.push(incr);
+        self.manifest.persist(&self.dir, &self.name)?;
+        Ok(seq)
+    }
+
+    /// Completes a rewrite: `base` replaces every file before incremental file `first_incr`.
+    pub fn finish_rewrite(&mut self, base: AofFile, first_incr: u64) -> io::Result<()> {
+        self.manifest
+            .replace_base(&self.dir, &self.name, base, Some(first_incr))?;
+        self.size = self
+            .manifest
+            .files()
+            .filter_map(|file| fs::metadata(self.dir.join(&file.name)).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        self.base_size = self.size;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::types::{
+        key_value::KeyValue,
+        sorted_set::{SortedSet, SortedValue},
+    };
+
+    fn argv(args: &[&str]) -> Vec<Bytes> {
+        args.iter()
+            .map(|arg| Bytes::from(arg.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut data = rdb::encode(&HashMap::new(), &[], 0);
+        data.extend(encode_command(&argv(&["SET", "k", "v"])));
+        data.extend(encode_command(&argv(&["RPUSH", "l", "a", "b"])));
+        let content = parse(&data).unwrap();
+        assert!(content.preamble.unwrap().entries.is_empty());
+        assert_eq!(
+            content.commands,
+            vec![
+                RespDataType::new_array(vec!["SET", "k", "v"]),
+                RespDataType::new_array(vec!["RPUSH", "l", "a", "b"]),
+            ]
+        );
+        assert_eq!(content.valid_len, data.len());
+        assert!(!content.truncated);
+
+        let complete = data.len();
+        data.extend_from_slice(b"*2\r\n$3\r\nGET");
+        let content = parse(&data).unwrap();
+        assert_eq!(content.commands.len(), 2);
+        assert_eq!(content.valid_len, complete);
+        assert!(content.truncated);
+
+        data.truncate(complete);
+        data.extend_from_slice(b"+OK\r\n");
+        assert!(matches!(parse(&data), Err(AofError::BadFormat(at)) if at == complete));
+
+        // A transaction is kept whole or not at all.
+        data.truncate(complete);
+        data.extend(encode_command(&argv(&["MULTI"])));
+        data.extend(encode_command(&argv(&["SET", "a", "1"])));
+        data.extend(encode_command(&argv(&["EXEC"])));
+        let committed = data.len();
+        data.extend(encode_command(&argv(&["multi"])));
+        data.extend(encode_command(&argv(&["SET", "b", "2"])));
+        let content = parse(&data).unwrap();
+        assert_eq!(content.commands.len(), 5);
+        assert_eq!(content.valid_len, committed);
+        assert!(content.truncated);
+    }
+
+    #[test]
+    fn test_manifest() {
+        let content = "file appendonly.aof.2.base.rdb seq 2 type b\n\
+                       file appendonly.aof.1.incr.aof seq 1 type h\n\
+                       file \"appendonly.aof.3.incr.aof\" seq 3 type i\n\
+                       file appendonly.aof.4.incr.aof seq 4 type i\n";
+        let manifest = Manifest::parse(content).unwrap();
+        assert_eq!(
+            manifest.files().map(|file| file.seq).collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+        assert_eq!(
+            manifest.to_string(),
+            "file appendonly.aof.2.base.rdb seq 2 type b\n\
+             file appendonly.aof.3.incr.aof seq 3 type i\n\
+             file appendonly.aof.4.incr.aof seq 4 type i\n"
+        );
+        assert_eq!(
+            manifest.next_base("appendonly.aof", "aof").name,
+            "appendonly.aof.3.base.aof"
+        );
+        assert!(Manifest::parse("file x seq 1 type z\n").is_err());
+        assert!(Manifest::parse("file x type b\n").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_and_append() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let name = "appendonly.aof";
+        let mut aof = Aof::create(dir, name, Manifest::default()).unwrap();
+        aof.append(&argv(&["SET", "k", "v"]), AppendFsync::Always)
+            .unwrap();
+        let base = aof.manifest().next_base(name, "rdb");
+        fs::write(dir.join(&base.name), rdb::encode(&HashMap::new(), &[], 0)).unwrap();
+        aof.finish_rewrite(base, aof.incr_seq()).unwrap();
+        assert_eq!(
+            Manifest::load(dir, name).unwrap().as_ref(),
+            Some(aof.manifest())
+        );
+
+        // Appends move to the new file right away, the old ones go once the base is written.
+        let first_incr = aof.start_incr().unwrap();
+        aof.append(&argv(&["SET", "a", "b"]), AppendFsync::No)
+            .unwrap();
+        assert_eq!(Manifest::load(dir, name).unwrap().unwrap().incrs.len(), 2);
+        let base = aof.manifest().next_base(name, "aof");
+        fs::write(
+            dir.join(&base.name),
+            encode_command(&argv(&["SET", "k", "v"])),
+        )
+        .unwrap();
+        aof.finish_rewrite(base, first_incr).unwrap();
+
+        let mut files: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                "appendonly.aof.2.base.aof",
+                "appendonly.aof.2.incr.aof",
+                "appendonly.aof.manifest"
+            ]
+        );
+        assert_eq!(
+            parse(&fs::read(dir.join("appendonly.aof.2.incr.aof")).unwrap())
+                .unwrap()
+                .commands,
+            vec![RespDataType::new_array(vec!["SET", "a", "b"])]
+        );
+        assert_eq!(aof.size, aof.base_size);
+    }
+
+    #[test]
+    fn test_base_content() {
+        let mut zset = SortedSet::new();
+        zset.replace(SortedValue {
+            member: Bytes::from("m"),
+            score: 1.5,
+        });
+        let mut data = HashMap::from([
+            (
+                "s".to_string(),
+                RedisType::String(KeyValue {
+                    value: Bytes::from("v"),
+                    expired_at_millis: Some(2000),
+                }),
+            ),
+            (
+                "expired".to_string(),
+                RedisType::String(KeyValue {
+                    value: Bytes::from("v"),
+                    expired_at_millis: Some(500),
+                }),
+            ),
+            (
+                "l".to_string(),
+                RedisType::List((0..100).map(|i| Bytes::from(i.to_string())).collect()),
+            ),
+            ("z".to_string(), RedisType::ZSet(zset)),
+        ]);
+
+        let functions = vec!["#!lua name=lib\n".to_string()];
+        let (content, ext) = base_content(&data, &functions, 1000, false);
+        assert_eq!(ext, "aof");
+        let mut commands: Vec<_> = parse(&content)
+            .unwrap()
+            .commands
+            .into_iter()
+            .map(|command| match command {
+                RespDataType::Array(args) => (args[0].to_string().unwrap(), args.len()),
+                _ => unreachable!(),
+            })
+            .collect();
+        commands.sort();
+        assert_eq!(
+            commands,
+            vec![
+                ("FUNCTION".to_string(), 3),
+                ("RPUSH".to_string(), 38),
+                ("RPUSH".to_string(), 66),
+                ("SET".to_string(), 5),
+                ("ZADD".to_string(), 4)
+            ]
+        );
+        assert_eq!(base_content(&data, &functions, 1000, true).1, "rdb");
+
+        data.insert(
+            "set".to_string(),
+            RedisType::Set(HashSet::from([Bytes::from("a")])),
+        );
+        assert_eq!(base_content(&data, &functions, 1000, false).1, "rdb");
+    }
+}
@@ -0,0 +1,566 @@
+//! Offline checker for the files the server persists its dataset to: an RDB file, a single AOF
+//! file, or a multi-part AOF given by its manifest or directory. Reports where the first
+//! corruption is, then loads the dataset the way the server does at startup to print
+//! statistics per type, or to export it as JSON lines.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use codecrafters_redis::{
+    aof::{self, AofError, Manifest},
+    config::{Config, LogLevel},
+    rdb::{self, Rdb},
+    resp,
+    server::RedisServer,
+    types::RedisType,
+    utils,
+};
+
+const USAGE: &str =
+    "Usage: redis-check [--export] <dump.rdb | file.aof | file.manifest | aof directory>
+
+Checks the file and prints statistics about the dataset it holds.
+  --export  print the dataset as JSON lines on stdout instead, one key per line";
+
+/// A corruption or a file that can't be read.
+#[derive(Debug, PartialEq)]
+struct CheckError {
+    /// `RDB` or `AOF`.
+    kind: &'static str,
+    message: String,
+}
+
+impl CheckError {
+    fn rdb(message: impl Into<String>) -> Self {
+        Self {
+            kind: "RDB",
+            message: message.into(),
+        }
+    }
+
+    fn aof(message: impl Into<String>) -> Self {
+        Self {
+            kind: "AOF",
+            message: message.into(),
+        }
+    }
+}
+
+/// Where progress goes: stdout, or stderr while stdout holds the export.
+struct Report {
+    export: bool,
+}
+
+impl Report {
+    fn line(&self, line: impl AsRef<str>) {
+        match self.export {
+            true => eprintln!("{}", line.as_ref()),
+            false => println!("{}", line.as_ref()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (export, path) = match args.as_slice() {
+        [path] if path != "--export" => (false, path),
+        [flag, path] | [path, flag] if flag == "--export" => (true, path),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    let report = Report { export };
+    // Only warnings, which the checks below report themselves first.
+    let server = Arc::new(RedisServer::new(Config {
+        loglevel: LogLevel::Warning,
+        ..Config::default()
+    }));
+    if let Err(e) = check(Path::new(path), &server, &report).await {
+        report.line(format!("--- {} ERROR DETECTED ---", e.kind));
+        report.line(e.message);
+        std::process::exit(1);
+    }
+
+    let dataset = export_lines_or_stats(&server.store.snapshot().await, export);
+    match export {
+        true => print!("{}", dataset),
+        false => report.line(dataset),
+    }
+}
+
+/// Checks and loads `path` into `server`.
+async fn check(path: &Path, server: &Arc<RedisServer>, report: &Report) -> Result<(), CheckError> {
+    if path.is_dir() {
+        let manifest = find_manifest(path)?;
+        return check_manifest(&manifest, server, report).await;
+    }
+    let name = path.display().to_string();
+    if name.ends_with(".manifest") {
+        return check_manifest(path, server, report).await;
+    }
+    let data = std::fs::read(path).map_err(|e| CheckError::rdb(format!("{}: {}", name, e)))?;
+    let is_aof = path.extension().is_some_and(|ext| ext == "aof") || !data.starts_with(b"REDIS");
+    match is_aof {
+        true => check_aof_file(&name, &data, true, server, report).await,
+        false => check_rdb(&name, &data, server, report).await,
+    }
+}
+
+fn find_manifest(dir: &Path) -> Result<PathBuf, CheckError> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| CheckError::aof(format!("{}: {}", dir.display(), e)))?;
+    let manifests: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "manifest"))
+        .collect();
+    match manifests.as_slice() {
+        [manifest] => Ok(manifest.clone()),
+        [] => Err(CheckError::aof(format!(
+            "No AOF manifest in {}",
+            dir.display()
+        ))),
+        _ => Err(CheckError::aof(format!(
+            "More than one AOF manifest in {}, pass the one to check",
+            dir.display()
+        ))),
+    }
+}
+
+async fn check_rdb(
+    name: &str,
+    data: &[u8],
+    server: &Arc<RedisServer>,
+    report: &Report,
+) -> Result<(), CheckError> {
+    report.line(format!("[offset 0] Checking RDB file {}", name));
+    let (rdb, len) = rdb::parse_prefix_with_offset(data)
+        .map_err(|(e, offset)| CheckError::rdb(format!("[offset {}] {}", offset, e)))?;
+    if len < data.len() {
+        return Err(CheckError::rdb(format!(
+            "[offset {}] {} unexpected bytes after the checksum",
+            len,
+            data.len() - len
+        )));
+    }
+    report.line(format!("[info] RDB version {}", rdb.version));
+    for (field, value) in &rdb.aux {
+        report.line(format!(
+            "[info] AUX FIELD {} = '{}'",
+            String::from_utf8_lossy(field),
+            String::from_utf8_lossy(value)
+        ));
+    }
+    load_rdb(rdb, server, report).await;
+    report.line(format!("[offset {}] \\o/ RDB looks OK! \\o/", len));
+    Ok(())
+}
+
+/// Loads the keys of database 0, like the server does.
+async fn load_rdb(rdb: Rdb, server: &Arc<RedisServer>, report: &Report) {
+    let now = utils::now_millis();
+    let (entries, other_dbs): (Vec<_>, Vec<_>) =
+        rdb.entries.into_iter().partition(|entry| entry.db == 0);
+    let expired = entries
+        .iter()
+        .filter(|entry| entry.expire_at_millis.is_some_and(|at| at <= now))
+        .count();
+    report.line(format!(
+        "[info] {} keys read, {} already expired",
+        entries.len(),
+        expired
+    ));
+    if !other_dbs.is_empty() {
+        report.line(format!(
+            "[info] {} keys in databases other than 0, which the server skips",
+            other_dbs.len()
+        ));
+    }
+    server.load_rdb_entries(Rdb { entries, ..rdb }).await;
+}
+
+async fn check_manifest(
+    path: &Path,
+    server: &Arc<RedisServer>,
+    report: &Report,
+) -> Result<(), CheckError> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = file_name.strip_suffix(".manifest").unwrap_or(&file_name);
+    let manifest = match Manifest::load(dir, name) {
+        Ok(Some(manifest)) => manifest,
+        Ok(None) => return Err(CheckError::aof(format!("{} doesn't exist", path.display()))),
+        Err(e) => return Err(CheckError::aof(e.to_string())),
+    };
+    report.line(format!(
+        "[info] Manifest {}: {} base file, {} incremental files",
+        path.display(),
+        manifest.base.iter().count(),
+        manifest.incrs.len()
+    ));
+
+    let files: Vec<_> = manifest.files().collect();
+    for (i, file) in files.iter().enumerate() {
+        let data = std::fs::read(dir.join(&file.name))
+            .map_err(|e| CheckError::aof(format!("{}: {}", file.name, e)))?;
+        check_aof_file(&file.name, &data, i + 1 == files.len(), server, report).await?;
+    }
+    Ok(())
+}
+
+/// Checks one AOF file. Only the `last` one of a multi-part AOF may end with a partial command.
+async fn check_aof_file(
+    name: &str,
+    data: &[u8],
+    last: bool,
+    server: &Arc<RedisServer>,
+    report: &Report,
+) -> Result<(), CheckError> {
+    report.line(format!("[offset 0] Checking AOF file {}", name));
+    let content = match aof::parse(data) {
+        Ok(content) => content,
+        Err(AofError::Preamble(e)) => {
+            let offset = rdb::parse_prefix_with_offset(data)
+                .err()
+                .map_or(0, |(_, at)| at);
+            return Err(CheckError::aof(format!(
+                "[offset {}] Bad RDB preamble: {}",
+                offset, e
+            )));
+        }
+        Err(AofError::BadFormat(offset)) => {
+            return Err(CheckError::aof(format!(
+                "[offset {}] Bad file format: expected a command",
+                offset
+            )))
+        }
+        Err(e) => return Err(CheckError::aof(e.to_string())),
+    };
+
+    if let Some(preamble) = &content.preamble {
+        report.line(format!(
+            "[info] RDB preamble with {} keys",
+            preamble.entries.len()
+        ));
+    }
+    report.line(format!("[info] {} commands", content.commands.len()));
+    if content.truncated {
+        let message = format!(
+            "[offset {}] Unexpected end of file in the middle of a command",
+            content.valid_len
+        );
+        if !last {
+            return Err(CheckError::aof(message));
+        }
+        report.line(format!(
+            "{}, dropped when loading with aof-load-truncated yes",
+            message
+        ));
+    }
+    let valid_len = content.valid_len;
+    server
+        .load_aof_content(content)
+        .await
+        .map_err(|e| CheckError::aof(e.to_string()))?;
+    report.line(format!("[offset {}] AOF {} is valid", valid_len, name));
+    Ok(())
+}
+
+/// Number of elements of a collection, bytes of a string.
+fn value_size(value: &RedisType) -> u64 {
+    (match value {
+        RedisType::None => 0,
+        RedisType::String(kv) => kv.value.len(),
+        RedisType::List(list) => list.len(),
+        RedisType::ZSet(zset) => zset.len() as usize,
+        RedisType::Stream(stream) => stream.entries().count(),
+        RedisType::Set(set) => set.len(),
+        RedisType::Hash(hash) => hash.len(),
+    }) as u64
+}
+
+/// Power of two histogram bin, like Redis' `INFO keysizes`: sizes from 2^n up to 2^(n+1)-1
+/// all count in bin 2^n, empty values in bin 0.
+fn size_bin(size: u64) -> u64 {
+    match size {
+        0 => 0,
+        size => 1 << (63 - size.leading_zeros()),
+    }
+}
+
+#[derive(Default)]
+struct TypeStats {
+    keys: u64,
+    expires: u64,
+    size: u64,
+    bins: BTreeMap<u64, u64>,
+}
+
+fn export_lines_or_stats(data: &HashMap<String, RedisType>, export: bool) -> String {
+    let now = utils::now_millis();
+    let mut keys: Vec<_> = data
+        .iter()
+        .filter(|(_, value)| match value {
+            RedisType::None => false,
+            RedisType::String(kv) => kv.expired_at_millis.is_none_or(|at| at > now),
+            _ => true,
+        })
+        .collect();
+    keys.sort_by_key(|(key, _)| *key);
+    match export {
+        true => keys
+            .iter()
+            .map(|(key, value)| json_line(key, value) + "\n")
+            .collect(),
+        false => stats(&keys),
+    }
+}
+
+fn stats(keys: &[(&String, &RedisType)]) -> String {
+    let mut by_type: BTreeMap<&str, TypeStats> = BTreeMap::new();
+    for (_, value) in keys {
+        let stats = by_type.entry(value.type_name()).or_default();
+        let size = value_size(value);
+        stats.keys += 1;
+        stats.size += size;
+        *stats.bins.entry(size_bin(size)).or_default() += 1;
+        if let RedisType::String(kv) = value {
+            stats.expires += kv.expired_at_millis.is_some() as u64;
+        }
+    }
+
+    let mut out = format!("== {} keys ==\n", keys.len());
+    for (name, stats) in &by_type {
+        let unit = if *name == "string" {
+            "bytes"
+        } else {
+            "elements"
+        };
+        let _ = writeln!(
+            out,
+            "{}: {} keys, {} with an expire, {} {} in total",
+            name, stats.keys, stats.expires, stats.size, unit
+        );
+    }
+    out.push_str("== Key sizes (bin=keys) ==\n");
+    for (name, stats) in &by_type {
+        let bins: Vec<String> = stats
+            .bins
+            .iter()
+            .map(|(bin, count)| format!("{}={}", bin, count))
+            .collect();
+        let _ = writeln!(out, "{}: {}", name, bins.join(","));
+    }
+    out.trim_end().to_string()
+}
+
+/// `{"key":...,"type":...,"value":...}`, plus `expire_at_ms` for strings that have one. Bytes
+/// that aren't valid UTF-8 are replaced.
+fn json_line(key: &str, value: &RedisType) -> String {
+    let json_value = match value {
+        RedisType::None => "null".to_string(),
+        RedisType::String(kv) => json_string(&kv.value),
+        RedisType::List(list) => json_array(list.iter().map(|item| json_string(item))),
+        RedisType::Set(set) => {
+            let mut members: Vec<_> = set.iter().collect();
+            members.sort();
+            json_array(members.into_iter().map(|member| json_string(member)))
+        }
+        RedisType::ZSet(zset) => json_array(zset.iter().map(|value| {
+            let score = resp::format_double(value.score);
+            let score = match value.score.is_finite() {
+                true => score,
+                false => json_string(score.as_bytes()),
+            };
+            format!(
+                "{{\"member\":{},\"score\":{}}}",
+                json_string(&value.member),
+                score
+            )
+        })),
+        RedisType::Hash(hash) => {
+            let mut fields: Vec<_> = hash.iter().collect();
+            fields.sort();
+            json_object(
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (json_string(field), json_string(value))),
+            )
+        }
+        RedisType::Stream(stream) => json_array(stream.entries().map(|(id, entry)| {
+            let mut fields: Vec<_> = entry.values().iter().collect();
+            fields.sort();
+            let fields = json_object(fields.into_iter().map(|(field, value)| {
+                (json_string(field.as_bytes()), json_string(value.as_bytes()))
+            }));
+            format!(
+                "{{\"id\":{},\"fields\":{}}}",
+                json_string(id.as_bytes()),
+                fields
+            )
+        })),
+    };
+
+    let mut line = format!(
+        "{{\"key\":{},\"type\":\"{}\",\"value\":{}",
+        json_string(key.as_bytes()),
+        value.type_name(),
+        json_value
+    );
+    if let RedisType::String(kv) = value {
+        if let Some(at) = kv.expired_at_millis {
+            let _ = write!(line, ",\"expire_at_ms\":{}", at);
+        }
+    }
+    line.push('}');
+    line
+}
+
+fn json_string(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(","))
+}
+
+fn json_object(pairs: impl Iterator<Item = (String, String)>) -> String {
+    let pairs: Vec<String> = pairs
+        .map(|(key, value)| format!("{}:{}", key, value))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use bytes::Bytes;
+    use codecrafters_redis::types::{
+        key_value::KeyValue,
+        sorted_set::{SortedSet, SortedValue},
+    };
+
+    use super::*;
+
+    fn server() -> Arc<RedisServer> {
+        Arc::new(RedisServer::new(Config {
+            loglevel: LogLevel::Warning,
+            ..Config::default()
+        }))
+    }
+
+    #[test]
+    fn test_json_line() {
+        let string = RedisType::String(KeyValue {
+            value: Bytes::from("a \"quoted\"\nline"),
+            expired_at_millis: Some(1000),
+        });
+        assert_eq!(
+            json_line("k", &string),
+            r#"{"key":"k","type":"string","value":"a \"quoted\"\nline","expire_at_ms":1000}"#
+        );
+        let list = RedisType::List(VecDeque::from([Bytes::from("a"), Bytes::from("\x01")]));
+        assert_eq!(
+            json_line("l", &list),
+            r#"{"key":"l","type":"list","value":["a","\u0001"]}"#
+        );
+        let mut zset = SortedSet::new();
+        zset.replace(SortedValue {
+            member: Bytes::from("m"),
+            score: f64::INFINITY,
+        });
+        assert_eq!(
+            json_line("z", &RedisType::ZSet(zset)),
+            r#"{"key":"z","type":"zset","value":[{"member":"m","score":"inf"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        assert_eq!([0, 1, 2, 3, 4, 1000].map(size_bin), [0, 1, 2, 2, 4, 512]);
+        let data = HashMap::from([
+            (
+                "s".to_string(),
+                RedisType::String(KeyValue {
+                    value: Bytes::from("abc"),
+                    expired_at_millis: None,
+                }),
+            ),
+            (
+                "l".to_string(),
+                RedisType::List(VecDeque::from([Bytes::from("a")])),
+            ),
+        ]);
+        assert_eq!(
+            export_lines_or_stats(&data, false),
+            "== 2 keys ==\n\
+             list: 1 keys, 0 with an expire, 1 elements in total\n\
+             string: 1 keys, 0 with an expire, 3 bytes in total\n\
+             == Key sizes (bin=keys) ==\n\
+             list: 1=1\n\
+             string: 2=1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_reports_offsets() {
+        let report = Report { export: true };
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        let data = HashMap::from([(
+            "k".to_string(),
+            RedisType::String(KeyValue {
+                value: Bytes::from("v"),
+                expired_at_millis: None,
+            }),
+        )]);
+        let mut dump = rdb::encode(&data, &[], 0);
+        let path = dir.join("dump.rdb");
+        std::fs::write(&path, &dump).unwrap();
+        let server = server();
+        check(&path, &server, &report).await.unwrap();
+        assert!(server.store.get_key(&"k".to_string()).await.is_some());
+
+        // The checksum no longer matches once a byte of the value changes.
+        let len = dump.len();
+        dump[len - 10] ^= 1;
+        std::fs::write(&path, &dump).unwrap();
+        let error = check(&path, &server, &report).await.unwrap_err();
+        assert_eq!(error.kind, "RDB");
+        assert!(error
+            .message
+            .starts_with(&format!("[offset {}] Wrong RDB checksum", len)));
+
+        let path = dir.join("appendonly.aof");
+        std::fs::write(&path, b"*1\r\n$4\r\nPING\r\n+OK\r\n").unwrap();
+        assert_eq!(
+            check(&path, &server, &report).await,
+            Err(CheckError::aof(
+                "[offset 14] Bad file format: expected a command"
+            ))
+        );
+    }
+}
@@ -1,60 +1,194 @@
-use std::{hash::Hash, sync::Arc};
-
-use nanoid::nanoid;
+use std::{
+    collections::HashSet,
+    hash::Hash,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+};
 
+use bytes::Bytes;
 use tokio::{
-    io::{AsyncRead, AsyncWrite, DuplexStream},
-    net::TcpStream,
-    sync::Notify,
+    sync::{
+        mpsc::{self, error::TrySendError, Receiver, Sender},
+        Notify,
+    },
     time::Instant,
 };
 
-use crate::connection::Connection;
+use crate::{
+    commands::RedisCommand,
+    pubsub::Kind,
+    resp::{ProtocolVersion, RespDataType},
+    store::RedisStore,
+};
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Pushes a client may have waiting to be written out, like Redis' `client-output-buffer-limit`
+/// for Pub/Sub clients: one that doesn't read them fast enough is disconnected rather than
+/// making the server buffer them without bound.
+pub const NOTIFICATIONS_LIMIT: usize = 1 << 16;
+
+/// Sends a client what it gets besides the replies to its commands, up to
+/// `NOTIFICATIONS_LIMIT` pushes waiting.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    sender: Sender<RespDataType>,
+    overflow: Arc<Notify>,
+}
+
+impl Notifier {
+    /// Queues a push, returning whether it was. When the client has too many waiting, it is
+    /// dropped and the connection task told to disconnect the client.
+    pub fn send(&self, push: RespDataType) -> bool {
+        match self.sender.try_send(push) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.overflow.notify_one();
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Completes once a push was dropped for going over the limit.
+    pub async fn overflowed(&self) {
+        self.overflow.notified().await
+    }
+}
 
+/// Per-connection state. The socket itself lives in `Connection`, owned by the client task.
 #[derive(Debug)]
-pub struct RedisClient<T: AsyncRead + AsyncWrite + Unpin + Send> {
-    pub id: String,
+pub struct RedisClient {
+    pub id: u64,
     pub created_at: Instant,
-    // TODO - transformar num channel
-    // https://tokio.rs/tokio/tutorial/channels
-    pub notifier: Arc<Notify>,
-    pub connection: Connection<T>,
+    /// Address of the peer, `None` when the client isn't connected over TCP.
+    pub addr: Option<SocketAddr>,
+    /// Where to send what the client gets besides the replies to its commands, like Pub/Sub
+    /// messages. The connection task writes them out as they come.
+    pub notifier: Notifier,
+    /// The other end of `notifier`, taken by the connection task.
+    pub notifications: Option<Receiver<RespDataType>>,
+    /// Channels subscribed to with `SUBSCRIBE`.
+    pub channels: HashSet<Bytes>,
+    /// Patterns subscribed to with `PSUBSCRIBE`.
+    pub patterns: HashSet<Bytes>,
+    /// Protocol negotiated with `HELLO`, used to encode every reply sent to this client.
+    pub protocol: ProtocolVersion,
+    /// Whether the client passed `AUTH` (or `HELLO ... AUTH`). Only checked when `requirepass`
+    /// is configured.
+    pub authenticated: bool,
+    /// Arguments of the command being executed, propagated to the AOF when it changes the
+    /// dataset. Commands replace them when replaying them as is would give a different result,
+    /// and clear them when there is nothing to propagate.
+    pub argv: Vec<Bytes>,
+    /// Set through `HELLO ... SETNAME`.
+    #[allow(dead_code)]
+    pub name: Option<String>,
+    /// Port a replica listens on, sent with `REPLCONF listening-port` before `PSYNC`.
+    pub replica_listening_port: Option<u16>,
+    /// Set by `PSYNC` to the replication ID and offset the replica wants to resume from: after
+    /// the reply is due the connection becomes a replication link.
+    pub psync: Option<(String, i64)>,
+    /// Set on the link a replica reads its master's commands from: they may write even when
+    /// the replica is read-only, and get no reply.
+    pub from_master: bool,
+    /// Replication offset right after the last write of this client, what `WAIT` and
+    /// `WAITAOF` wait for.
+    pub woff: u64,
+    /// Set by `MULTI`: the commands to run on `EXEC`.
+    pub multi: Option<MultiState>,
+    /// Keys watched with `WATCH`, with the Unix time in milliseconds they were watched at.
+    pub watched_keys: Vec<(String, u128)>,
+    /// Set by the store when a watched key changes: `EXEC` then fails.
+    pub watch_touched: Arc<AtomicBool>,
+    /// Set on the client a script runs its commands with.
+    pub in_script: bool,
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin + Send> PartialEq for RedisClient<T> {
+/// A transaction being queued.
+#[derive(Debug, Default)]
+pub struct MultiState {
+    /// Each command with its arguments, to propagate it once run.
+    pub commands: Vec<(RedisCommand, Vec<Bytes>)>,
+    /// Set when a command was refused while queueing: `EXEC` then discards the transaction.
+    pub aborted: bool,
+}
+
+impl PartialEq for RedisClient {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id && self.created_at == other.created_at
     }
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin + Send> Eq for RedisClient<T> {}
+impl Eq for RedisClient {}
 
-impl<T: AsyncRead + AsyncWrite + Unpin + Send> Hash for RedisClient<T> {
+impl Hash for RedisClient {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         self.id.hash(state);
         self.created_at.hash(state);
     }
 }
 
-impl RedisClient<TcpStream> {
-    pub fn new(stream: TcpStream) -> Self {
+impl RedisClient {
+    pub fn new() -> Self {
+        let (sender, notifications) = mpsc::channel(NOTIFICATIONS_LIMIT);
         return Self {
-            id: nanoid!(),
+            id: NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed),
             created_at: Instant::now(),
-            notifier: Arc::new(Notify::new()),
-            connection: Connection::new(stream),
+            addr: None,
+            notifier: Notifier {
+                sender,
+                overflow: Arc::new(Notify::new()),
+            },
+            notifications: Some(notifications),
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+            protocol: ProtocolVersion::default(),
+            authenticated: false,
+            argv: Vec::new(),
+            name: None,
+            replica_listening_port: None,
+            psync: None,
+            from_master: false,
+            woff: 0,
+            multi: None,
+            watched_keys: Vec::new(),
+            watch_touched: Arc::new(AtomicBool::new(false)),
+            in_script: false,
         };
     }
-}
 
-#[cfg(test)]
-impl RedisClient<DuplexStream> {
-    pub fn mock_new(stream: DuplexStream) -> Self {
-        Self {
-            id: nanoid!(),
-            created_at: Instant::now(),
-            notifier: Arc::new(Notify::new()),
-            connection: Connection::new(stream),
+    /// Inside a transaction or a script nothing else runs, so blocking commands reply as if
+    /// they timed out and `WAIT` doesn't wait.
+    pub fn deny_blocking(&self) -> bool {
+        self.multi.is_some() || self.in_script
+    }
+
+    /// Forgets the watched keys, after `EXEC`, `DISCARD` or `UNWATCH`.
+    pub fn unwatch(&mut self, store: &RedisStore) {
+        for (key, _) in self.watched_keys.drain(..) {
+            store.unwatch(&key, &self.watch_touched);
         }
+        self.watch_touched.store(false, Ordering::SeqCst);
+    }
+
+    pub fn subscriptions(&mut self, kind: Kind) -> &mut HashSet<Bytes> {
+        match kind {
+            Kind::Channel => &mut self.channels,
+            Kind::Pattern => &mut self.patterns,
+        }
+    }
+
+    pub fn subscription_count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// A RESP2 client with subscriptions can only run commands about them: its connection is
+    /// for receiving messages.
+    pub fn in_pubsub_mode(&self) -> bool {
+        self.protocol == ProtocolVersion::Resp2 && self.subscription_count() > 0
     }
 }
@@ -1,15 +1,53 @@
+pub mod auth;
+pub mod bgrewriteaof;
+pub mod bgsave;
 pub mod blpop;
+pub mod command;
+pub mod config;
+pub mod discard;
+pub mod dump;
 pub mod echo;
+pub mod eval;
+pub mod eval_ro;
+pub mod evalsha;
+pub mod evalsha_ro;
+pub mod exec;
+pub mod fcall;
+pub mod fcall_ro;
+pub mod flushall;
+pub mod function;
 pub mod get;
+pub mod hello;
+pub mod info;
 pub mod key_type;
+pub mod lastsave;
 pub mod llen;
 pub mod lpop;
 pub mod lpush;
 pub mod lrange;
+pub mod multi;
 pub mod ping;
+pub mod psubscribe;
+pub mod psync;
+pub mod publish;
+pub mod punsubscribe;
+pub mod quit;
+pub mod replconf;
+pub mod replicaof;
+pub mod reset;
+pub mod restore;
 pub mod rpush;
+pub mod save;
+pub mod script;
 pub mod set;
+pub mod subscribe;
+pub mod table;
 pub mod traits;
+pub mod unsubscribe;
+pub mod unwatch;
+pub mod wait;
+pub mod waitaof;
+pub mod watch;
 pub mod xadd;
 pub mod zadd;
 pub mod zcard;
@@ -24,21 +62,60 @@ use std::{
     vec::IntoIter,
 };
 
-use crate::command_parser;
+use crate::command_registry;
 use crate::{
+    client::RedisClient,
     commands::{
+        auth::AuthCommand,
+        bgrewriteaof::BgRewriteAofCommand,
+        bgsave::BgSaveCommand,
         blpop::BLPopCommand,
+        command::CommandCommand,
+        config::ConfigCommand,
+        discard::DiscardCommand,
+        dump::DumpCommand,
         echo::EchoCommand,
+        eval::EvalCommand,
+        eval_ro::EvalRoCommand,
+        evalsha::EvalShaCommand,
+        evalsha_ro::EvalShaRoCommand,
+        exec::ExecCommand,
+        fcall::FCallCommand,
+        fcall_ro::FCallRoCommand,
+        flushall::FlushAllCommand,
+        function::FunctionCommand,
         get::GetCommand,
+        hello::HelloCommand,
+        info::InfoCommand,
         key_type::KeyTypeCommand,
+        lastsave::LastSaveCommand,
         llen::LLenCommand,
         lpop::LPopCommand,
         lpush::LPushCommand,
         lrange::LRangeCommand,
+        multi::MultiCommand,
         ping::PingCommand,
+        psubscribe::PSubscribeCommand,
+        psync::PSyncCommand,
+        publish::PublishCommand,
+        punsubscribe::PUnsubscribeCommand,
+        quit::QuitCommand,
+        replconf::ReplConfCommand,
+        replicaof::ReplicaOfCommand,
+        reset::ResetCommand,
+        restore::RestoreCommand,
         rpush::RPushCommand,
+        save::SaveCommand,
+        script::ScriptCommand,
         set::SetCommand,
-        traits::{ParseableCommand, RunnableCommand},
+        subscribe::SubscribeCommand,
+        table::{CommandFlag, CommandSpec},
+        traits::{DescribedCommand, ParseableCommand, RunnableCommand},
+        unsubscribe::UnsubscribeCommand,
+        unwatch::UnwatchCommand,
+        wait::WaitCommand,
+        waitaof::WaitAofCommand,
+        watch::WatchCommand,
         xadd::XAddCommand,
         zadd::ZAddCommand,
         zcard::ZCardCommand,
@@ -47,38 +124,101 @@ use crate::{
         zrem::ZRemCommand,
         zscore::ZScoreCommand,
     },
+    error::RedisError,
     resp::RespDataType,
-    store::RedisStore,
+    server::RedisServer,
     utils,
 };
-use tokio::sync::Notify;
-
-#[derive(Debug, PartialEq, Clone)]
-pub enum RedisCommand {
-    GET(GetCommand),
-    SET(SetCommand),
-    PING(PingCommand),
-    ECHO(EchoCommand),
-    RPUSH(RPushCommand),
-    LPUSH(LPushCommand),
-    LRANGE(LRangeCommand),
-    LLEN(LLenCommand),
-    LPOP(LPopCommand),
-    BLPOP(BLPopCommand),
-    ZADD(ZAddCommand),
-    ZRANK(ZRankCommand),
-    ZRANGE(ZRangeCommand),
-    ZCARD(ZCardCommand),
-    ZSCORE(ZScoreCommand),
-    ZREM(ZRemCommand),
-    TYPE(KeyTypeCommand),
-    XADD(XAddCommand),
+
+command_registry! {
+    PING => PingCommand,
+    ECHO => EchoCommand,
+    GET => GetCommand,
+    SET => SetCommand,
+    RPUSH => RPushCommand,
+    LPUSH => LPushCommand,
+    LRANGE => LRangeCommand,
+    LLEN => LLenCommand,
+    LPOP => LPopCommand,
+    BLPOP => BLPopCommand,
+    ZADD => ZAddCommand,
+    ZRANK => ZRankCommand,
+    ZRANGE => ZRangeCommand,
+    ZCARD => ZCardCommand,
+    ZSCORE => ZScoreCommand,
+    ZREM => ZRemCommand,
+    TYPE => KeyTypeCommand,
+    XADD => XAddCommand,
+    HELLO => HelloCommand,
+    AUTH => AuthCommand,
+    COMMAND => CommandCommand,
+    CONFIG => ConfigCommand,
+    SAVE => SaveCommand,
+    BGSAVE => BgSaveCommand,
+    LASTSAVE => LastSaveCommand,
+    BGREWRITEAOF => BgRewriteAofCommand,
+    DUMP => DumpCommand,
+    RESTORE => RestoreCommand,
+    INFO => InfoCommand,
+    REPLCONF => ReplConfCommand,
+    PSYNC => PSyncCommand,
+    REPLICAOF => ReplicaOfCommand,
+    WAIT => WaitCommand,
+    WAITAOF => WaitAofCommand,
+    MULTI => MultiCommand,
+    EXEC => ExecCommand,
+    DISCARD => DiscardCommand,
+    WATCH => WatchCommand,
+    UNWATCH => UnwatchCommand,
+    FLUSHALL => FlushAllCommand,
+    EVAL => EvalCommand,
+    EVALSHA => EvalShaCommand,
+    EVALRO => EvalRoCommand,
+    EVALSHARO => EvalShaRoCommand,
+    SCRIPT => ScriptCommand,
+    FUNCTION => FunctionCommand,
+    FCALL => FCallCommand,
+    FCALLRO => FCallRoCommand,
+    SUBSCRIBE => SubscribeCommand,
+    UNSUBSCRIBE => UnsubscribeCommand,
+    PSUBSCRIBE => PSubscribeCommand,
+    PUNSUBSCRIBE => PUnsubscribeCommand,
+    PUBLISH => PublishCommand,
+    QUIT => QuitCommand,
+    RESET => ResetCommand,
 }
 
-// TODO - tentar implementar algo como uma linguagem para fazer o parse, algo declarativo
-// e que cuide se está valido a quantidade de argumentos e os valores dos argumentos
 impl RedisCommand {
-    pub fn build(values: Vec<RespDataType>) -> Result<Vec<RedisCommand>, String> {
+    /// Looks a command up by name, ignoring case.
+    pub fn lookup(name: &str) -> Option<&'static CommandSpec> {
+        COMMAND_TABLE
+            .iter()
+            .find(|spec| spec.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// Whether the command has `flag`. For commands with subcommands as different as those of
+    /// `FUNCTION`, the flags of the subcommand rather than of the command.
+    pub fn has_flag(&self, flag: CommandFlag) -> bool {
+        match self {
+            RedisCommand::FUNCTION(function) => function.subcommand.flags().contains(&flag),
+            command => command.spec().has_flag(flag),
+        }
+    }
+
+    /// Whether the command is one of `(P)SUBSCRIBE` and `(P)UNSUBSCRIBE`, that reply with
+    /// pushes sent through the notifier of the client rather than with a reply of their own.
+    pub fn is_subscription(&self) -> bool {
+        matches!(
+            self,
+            RedisCommand::SUBSCRIBE(_)
+                | RedisCommand::UNSUBSCRIBE(_)
+                | RedisCommand::PSUBSCRIBE(_)
+                | RedisCommand::PUNSUBSCRIBE(_)
+        )
+    }
+
+    pub fn build(values: Vec<RespDataType>) -> Result<Vec<RedisCommand>, RedisError> {
         let mut commands: Vec<RedisCommand> = Vec::new();
         for value in values {
             match value {
@@ -87,37 +227,17 @@ impl RedisCommand {
                         continue;
                     }
 
+                    let argc = arr.len();
                     let mut args = arr.into_iter();
-                    let command_name_result = args
+                    let command_name = args
                         .next()
                         .and_then(|f| f.to_string())
-                        .ok_or_else(|| "cant get the command name".to_string())?;
-                    let command_name_uppercase = command_name_result.to_ascii_uppercase();
-                    let command_name = command_name_uppercase.as_str();
-
-                    command_parser! {
-                        command_name,
-                        args,
-                        commands,
-                        "PING" => (PING, PingCommand),
-                        "ECHO" => (ECHO, EchoCommand),
-                        "GET" => (GET, GetCommand),
-                        "SET" => (SET, SetCommand),
-                        "RPUSH" => (RPUSH, RPushCommand),
-                        "LPUSH" => (LPUSH, LPushCommand),
-                        "LRANGE" => (LRANGE, LRangeCommand),
-                        "LLEN" => (LLEN, LLenCommand),
-                        "LPOP" => (LPOP, LPopCommand),
-                        "BLPOP" => (BLPOP, BLPopCommand),
-                        "ZADD" => (ZADD, ZAddCommand),
-                        "ZRANK" => (ZRANK, ZRankCommand),
-                        "ZRANGE" => (ZRANGE, ZRangeCommand),
-                        "ZCARD" => (ZCARD, ZCardCommand),
-                        "ZSCORE" => (ZSCORE, ZScoreCommand),
-                        "ZREM" => (ZREM, ZRemCommand),
-                        "TYPE" => (TYPE, KeyTypeCommand),
-                        "XADD" => (XADD, XAddCommand),
-                    }
+                        .ok_or_else(|| RedisError::custom("invalid command name"))?;
+                    let spec = Self::lookup(&command_name).ok_or_else(|| {
+                        RedisError::unknown_command(&command_name, args.as_slice())
+                    })?;
+                    spec.check_arity(argc)?;
+                    commands.push(Self::parse_with_spec(spec, &mut args)?);
                 }
                 RespDataType::BulkString(bytes) if bytes.eq_ignore_ascii_case(b"PING") => {
                     commands.push(RedisCommand::PING(PingCommand));
@@ -126,52 +246,12 @@ impl RedisCommand {
                     commands.push(RedisCommand::PING(PingCommand));
                 }
                 _ => {
-                    return Err("value type not correct".to_string());
+                    return Err(RedisError::custom("expected an array of bulk strings"));
                 }
             }
         }
         Ok(commands)
     }
-
-    pub fn parse(values: Vec<u8>) -> Result<Vec<RedisCommand>, String> {
-        let received_values = RespDataType::parse(values);
-        println!("Received values: {:?}", received_values);
-
-        let received_commands = Self::build(received_values)?;
-        println!("Received commands: {:?}", received_commands);
-
-        return Ok(received_commands);
-    }
-}
-
-impl RunnableCommand for RedisCommand {
-    async fn execute(
-        &self,
-        client_id: &str,
-        store: &Arc<RedisStore>,
-        client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match self {
-            RedisCommand::PING(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ECHO(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::GET(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::SET(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::LPUSH(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::RPUSH(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::LRANGE(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::LLEN(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::LPOP(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::BLPOP(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ZADD(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ZCARD(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ZRANK(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ZRANGE(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ZSCORE(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::ZREM(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::TYPE(cmd) => cmd.execute(client_id, store, client_notifier).await,
-            RedisCommand::XADD(cmd) => cmd.execute(client_id, store, client_notifier).await,
-        }
-    }
 }
 
 #[cfg(test)]
@@ -179,11 +259,53 @@ mod tests {
     use std::collections::HashMap;
 
     use crate::{
+        commands::command::{CommandListFilter, CommandSubcommand},
+        commands::config::ConfigSubcommand,
+        commands::function::FunctionSubcommand,
+        commands::replconf::ReplConfOption,
+        commands::script::ScriptSubcommand,
         commands::zadd::ZAddOptions,
+        functions::RestorePolicy,
         types::{key_value::KeyValue, sorted_set::SortedValue},
     };
 
     use super::*;
+    use bytes::Bytes;
+
+    #[test]
+    fn test_command_table_is_consistent() {
+        for (i, spec) in COMMAND_TABLE.iter().enumerate() {
+            assert_eq!(spec.name, spec.name.to_ascii_lowercase());
+            assert!(spec.arity != 0, "{} has no arity", spec.name);
+            assert!(
+                COMMAND_TABLE[i + 1..]
+                    .iter()
+                    .all(|other| other.name != spec.name),
+                "{} is declared twice",
+                spec.name
+            );
+            assert_eq!(
+                RedisCommand::lookup(&spec.name.to_ascii_uppercase()),
+                Some(*spec)
+            );
+        }
+        assert_eq!(RedisCommand::lookup("nope"), None);
+    }
+
+    #[test]
+    fn test_commands_build_checks_arity_before_parse() {
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["get", "a", "b"])]);
+        assert_eq!(result, Err(RedisError::WrongArity("get")));
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["Echo"])]);
+        assert_eq!(result, Err(RedisError::WrongArity("echo")));
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["llen", "a"])]);
+        assert_eq!(
+            result.unwrap()[0].spec(),
+            &<LLenCommand as DescribedCommand>::SPEC
+        );
+    }
 
     #[test]
     fn test_commands_build_ping() {
@@ -207,7 +329,7 @@ mod tests {
         assert_eq!(
             result,
             Ok(vec![RedisCommand::ECHO(EchoCommand {
-                message: "teste teste".to_string()
+                message: Bytes::from("teste teste")
             })])
         );
     }
@@ -232,7 +354,7 @@ mod tests {
             Ok(vec![RedisCommand::SET(SetCommand {
                 key: "test".to_string(),
                 value: KeyValue {
-                    value: "test".to_string(),
+                    value: Bytes::from("test"),
                     expired_at_millis: None
                 }
             })])
@@ -282,7 +404,7 @@ mod tests {
             result,
             Ok(vec![RedisCommand::RPUSH(RPushCommand {
                 key: "mylist".to_string(),
-                values: vec!["one".to_string(), "two".to_string()]
+                values: vec![Bytes::from("one"), Bytes::from("two")]
             })])
         );
     }
@@ -296,7 +418,7 @@ mod tests {
             result,
             Ok(vec![RedisCommand::LPUSH(LPushCommand {
                 key: "mylist".to_string(),
-                values: vec!["one".to_string(), "two".to_string()]
+                values: vec![Bytes::from("one"), Bytes::from("two")]
             })])
         );
     }
@@ -337,12 +459,16 @@ mod tests {
     #[test]
     fn test_commands_build_blpop() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["bLPOP", "mylist"])]);
-        assert_eq!(result, Err("timeout not found".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("blpop")));
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "bLPOP", "list1", "list2", "0",
+        ])]);
+        assert_eq!(result, Err(RedisError::WrongArity("blpop")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "bLPOP", "mylist", "err",
         ])]);
-        assert_eq!(result, Err("timeout is not a float".to_string()));
+        assert_eq!(result, Err(RedisError::InvalidTimeout));
 
         let result =
             RedisCommand::build(vec![RespDataType::new_array(vec!["bLPOP", "mylist", "2"])]);
@@ -409,19 +535,19 @@ mod tests {
     #[test]
     fn test_redis_key_value_is_expired() {
         let not_expired = KeyValue {
-            value: "value".to_string(),
+            value: Bytes::from("value"),
             expired_at_millis: Some(utils::now_millis() + 10000),
         };
         assert!(!not_expired.is_expired());
 
         let expired = KeyValue {
-            value: "value".to_string(),
+            value: Bytes::from("value"),
             expired_at_millis: Some(utils::now_millis() - 1),
         };
         assert!(expired.is_expired());
 
         let no_expiry = KeyValue {
-            value: "value".to_string(),
+            value: Bytes::from("value"),
             expired_at_millis: None,
         };
         assert!(!no_expiry.is_expired());
@@ -439,32 +565,45 @@ mod tests {
             RespDataType::bulk_string("PX"),
         ]
         .into_iter();
+        assert_eq!(KeyValue::parse(&mut args).unwrap_err(), RedisError::Syntax);
+
+        // PX with non-numeric value
+        let mut args = vec![
+            RespDataType::bulk_string("value"),
+            RespDataType::bulk_string("PX"),
+            RespDataType::bulk_string("abc"),
+        ]
+        .into_iter();
         assert_eq!(
             KeyValue::parse(&mut args).unwrap_err(),
-            "PX option requires a value"
+            RedisError::NotInteger
         );
 
-        // PX with non-numeric value
+        // PX must be positive
         let mut args = vec![
             RespDataType::bulk_string("value"),
             RespDataType::bulk_string("PX"),
-            RespDataType::bulk_string("abc"),
+            RespDataType::bulk_string("0"),
         ]
         .into_iter();
-        assert!(KeyValue::parse(&mut args)
-            .unwrap_err()
-            .contains("Cannot parse PX value"));
+        assert_eq!(
+            KeyValue::parse(&mut args).unwrap_err(),
+            RedisError::InvalidExpireTime("set")
+        );
     }
 
     #[test]
     fn test_sorted_value_parse_edge_cases() {
         // Empty args
         let mut args = vec![].into_iter();
-        assert_eq!(SortedValue::parse(&mut args), None);
+        assert_eq!(
+            SortedValue::parse(&mut args),
+            Err(RedisError::WrongArity("zadd"))
+        );
 
         // Odd number of args
         let mut args = vec![RespDataType::bulk_string("10.5")].into_iter();
-        assert_eq!(SortedValue::parse(&mut args), None);
+        assert_eq!(SortedValue::parse(&mut args), Err(RedisError::Syntax));
 
         // Invalid score
         let mut args = vec![
@@ -472,7 +611,7 @@ mod tests {
             RespDataType::bulk_string("member1"),
         ]
         .into_iter();
-        assert_eq!(SortedValue::parse(&mut args), None);
+        assert_eq!(SortedValue::parse(&mut args), Err(RedisError::NotFloat));
     }
 
     #[test]
@@ -495,11 +634,11 @@ mod tests {
                 values: vec![
                     SortedValue {
                         score: 1.0,
-                        member: "one".to_string()
+                        member: Bytes::from("one")
                     },
                     SortedValue {
                         score: 2.0,
-                        member: "two".to_string()
+                        member: Bytes::from("two")
                     }
                 ]
             })])
@@ -525,7 +664,7 @@ mod tests {
                 },
                 values: vec![SortedValue {
                     score: 1.0,
-                    member: "one".to_string()
+                    member: Bytes::from("one")
                 },]
             })])
         );
@@ -535,34 +674,28 @@ mod tests {
     fn test_commands_build_errors() {
         // RPUSH with no values
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["rpush", "mylist"])]);
-        assert_eq!(result, Err("RPUSH requires at least one value".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("rpush")));
 
         // LRANGE with missing args
         let result =
             RedisCommand::build(vec![RespDataType::new_array(vec!["lrange", "mylist", "0"])]);
-        assert_eq!(
-            result,
-            Err("Expected values for LRANGE start and end".to_string())
-        );
+        assert_eq!(result, Err(RedisError::WrongArity("lrange")));
 
         // LRANGE with non-integer args
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "lrange", "mylist", "a", "b",
         ])]);
-        assert_eq!(
-            result,
-            Err("Expected integer values for LRANGE start and end".to_string())
-        );
+        assert_eq!(result, Err(RedisError::NotInteger));
     }
 
     #[test]
     fn test_commands_build_zrank() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["zrank"])]);
-        assert_eq!(result, Err("ZADD command requires a key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zrank")));
 
         let result =
             RedisCommand::build(vec![RespDataType::new_array(vec!["zRank", "sorted_set"])]);
-        assert_eq!(result, Err("ZADD command requires a member".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zrank")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "Zrank",
@@ -574,7 +707,7 @@ mod tests {
             result,
             Ok(vec![RedisCommand::ZRANK(ZRankCommand {
                 key: "sorted_set".to_string(),
-                member: "mykey".to_string()
+                member: Bytes::from("mykey")
             })])
         );
     }
@@ -582,24 +715,18 @@ mod tests {
     #[test]
     fn test_commands_build_zrange() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["zrange"])]);
-        assert_eq!(result, Err("ZRANGE command requires a key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zrange")));
 
         let result =
             RedisCommand::build(vec![RespDataType::new_array(vec!["zRange", "sorted_set"])]);
-        assert_eq!(
-            result,
-            Err("Expected values for ZRANGE start and end".to_string())
-        );
+        assert_eq!(result, Err(RedisError::WrongArity("zrange")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "zRange",
             "sorted_set",
             "A",
         ])]);
-        assert_eq!(
-            result,
-            Err("Expected values for ZRANGE start and end".to_string())
-        );
+        assert_eq!(result, Err(RedisError::WrongArity("zrange")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "zRange",
@@ -607,10 +734,7 @@ mod tests {
             "A",
             "A",
         ])]);
-        assert_eq!(
-            result,
-            Err("Expected integer values for ZRANGE start and end".to_string())
-        );
+        assert_eq!(result, Err(RedisError::NotInteger));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "zRange",
@@ -618,10 +742,7 @@ mod tests {
             "1",
             "A",
         ])]);
-        assert_eq!(
-            result,
-            Err("Expected integer values for ZRANGE start and end".to_string())
-        );
+        assert_eq!(result, Err(RedisError::NotInteger));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "zRange",
@@ -635,7 +756,8 @@ mod tests {
             Ok(vec![RedisCommand::ZRANGE(ZRangeCommand {
                 key: "sorted_set".to_string(),
                 start: 0,
-                end: 1
+                end: 1,
+                with_scores: false,
             })])
         );
     }
@@ -643,7 +765,7 @@ mod tests {
     #[test]
     fn test_commands_build_zcard() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["ZCARD"])]);
-        assert_eq!(result, Err("ZCARD command requires a key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zcard")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["ZCARD", "zset_key"])]);
         assert_eq!(
@@ -657,11 +779,11 @@ mod tests {
     #[test]
     fn test_commands_build_zscore() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["zscore"])]);
-        assert_eq!(result, Err("ZSCORE command requires a key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zscore")));
 
         let result =
             RedisCommand::build(vec![RespDataType::new_array(vec!["zscore", "sorted_set"])]);
-        assert_eq!(result, Err("ZSCORE command requires a member".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zscore")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "zscore",
@@ -673,7 +795,7 @@ mod tests {
             result,
             Ok(vec![RedisCommand::ZSCORE(ZScoreCommand {
                 key: "sorted_set".to_string(),
-                member: "mykey".to_string()
+                member: Bytes::from("mykey")
             })])
         );
     }
@@ -681,10 +803,10 @@ mod tests {
     #[test]
     fn test_commands_build_zrem() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["zrem"])]);
-        assert_eq!(result, Err("ZREM command requires a key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zrem")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["zrem", "sorted_set"])]);
-        assert_eq!(result, Err("ZREM command requires a member".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("zrem")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "zrem",
@@ -696,15 +818,492 @@ mod tests {
             result,
             Ok(vec![RedisCommand::ZREM(ZRemCommand {
                 key: "sorted_set".to_string(),
-                member: "mykey".to_string()
+                member: Bytes::from("mykey")
             })])
         );
     }
 
+    #[test]
+    fn test_commands_build_zrange_withscores() {
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "zrange",
+            "sorted_set",
+            "0",
+            "-1",
+            "withscores",
+        ])]);
+        assert_eq!(
+            result,
+            Ok(vec![RedisCommand::ZRANGE(ZRangeCommand {
+                key: "sorted_set".to_string(),
+                start: 0,
+                end: -1,
+                with_scores: true,
+            })])
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "zrange",
+            "sorted_set",
+            "0",
+            "-1",
+            "limit",
+        ])]);
+        assert_eq!(result, Err(RedisError::Syntax));
+    }
+
+    #[test]
+    fn test_commands_build_hello() {
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["hello"])]);
+        assert_eq!(
+            result,
+            Ok(vec![RedisCommand::HELLO(HelloCommand {
+                protocol: None,
+                auth: None,
+                client_name: None,
+            })])
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "HELLO", "3", "auth", "default", "pass", "SETNAME", "conn",
+        ])]);
+        assert_eq!(
+            result,
+            Ok(vec![RedisCommand::HELLO(HelloCommand {
+                protocol: Some(3),
+                auth: Some(("default".to_string(), "pass".to_string())),
+                client_name: Some("conn".to_string()),
+            })])
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["HELLO", "three"])]);
+        assert_eq!(
+            result,
+            Err(RedisError::custom(
+                "Protocol version is not an integer or out of range"
+            ))
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "HELLO", "3", "AUTH", "default",
+        ])]);
+        assert_eq!(
+            result,
+            Err(RedisError::custom("Syntax error in HELLO option 'AUTH'"))
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["HELLO", "3", "FOO"])]);
+        assert_eq!(
+            result,
+            Err(RedisError::custom("Syntax error in HELLO option 'FOO'"))
+        );
+    }
+
+    #[test]
+    fn test_commands_build_auth() {
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["AUTH", "pass"])]);
+        assert_eq!(
+            result,
+            Ok(vec![RedisCommand::AUTH(AuthCommand {
+                username: None,
+                password: "pass".to_string(),
+            })])
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "auth", "default", "pass",
+        ])]);
+        assert_eq!(
+            result,
+            Ok(vec![RedisCommand::AUTH(AuthCommand {
+                username: Some("default".to_string()),
+                password: "pass".to_string(),
+            })])
+        );
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec![
+            "AUTH", "default", "pass", "extra",
+        ])]);
+        assert_eq!(result, Err(RedisError::Syntax));
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["AUTH"])]);
+        assert_eq!(result, Err(RedisError::WrongArity("auth")));
+    }
+
+    #[test]
+    fn test_commands_build_command() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        let command = |subcommand| Ok(vec![RedisCommand::COMMAND(CommandCommand { subcommand })]);
+
+        assert_eq!(
+            build(vec!["command"]),
+            command(CommandSubcommand::Info(vec![]))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "count"]),
+            command(CommandSubcommand::Count)
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "count", "x"]),
+            Err(RedisError::WrongArity("command|count"))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "INFO", "get", "set"]),
+            command(CommandSubcommand::Info(vec![
+                "get".to_string(),
+                "set".to_string()
+            ]))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "DOCS"]),
+            command(CommandSubcommand::Docs(vec![]))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "LIST", "FILTERBY", "aclcat", "list"]),
+            command(CommandSubcommand::List(Some(CommandListFilter::AclCat(
+                "list".to_string()
+            ))))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "LIST", "FILTERBY", "aclcat"]),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "GETKEYS", "get", "foo"]),
+            command(CommandSubcommand::GetKeys(vec![
+                Bytes::from("get"),
+                Bytes::from("foo")
+            ]))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "GETKEYS"]),
+            Err(RedisError::WrongArity("command|getkeys"))
+        );
+        assert_eq!(
+            build(vec!["COMMAND", "foo"]),
+            Err(RedisError::UnknownSubcommand("foo".to_string(), "COMMAND"))
+        );
+    }
+
+    #[test]
+    fn test_commands_build_bgsave() {
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["bgsave", "SCHEDULE"])]);
+        assert_eq!(result, Ok(vec![RedisCommand::BGSAVE(BgSaveCommand)]));
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["bgsave", "now"])]);
+        assert_eq!(result, Err(RedisError::Syntax));
+
+        let result = RedisCommand::build(vec![RespDataType::new_array(vec!["save", "now"])]);
+        assert_eq!(result, Err(RedisError::WrongArity("save")));
+    }
+
+    #[test]
+    fn test_commands_build_restore() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        assert_eq!(
+            build(vec![
+                "restore", "k", "1000", "p", "replace", "IDLETIME", "5"
+            ]),
+            Ok(vec![RedisCommand::RESTORE(RestoreCommand {
+                key: "k".to_string(),
+                ttl: 1000,
+                payload: Bytes::from("p"),
+                replace: true,
+                absttl: false,
+            })])
+        );
+        assert_eq!(
+            build(vec!["restore", "k", "-1", "p"]),
+            Err(RedisError::custom("Invalid TTL value, must be >= 0"))
+        );
+        assert_eq!(
+            build(vec!["restore", "k", "0", "p", "FREQ", "256"]),
+            Err(RedisError::custom(
+                "Invalid FREQ value, must be >= 0 and <= 255"
+            ))
+        );
+        assert_eq!(
+            build(vec!["restore", "k", "0", "p", "IDLETIME", "1", "FREQ", "1"]),
+            Err(RedisError::Syntax)
+        );
+        assert_eq!(
+            build(vec!["restore", "k", "0"]),
+            Err(RedisError::WrongArity("restore"))
+        );
+    }
+
+    #[test]
+    fn test_commands_build_replconf() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        assert_eq!(
+            build(vec!["REPLCONF", "listening-port", "6380", "capa", "psync2"]),
+            Ok(vec![RedisCommand::REPLCONF(ReplConfCommand {
+                options: vec![
+                    ReplConfOption::ListeningPort(6380),
+                    ReplConfOption::Capa("psync2".to_string()),
+                ]
+            })])
+        );
+        assert_eq!(build(vec!["REPLCONF", "ACK"]), Err(RedisError::Syntax));
+        assert_eq!(
+            build(vec!["REPLCONF", "ACK", "x"]),
+            Err(RedisError::NotInteger)
+        );
+        assert_eq!(
+            build(vec!["REPLCONF", "foo", "bar"]),
+            Err(RedisError::custom("Unrecognized REPLCONF option: foo"))
+        );
+        assert_eq!(
+            build(vec!["PSYNC", "?", "-1"]),
+            Ok(vec![RedisCommand::PSYNC(PSyncCommand {
+                replid: "?".to_string(),
+                offset: -1
+            })])
+        );
+    }
+
+    #[test]
+    fn test_commands_build_replicaof() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        assert_eq!(
+            build(vec!["REPLICAOF", "localhost", "6379"]),
+            Ok(vec![RedisCommand::REPLICAOF(ReplicaOfCommand {
+                master: Some(("localhost".to_string(), 6379))
+            })])
+        );
+        assert_eq!(
+            build(vec!["replicaof", "no", "One"]),
+            Ok(vec![RedisCommand::REPLICAOF(ReplicaOfCommand {
+                master: None
+            })])
+        );
+        assert_eq!(
+            build(vec!["REPLICAOF", "localhost", "port"]),
+            Err(RedisError::NotInteger)
+        );
+    }
+
+    #[test]
+    fn test_commands_build_wait() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        assert_eq!(
+            build(vec!["WAIT", "2", "100"]),
+            Ok(vec![RedisCommand::WAIT(WaitCommand {
+                numreplicas: 2,
+                timeout: 100
+            })])
+        );
+        assert_eq!(
+            build(vec!["WAITAOF", "1", "0", "0"]),
+            Ok(vec![RedisCommand::WAITAOF(WaitAofCommand {
+                numlocal: 1,
+                numreplicas: 0,
+                timeout: 0
+            })])
+        );
+        assert_eq!(
+            build(vec!["WAIT", "1", "-1"]),
+            Err(RedisError::NegativeTimeout)
+        );
+        assert_eq!(
+            build(vec!["WAITAOF", "1", "0", "1.5"]),
+            Err(RedisError::custom(
+                "timeout is not an integer or out of range"
+            ))
+        );
+        assert_eq!(build(vec!["WAIT", "x", "0"]), Err(RedisError::NotInteger));
+    }
+
+    #[test]
+    fn test_commands_build_function() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        let function =
+            |subcommand| Ok(vec![RedisCommand::FUNCTION(FunctionCommand { subcommand })]);
+        assert_eq!(
+            build(vec!["FUNCTION", "LOAD", "REPLACE", "code"]),
+            function(FunctionSubcommand::Load {
+                code: "code".to_string(),
+                replace: true
+            })
+        );
+        assert_eq!(
+            build(vec!["FUNCTION", "list", "libraryname", "l*", "withcode"]),
+            function(FunctionSubcommand::List {
+                with_code: true,
+                pattern: Some("l*".to_string())
+            })
+        );
+        assert_eq!(
+            build(vec!["FUNCTION", "RESTORE", "payload", "FLUSH"]),
+            function(FunctionSubcommand::Restore {
+                payload: Bytes::from("payload"),
+                policy: RestorePolicy::Flush
+            })
+        );
+        assert_eq!(
+            build(vec!["FUNCTION", "RESTORE", "payload", "MERGE"]),
+            Err(RedisError::custom(
+                "Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE."
+            ))
+        );
+        assert_eq!(
+            build(vec!["FUNCTION", "LIST", "WITHCODES"]),
+            Err(RedisError::custom("Unknown argument WITHCODES"))
+        );
+        // The flags are those of the subcommand.
+        let flags = |args| build(args).unwrap().remove(0);
+        assert!(flags(vec!["FUNCTION", "DELETE", "lib"]).has_flag(CommandFlag::Write));
+        assert!(!flags(vec!["FUNCTION", "DUMP"]).has_flag(CommandFlag::Write));
+        assert!(flags(vec!["FUNCTION", "KILL"]).has_flag(CommandFlag::AllowBusy));
+    }
+
+    #[test]
+    fn test_commands_build_pubsub() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        assert_eq!(
+            build(vec!["SUBSCRIBE", "news", "weather"]),
+            Ok(vec![RedisCommand::SUBSCRIBE(SubscribeCommand {
+                channels: vec![Bytes::from("news"), Bytes::from("weather")]
+            })])
+        );
+        assert_eq!(
+            build(vec!["PUNSUBSCRIBE"]),
+            Ok(vec![RedisCommand::PUNSUBSCRIBE(PUnsubscribeCommand {
+                patterns: Vec::new()
+            })])
+        );
+        assert_eq!(
+            build(vec!["PUBLISH", "news", "hi"]),
+            Ok(vec![RedisCommand::PUBLISH(PublishCommand {
+                channel: Bytes::from("news"),
+                message: Bytes::from("hi")
+            })])
+        );
+        assert_eq!(
+            build(vec!["SUBSCRIBE"]),
+            Err(RedisError::WrongArity("subscribe"))
+        );
+        assert!(build(vec!["PSUBSCRIBE", "n*"]).unwrap()[0].is_subscription());
+        assert!(!build(vec!["PUBLISH", "news", "hi"]).unwrap()[0].is_subscription());
+    }
+
+    #[test]
+    fn test_commands_build_eval() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        assert_eq!(
+            build(vec!["EVAL", "return 1", "2", "a", "b", "c"]),
+            Ok(vec![RedisCommand::EVAL(EvalCommand {
+                script: "return 1".to_string(),
+                keys: vec![Bytes::from("a"), Bytes::from("b")],
+                args: vec![Bytes::from("c")],
+            })])
+        );
+        assert_eq!(
+            build(vec!["EVALSHA_RO", "abc", "0"]),
+            Ok(vec![RedisCommand::EVALSHARO(EvalShaRoCommand {
+                sha: "abc".to_string(),
+                keys: vec![],
+                args: vec![],
+            })])
+        );
+        assert_eq!(
+            build(vec!["EVAL", "return 1", "2", "a"]),
+            Err(RedisError::custom(
+                "Number of keys can't be greater than number of args"
+            ))
+        );
+        assert_eq!(
+            build(vec!["EVAL_RO", "return 1", "-1"]),
+            Err(RedisError::custom("Number of keys can't be negative"))
+        );
+        assert_eq!(
+            build(vec!["SCRIPT", "FLUSH", "ASYNC"]),
+            Ok(vec![RedisCommand::SCRIPT(ScriptCommand {
+                subcommand: ScriptSubcommand::Flush
+            })])
+        );
+        assert_eq!(
+            build(vec!["SCRIPT", "LOAD"]),
+            Err(RedisError::WrongArity("script|load"))
+        );
+        assert_eq!(
+            build(vec!["SCRIPT", "DEBUG", "YES"]),
+            Err(RedisError::UnknownSubcommand("DEBUG".to_string(), "SCRIPT"))
+        );
+    }
+
+    #[test]
+    fn test_commands_build_config() {
+        let build = |args: Vec<&str>| RedisCommand::build(vec![RespDataType::new_array(args)]);
+        let config = |subcommand| Ok(vec![RedisCommand::CONFIG(ConfigCommand { subcommand })]);
+
+        assert_eq!(
+            build(vec!["CONFIG", "get", "max*", "port"]),
+            config(ConfigSubcommand::Get(vec![
+                "max*".to_string(),
+                "port".to_string()
+            ]))
+        );
+        assert_eq!(
+            build(vec!["config", "SET", "port", "7000", "save", ""]),
+            config(ConfigSubcommand::Set(vec![
+                ("port".to_string(), "7000".to_string()),
+                ("save".to_string(), "".to_string()),
+            ]))
+        );
+        assert_eq!(
+            build(vec!["CONFIG", "RESETSTAT"]),
+            config(ConfigSubcommand::ResetStat)
+        );
+        let binary = |name: &[u8], value: &[u8]| {
+            RedisCommand::build(vec![RespDataType::Array(vec![
+                RespDataType::bulk_string("CONFIG"),
+                RespDataType::bulk_string("SET"),
+                RespDataType::bulk_string(name),
+                RespDataType::bulk_string(value),
+            ])])
+        };
+        assert_eq!(
+            binary(b"requirepass", b"\xff\xfe"),
+            Err(RedisError::custom(
+                "CONFIG SET failed (possibly related to argument 'requirepass') - argument must \
+                 be valid UTF-8"
+            ))
+        );
+        assert_eq!(
+            binary(b"port\xff", b"7000"),
+            Err(RedisError::custom(
+                "Unknown option or number of arguments for CONFIG SET - 'port\u{fffd}'"
+            ))
+        );
+        assert_eq!(
+            build(vec!["CONFIG", "rewrite"]),
+            config(ConfigSubcommand::Rewrite)
+        );
+
+        assert_eq!(build(vec!["CONFIG"]), Err(RedisError::WrongArity("config")));
+        assert_eq!(
+            build(vec!["CONFIG", "GET"]),
+            Err(RedisError::WrongArity("config|get"))
+        );
+        assert_eq!(
+            build(vec!["CONFIG", "SET", "port"]),
+            Err(RedisError::WrongArity("config|set"))
+        );
+        assert_eq!(
+            build(vec!["CONFIG", "REWRITE", "now"]),
+            Err(RedisError::WrongArity("config|rewrite"))
+        );
+        assert_eq!(
+            build(vec!["CONFIG", "FOO"]),
+            Err(RedisError::UnknownSubcommand("FOO".to_string(), "CONFIG"))
+        );
+    }
+
     #[test]
     fn test_commands_build_type() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["type"])]);
-        assert_eq!(result, Err("TYPE command requires a key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("type")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["type", "sorted_set"])]);
 
@@ -719,23 +1318,17 @@ mod tests {
     #[test]
     fn test_commands_build_xadd() {
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["xadd"])]);
-        assert_eq!(
-            result,
-            Err("XADD command requires a stream key".to_string())
-        );
+        assert_eq!(result, Err(RedisError::WrongArity("xadd")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec!["xadd", "my_stream"])]);
-        assert_eq!(result, Err("XADD command requires a entry key".to_string()));
+        assert_eq!(result, Err(RedisError::WrongArity("xadd")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "xadd",
             "my_stream",
             "0-1",
         ])]);
-        assert_eq!(
-            result,
-            Err("XADD command requires at least one key-value pair".to_string())
-        );
+        assert_eq!(result, Err(RedisError::WrongArity("xadd")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "xadd",
@@ -743,10 +1336,7 @@ mod tests {
             "0-1",
             "temperatura",
         ])]);
-        assert_eq!(
-            result,
-            Err("XADD command requires key-value pair".to_string())
-        );
+        assert_eq!(result, Err(RedisError::WrongArity("xadd")));
 
         let result = RedisCommand::build(vec![RespDataType::new_array(vec![
             "xadd",
@@ -0,0 +1,97 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuthCommand {
+    pub username: Option<String>,
+    pub password: String,
+}
+
+impl DescribedCommand for AuthCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "auth",
+        summary: "Authenticates the connection.",
+        since: "1.0.0",
+        arity: -2,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Connection],
+    };
+}
+
+impl ParseableCommand for AuthCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let first = Self::get_arg_as_string(args, RedisError::WrongArity("auth"))?;
+        let command = match args.next() {
+            Some(password) => AuthCommand {
+                username: Some(first),
+                password: password.to_string().ok_or(RedisError::Syntax)?,
+            },
+            None => AuthCommand {
+                username: None,
+                password: first,
+            },
+        };
+        if args.len() != 0 {
+            return Err(RedisError::Syntax);
+        }
+        Ok(command)
+    }
+}
+
+/// Checks the credentials against the only user there is, `default`, whose password is
+/// `requirepass`. Marks the client as authenticated on success.
+pub fn authenticate(
+    client: &mut RedisClient,
+    server: &Arc<RedisServer>,
+    username: &str,
+    password: &str,
+) -> Result<(), RedisError> {
+    let valid = match &server.config().requirepass {
+        Some(expected) => constant_time_eq(expected.as_bytes(), password.as_bytes()),
+        None => true,
+    };
+    if username != "default" || !valid {
+        return Err(RedisError::WrongPass);
+    }
+    client.authenticated = true;
+    Ok(())
+}
+
+/// Compares without returning early, so the time taken doesn't leak how much of the password
+/// matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl RunnableCommand for AuthCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        if self.username.is_none() && server.config().requirepass.is_none() {
+            return Err(RedisError::custom(
+                "AUTH <password> called without any password configured for the default user. \
+                 Are you sure your configuration is correct?",
+            ));
+        }
+        let username = self.username.as_deref().unwrap_or("default");
+        authenticate(client, server, username, &self.password)?;
+        Ok(RespDataType::ok())
+    }
+}
@@ -0,0 +1,44 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BgRewriteAofCommand;
+
+impl DescribedCommand for BgRewriteAofCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "bgrewriteaof",
+        summary: "Asynchronously rewrites the append-only file to disk.",
+        since: "1.0.0",
+        arity: 1,
+        flags: &[CommandFlag::Admin, CommandFlag::NoScript],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for BgRewriteAofCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(BgRewriteAofCommand)
+    }
+}
+
+impl RunnableCommand for BgRewriteAofCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server.bgrewriteaof()?;
+        Ok(RespDataType::simple_string(
+            "Background append only file rewriting started",
+        ))
+    }
+}
@@ -0,0 +1,49 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct BgSaveCommand;
+
+impl DescribedCommand for BgSaveCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "bgsave",
+        summary: "Asynchronously saves the database(s) to disk.",
+        since: "1.0.0",
+        arity: -1,
+        flags: &[CommandFlag::Admin, CommandFlag::NoScript],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for BgSaveCommand {
+    /// `SCHEDULE` only matters while an AOF rewrite runs, which never blocks a save here.
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        match args.next().and_then(|arg| arg.to_string()) {
+            None => Ok(BgSaveCommand),
+            Some(arg) if arg.eq_ignore_ascii_case("schedule") && args.len() == 0 => {
+                Ok(BgSaveCommand)
+            }
+            Some(_) => Err(RedisError::Syntax),
+        }
+    }
+}
+
+impl RunnableCommand for BgSaveCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server.bgsave()?;
+        Ok(RespDataType::simple_string("Background saving started"))
+    }
+}
@@ -1,7 +1,8 @@
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct BLPopCommand {
@@ -9,14 +10,38 @@ pub struct BLPopCommand {
     pub timeout: f64,
 }
 
+impl DescribedCommand for BLPopCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "blpop",
+        summary: "Removes and returns the first element in a list. Blocks until an element is available otherwise.",
+        since: "2.0.0",
+        // A single key is supported, unlike Redis.
+        arity: 3,
+        flags: &[CommandFlag::Write, CommandFlag::Blocking],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[
+            AclCategory::Write,
+            AclCategory::List,
+            AclCategory::Slow,
+            AclCategory::Blocking,
+        ],
+    };
+}
+
 impl ParseableCommand for BLPopCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "BLPOP command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("blpop"))?;
         let timeout = args
             .next()
-            .ok_or_else(|| "timeout not found")?
+            .ok_or(RedisError::WrongArity("blpop"))?
             .to_float()
-            .ok_or_else(|| "timeout is not a float".to_string())?;
+            .filter(|timeout| timeout.is_finite())
+            .ok_or(RedisError::InvalidTimeout)?;
+        if timeout < 0.0 {
+            return Err(RedisError::NegativeTimeout);
+        }
 
         Ok(BLPopCommand { key, timeout })
     }
@@ -25,10 +50,11 @@ impl ParseableCommand for BLPopCommand {
 impl RunnableCommand for BLPopCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        // Replaying the wait makes no sense, only the pop is propagated, as it happens.
+        client.argv.clear();
         let timeout_duration = if self.timeout > 0.0 {
             Some(std::time::Duration::from_secs_f64(self.timeout))
         } else {
@@ -37,26 +63,42 @@ impl RunnableCommand for BLPopCommand {
         let start_time = std::time::Instant::now();
 
         loop {
-            // Try to pop
-            if let Some(mut list) = store.get_list(&self.key).await {
+            // Held from the attempt to the propagation of the pop, like for any other command,
+            // but not while waiting. Inside a transaction or a script the caller holds it.
+            let exec = match client.deny_blocking() {
+                true => None,
+                false => Some(server.exec_shared().await),
+            };
+            // Before the attempt, so that a push right after it isn't missed.
+            let mut receiver = server.store.subscribe_to_key(&self.key).await;
+            if let Some(mut list) = server.store.get_list(&self.key).await {
                 if let Some(val) = list.pop_front() {
-                    return Some(RespDataType::Array(vec![
+                    drop(list);
+                    server.store.shrink(val.len());
+                    server.store.notify_key_modified(&self.key).await;
+                    client.argv = vec![Bytes::from("LPOP"), Bytes::from(self.key.clone())];
+                    server.propagate_write(client);
+                    client.argv.clear();
+                    return Ok(RespDataType::Array(vec![
                         RespDataType::bulk_string(&self.key),
                         RespDataType::bulk_string(&val),
                     ]));
                 }
             }
 
+            if client.deny_blocking() {
+                return Ok(RespDataType::NullArray);
+            }
+
             // Check timeout
             if let Some(timeout) = timeout_duration {
                 if start_time.elapsed() >= timeout {
-                    return Some(RespDataType::NullArray);
+                    return Ok(RespDataType::NullArray);
                 }
             }
+            drop(exec);
 
             // Wait for notification or timeout
-            let mut receiver = store.subscribe_to_key(&self.key).await;
-
             if let Some(timeout) = timeout_duration {
                 let remaining_timeout = timeout.saturating_sub(start_time.elapsed());
                 tokio::select! {
@@ -64,7 +106,7 @@ impl RunnableCommand for BLPopCommand {
                         // Key was modified, loop again
                     }
                     _ = tokio::time::sleep(remaining_timeout) => {
-                        return Some(RespDataType::NullArray); // Timeout
+                        return Ok(RespDataType::NullArray); // Timeout
                     }
                 }
             } else {
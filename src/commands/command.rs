@@ -0,0 +1,292 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use super::{RedisCommand, COMMAND_TABLE};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer, utils,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CommandListFilter {
+    Module(String),
+    AclCat(String),
+    Pattern(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CommandSubcommand {
+    /// `COMMAND` and `COMMAND INFO [name ...]`, an empty list means every command.
+    Info(Vec<String>),
+    Count,
+    Docs(Vec<String>),
+    List(Option<CommandListFilter>),
+    /// The full command line to extract keys from, command name included.
+    GetKeys(Vec<Bytes>),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommandCommand {
+    pub subcommand: CommandSubcommand,
+}
+
+impl DescribedCommand for CommandCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "command",
+        summary: "Returns detailed information about all commands.",
+        since: "2.8.13",
+        arity: -1,
+        flags: &[CommandFlag::Loading, CommandFlag::Stale],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Connection],
+    };
+}
+
+impl ParseableCommand for CommandCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let subcommand_name = match args.next() {
+            Some(arg) => arg.to_string().unwrap_or_default(),
+            None => {
+                return Ok(CommandCommand {
+                    subcommand: CommandSubcommand::Info(vec![]),
+                })
+            }
+        };
+
+        let subcommand = match subcommand_name.to_ascii_uppercase().as_str() {
+            "COUNT" => {
+                if args.len() != 0 {
+                    return Err(RedisError::WrongArity("command|count"));
+                }
+                CommandSubcommand::Count
+            }
+            "INFO" => CommandSubcommand::Info(Self::names(args)),
+            "DOCS" => CommandSubcommand::Docs(Self::names(args)),
+            "LIST" => CommandSubcommand::List(Self::parse_filter(args)?),
+            "GETKEYS" => {
+                let command_line: Vec<Bytes> = args.filter_map(|arg| arg.into_bytes()).collect();
+                if command_line.is_empty() {
+                    return Err(RedisError::WrongArity("command|getkeys"));
+                }
+                CommandSubcommand::GetKeys(command_line)
+            }
+            _ => return Err(RedisError::UnknownSubcommand(subcommand_name, "COMMAND")),
+        };
+        Ok(CommandCommand { subcommand })
+    }
+}
+
+impl CommandCommand {
+    fn names(args: &mut IntoIter<RespDataType>) -> Vec<String> {
+        args.map(|arg| arg.to_string().unwrap_or_default())
+            .collect()
+    }
+
+    fn parse_filter(
+        args: &mut IntoIter<RespDataType>,
+    ) -> Result<Option<CommandListFilter>, RedisError> {
+        if args.len() == 0 {
+            return Ok(None);
+        }
+        if args.len() != 3 {
+            return Err(RedisError::Syntax);
+        }
+        let filterby = Self::get_arg_as_string(args, RedisError::Syntax)?;
+        let kind = Self::get_arg_as_string(args, RedisError::Syntax)?;
+        let value = Self::get_arg_as_string(args, RedisError::Syntax)?;
+        if !filterby.eq_ignore_ascii_case("FILTERBY") {
+            return Err(RedisError::Syntax);
+        }
+        match kind.to_ascii_uppercase().as_str() {
+            "MODULE" => Ok(Some(CommandListFilter::Module(value))),
+            "ACLCAT" => Ok(Some(CommandListFilter::AclCat(value))),
+            "PATTERN" => Ok(Some(CommandListFilter::Pattern(value))),
+            _ => Err(RedisError::Syntax),
+        }
+    }
+
+    fn info(spec: &CommandSpec) -> RespDataType {
+        RespDataType::Array(vec![
+            RespDataType::bulk_string(spec.name),
+            RespDataType::Integer(spec.arity),
+            RespDataType::Set(
+                spec.flags
+                    .iter()
+                    .map(|flag| RespDataType::simple_string(flag.name()))
+                    .collect(),
+            ),
+            RespDataType::Integer(spec.first_key),
+            RespDataType::Integer(spec.last_key),
+            RespDataType::Integer(spec.step),
+            RespDataType::Set(
+                spec.acl_categories
+                    .iter()
+                    .map(|category| RespDataType::simple_string(&format!("@{}", category.name())))
+                    .collect(),
+            ),
+            // Tips
+            RespDataType::Array(vec![]),
+            Self::key_specs(spec),
+            // Subcommands
+            RespDataType::Array(vec![]),
+        ])
+    }
+
+    /// Redis 7 key specs, derived from the legacy first/last/step triple.
+    fn key_specs(spec: &CommandSpec) -> RespDataType {
+        if spec.first_key <= 0 {
+            return RespDataType::Array(vec![]);
+        }
+        let flags = if spec.has_flag(CommandFlag::Write) {
+            ["RW", "UPDATE"]
+        } else {
+            ["RO", "ACCESS"]
+        };
+        let last_key = if spec.last_key >= 0 {
+            spec.last_key - spec.first_key
+        } else {
+            spec.last_key
+        };
+        let field = |name: &str, value: RespDataType| (RespDataType::bulk_string(name), value);
+
+        RespDataType::Array(vec![RespDataType::Map(vec![
+            field(
+                "flags",
+                RespDataType::Set(
+                    flags
+                        .iter()
+                        .map(|flag| RespDataType::simple_string(flag))
+                        .collect(),
+                ),
+            ),
+            field(
+                "begin_search",
+                RespDataType::Map(vec![
+                    field("type", RespDataType::bulk_string("index")),
+                    field(
+                        "spec",
+                        RespDataType::Map(vec![field(
+                            "index",
+                            RespDataType::Integer(spec.first_key),
+                        )]),
+                    ),
+                ]),
+            ),
+            field(
+                "find_keys",
+                RespDataType::Map(vec![
+                    field("type", RespDataType::bulk_string("range")),
+                    field(
+                        "spec",
+                        RespDataType::Map(vec![
+                            field("lastkey", RespDataType::Integer(last_key)),
+                            field("keystep", RespDataType::Integer(spec.step)),
+                            field("limit", RespDataType::Integer(0)),
+                        ]),
+                    ),
+                ]),
+            ),
+        ])])
+    }
+
+    fn docs(spec: &CommandSpec) -> RespDataType {
+        RespDataType::Map(vec![
+            (
+                RespDataType::bulk_string("summary"),
+                RespDataType::bulk_string(spec.summary),
+            ),
+            (
+                RespDataType::bulk_string("since"),
+                RespDataType::bulk_string(spec.since),
+            ),
+            (
+                RespDataType::bulk_string("group"),
+                RespDataType::bulk_string(spec.group()),
+            ),
+        ])
+    }
+
+    fn matches_filter(spec: &CommandSpec, filter: &Option<CommandListFilter>) -> bool {
+        match filter {
+            None => true,
+            // There is no module support, every command is built in.
+            Some(CommandListFilter::Module(_)) => false,
+            Some(CommandListFilter::AclCat(category)) => spec.has_category(category),
+            Some(CommandListFilter::Pattern(pattern)) => {
+                utils::glob_match(pattern.as_bytes(), spec.name.as_bytes(), true)
+            }
+        }
+    }
+
+    fn get_keys(command_line: &[Bytes]) -> Result<RespDataType, RedisError> {
+        let spec = std::str::from_utf8(&command_line[0])
+            .ok()
+            .and_then(RedisCommand::lookup)
+            .ok_or_else(|| RedisError::custom("Invalid command specified"))?;
+        if spec.check_arity(command_line.len()).is_err() {
+            return Err(RedisError::custom(
+                "Invalid number of arguments specified for command",
+            ));
+        }
+        let positions = spec.key_positions(command_line.len());
+        if positions.is_empty() {
+            return Err(RedisError::custom("The command has no key arguments"));
+        }
+        Ok(RespDataType::Array(
+            positions
+                .into_iter()
+                .map(|i| RespDataType::bulk_string(&command_line[i]))
+                .collect(),
+        ))
+    }
+}
+
+impl RunnableCommand for CommandCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match &self.subcommand {
+            CommandSubcommand::Info(names) if names.is_empty() => Ok(RespDataType::Array(
+                COMMAND_TABLE.iter().map(|spec| Self::info(spec)).collect(),
+            )),
+            CommandSubcommand::Info(names) => Ok(RespDataType::Array(
+                names
+                    .iter()
+                    .map(|name| match RedisCommand::lookup(name) {
+                        Some(spec) => Self::info(spec),
+                        None => RespDataType::Null,
+                    })
+                    .collect(),
+            )),
+            CommandSubcommand::Count => Ok(RespDataType::Integer(COMMAND_TABLE.len() as i64)),
+            CommandSubcommand::Docs(names) => {
+                let specs: Vec<&CommandSpec> = if names.is_empty() {
+                    COMMAND_TABLE.to_vec()
+                } else {
+                    names
+                        .iter()
+                        .filter_map(|name| RedisCommand::lookup(name))
+                        .collect()
+                };
+                Ok(RespDataType::Map(
+                    specs
+                        .into_iter()
+                        .map(|spec| (RespDataType::bulk_string(spec.name), Self::docs(spec)))
+                        .collect(),
+                ))
+            }
+            CommandSubcommand::List(filter) => Ok(RespDataType::Array(
+                COMMAND_TABLE
+                    .iter()
+                    .filter(|spec| Self::matches_filter(spec, filter))
+                    .map(|spec| RespDataType::bulk_string(spec.name))
+                    .collect(),
+            )),
+            CommandSubcommand::GetKeys(command_line) => Self::get_keys(command_line),
+        }
+    }
+}
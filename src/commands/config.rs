@@ -0,0 +1,206 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient,
+    config::{Parameter, PARAMETERS},
+    error::RedisError,
+    resp::RespDataType,
+    server::RedisServer,
+    utils,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ConfigSubcommand {
+    /// Glob patterns matched against parameter names and aliases.
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+    ResetStat,
+    Rewrite,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ConfigCommand {
+    pub subcommand: ConfigSubcommand,
+}
+
+impl DescribedCommand for ConfigCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "config",
+        summary: "Gets, sets and persists configuration parameters at runtime.",
+        since: "2.0.0",
+        arity: -2,
+        flags: &[
+            CommandFlag::Admin,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for ConfigCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let subcommand_name = Self::get_arg_as_string(args, RedisError::WrongArity("config"))?;
+        let subcommand = match subcommand_name.to_ascii_uppercase().as_str() {
+            "GET" => {
+                let patterns: Vec<String> = args
+                    .map(|arg| arg.to_string().unwrap_or_default())
+                    .collect();
+                if patterns.is_empty() {
+                    return Err(RedisError::WrongArity("config|get"));
+                }
+                ConfigSubcommand::Get(patterns)
+            }
+            "SET" => {
+                if args.len() == 0 || !args.len().is_multiple_of(2) {
+                    return Err(RedisError::WrongArity("config|set"));
+                }
+                // No parameter takes binary data: it would be mangled, e.g. a `requirepass`
+                // turning into an empty one.
+                let mut pairs = Vec::new();
+                while let Some(name) = args.next() {
+                    let Some(name) = name.to_string() else {
+                        let name = String::from_utf8_lossy(&name.into_bytes().unwrap_or_default())
+                            .into_owned();
+                        return Err(RedisError::custom(format!(
+                            "Unknown option or number of arguments for CONFIG SET - '{}'",
+                            name
+                        )));
+                    };
+                    let Some(value) = args.next().and_then(|arg| arg.to_string()) else {
+                        return Err(RedisError::custom(format!(
+                            "CONFIG SET failed (possibly related to argument '{}') - argument \
+                             must be valid UTF-8",
+                            name
+                        )));
+                    };
+                    pairs.push((name, value));
+                }
+                ConfigSubcommand::Set(pairs)
+            }
+            "RESETSTAT" if args.len() == 0 => ConfigSubcommand::ResetStat,
+            "RESETSTAT" => return Err(RedisError::WrongArity("config|resetstat")),
+            "REWRITE" if args.len() == 0 => ConfigSubcommand::Rewrite,
+            "REWRITE" => return Err(RedisError::WrongArity("config|rewrite")),
+            _ => return Err(RedisError::UnknownSubcommand(subcommand_name, "CONFIG")),
+        };
+        Ok(ConfigCommand { subcommand })
+    }
+}
+
+impl ConfigCommand {
+    fn get(server: &RedisServer, patterns: &[String]) -> RespDataType {
+        let config = server.config();
+        let mut pairs: Vec<(RespDataType, RespDataType)> = Vec::new();
+        let mut matched: Vec<&str> = Vec::new();
+        for pattern in patterns {
+            for parameter in PARAMETERS {
+                for name in std::iter::once(parameter.name).chain(parameter.alias) {
+                    if matched.contains(&name)
+                        || !utils::glob_match(pattern.as_bytes(), name.as_bytes(), true)
+                    {
+                        continue;
+                    }
+                    matched.push(name);
+                    pairs.push((
+                        RespDataType::bulk_string(name),
+                        RespDataType::bulk_string((parameter.get)(&config)),
+                    ));
+                }
+            }
+        }
+        RespDataType::Map(pairs)
+    }
+
+    /// Sets every pair or none: values are validated in order and the old configuration is
+    /// restored on the first failure, including when applying the change to the server fails.
+    async fn set(server: &Arc<RedisServer>, pairs: &[(String, String)]) -> Result<(), RedisError> {
+        let failed = |name: &str, reason: &str| {
+            RedisError::custom(format!(
+                "CONFIG SET failed (possibly related to argument '{}') - {}",
+                name, reason
+            ))
+        };
+
+        let mut parameters: Vec<&'static Parameter> = Vec::new();
+        for (name, _) in pairs {
+            let parameter = Parameter::lookup(name).ok_or_else(|| {
+                RedisError::custom(format!(
+                    "Unknown option or number of arguments for CONFIG SET - '{}'",
+                    name
+                ))
+            })?;
+            if parameter.immutable {
+                return Err(failed(name, "can't set immutable config"));
+            }
+            if parameters.iter().any(|p| p.name == parameter.name) {
+                return Err(failed(name, "duplicate parameter"));
+            }
+            parameters.push(parameter);
+        }
+
+        let old = {
+            let mut config = server.config_mut();
+            let old = config.clone();
+            for (name, value) in pairs {
+                if let Err(reason) = config.set(name, value) {
+                    *config = old;
+                    return Err(failed(name, &reason));
+                }
+            }
+            old
+        };
+
+        let mut applied = Vec::new();
+        for (parameter, (name, _)) in parameters.iter().zip(pairs) {
+            let Some(apply) = parameter.apply else {
+                continue;
+            };
+            if applied.contains(&apply) {
+                continue;
+            }
+            applied.push(apply);
+            if let Err(reason) = server.apply_config(apply).await {
+                *server.config_mut() = old;
+                for apply in applied {
+                    let _ = server.apply_config(apply).await;
+                }
+                return Err(failed(name, &reason));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RunnableCommand for ConfigCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match &self.subcommand {
+            ConfigSubcommand::Get(patterns) => Ok(Self::get(server, patterns)),
+            ConfigSubcommand::Set(pairs) => {
+                Self::set(server, pairs).await?;
+                Ok(RespDataType::ok())
+            }
+            ConfigSubcommand::ResetStat => {
+                server.stats.reset();
+                Ok(RespDataType::ok())
+            }
+            ConfigSubcommand::Rewrite => {
+                server.config().rewrite().map_err(RedisError::custom)?;
+                Ok(RespDataType::ok())
+            }
+        }
+    }
+}
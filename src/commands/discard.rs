@@ -0,0 +1,49 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiscardCommand;
+
+impl DescribedCommand for DiscardCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "discard",
+        summary: "Discards a transaction.",
+        since: "2.0.0",
+        arity: 1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Transaction],
+    };
+}
+
+impl ParseableCommand for DiscardCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(DiscardCommand)
+    }
+}
+
+impl RunnableCommand for DiscardCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match client.multi.take() {
+            Some(_) => {
+                client.unwatch(&server.store);
+                Ok(RespDataType::ok())
+            }
+            None => Err(RedisError::custom("DISCARD without MULTI")),
+        }
+    }
+}
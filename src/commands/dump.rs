@@ -0,0 +1,50 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, rdb, resp::RespDataType, server::RedisServer,
+    types::RedisType,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DumpCommand {
+    pub key: String,
+}
+
+impl DescribedCommand for DumpCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "dump",
+        summary: "Returns a serialized representation of the value stored at a key.",
+        since: "2.6.0",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Keyspace, AclCategory::Read, AclCategory::Slow],
+    };
+}
+
+impl ParseableCommand for DumpCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("dump"))?;
+        Ok(DumpCommand { key })
+    }
+}
+
+impl RunnableCommand for DumpCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let payload = match server.store.get_key(&self.key).await {
+            Some(value) => match &*value {
+                RedisType::String(kv) if kv.is_expired() => None,
+                value => rdb::dump(value),
+            },
+            None => None,
+        };
+        Ok(payload.map_or(RespDataType::Null, RespDataType::bulk_string))
+    }
+}
@@ -1,30 +1,41 @@
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct EchoCommand {
-    pub message: String,
+    pub message: Bytes,
+}
+
+impl DescribedCommand for EchoCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "echo",
+        summary: "Returns the given string.",
+        since: "1.0.0",
+        arity: 2,
+        flags: &[CommandFlag::Fast],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Connection],
+    };
 }
 
 impl ParseableCommand for EchoCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        if let Some(message) = args.next().and_then(|arg| arg.to_string()) {
-            Ok(EchoCommand { message })
-        } else {
-            Err("ECHO command requires a message".to_string())
-        }
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let message = Self::get_arg_as_bytes(args, RedisError::WrongArity("echo"))?;
+        Ok(EchoCommand { message })
     }
 }
 
 impl RunnableCommand for EchoCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        _store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        Some(RespDataType::bulk_string(&self.message))
+        _client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        Ok(RespDataType::bulk_string(&self.message))
     }
 }
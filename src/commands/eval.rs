@@ -0,0 +1,80 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, scripting::Program,
+    server::RedisServer,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EvalCommand {
+    pub script: String,
+    pub keys: Vec<Bytes>,
+    pub args: Vec<Bytes>,
+}
+
+impl DescribedCommand for EvalCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "eval",
+        summary: "Executes a server-side Lua script.",
+        since: "2.6.0",
+        arity: -3,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Stale,
+            CommandFlag::Loading,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+/// `script numkeys [key ...] [arg ...]`, shared by `EVAL`, `EVALSHA` and their read-only
+/// variants.
+pub fn parse_script_call(
+    args: &mut IntoIter<RespDataType>,
+    name: &'static str,
+) -> Result<(String, Vec<Bytes>, Vec<Bytes>), RedisError> {
+    let script = EvalCommand::get_arg_as_string(args, RedisError::WrongArity(name))?;
+    let numkeys = EvalCommand::get_arg_as_int(args, RedisError::WrongArity(name))?;
+    let numkeys = usize::try_from(numkeys)
+        .map_err(|_| RedisError::custom("Number of keys can't be negative"))?;
+    if numkeys > args.len() {
+        return Err(RedisError::custom(
+            "Number of keys can't be greater than number of args",
+        ));
+    }
+    let mut values = args.filter_map(|arg| arg.into_bytes());
+    let keys = values.by_ref().take(numkeys).collect();
+    Ok((script, keys, values.collect()))
+}
+
+impl ParseableCommand for EvalCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let (script, keys, args) = parse_script_call(args, "eval")?;
+        Ok(EvalCommand { script, keys, args })
+    }
+}
+
+impl RunnableCommand for EvalCommand {
+    /// Caches the script so that `EVALSHA` can run it next.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server.scripting.cache(&self.script);
+        server
+            .run_script(
+                client,
+                Program::Script(self.script.clone()),
+                self.keys.clone(),
+                self.args.clone(),
+                false,
+            )
+            .await
+    }
+}
@@ -0,0 +1,62 @@
+use super::eval::parse_script_call;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, scripting::Program,
+    server::RedisServer,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EvalRoCommand {
+    pub script: String,
+    pub keys: Vec<Bytes>,
+    pub args: Vec<Bytes>,
+}
+
+impl DescribedCommand for EvalRoCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "eval_ro",
+        summary: "Executes a read-only server-side Lua script.",
+        since: "7.0.0",
+        arity: -3,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Stale,
+            CommandFlag::Loading,
+            CommandFlag::ReadOnly,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+impl ParseableCommand for EvalRoCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let (script, keys, args) = parse_script_call(args, "eval_ro")?;
+        Ok(EvalRoCommand { script, keys, args })
+    }
+}
+
+impl RunnableCommand for EvalRoCommand {
+    /// Like `EVAL`, but the script can't run write commands.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server.scripting.cache(&self.script);
+        server
+            .run_script(
+                client,
+                Program::Script(self.script.clone()),
+                self.keys.clone(),
+                self.args.clone(),
+                true,
+            )
+            .await
+    }
+}
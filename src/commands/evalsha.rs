@@ -0,0 +1,64 @@
+use super::eval::parse_script_call;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, scripting::Program,
+    server::RedisServer,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EvalShaCommand {
+    /// SHA1 digest of a script loaded with `SCRIPT LOAD` or run with `EVAL`.
+    pub sha: String,
+    pub keys: Vec<Bytes>,
+    pub args: Vec<Bytes>,
+}
+
+impl DescribedCommand for EvalShaCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "evalsha",
+        summary: "Executes a server-side Lua script by SHA1 digest.",
+        since: "2.6.0",
+        arity: -3,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Stale,
+            CommandFlag::Loading,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+impl ParseableCommand for EvalShaCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let (sha, keys, args) = parse_script_call(args, "evalsha")?;
+        Ok(EvalShaCommand { sha, keys, args })
+    }
+}
+
+impl RunnableCommand for EvalShaCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let script = server
+            .scripting
+            .get(&self.sha)
+            .ok_or(RedisError::NoScript)?;
+        server
+            .run_script(
+                client,
+                Program::Script(script),
+                self.keys.clone(),
+                self.args.clone(),
+                false,
+            )
+            .await
+    }
+}
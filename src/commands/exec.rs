@@ -0,0 +1,47 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExecCommand;
+
+impl DescribedCommand for ExecCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "exec",
+        summary: "Executes all commands in a transaction.",
+        since: "1.2.0",
+        arity: 1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Transaction],
+    };
+}
+
+impl ParseableCommand for ExecCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(ExecCommand)
+    }
+}
+
+impl RunnableCommand for ExecCommand {
+    /// A transaction is run by `RedisServer::exec_transaction`. This is only reached without
+    /// one, or when commands run as they come, e.g. replaying the AOF: there is nothing left
+    /// to run then.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match client.multi.take() {
+            Some(_) => Ok(RespDataType::Array(Vec::new())),
+            None => Err(RedisError::custom("EXEC without MULTI")),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use super::eval::parse_script_call;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, scripting::Program,
+    server::RedisServer,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FCallCommand {
+    pub function: String,
+    pub keys: Vec<Bytes>,
+    pub args: Vec<Bytes>,
+}
+
+impl DescribedCommand for FCallCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "fcall",
+        summary: "Invokes a function.",
+        since: "7.0.0",
+        arity: -3,
+        flags: &[CommandFlag::NoScript, CommandFlag::Stale],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+impl ParseableCommand for FCallCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let (function, keys, args) = parse_script_call(args, "fcall")?;
+        Ok(FCallCommand {
+            function,
+            keys,
+            args,
+        })
+    }
+}
+
+/// Runs a function loaded with `FUNCTION LOAD`. Those flagged `no-writes` can't write, and
+/// are the only ones `read_only` calls accept.
+pub async fn call_function(
+    client: &mut RedisClient,
+    server: &Arc<RedisServer>,
+    name: &str,
+    keys: &[Bytes],
+    args: &[Bytes],
+    read_only: bool,
+) -> Result<RespDataType, RedisError> {
+    let (code, function) = server
+        .functions
+        .find(name)
+        .ok_or_else(|| RedisError::custom("Function not found"))?;
+    if read_only && !function.no_writes() {
+        return Err(RedisError::custom(
+            "Can not execute a script with write flag using *_ro command.",
+        ));
+    }
+    let program = Program::Function {
+        name: function.name.clone(),
+        code,
+    };
+    server
+        .run_script(
+            client,
+            program,
+            keys.to_vec(),
+            args.to_vec(),
+            function.no_writes(),
+        )
+        .await
+}
+
+impl RunnableCommand for FCallCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        call_function(
+            client,
+            server,
+            &self.function,
+            &self.keys,
+            &self.args,
+            false,
+        )
+        .await
+    }
+}
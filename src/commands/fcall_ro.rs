@@ -0,0 +1,53 @@
+use super::eval::parse_script_call;
+use super::fcall::call_function;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FCallRoCommand {
+    pub function: String,
+    pub keys: Vec<Bytes>,
+    pub args: Vec<Bytes>,
+}
+
+impl DescribedCommand for FCallRoCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "fcall_ro",
+        summary: "Invokes a read-only function.",
+        since: "7.0.0",
+        arity: -3,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Stale,
+            CommandFlag::ReadOnly,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+impl ParseableCommand for FCallRoCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let (function, keys, args) = parse_script_call(args, "fcall_ro")?;
+        Ok(FCallRoCommand {
+            function,
+            keys,
+            args,
+        })
+    }
+}
+
+impl RunnableCommand for FCallRoCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        call_function(client, server, &self.function, &self.keys, &self.args, true).await
+    }
+}
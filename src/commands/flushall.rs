@@ -0,0 +1,52 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FlushAllCommand;
+
+impl DescribedCommand for FlushAllCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "flushall",
+        summary: "Removes all keys from all databases.",
+        since: "1.0.0",
+        arity: -1,
+        flags: &[CommandFlag::Write],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Keyspace,
+            AclCategory::Write,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for FlushAllCommand {
+    /// `ASYNC` and `SYNC` are accepted, the keys are always dropped right away.
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        match args.next().and_then(|arg| arg.to_string()) {
+            None => {}
+            Some(mode) if matches!(mode.to_ascii_uppercase().as_str(), "ASYNC" | "SYNC") => {}
+            Some(_) => return Err(RedisError::Syntax),
+        }
+        if args.next().is_some() {
+            return Err(RedisError::Syntax);
+        }
+        Ok(FlushAllCommand)
+    }
+}
+
+impl RunnableCommand for FlushAllCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server.store.clear().await;
+        Ok(RespDataType::ok())
+    }
+}
@@ -0,0 +1,301 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient,
+    error::RedisError,
+    functions::{Library, RestorePolicy},
+    rdb,
+    resp::RespDataType,
+    server::RedisServer,
+    utils,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FunctionSubcommand {
+    Load {
+        code: String,
+        replace: bool,
+    },
+    List {
+        with_code: bool,
+        /// Glob pattern matched against library names.
+        pattern: Option<String>,
+    },
+    Delete(String),
+    Flush,
+    Dump,
+    Restore {
+        payload: Bytes,
+        policy: RestorePolicy,
+    },
+    Stats,
+    Kill,
+}
+
+impl FunctionSubcommand {
+    /// The flags of each subcommand, checked instead of those of `FUNCTION` itself.
+    pub fn flags(&self) -> &'static [CommandFlag] {
+        match self {
+            FunctionSubcommand::Load { .. } | FunctionSubcommand::Restore { .. } => &[
+                CommandFlag::Write,
+                CommandFlag::DenyOom,
+                CommandFlag::NoScript,
+            ],
+            FunctionSubcommand::Delete(_) | FunctionSubcommand::Flush => {
+                &[CommandFlag::Write, CommandFlag::NoScript]
+            }
+            FunctionSubcommand::List { .. } | FunctionSubcommand::Dump => &[CommandFlag::NoScript],
+            FunctionSubcommand::Stats | FunctionSubcommand::Kill => {
+                &[CommandFlag::NoScript, CommandFlag::AllowBusy]
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct FunctionCommand {
+    pub subcommand: FunctionSubcommand,
+}
+
+impl DescribedCommand for FunctionCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "function",
+        summary: "A container for function commands.",
+        since: "7.0.0",
+        arity: -2,
+        flags: &[CommandFlag::NoScript],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+impl ParseableCommand for FunctionCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let subcommand_name = Self::get_arg_as_string(args, RedisError::WrongArity("function"))?;
+        let subcommand = match subcommand_name.to_ascii_uppercase().as_str() {
+            "LOAD" => {
+                let mut code =
+                    Self::get_arg_as_string(args, RedisError::WrongArity("function|load"))?;
+                let replace = code.eq_ignore_ascii_case("replace");
+                if replace {
+                    code = Self::get_arg_as_string(args, RedisError::WrongArity("function|load"))?;
+                }
+                if args.len() > 0 {
+                    return Err(RedisError::WrongArity("function|load"));
+                }
+                FunctionSubcommand::Load { code, replace }
+            }
+            "LIST" => {
+                let (mut with_code, mut pattern) = (false, None);
+                while let Some(arg) = args.next().and_then(|arg| arg.to_string()) {
+                    if arg.eq_ignore_ascii_case("withcode") {
+                        with_code = true;
+                    } else if arg.eq_ignore_ascii_case("libraryname") {
+                        let name =
+                            args.next().and_then(|arg| arg.to_string()).ok_or_else(|| {
+                                RedisError::custom("library name argument was not given")
+                            })?;
+                        pattern = Some(name);
+                    } else {
+                        return Err(RedisError::custom(format!("Unknown argument {}", arg)));
+                    }
+                }
+                FunctionSubcommand::List { with_code, pattern }
+            }
+            "DELETE" if args.len() == 1 => FunctionSubcommand::Delete(Self::get_arg_as_string(
+                args,
+                RedisError::WrongArity("function|delete"),
+            )?),
+            "DELETE" => return Err(RedisError::WrongArity("function|delete")),
+            // The ASYNC and SYNC modes make no difference for a registry this small.
+            "FLUSH" => match args.next().and_then(|arg| arg.to_string()) {
+                None => FunctionSubcommand::Flush,
+                Some(mode)
+                    if args.len() == 0
+                        && (mode.eq_ignore_ascii_case("async")
+                            || mode.eq_ignore_ascii_case("sync")) =>
+                {
+                    FunctionSubcommand::Flush
+                }
+                Some(_) => return Err(RedisError::Syntax),
+            },
+            "DUMP" if args.len() == 0 => FunctionSubcommand::Dump,
+            "DUMP" => return Err(RedisError::WrongArity("function|dump")),
+            "RESTORE" => {
+                let payload =
+                    Self::get_arg_as_bytes(args, RedisError::WrongArity("function|restore"))?;
+                let policy = match args.next().and_then(|arg| arg.to_string()) {
+                    None => RestorePolicy::Append,
+                    Some(policy) if args.len() == 0 => match policy.to_ascii_uppercase().as_str() {
+                        "APPEND" => RestorePolicy::Append,
+                        "REPLACE" => RestorePolicy::Replace,
+                        "FLUSH" => RestorePolicy::Flush,
+                        _ => {
+                            return Err(RedisError::custom(
+                                "Wrong restore policy given, value should be either FLUSH, APPEND or REPLACE.",
+                            ))
+                        }
+                    },
+                    Some(_) => return Err(RedisError::WrongArity("function|restore")),
+                };
+                FunctionSubcommand::Restore { payload, policy }
+            }
+            "STATS" if args.len() == 0 => FunctionSubcommand::Stats,
+            "STATS" => return Err(RedisError::WrongArity("function|stats")),
+            "KILL" if args.len() == 0 => FunctionSubcommand::Kill,
+            "KILL" => return Err(RedisError::WrongArity("function|kill")),
+            _ => return Err(RedisError::UnknownSubcommand(subcommand_name, "FUNCTION")),
+        };
+        Ok(FunctionCommand { subcommand })
+    }
+}
+
+impl FunctionCommand {
+    /// A map per library with its name, engine, functions and optionally its code.
+    fn list(libraries: Vec<Library>, with_code: bool, pattern: Option<&str>) -> RespDataType {
+        let field = |name: &str, value: RespDataType| (RespDataType::bulk_string(name), value);
+        let libraries = libraries
+            .into_iter()
+            .filter(|library| {
+                pattern.is_none_or(|pattern| {
+                    utils::glob_match(pattern.as_bytes(), library.name.as_bytes(), true)
+                })
+            })
+            .map(|library| {
+                let functions = library
+                    .functions
+                    .iter()
+                    .map(|function| {
+                        RespDataType::Map(vec![
+                            field("name", RespDataType::bulk_string(&function.name)),
+                            field(
+                                "description",
+                                function
+                                    .description
+                                    .as_ref()
+                                    .map_or(RespDataType::Null, RespDataType::bulk_string),
+                            ),
+                            field(
+                                "flags",
+                                RespDataType::Set(
+                                    function
+                                        .flags
+                                        .iter()
+                                        .map(|flag| RespDataType::simple_string(flag))
+                                        .collect(),
+                                ),
+                            ),
+                        ])
+                    })
+                    .collect();
+                let mut fields = vec![
+                    field("library_name", RespDataType::bulk_string(&library.name)),
+                    field("engine", RespDataType::bulk_string("LUA")),
+                    field("functions", RespDataType::Array(functions)),
+                ];
+                if with_code {
+                    fields.push(field(
+                        "library_code",
+                        RespDataType::bulk_string(&library.code),
+                    ));
+                }
+                RespDataType::Map(fields)
+            })
+            .collect();
+        RespDataType::Array(libraries)
+    }
+
+    /// The function running, if any, and the number of libraries and functions.
+    fn stats(server: &RedisServer) -> RespDataType {
+        let field = |name: &str, value: RespDataType| (RespDataType::bulk_string(name), value);
+        let running = server.scripting.running();
+        let running_script = match running.as_ref().and_then(|script| {
+            let name = script.function.as_ref()?;
+            Some((script, name))
+        }) {
+            Some((script, name)) => RespDataType::Map(vec![
+                field("name", RespDataType::bulk_string(name)),
+                field(
+                    "command",
+                    RespDataType::Array(
+                        script
+                            .command
+                            .iter()
+                            .map(RespDataType::bulk_string)
+                            .collect(),
+                    ),
+                ),
+                field(
+                    "duration_ms",
+                    RespDataType::Integer(script.elapsed().as_millis() as i64),
+                ),
+            ]),
+            None => RespDataType::Null,
+        };
+        let libraries = server.functions.libraries();
+        let functions: usize = libraries.iter().map(|l| l.functions.len()).sum();
+        RespDataType::Map(vec![
+            field("running_script", running_script),
+            field(
+                "engines",
+                RespDataType::Map(vec![field(
+                    "LUA",
+                    RespDataType::Map(vec![
+                        field(
+                            "libraries_count",
+                            RespDataType::Integer(libraries.len() as i64),
+                        ),
+                        field("functions_count", RespDataType::Integer(functions as i64)),
+                    ]),
+                )]),
+            ),
+        ])
+    }
+}
+
+impl RunnableCommand for FunctionCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let functions = &server.functions;
+        match &self.subcommand {
+            FunctionSubcommand::Load { code, replace } => {
+                let name = functions.load(code, *replace)?;
+                Ok(RespDataType::bulk_string(name))
+            }
+            FunctionSubcommand::List { with_code, pattern } => Ok(Self::list(
+                functions.libraries(),
+                *with_code,
+                pattern.as_deref(),
+            )),
+            FunctionSubcommand::Delete(name) => {
+                functions.delete(name)?;
+                Ok(RespDataType::ok())
+            }
+            FunctionSubcommand::Flush => {
+                functions.flush();
+                Ok(RespDataType::ok())
+            }
+            FunctionSubcommand::Dump => Ok(RespDataType::bulk_string(rdb::dump_functions(
+                &functions.codes(),
+            ))),
+            FunctionSubcommand::Restore { payload, policy } => {
+                let codes = rdb::restore_functions(payload)
+                    .map_err(|_| RedisError::custom("payload version or checksum are wrong"))?;
+                functions.restore(&codes, *policy)?;
+                Ok(RespDataType::ok())
+            }
+            FunctionSubcommand::Stats => Ok(Self::stats(server)),
+            FunctionSubcommand::Kill => {
+                server.scripting.kill()?;
+                Ok(RespDataType::ok())
+            }
+        }
+    }
+}
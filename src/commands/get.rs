@@ -1,16 +1,30 @@
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct GetCommand {
     pub key: String,
 }
 
+impl DescribedCommand for GetCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "get",
+        summary: "Returns the string value of a key.",
+        since: "1.0.0",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::String, AclCategory::Fast],
+    };
+}
+
 impl ParseableCommand for GetCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "GET command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("get"))?;
         Ok(GetCommand { key })
     }
 }
@@ -18,14 +32,13 @@ impl ParseableCommand for GetCommand {
 impl RunnableCommand for GetCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        let response = match store.get_key_value(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let response = match server.store.get_key_value(&self.key).await {
             Some(val) if !val.is_expired() => RespDataType::bulk_string(&val.value),
             _ => RespDataType::Null,
         };
-        Some(response)
+        Ok(response)
     }
 }
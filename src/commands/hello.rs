@@ -0,0 +1,146 @@
+use super::auth::authenticate;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient,
+    error::RedisError,
+    resp::{ProtocolVersion, RespDataType},
+    server::{RedisServer, REDIS_VERSION},
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct HelloCommand {
+    pub protocol: Option<i64>,
+    pub auth: Option<(String, String)>,
+    pub client_name: Option<String>,
+}
+
+impl DescribedCommand for HelloCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "hello",
+        summary: "Handshakes with the Redis server.",
+        since: "6.0.0",
+        arity: -1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Connection],
+    };
+}
+
+impl ParseableCommand for HelloCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let protocol = match args.next() {
+            Some(arg) => Some(arg.to_int().ok_or_else(|| {
+                RedisError::custom("Protocol version is not an integer or out of range")
+            })?),
+            None => None,
+        };
+
+        let mut auth = None;
+        let mut client_name = None;
+        while let Some(option) = args.next().and_then(|arg| arg.to_string()) {
+            match option.to_ascii_uppercase().as_str() {
+                "AUTH" => {
+                    let username = Self::get_arg_as_string(args, Self::option_error(&option))?;
+                    let password = Self::get_arg_as_string(args, Self::option_error(&option))?;
+                    auth = Some((username, password));
+                }
+                "SETNAME" => {
+                    let name = Self::get_arg_as_string(args, Self::option_error(&option))?;
+                    client_name = Some(name);
+                }
+                _ => return Err(Self::option_error(&option)),
+            }
+        }
+
+        Ok(HelloCommand {
+            protocol,
+            auth,
+            client_name,
+        })
+    }
+}
+
+impl HelloCommand {
+    fn option_error(option: &str) -> RedisError {
+        RedisError::custom(format!("Syntax error in HELLO option '{}'", option))
+    }
+}
+
+impl RunnableCommand for HelloCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let protocol = match self.protocol {
+            Some(version) => match ProtocolVersion::from_number(version) {
+                Some(protocol) => protocol,
+                None => return Err(RedisError::NoProto),
+            },
+            None => client.protocol,
+        };
+
+        match &self.auth {
+            Some((username, password)) => authenticate(client, server, username, password)?,
+            None if !client.authenticated && server.config().requirepass.is_some() => {
+                return Err(RedisError::NoAuth)
+            }
+            None => {}
+        }
+
+        if let Some(name) = &self.client_name {
+            if name.bytes().any(|b| !(b'!'..=b'~').contains(&b)) {
+                return Err(RedisError::custom(
+                    "Client names cannot contain spaces, newlines or special characters.",
+                ));
+            }
+            client.name = Some(name.clone());
+        }
+        client.protocol = protocol;
+
+        Ok(RespDataType::Map(vec![
+            (
+                RespDataType::bulk_string("server"),
+                RespDataType::bulk_string("redis"),
+            ),
+            (
+                RespDataType::bulk_string("version"),
+                RespDataType::bulk_string(REDIS_VERSION),
+            ),
+            (
+                RespDataType::bulk_string("proto"),
+                RespDataType::Integer(protocol.number()),
+            ),
+            (
+                RespDataType::bulk_string("id"),
+                RespDataType::Integer(client.id as i64),
+            ),
+            (
+                RespDataType::bulk_string("mode"),
+                RespDataType::bulk_string("standalone"),
+            ),
+            (
+                RespDataType::bulk_string("role"),
+                RespDataType::bulk_string(match server.config().replicaof {
+                    Some(_) => "replica",
+                    None => "master",
+                }),
+            ),
+            (
+                RespDataType::bulk_string("modules"),
+                RespDataType::Array(vec![]),
+            ),
+        ]))
+    }
+}
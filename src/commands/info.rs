@@ -0,0 +1,253 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient,
+    error::RedisError,
+    replication::LinkState,
+    resp::RespDataType,
+    server::{RedisServer, REDIS_VERSION},
+    utils,
+};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    vec::IntoIter,
+};
+
+type SectionFields = fn(&RedisServer) -> Vec<(String, String)>;
+
+/// Sections in the order they are printed: name, title and fields.
+const SECTIONS: &[(&str, &str, SectionFields)] = &[
+    ("server", "Server", server_fields),
+    ("persistence", "Persistence", persistence_fields),
+    ("stats", "Stats", stats_fields),
+    ("replication", "Replication", replication_fields),
+];
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct InfoCommand {
+    /// Lowercase section names, every section when empty.
+    pub sections: Vec<String>,
+}
+
+impl DescribedCommand for InfoCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "info",
+        summary: "Returns information and statistics about the server.",
+        since: "1.0.0",
+        arity: -1,
+        flags: &[CommandFlag::Loading, CommandFlag::Stale],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Dangerous],
+    };
+}
+
+impl ParseableCommand for InfoCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let sections = args
+            .filter_map(|arg| arg.to_string())
+            .map(|section| section.to_ascii_lowercase())
+            .collect();
+        Ok(InfoCommand { sections })
+    }
+}
+
+impl InfoCommand {
+    fn includes(&self, section: &str) -> bool {
+        self.sections.is_empty()
+            || self.sections.iter().any(|name| {
+                matches!(name.as_str(), "all" | "default" | "everything") || name == section
+            })
+    }
+
+    /// `# Title` followed by `field:value` lines, sections separated by an empty line. Unknown
+    /// section names are ignored.
+    pub fn render(&self, server: &RedisServer) -> String {
+        let mut sections = Vec::new();
+        for (name, title, fields) in SECTIONS {
+            if !self.includes(name) {
+                continue;
+            }
+            let mut section = format!("# {}\r\n", title);
+            for (field, value) in fields(server) {
+                section.push_str(&format!("{}:{}\r\n", field, value));
+            }
+            sections.push(section);
+        }
+        sections.join("\r\n")
+    }
+}
+
+fn fields<const N: usize>(fields: [(&str, String); N]) -> Vec<(String, String)> {
+    fields
+        .into_iter()
+        .map(|(field, value)| (field.to_string(), value))
+        .collect()
+}
+
+fn server_fields(server: &RedisServer) -> Vec<(String, String)> {
+    let uptime = server.started_at.elapsed().as_secs();
+    fields([
+        ("redis_version", REDIS_VERSION.to_string()),
+        ("redis_mode", "standalone".to_string()),
+        ("process_id", std::process::id().to_string()),
+        ("tcp_port", server.config().port.to_string()),
+        ("uptime_in_seconds", uptime.to_string()),
+        ("uptime_in_days", (uptime / 86400).to_string()),
+    ])
+}
+
+fn persistence_fields(server: &RedisServer) -> Vec<(String, String)> {
+    let persistence = &server.persistence;
+    let flag = |value: bool| (value as u8).to_string();
+    let mut result = fields([
+        (
+            "rdb_changes_since_last_save",
+            persistence.dirty.load(Ordering::Relaxed).to_string(),
+        ),
+        (
+            "rdb_bgsave_in_progress",
+            flag(persistence.saving.load(Ordering::Relaxed)),
+        ),
+        (
+            "rdb_last_save_time",
+            persistence.lastsave.load(Ordering::Relaxed).to_string(),
+        ),
+        (
+            "rdb_last_bgsave_status",
+            match persistence.last_bgsave_ok.load(Ordering::Relaxed) {
+                true => "ok".to_string(),
+                false => "err".to_string(),
+            },
+        ),
+        ("aof_enabled", flag(server.config().appendonly)),
+        (
+            "aof_rewrite_in_progress",
+            flag(persistence.aof_rewriting.load(Ordering::Relaxed)),
+        ),
+        (
+            "aof_rewrite_scheduled",
+            flag(persistence.aof_rewrite_scheduled.load(Ordering::Relaxed)),
+        ),
+    ]);
+    if let Some((size, base_size)) = server.aof_sizes() {
+        result.extend(fields([
+            ("aof_current_size", size.to_string()),
+            ("aof_base_size", base_size.to_string()),
+        ]));
+    }
+    result
+}
+
+fn stats_fields(server: &RedisServer) -> Vec<(String, String)> {
+    let stats = &server.stats;
+    fields([
+        (
+            "total_connections_received",
+            stats
+                .total_connections_received
+                .load(Ordering::Relaxed)
+                .to_string(),
+        ),
+        (
+            "total_commands_processed",
+            stats
+                .total_commands_processed
+                .load(Ordering::Relaxed)
+                .to_string(),
+        ),
+        (
+            "evicted_keys",
+            stats.evicted_keys.load(Ordering::Relaxed).to_string(),
+        ),
+    ])
+}
+
+/// The role, the state of the link to the master on a replica, the replicas with the offset
+/// each acknowledged and the seconds since, then the history replicas can resume from.
+fn replication_fields(server: &RedisServer) -> Vec<(String, String)> {
+    let replication = &server.replication;
+    let replicas = replication.replicas();
+    let now = utils::now_millis() as u64;
+    let (replicaof, read_only) = {
+        let config = server.config();
+        (config.replicaof.clone(), config.replica_read_only)
+    };
+    let mut result = match replicaof {
+        None => fields([("role", "master".to_string())]),
+        Some((host, port)) => {
+            let link = replication.master_link();
+            let state = link.as_ref().map(|link| link.state());
+            let last_io = link
+                .as_ref()
+                .map(|link| link.last_io.load(Ordering::Relaxed))
+                .filter(|last_io| *last_io > 0)
+                .map_or(-1, |last_io| (now.saturating_sub(last_io) / 1000) as i64);
+            let flag = |value: bool| (value as u8).to_string();
+            fields([
+                ("role", "slave".to_string()),
+                ("master_host", host),
+                ("master_port", port.to_string()),
+                (
+                    "master_link_status",
+                    match state {
+                        Some(LinkState::Connected) => "up".to_string(),
+                        _ => "down".to_string(),
+                    },
+                ),
+                ("master_last_io_seconds_ago", last_io.to_string()),
+                (
+                    "master_sync_in_progress",
+                    flag(state == Some(LinkState::Transfer)),
+                ),
+                ("slave_repl_offset", replication.offset().to_string()),
+                ("slave_read_only", flag(read_only)),
+            ])
+        }
+    };
+    result.push(("connected_slaves".to_string(), replicas.len().to_string()));
+    for (i, replica) in replicas.iter().enumerate() {
+        let lag = now.saturating_sub(replica.ack_time.load(Ordering::Relaxed)) / 1000;
+        result.push((
+            format!("slave{}", i),
+            format!(
+                "ip={},port={},state={},offset={},lag={}",
+                replica.ip,
+                replica.listening_port,
+                replica.state(),
+                replica.ack_offset.load(Ordering::SeqCst),
+                lag
+            ),
+        ));
+    }
+    let (replid2, second_offset) = replication.replid2();
+    let (backlog_size, first_byte, histlen) = replication.backlog_info().unwrap_or_default();
+    result.extend(fields([
+        ("master_replid", replication.replid()),
+        ("master_replid2", replid2),
+        ("master_repl_offset", replication.offset().to_string()),
+        ("second_repl_offset", second_offset.to_string()),
+        (
+            "repl_backlog_active",
+            (replication.has_backlog() as u8).to_string(),
+        ),
+        ("repl_backlog_size", backlog_size.to_string()),
+        ("repl_backlog_first_byte_offset", first_byte.to_string()),
+        ("repl_backlog_histlen", histlen.to_string()),
+    ]));
+    result
+}
+
+impl RunnableCommand for InfoCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        Ok(RespDataType::VerbatimString(
+            "txt".to_string(),
+            self.render(server).into_bytes(),
+        ))
+    }
+}
@@ -1,8 +1,8 @@
-use tokio::sync::Notify;
-
-use super::traits::ParseableCommand;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand};
 use crate::{
-    commands::traits::RunnableCommand, resp::RespDataType, store::RedisStore, types::RedisType,
+    client::RedisClient, commands::traits::RunnableCommand, error::RedisError, resp::RespDataType,
+    server::RedisServer, types::RedisType,
 };
 use std::{sync::Arc, vec::IntoIter};
 
@@ -11,9 +11,23 @@ pub struct KeyTypeCommand {
     pub key: String,
 }
 
+impl DescribedCommand for KeyTypeCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "type",
+        summary: "Determines the type of value stored at a key.",
+        since: "1.0.0",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Keyspace, AclCategory::Read, AclCategory::Fast],
+    };
+}
+
 impl ParseableCommand for KeyTypeCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "TYPE command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("type"))?;
 
         Ok(KeyTypeCommand { key })
     }
@@ -22,13 +36,12 @@ impl ParseableCommand for KeyTypeCommand {
 impl RunnableCommand for KeyTypeCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_key(&self.key).await {
-            Some(key_type) => Some(key_type.to_type_resp()),
-            None => Some(RedisType::None.to_type_resp()), // "none" is the default for non-existent keys
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_key(&self.key).await {
+            Some(key_type) => Ok(key_type.to_type_resp()),
+            None => Ok(RedisType::None.to_type_resp()), // "none" is the default for non-existent keys
         }
     }
 }
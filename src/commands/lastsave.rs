@@ -0,0 +1,45 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    vec::IntoIter,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LastSaveCommand;
+
+impl DescribedCommand for LastSaveCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "lastsave",
+        summary: "Returns the Unix timestamp of the last successful save to disk.",
+        since: "1.0.0",
+        arity: 1,
+        flags: &[CommandFlag::Loading, CommandFlag::Stale, CommandFlag::Fast],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Fast,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for LastSaveCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(LastSaveCommand)
+    }
+}
+
+impl RunnableCommand for LastSaveCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let lastsave = server.persistence.lastsave.load(Ordering::Relaxed);
+        Ok(RespDataType::Integer(lastsave as i64))
+    }
+}
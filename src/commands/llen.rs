@@ -1,18 +1,32 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
 use std::collections::VecDeque;
 
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LLenCommand {
     pub key: String,
 }
 
+impl DescribedCommand for LLenCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "llen",
+        summary: "Returns the length of a list.",
+        since: "1.0.0",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::List, AclCategory::Fast],
+    };
+}
+
 impl ParseableCommand for LLenCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "LLEN command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("llen"))?;
         Ok(LLenCommand { key })
     }
 }
@@ -20,16 +34,15 @@ impl ParseableCommand for LLenCommand {
 impl RunnableCommand for LLenCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_list(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_list(&self.key).await {
             Some(list) => {
                 let len = list.len() as i64;
-                Some(RespDataType::Integer(len))
+                Ok(RespDataType::Integer(len))
             }
-            None => Some(RespDataType::Integer(0)),
+            None => Ok(RespDataType::Integer(0)),
         }
     }
 }
@@ -1,12 +1,12 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
 use std::collections::{
     hash_map::Entry::{Occupied, Vacant},
     VecDeque,
 };
 
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LPopCommand {
@@ -14,14 +14,31 @@ pub struct LPopCommand {
     pub count: i64,
 }
 
+impl DescribedCommand for LPopCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "lpop",
+        summary: "Returns the first elements in a list after removing it.",
+        since: "1.0.0",
+        arity: -2,
+        flags: &[CommandFlag::Write, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Write, AclCategory::List, AclCategory::Fast],
+    };
+}
+
 impl ParseableCommand for LPopCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "LPOP command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("lpop"))?;
 
-        let count_arg = args.next().unwrap_or(RespDataType::Integer(1));
-        let count = count_arg
-            .to_int()
-            .ok_or_else(|| "cannot convert count to int".to_string())?;
+        let count = match args.next() {
+            Some(count_arg) => count_arg.to_int().ok_or(RedisError::OutOfRange)?,
+            None => 1,
+        };
+        if count < 0 {
+            return Err(RedisError::OutOfRange);
+        }
 
         Ok(LPopCommand { key, count })
     }
@@ -30,28 +47,32 @@ impl ParseableCommand for LPopCommand {
 impl RunnableCommand for LPopCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_list(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_list(&self.key).await {
             Some(mut list) => {
                 let mut popped_elements: Vec<RespDataType> = Vec::new();
                 for _i in 0..self.count {
                     if let Some(val) = list.pop_front() {
-                        popped_elements.push(RespDataType::bulk_string(val.as_str()));
+                        server.store.shrink(val.len());
+                        popped_elements.push(RespDataType::bulk_string(val));
                     } else {
                         break;
                     }
                 }
+                drop(list);
+                if !popped_elements.is_empty() {
+                    server.store.notify_key_modified(&self.key).await;
+                }
                 if self.count == 1 && !popped_elements.is_empty() {
-                    return Some(popped_elements.remove(0));
+                    return Ok(popped_elements.remove(0));
                 } else if self.count > 1 {
-                    return Some(RespDataType::Array(popped_elements));
+                    return Ok(RespDataType::Array(popped_elements));
                 }
-                Some(RespDataType::Null)
+                Ok(RespDataType::Null)
             }
-            None => Some(RespDataType::Null),
+            None => Ok(RespDataType::Null),
         }
     }
 }
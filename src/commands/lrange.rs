@@ -1,7 +1,7 @@
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LRangeCommand {
@@ -10,23 +10,29 @@ pub struct LRangeCommand {
     pub end: i64,
 }
 
+impl DescribedCommand for LRangeCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "lrange",
+        summary: "Returns a range of elements from a list.",
+        since: "1.0.0",
+        arity: 4,
+        flags: &[CommandFlag::ReadOnly],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::List, AclCategory::Slow],
+    };
+}
+
 impl ParseableCommand for LRangeCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "LRANGE command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("lrange"))?;
 
-        let start_arg = args
-            .next()
-            .ok_or_else(|| "Expected values for LRANGE start and end".to_string())?;
-        let end_arg = args
-            .next()
-            .ok_or_else(|| "Expected values for LRANGE start and end".to_string())?;
+        let start_arg = args.next().ok_or(RedisError::WrongArity("lrange"))?;
+        let end_arg = args.next().ok_or(RedisError::WrongArity("lrange"))?;
 
-        let start = start_arg
-            .to_int()
-            .ok_or_else(|| "Expected integer values for LRANGE start and end".to_string())?;
-        let end = end_arg
-            .to_int()
-            .ok_or_else(|| "Expected integer values for LRANGE start and end".to_string())?;
+        let start = start_arg.to_int().ok_or(RedisError::NotInteger)?;
+        let end = end_arg.to_int().ok_or(RedisError::NotInteger)?;
 
         Ok(LRangeCommand { key, start, end })
     }
@@ -63,24 +69,23 @@ impl LRangeCommand {
 impl RunnableCommand for LRangeCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_list(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_list(&self.key).await {
             Some(list_value) => {
                 let list_len = list_value.len() as i64;
                 let (start, end) = match self.clone().treat_bounds(list_len) {
                     Some(value) => value,
-                    None => return Some(RespDataType::Array(vec![])),
+                    None => return Ok(RespDataType::Array(vec![])),
                 };
                 let mut result_list: Vec<RespDataType> = Vec::new();
                 for i in start..=end {
                     result_list.push(RespDataType::bulk_string(&list_value[i]));
                 }
-                return Some(RespDataType::Array(result_list));
+                return Ok(RespDataType::Array(result_list));
             }
-            _ => Some(RespDataType::Array(vec![])),
+            _ => Ok(RespDataType::Array(vec![])),
         }
     }
 }
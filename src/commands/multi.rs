@@ -0,0 +1,53 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::{MultiState, RedisClient},
+    error::RedisError,
+    resp::RespDataType,
+    server::RedisServer,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MultiCommand;
+
+impl DescribedCommand for MultiCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "multi",
+        summary: "Starts a transaction.",
+        since: "1.2.0",
+        arity: 1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Transaction],
+    };
+}
+
+impl ParseableCommand for MultiCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(MultiCommand)
+    }
+}
+
+impl RunnableCommand for MultiCommand {
+    /// The commands that follow are queued by `RedisServer::process_command` until `EXEC`.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        if client.multi.is_some() {
+            return Err(RedisError::custom("MULTI calls can not be nested"));
+        }
+        client.multi = Some(MultiState::default());
+        Ok(RespDataType::ok())
+    }
+}
@@ -1,24 +1,42 @@
-use super::traits::{ParseableCommand, RunnableCommand};
-use crate::{resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct PingCommand;
 
+impl DescribedCommand for PingCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "ping",
+        summary: "Returns the server's liveliness response.",
+        since: "1.0.0",
+        arity: -1,
+        flags: &[CommandFlag::Fast],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Connection],
+    };
+}
+
 impl ParseableCommand for PingCommand {
-    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
         Ok(PingCommand)
     }
 }
 
 impl RunnableCommand for PingCommand {
+    /// A client in Pub/Sub mode can't tell replies from messages by their type, so it gets
+    /// one shaped like a message.
     async fn execute(
         &self,
-        _client_id: &str,
-        _store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        Some(RespDataType::pong())
+        client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        if client.in_pubsub_mode() {
+            return Ok(RespDataType::new_array(vec!["pong", ""]));
+        }
+        Ok(RespDataType::pong())
     }
 }
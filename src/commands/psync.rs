@@ -0,0 +1,55 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PSyncCommand {
+    /// Replication ID the replica was following, `?` for none.
+    pub replid: String,
+    /// Offset the replica wants to resume from, `-1` for none.
+    pub offset: i64,
+}
+
+impl DescribedCommand for PSyncCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "psync",
+        summary: "An internal command used in replication.",
+        since: "2.8.0",
+        arity: -3,
+        flags: &[
+            CommandFlag::Admin,
+            CommandFlag::NoScript,
+            CommandFlag::NoMulti,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for PSyncCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let replid = Self::get_arg_as_string(args, RedisError::WrongArity("psync"))?;
+        let offset = Self::get_arg_as_int(args, RedisError::WrongArity("psync"))?;
+        Ok(PSyncCommand { replid, offset })
+    }
+}
+
+impl RunnableCommand for PSyncCommand {
+    /// The reply is the synchronization itself: `client_process` hands the connection over to
+    /// `RedisServer::sync_replica` instead of writing this one.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        client.psync = Some((self.replid.clone(), self.offset));
+        Ok(RespDataType::Null)
+    }
+}
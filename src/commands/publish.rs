@@ -0,0 +1,50 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PublishCommand {
+    pub channel: Bytes,
+    pub message: Bytes,
+}
+
+impl DescribedCommand for PublishCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "publish",
+        summary: "Posts a message to a channel.",
+        since: "2.0.0",
+        arity: 3,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::PubSub, AclCategory::Fast],
+    };
+}
+
+impl ParseableCommand for PublishCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let channel = Self::get_arg_as_bytes(args, RedisError::WrongArity("publish"))?;
+        let message = Self::get_arg_as_bytes(args, RedisError::WrongArity("publish"))?;
+        Ok(PublishCommand { channel, message })
+    }
+}
+
+impl RunnableCommand for PublishCommand {
+    /// Replies with the number of clients the message was sent to.
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let receivers = server.pubsub.publish(&self.channel, &self.message);
+        Ok(RespDataType::Integer(receivers as i64))
+    }
+}
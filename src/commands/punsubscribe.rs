@@ -0,0 +1,54 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, pubsub::Kind, resp::RespDataType, server::RedisServer,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PUnsubscribeCommand {
+    pub patterns: Vec<Bytes>,
+}
+
+impl DescribedCommand for PUnsubscribeCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "punsubscribe",
+        summary:
+            "Stops listening to messages published to channels that match one or more patterns.",
+        since: "2.0.0",
+        arity: -1,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::PubSub, AclCategory::Slow],
+    };
+}
+
+impl ParseableCommand for PUnsubscribeCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let patterns = args.filter_map(|arg| arg.into_bytes()).collect();
+        Ok(PUnsubscribeCommand { patterns })
+    }
+}
+
+impl RunnableCommand for PUnsubscribeCommand {
+    /// The reply is a push per pattern, sent through `client.notifier` like those of
+    /// `PSUBSCRIBE`.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server
+            .pubsub
+            .unsubscribe(client, Kind::Pattern, &self.patterns);
+        Ok(RespDataType::Null)
+    }
+}
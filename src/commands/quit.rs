@@ -0,0 +1,45 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct QuitCommand;
+
+impl DescribedCommand for QuitCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "quit",
+        summary: "Closes the connection.",
+        since: "1.0.0",
+        arity: -1,
+        flags: &[
+            CommandFlag::AllowBusy,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Connection],
+    };
+}
+
+impl ParseableCommand for QuitCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(QuitCommand)
+    }
+}
+
+impl RunnableCommand for QuitCommand {
+    /// `client_process` closes the connection once the reply is written.
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        Ok(RespDataType::ok())
+    }
+}
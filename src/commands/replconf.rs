@@ -0,0 +1,116 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReplConfOption {
+    ListeningPort(u16),
+    /// Capabilities of the replica, e.g. `eof` or `psync2`. Accepted, none changes the
+    /// transfer yet.
+    Capa(String),
+    /// Replication offset processed by a replica, sent on its replication link.
+    Ack(u64),
+    /// Offset a replica has fsynced to its AOF, sent along with `ACK`.
+    Fack(u64),
+    /// Sent by a master on the replication link, the replica answers with `REPLCONF ACK`.
+    GetAck,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReplConfCommand {
+    pub options: Vec<ReplConfOption>,
+}
+
+impl DescribedCommand for ReplConfCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "replconf",
+        summary: "An internal command for configuring the replication stream.",
+        since: "3.0.0",
+        arity: -1,
+        flags: &[
+            CommandFlag::Admin,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for ReplConfCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        if !args.len().is_multiple_of(2) {
+            return Err(RedisError::Syntax);
+        }
+        let mut options = Vec::new();
+        while let Some(name) = args.next().and_then(|arg| arg.to_string()) {
+            let value = args
+                .next()
+                .and_then(|arg| arg.to_string())
+                .unwrap_or_default();
+            let option = match name.to_ascii_lowercase().as_str() {
+                "listening-port" => ReplConfOption::ListeningPort(
+                    value.parse().map_err(|_| RedisError::NotInteger)?,
+                ),
+                "capa" => ReplConfOption::Capa(value),
+                "getack" => ReplConfOption::GetAck,
+                "ack" => ReplConfOption::Ack(value.parse().map_err(|_| RedisError::NotInteger)?),
+                "fack" => ReplConfOption::Fack(value.parse().map_err(|_| RedisError::NotInteger)?),
+                _ => {
+                    return Err(RedisError::custom(format!(
+                        "Unrecognized REPLCONF option: {}",
+                        name
+                    )))
+                }
+            };
+            options.push(option);
+        }
+        Ok(ReplConfCommand { options })
+    }
+}
+
+impl ReplConfCommand {
+    /// The offset of a `REPLCONF ACK`.
+    pub fn ack(&self) -> Option<u64> {
+        self.options.iter().find_map(|option| match option {
+            ReplConfOption::Ack(offset) => Some(*offset),
+            _ => None,
+        })
+    }
+
+    /// The AOF offset of a `REPLCONF ACK`, sent by replicas with the AOF enabled.
+    pub fn fack(&self) -> Option<u64> {
+        self.options.iter().find_map(|option| match option {
+            ReplConfOption::Fack(offset) => Some(*offset),
+            _ => None,
+        })
+    }
+
+    pub fn getack(&self) -> bool {
+        self.options.contains(&ReplConfOption::GetAck)
+    }
+}
+
+impl RunnableCommand for ReplConfCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        _server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        for option in &self.options {
+            if let ReplConfOption::ListeningPort(port) = option {
+                client.replica_listening_port = Some(*port);
+            }
+        }
+        Ok(RespDataType::ok())
+    }
+}
@@ -0,0 +1,83 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, config::Apply, config::LogLevel, error::RedisError, resp::RespDataType,
+    server::RedisServer,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ReplicaOfCommand {
+    /// The master to replicate, `None` for `REPLICAOF NO ONE`.
+    pub master: Option<(String, u16)>,
+}
+
+impl DescribedCommand for ReplicaOfCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "replicaof",
+        summary: "Configures a server as replica of another, or promotes it to a master.",
+        since: "5.0.0",
+        arity: 3,
+        flags: &[
+            CommandFlag::Admin,
+            CommandFlag::NoScript,
+            CommandFlag::Stale,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for ReplicaOfCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let host = Self::get_arg_as_string(args, RedisError::WrongArity("replicaof"))?;
+        let port = Self::get_arg_as_string(args, RedisError::WrongArity("replicaof"))?;
+        if host.eq_ignore_ascii_case("no") && port.eq_ignore_ascii_case("one") {
+            return Ok(ReplicaOfCommand { master: None });
+        }
+        let port = port.parse().map_err(|_| RedisError::NotInteger)?;
+        Ok(ReplicaOfCommand {
+            master: Some((host, port)),
+        })
+    }
+}
+
+impl RunnableCommand for ReplicaOfCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        {
+            let mut config = server.config_mut();
+            if config.replicaof == self.master {
+                return Ok(match self.master {
+                    Some(_) => {
+                        RespDataType::simple_string("OK Already connected to specified master")
+                    }
+                    None => RespDataType::ok(),
+                });
+            }
+            config.replicaof = self.master.clone();
+        }
+        let message = match &self.master {
+            Some((host, port)) => format!("REPLICAOF {}:{} enabled", host, port),
+            None => "MASTER MODE enabled".to_string(),
+        };
+        server.log(
+            LogLevel::Notice,
+            format!("{} (user request from 'id={}')", message, client.id),
+        );
+        server
+            .apply_config(Apply::Replication)
+            .await
+            .map_err(RedisError::custom)?;
+        Ok(RespDataType::ok())
+    }
+}
@@ -0,0 +1,57 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient,
+    error::RedisError,
+    resp::{ProtocolVersion, RespDataType},
+    server::RedisServer,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResetCommand;
+
+impl DescribedCommand for ResetCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "reset",
+        summary: "Resets the connection.",
+        since: "6.2.0",
+        arity: 1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::NoAuth,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Connection],
+    };
+}
+
+impl ParseableCommand for ResetCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(ResetCommand)
+    }
+}
+
+impl RunnableCommand for ResetCommand {
+    /// Puts the client back the way it connected: no transaction, watched keys or
+    /// subscriptions, RESP2, no name, and not authenticated.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        client.multi = None;
+        client.unwatch(&server.store);
+        server.pubsub.unsubscribe_all(client);
+        client.protocol = ProtocolVersion::Resp2;
+        client.name = None;
+        client.authenticated = false;
+        Ok(RespDataType::simple_string("RESET"))
+    }
+}
@@ -0,0 +1,147 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient,
+    error::RedisError,
+    rdb::{self, RdbError},
+    resp::RespDataType,
+    server::RedisServer,
+    types::RedisType,
+    utils,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RestoreCommand {
+    pub key: String,
+    /// Milliseconds to live, 0 for no expire.
+    pub ttl: u64,
+    pub payload: Bytes,
+    pub replace: bool,
+    /// `ttl` is an absolute Unix time in milliseconds.
+    pub absttl: bool,
+}
+
+impl DescribedCommand for RestoreCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "restore",
+        summary: "Creates a key from the serialized representation of a value.",
+        since: "2.6.0",
+        arity: -4,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[
+            AclCategory::Keyspace,
+            AclCategory::Write,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for RestoreCommand {
+    /// `IDLETIME` and `FREQ` are validated but ignored, access times aren't tracked.
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("restore"))?;
+        let ttl = Self::get_arg_as_int(args, RedisError::WrongArity("restore"))?;
+        let payload = Self::get_arg_as_bytes(args, RedisError::WrongArity("restore"))?;
+        let mut command = RestoreCommand {
+            key,
+            ttl: 0,
+            payload,
+            replace: false,
+            absttl: false,
+        };
+
+        let (mut idletime, mut freq) = (false, false);
+        while let Some(option) = args.next().and_then(|arg| arg.to_string()) {
+            match option.to_ascii_uppercase().as_str() {
+                "REPLACE" => command.replace = true,
+                "ABSTTL" => command.absttl = true,
+                "IDLETIME" if args.len() > 0 && !freq => {
+                    if Self::get_arg_as_int(args, RedisError::Syntax)? < 0 {
+                        return Err(RedisError::custom("Invalid IDLETIME value, must be >= 0"));
+                    }
+                    idletime = true;
+                }
+                "FREQ" if args.len() > 0 && !idletime => {
+                    if !(0..=255).contains(&Self::get_arg_as_int(args, RedisError::Syntax)?) {
+                        return Err(RedisError::custom(
+                            "Invalid FREQ value, must be >= 0 and <= 255",
+                        ));
+                    }
+                    freq = true;
+                }
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        command.ttl = u64::try_from(ttl)
+            .map_err(|_| RedisError::custom("Invalid TTL value, must be >= 0"))?;
+        Ok(command)
+    }
+}
+
+impl RunnableCommand for RestoreCommand {
+    /// Only strings can expire, a TTL given with another type is refused.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let exists = match server.store.get_key(&self.key).await {
+            Some(value) => !matches!(&*value, RedisType::String(kv) if kv.is_expired()),
+            None => false,
+        };
+        if exists && !self.replace {
+            return Err(RedisError::BusyKey);
+        }
+        let mut value = rdb::restore(&self.payload).map_err(|e| match e {
+            RdbError::BadPayload => RedisError::custom(e.to_string()),
+            _ => RedisError::custom("Bad data format"),
+        })?;
+
+        let now = utils::now_millis();
+        let expire_at = match self.ttl {
+            0 => None,
+            ttl if self.absttl => Some(ttl as u128),
+            ttl => Some(now + ttl as u128),
+        };
+        // Like when loading an RDB file, whether the TTL already passed or not.
+        if expire_at.is_some() && !matches!(value, RedisType::String(_)) {
+            return Err(RedisError::custom(
+                "TTL is only supported for string values",
+            ));
+        }
+        if expire_at.is_some_and(|at| at <= now) {
+            // Restored and expired right away: only the removal of the old value is kept.
+            client.argv = match server.store.remove(&self.key).await {
+                Some(_) if exists => vec![Bytes::from("DEL"), Bytes::from(self.key.clone())],
+                _ => vec![],
+            };
+            return Ok(RespDataType::ok());
+        }
+
+        if let RedisType::String(kv) = &mut value {
+            kv.expired_at_millis = expire_at;
+        }
+        server.store.insert(self.key.clone(), value).await;
+        server.store.notify_key_modified(&self.key).await;
+
+        // A relative TTL would restart counting when replayed.
+        if let (Some(at), false) = (expire_at, self.absttl) {
+            client.argv = vec![
+                Bytes::from("RESTORE"),
+                Bytes::from(self.key.clone()),
+                Bytes::from(at.to_string()),
+                self.payload.clone(),
+                Bytes::from("REPLACE"),
+                Bytes::from("ABSTTL"),
+            ];
+        }
+        Ok(RespDataType::ok())
+    }
+}
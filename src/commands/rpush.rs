@@ -1,26 +1,40 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
 use std::collections::VecDeque;
 
-use super::traits::{ParseableCommand, RunnableCommand};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
 use crate::{
-    resp::RespDataType,
-    store::{KeyResult, RedisStore},
-    types::RedisType,
+    client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer,
+    store::KeyResult, types::RedisType,
 };
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct RPushCommand {
     pub key: String,
-    pub values: Vec<String>,
+    pub values: Vec<Bytes>,
+}
+
+impl DescribedCommand for RPushCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "rpush",
+        summary: "Appends one or more elements to a list. Creates the key if it doesn't exist.",
+        since: "1.0.0",
+        arity: -3,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Write, AclCategory::List, AclCategory::Fast],
+    };
 }
 
 impl ParseableCommand for RPushCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "RPUSH command requires a key")?;
-        let values: Vec<String> = args.filter_map(|t| t.to_string()).collect();
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("rpush"))?;
+        let values: Vec<Bytes> = args.filter_map(|t| t.into_bytes()).collect();
         if values.is_empty() {
-            return Err("RPUSH requires at least one value".to_string());
+            return Err(RedisError::WrongArity("rpush"));
         }
 
         Ok(RPushCommand { key, values })
@@ -30,18 +44,18 @@ impl ParseableCommand for RPushCommand {
 impl RunnableCommand for RPushCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_list(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_list(&self.key).await {
             Some(mut list) => {
                 for value in self.values.iter() {
                     list.push_back(value.clone());
                 }
-                store.notify_key_modified(&self.key).await;
+                server.store.grow(self.values.iter().map(Bytes::len).sum());
+                server.store.notify_key_modified(&self.key).await;
                 let len = list.len() as i64;
-                return Some(RespDataType::Integer(len));
+                return Ok(RespDataType::Integer(len));
             }
             None => {
                 let mut new_list = VecDeque::new();
@@ -49,13 +63,16 @@ impl RunnableCommand for RPushCommand {
                     new_list.push_back(value.clone());
                 }
                 let len = new_list.len() as i64;
-                let result = store.create(&self.key, RedisType::List(new_list)).await;
+                let result = server
+                    .store
+                    .create(&self.key, RedisType::List(new_list))
+                    .await;
 
                 match result {
-                    KeyResult::Error(e) => Some(RespDataType::error(&e)),
+                    KeyResult::Error(e) => Err(e),
                     _ => {
-                        store.notify_key_modified(&self.key).await;
-                        Some(RespDataType::Integer(len))
+                        server.store.notify_key_modified(&self.key).await;
+                        Ok(RespDataType::Integer(len))
                     }
                 }
             }
@@ -0,0 +1,42 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SaveCommand;
+
+impl DescribedCommand for SaveCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "save",
+        summary: "Synchronously saves the database(s) to disk.",
+        since: "1.0.0",
+        arity: 1,
+        flags: &[CommandFlag::Admin, CommandFlag::NoScript],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[
+            AclCategory::Admin,
+            AclCategory::Slow,
+            AclCategory::Dangerous,
+        ],
+    };
+}
+
+impl ParseableCommand for SaveCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(SaveCommand)
+    }
+}
+
+impl RunnableCommand for SaveCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server.save().await?;
+        Ok(RespDataType::ok())
+    }
+}
@@ -0,0 +1,95 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, scripting, server::RedisServer,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ScriptSubcommand {
+    Load(String),
+    Exists(Vec<String>),
+    Flush,
+    Kill,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScriptCommand {
+    pub subcommand: ScriptSubcommand,
+}
+
+impl DescribedCommand for ScriptCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "script",
+        summary: "Manages the server-side Lua script cache.",
+        since: "2.6.0",
+        arity: -2,
+        flags: &[CommandFlag::NoScript, CommandFlag::AllowBusy],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Scripting],
+    };
+}
+
+impl ParseableCommand for ScriptCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let subcommand_name = Self::get_arg_as_string(args, RedisError::WrongArity("script"))?;
+        let subcommand = match subcommand_name.to_ascii_uppercase().as_str() {
+            "LOAD" if args.len() == 1 => ScriptSubcommand::Load(Self::get_arg_as_string(
+                args,
+                RedisError::WrongArity("script|load"),
+            )?),
+            "LOAD" => return Err(RedisError::WrongArity("script|load")),
+            "EXISTS" if args.len() > 0 => {
+                ScriptSubcommand::Exists(args.filter_map(|arg| arg.to_string()).collect())
+            }
+            "EXISTS" => return Err(RedisError::WrongArity("script|exists")),
+            // The ASYNC and SYNC modes make no difference for a cache this small.
+            "FLUSH" => match args.next().and_then(|arg| arg.to_string()) {
+                None => ScriptSubcommand::Flush,
+                Some(mode)
+                    if args.len() == 0
+                        && (mode.eq_ignore_ascii_case("async")
+                            || mode.eq_ignore_ascii_case("sync")) =>
+                {
+                    ScriptSubcommand::Flush
+                }
+                Some(_) => return Err(RedisError::Syntax),
+            },
+            "KILL" if args.len() == 0 => ScriptSubcommand::Kill,
+            "KILL" => return Err(RedisError::WrongArity("script|kill")),
+            _ => return Err(RedisError::UnknownSubcommand(subcommand_name, "SCRIPT")),
+        };
+        Ok(ScriptCommand { subcommand })
+    }
+}
+
+impl RunnableCommand for ScriptCommand {
+    async fn execute(
+        &self,
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let scripts = &server.scripting;
+        match &self.subcommand {
+            ScriptSubcommand::Load(body) => {
+                scripting::compile(body)?;
+                Ok(RespDataType::bulk_string(scripts.cache(body)))
+            }
+            ScriptSubcommand::Exists(shas) => Ok(RespDataType::Array(
+                shas.iter()
+                    .map(|sha| RespDataType::Integer(scripts.get(sha).is_some() as i64))
+                    .collect(),
+            )),
+            ScriptSubcommand::Flush => {
+                scripts.flush();
+                Ok(RespDataType::ok())
+            }
+            ScriptSubcommand::Kill => {
+                scripts.kill()?;
+                Ok(RespDataType::ok())
+            }
+        }
+    }
+}
@@ -1,12 +1,16 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
 use crate::{
+    client::RedisClient,
+    error::RedisError,
     resp::RespDataType,
-    store::{KeyResult, RedisStore},
+    server::RedisServer,
+    store::KeyResult,
     types::{key_value::KeyValue, RedisType},
 };
 
-use super::traits::{ParseableCommand, RunnableCommand};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct SetCommand {
@@ -14,9 +18,23 @@ pub struct SetCommand {
     pub value: KeyValue,
 }
 
+impl DescribedCommand for SetCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "set",
+        summary: "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist.",
+        since: "1.0.0",
+        arity: -3,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Write, AclCategory::String, AclCategory::Slow],
+    };
+}
+
 impl ParseableCommand for SetCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "SET command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("set"))?;
         let value = KeyValue::parse(args)?;
         Ok(SetCommand { key, value })
     }
@@ -25,16 +43,29 @@ impl ParseableCommand for SetCommand {
 impl RunnableCommand for SetCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server
+            .store
             .create_or_update_key(&self.key, RedisType::String(self.value.clone()))
             .await
         {
-            KeyResult::Error(e) => Some(RespDataType::error(&e)),
-            _ => Some(RespDataType::ok()),
+            KeyResult::Error(e) => Err(e),
+            _ => {
+                server.store.notify_key_modified(&self.key).await;
+                // A relative expire would restart counting when replayed.
+                if let Some(at) = self.value.expired_at_millis {
+                    client.argv = vec![
+                        Bytes::from("SET"),
+                        Bytes::from(self.key.clone()),
+                        self.value.value.clone(),
+                        Bytes::from("PXAT"),
+                        Bytes::from(at.to_string()),
+                    ];
+                }
+                Ok(RespDataType::ok())
+            }
         }
     }
 }
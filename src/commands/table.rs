@@ -0,0 +1,229 @@
+use crate::error::RedisError;
+
+/// Behaviour flags, named like the ones Redis reports through `COMMAND INFO`.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandFlag {
+    /// May modify the keyspace.
+    Write,
+    /// Never modifies the keyspace.
+    ReadOnly,
+    /// May grow memory usage, refused when over `maxmemory`.
+    DenyOom,
+    /// Administrative command.
+    Admin,
+    /// Pub/Sub related command.
+    PubSub,
+    /// Not allowed from scripts.
+    NoScript,
+    /// May block the client.
+    Blocking,
+    /// Runs in constant or logarithmic time.
+    Fast,
+    /// Allowed while the dataset is loading.
+    Loading,
+    /// Allowed on a replica with stale data.
+    Stale,
+    /// Allowed before the client authenticates.
+    NoAuth,
+    /// Allowed while a script is running past the busy threshold.
+    AllowBusy,
+    /// Not allowed inside a transaction.
+    NoMulti,
+}
+
+impl CommandFlag {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandFlag::Write => "write",
+            CommandFlag::ReadOnly => "readonly",
+            CommandFlag::DenyOom => "denyoom",
+            CommandFlag::Admin => "admin",
+            CommandFlag::PubSub => "pubsub",
+            CommandFlag::NoScript => "noscript",
+            CommandFlag::Blocking => "blocking",
+            CommandFlag::Fast => "fast",
+            CommandFlag::Loading => "loading",
+            CommandFlag::Stale => "stale",
+            CommandFlag::NoAuth => "no_auth",
+            CommandFlag::AllowBusy => "allow_busy",
+            CommandFlag::NoMulti => "no_multi",
+        }
+    }
+}
+
+/// ACL categories a command belongs to.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclCategory {
+    Keyspace,
+    Read,
+    Write,
+    String,
+    List,
+    SortedSet,
+    Stream,
+    PubSub,
+    Admin,
+    Fast,
+    Slow,
+    Blocking,
+    Dangerous,
+    Connection,
+    Transaction,
+    Scripting,
+}
+
+impl AclCategory {
+    /// The category name without the leading `@`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            AclCategory::Keyspace => "keyspace",
+            AclCategory::Read => "read",
+            AclCategory::Write => "write",
+            AclCategory::String => "string",
+            AclCategory::List => "list",
+            AclCategory::SortedSet => "sortedset",
+            AclCategory::Stream => "stream",
+            AclCategory::PubSub => "pubsub",
+            AclCategory::Admin => "admin",
+            AclCategory::Fast => "fast",
+            AclCategory::Slow => "slow",
+            AclCategory::Blocking => "blocking",
+            AclCategory::Dangerous => "dangerous",
+            AclCategory::Connection => "connection",
+            AclCategory::Transaction => "transaction",
+            AclCategory::Scripting => "scripting",
+        }
+    }
+}
+
+/// Static description of a command, declared next to its implementation.
+///
+/// Arity and key positions follow the Redis conventions: a positive arity is the exact number of
+/// arguments (command name included), a negative one the minimum. `first_key`, `last_key` and
+/// `step` are 1-based argument indexes, a negative `last_key` counts from the end.
+#[derive(Debug, PartialEq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// One line description and first Redis version with the command, for `COMMAND DOCS`.
+    pub summary: &'static str,
+    pub since: &'static str,
+    pub arity: i64,
+    pub flags: &'static [CommandFlag],
+    pub first_key: i64,
+    pub last_key: i64,
+    pub step: i64,
+    pub acl_categories: &'static [AclCategory],
+}
+
+impl CommandSpec {
+    /// Checks `argc`, the number of arguments including the command name, against the arity.
+    pub fn check_arity(&self, argc: usize) -> Result<(), RedisError> {
+        let argc = argc as i64;
+        if (self.arity > 0 && argc != self.arity) || argc < -self.arity {
+            return Err(RedisError::WrongArity(self.name));
+        }
+        Ok(())
+    }
+
+    pub fn has_flag(&self, flag: CommandFlag) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    pub fn has_category(&self, category: &str) -> bool {
+        self.acl_categories
+            .iter()
+            .any(|c| c.name().eq_ignore_ascii_case(category))
+    }
+
+    /// The documentation group, derived from the data type the command works on.
+    pub fn group(&self) -> &'static str {
+        for category in self.acl_categories {
+            match category {
+                AclCategory::String => return "string",
+                AclCategory::List => return "list",
+                AclCategory::SortedSet => return "sorted-set",
+                AclCategory::Stream => return "stream",
+                AclCategory::Keyspace => return "generic",
+                AclCategory::Connection => return "connection",
+                AclCategory::PubSub => return "pubsub",
+                AclCategory::Transaction => return "transactions",
+                AclCategory::Scripting => return "scripting",
+                _ => {}
+            }
+        }
+        "server"
+    }
+
+    /// Indexes of the key arguments for a call with `argc` arguments, command name included.
+    pub fn key_positions(&self, argc: usize) -> Vec<usize> {
+        if self.first_key <= 0 {
+            return vec![];
+        }
+        let argc = argc as i64;
+        let last = if self.last_key < 0 {
+            argc + self.last_key
+        } else {
+            self.last_key.min(argc - 1)
+        };
+        (self.first_key..=last)
+            .step_by(self.step.max(1) as usize)
+            .map(|i| i as usize)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: CommandSpec = CommandSpec {
+        name: "test",
+        summary: "",
+        since: "1.0.0",
+        arity: -3,
+        flags: &[CommandFlag::Write],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Write],
+    };
+
+    #[test]
+    fn test_check_arity() {
+        assert_eq!(SPEC.check_arity(2), Err(RedisError::WrongArity("test")));
+        assert_eq!(SPEC.check_arity(3), Ok(()));
+        assert_eq!(SPEC.check_arity(10), Ok(()));
+
+        let exact = CommandSpec { arity: 2, ..SPEC };
+        assert_eq!(exact.check_arity(2), Ok(()));
+        assert_eq!(exact.check_arity(3), Err(RedisError::WrongArity("test")));
+    }
+
+    #[test]
+    fn test_key_positions() {
+        assert_eq!(SPEC.key_positions(3), vec![1]);
+
+        let all_but_last = CommandSpec {
+            last_key: -2,
+            ..SPEC
+        };
+        assert_eq!(all_but_last.key_positions(5), vec![1, 2, 3]);
+
+        let pairs = CommandSpec {
+            last_key: -1,
+            step: 2,
+            ..SPEC
+        };
+        assert_eq!(pairs.key_positions(5), vec![1, 3]);
+
+        let keyless = CommandSpec {
+            first_key: 0,
+            last_key: 0,
+            step: 0,
+            ..SPEC
+        };
+        assert!(keyless.key_positions(3).is_empty());
+    }
+}
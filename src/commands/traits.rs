@@ -1,30 +1,50 @@
-use tokio::sync::Notify;
+use bytes::Bytes;
 
-use crate::{resp::RespDataType, store::RedisStore};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+
+use super::table::CommandSpec;
 use std::{sync::Arc, vec::IntoIter};
 
+/// Commands registered in `command_registry!` declare their metadata here: name, arity, flags,
+/// key positions and ACL categories.
+pub trait DescribedCommand {
+    const SPEC: CommandSpec;
+}
+
 pub trait ParseableCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String>
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError>
     where
         Self: Sized;
 
     fn get_arg_as_string(
         args: &mut IntoIter<RespDataType>,
-        error_message: &str,
-    ) -> Result<String, String> {
-        if let Some(arg) = args.next().and_then(|f| f.to_string()) {
-            Ok(arg)
-        } else {
-            Err(error_message.to_string())
-        }
+        error: RedisError,
+    ) -> Result<String, RedisError> {
+        args.next().and_then(|f| f.to_string()).ok_or(error)
+    }
+
+    fn get_arg_as_bytes(
+        args: &mut IntoIter<RespDataType>,
+        error: RedisError,
+    ) -> Result<Bytes, RedisError> {
+        args.next().and_then(|f| f.into_bytes()).ok_or(error)
+    }
+
+    fn get_arg_as_int(
+        args: &mut IntoIter<RespDataType>,
+        error: RedisError,
+    ) -> Result<i64, RedisError> {
+        args.next()
+            .ok_or(error)?
+            .to_int()
+            .ok_or(RedisError::NotInteger)
     }
 }
 
 pub trait RunnableCommand: Send + Sync {
     async fn execute(
         &self,
-        client_id: &str,
-        store: &Arc<RedisStore>,
-        client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType>;
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError>;
 }
@@ -0,0 +1,53 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, pubsub::Kind, resp::RespDataType, server::RedisServer,
+};
+use bytes::Bytes;
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnsubscribeCommand {
+    pub channels: Vec<Bytes>,
+}
+
+impl DescribedCommand for UnsubscribeCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "unsubscribe",
+        summary: "Stops listening to messages posted to channels.",
+        since: "2.0.0",
+        arity: -1,
+        flags: &[
+            CommandFlag::PubSub,
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::PubSub, AclCategory::Slow],
+    };
+}
+
+impl ParseableCommand for UnsubscribeCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let channels = args.filter_map(|arg| arg.into_bytes()).collect();
+        Ok(UnsubscribeCommand { channels })
+    }
+}
+
+impl RunnableCommand for UnsubscribeCommand {
+    /// The reply is a push per channel, sent through `client.notifier` like those of
+    /// `SUBSCRIBE`.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        server
+            .pubsub
+            .unsubscribe(client, Kind::Channel, &self.channels);
+        Ok(RespDataType::Null)
+    }
+}
@@ -0,0 +1,44 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnwatchCommand;
+
+impl DescribedCommand for UnwatchCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "unwatch",
+        summary: "Forgets about watched keys of a transaction.",
+        since: "2.2.0",
+        arity: 1,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Fast, AclCategory::Transaction],
+    };
+}
+
+impl ParseableCommand for UnwatchCommand {
+    fn parse(_args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        Ok(UnwatchCommand)
+    }
+}
+
+impl RunnableCommand for UnwatchCommand {
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        client.unwatch(&server.store);
+        Ok(RespDataType::ok())
+    }
+}
@@ -0,0 +1,86 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use bytes::Bytes;
+use std::{sync::Arc, time::Duration, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WaitCommand {
+    pub numreplicas: i64,
+    /// Milliseconds, 0 to wait forever.
+    pub timeout: u64,
+}
+
+impl DescribedCommand for WaitCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "wait",
+        summary: "Blocks until the asynchronous replication of all preceding write commands sent by the connection is completed.",
+        since: "3.0.0",
+        arity: 3,
+        flags: &[CommandFlag::NoScript, CommandFlag::Blocking],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Connection],
+    };
+}
+
+/// The timeout of `WAIT` and `WAITAOF`, in milliseconds.
+pub fn parse_timeout(
+    args: &mut IntoIter<RespDataType>,
+    name: &'static str,
+) -> Result<u64, RedisError> {
+    let timeout = args
+        .next()
+        .ok_or(RedisError::WrongArity(name))?
+        .to_int()
+        .ok_or_else(|| RedisError::custom("timeout is not an integer or out of range"))?;
+    u64::try_from(timeout).map_err(|_| RedisError::NegativeTimeout)
+}
+
+/// Asks the replicas to acknowledge their offset now rather than on their next periodic ACK.
+pub fn request_acks(server: &RedisServer) {
+    server.replication.feed(&[
+        Bytes::from_static(b"REPLCONF"),
+        Bytes::from_static(b"GETACK"),
+        Bytes::from_static(b"*"),
+    ]);
+}
+
+impl ParseableCommand for WaitCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let numreplicas = Self::get_arg_as_int(args, RedisError::WrongArity("wait"))?;
+        let timeout = parse_timeout(args, "wait")?;
+        Ok(WaitCommand {
+            numreplicas,
+            timeout,
+        })
+    }
+}
+
+impl RunnableCommand for WaitCommand {
+    /// Replies with the number of replicas that acknowledged the last write of the client,
+    /// once there are enough or the timeout passed.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        if server.config().replicaof.is_some() {
+            return Err(RedisError::custom(
+                "WAIT cannot be used with replica instances. Please also note that since Redis 4.0 if a replica is configured to be writable (which is not the default) writes to replicas are just local and are not propagated.",
+            ));
+        }
+        let offset = client.woff;
+        let replication = &server.replication;
+        let acked = || replication.count_acked(offset, false);
+        if (acked() as i64) < self.numreplicas && !client.deny_blocking() {
+            request_acks(server);
+            let timeout = (self.timeout > 0).then(|| Duration::from_millis(self.timeout));
+            replication
+                .wait_progress(timeout, || acked() as i64 >= self.numreplicas)
+                .await;
+        }
+        Ok(RespDataType::Integer(acked() as i64))
+    }
+}
@@ -0,0 +1,87 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use super::wait::{parse_timeout, request_acks};
+use crate::{client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer};
+use std::{
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+    vec::IntoIter,
+};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WaitAofCommand {
+    /// Whether the local AOF must have it on disk, any positive number counts as one.
+    pub numlocal: i64,
+    pub numreplicas: i64,
+    /// Milliseconds, 0 to wait forever.
+    pub timeout: u64,
+}
+
+impl DescribedCommand for WaitAofCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "waitaof",
+        summary: "Blocks until all of the preceding write commands sent by the connection are written to the append-only file of the master and/or replicas.",
+        since: "7.2.0",
+        arity: 4,
+        flags: &[CommandFlag::NoScript, CommandFlag::Blocking],
+        first_key: 0,
+        last_key: 0,
+        step: 0,
+        acl_categories: &[AclCategory::Slow, AclCategory::Connection],
+    };
+}
+
+impl ParseableCommand for WaitAofCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let numlocal = Self::get_arg_as_int(args, RedisError::WrongArity("waitaof"))?;
+        let numreplicas = Self::get_arg_as_int(args, RedisError::WrongArity("waitaof"))?;
+        let timeout = parse_timeout(args, "waitaof")?;
+        Ok(WaitAofCommand {
+            numlocal,
+            numreplicas,
+            timeout,
+        })
+    }
+}
+
+impl RunnableCommand for WaitAofCommand {
+    /// Replies with whether the local AOF has the last write of the client on disk, and the
+    /// number of replicas that fsynced it to theirs, once both are enough or the timeout
+    /// passed.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let (replicaof, appendonly) = {
+            let config = server.config();
+            (config.replicaof.is_some(), config.appendonly)
+        };
+        if replicaof {
+            return Err(RedisError::custom(
+                "WAITAOF cannot be used with replica instances. Please also note that writes to replicas are just local and are not propagated.",
+            ));
+        }
+        if self.numlocal > 0 && !appendonly {
+            return Err(RedisError::custom(
+                "WAITAOF cannot be used when numlocal is set but appendonly is disabled.",
+            ));
+        }
+        let offset = client.woff;
+        let replication = &server.replication;
+        let local = || server.persistence.aof_fsynced_offset.load(Ordering::SeqCst) >= offset;
+        let acked = || replication.count_acked(offset, true);
+        let reached = || (self.numlocal <= 0 || local()) && acked() as i64 >= self.numreplicas;
+        if !reached() && !client.deny_blocking() {
+            if (acked() as i64) < self.numreplicas {
+                request_acks(server);
+            }
+            let timeout = (self.timeout > 0).then(|| Duration::from_millis(self.timeout));
+            replication.wait_progress(timeout, reached).await;
+        }
+        Ok(RespDataType::Array(vec![
+            RespDataType::Integer(local() as i64),
+            RespDataType::Integer(acked() as i64),
+        ]))
+    }
+}
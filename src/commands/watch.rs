@@ -0,0 +1,64 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand, RunnableCommand};
+use crate::{
+    client::RedisClient, error::RedisError, resp::RespDataType, server::RedisServer, utils,
+};
+use std::{sync::Arc, vec::IntoIter};
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct WatchCommand {
+    pub keys: Vec<String>,
+}
+
+impl DescribedCommand for WatchCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "watch",
+        summary: "Monitors changes to keys to determine the execution of a transaction.",
+        since: "2.2.0",
+        arity: -2,
+        flags: &[
+            CommandFlag::NoScript,
+            CommandFlag::Loading,
+            CommandFlag::Stale,
+            CommandFlag::Fast,
+            CommandFlag::AllowBusy,
+        ],
+        first_key: 1,
+        last_key: -1,
+        step: 1,
+        acl_categories: &[AclCategory::Fast, AclCategory::Transaction],
+    };
+}
+
+impl ParseableCommand for WatchCommand {
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let keys = args.filter_map(|arg| arg.to_string()).collect();
+        Ok(WatchCommand { keys })
+    }
+}
+
+impl RunnableCommand for WatchCommand {
+    /// `EXEC` fails if any of the keys changes or expires from now on.
+    async fn execute(
+        &self,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        if client.multi.is_some() {
+            return Err(RedisError::custom("WATCH inside MULTI is not allowed"));
+        }
+        let now = utils::now_millis();
+        for key in &self.keys {
+            if client
+                .watched_keys
+                .iter()
+                .any(|(watched, _)| watched == key)
+            {
+                continue;
+            }
+            server.store.watch(key, &client.watch_touched);
+            client.watched_keys.push((key.clone(), now));
+        }
+        Ok(RespDataType::ok())
+    }
+}
@@ -1,14 +1,16 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use crate::client::RedisClient;
 use crate::commands::traits::RunnableCommand;
+use crate::error::RedisError;
 use crate::resp::RespDataType;
-use crate::store::RedisStore;
+use crate::server::RedisServer;
 use crate::types::stream::{RedisStream, StreamEntry};
 use crate::types::RedisType;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::vec::IntoIter;
-use tokio::sync::Notify;
 
-use super::traits::ParseableCommand;
+use super::traits::{DescribedCommand, ParseableCommand};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct XAddCommand {
@@ -17,7 +19,7 @@ pub struct XAddCommand {
     pub values: HashMap<String, String>,
 }
 
-fn parse_map(args: &mut IntoIter<RespDataType>) -> Result<HashMap<String, String>, String> {
+fn parse_map(args: &mut IntoIter<RespDataType>) -> Result<HashMap<String, String>, RedisError> {
     let mut map = HashMap::new();
     loop {
         let name_arg = args.next();
@@ -25,25 +27,41 @@ fn parse_map(args: &mut IntoIter<RespDataType>) -> Result<HashMap<String, String
         if name_arg.is_none() && value_arg.is_none() {
             break;
         } else if name_arg.is_none() || value_arg.is_none() {
-            return Err("XADD command requires key-value pair".to_string());
+            return Err(RedisError::WrongArity("xadd"));
         }
 
-        let name_value = name_arg.unwrap().to_string().unwrap();
-        let value_value = value_arg.unwrap().to_string().unwrap();
+        let (name_value, value_value) = name_arg
+            .and_then(|name| name.to_string())
+            .zip(value_arg.and_then(|value| value.to_string()))
+            .ok_or_else(|| RedisError::custom("XADD fields and values must be valid UTF-8"))?;
         map.insert(name_value, value_value);
     }
 
     if map.is_empty() {
-        return Err("XADD command requires at least one key-value pair".to_string());
+        return Err(RedisError::WrongArity("xadd"));
     } else {
         return Ok(map);
     }
 }
 
+impl DescribedCommand for XAddCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "xadd",
+        summary: "Appends a new message to a stream. Creates the key if it doesn't exist.",
+        since: "5.0.0",
+        arity: -5,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Write, AclCategory::Stream, AclCategory::Fast],
+    };
+}
+
 impl ParseableCommand for XAddCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let stream_key = Self::get_arg_as_string(args, "XADD command requires a stream key")?;
-        let entry_key = Self::get_arg_as_string(args, "XADD command requires a entry key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let stream_key = Self::get_arg_as_string(args, RedisError::WrongArity("xadd"))?;
+        let entry_key = Self::get_arg_as_string(args, RedisError::WrongArity("xadd"))?;
         let values = parse_map(args)?;
 
         Ok(XAddCommand {
@@ -57,28 +75,25 @@ impl ParseableCommand for XAddCommand {
 impl RunnableCommand for XAddCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        let mut stream = match store.get_key(&self.stream_key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        let mut stream = match server.store.get_key(&self.stream_key).await {
             Some(value) => match *value {
                 RedisType::Stream(ref s) => s.clone(),
-                _ => {
-                    return Some(RespDataType::error(
-                        "WRONGTYPE Operation against a key holding the wrong kind of value",
-                    ));
-                }
+                _ => return Err(RedisError::WrongType),
             },
             None => RedisStream::new(),
         };
 
         let entry = StreamEntry::new(self.values.clone());
         stream.add_entry(self.entry_key.clone(), entry);
-        store
+        server
+            .store
             .create_or_update_key(&self.stream_key, RedisType::Stream(stream))
             .await;
+        server.store.notify_key_modified(&self.stream_key).await;
 
-        Some(RespDataType::bulk_string(&self.entry_key))
+        Ok(RespDataType::bulk_string(&self.entry_key))
     }
 }
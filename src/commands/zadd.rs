@@ -1,9 +1,11 @@
-use tokio::sync::Notify;
-
-use super::traits::ParseableCommand;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand};
+use crate::client::RedisClient;
 use crate::commands::traits::RunnableCommand;
+use crate::error::RedisError;
 use crate::resp::RespDataType;
-use crate::store::{KeyResult, RedisStore};
+use crate::server::RedisServer;
+use crate::store::KeyResult;
 use crate::types::sorted_set::{SortedSet, SortedValue};
 use crate::types::RedisType;
 use std::sync::Arc;
@@ -48,10 +50,10 @@ impl ZAddOptions {
         let mut options = ZAddOptions::new();
 
         while let Some(prop_name) = args.as_slice().get(0) {
-            let option_str = prop_name
-                .to_string()
-                .expect("cannot convert value to string")
-                .to_ascii_uppercase();
+            let option_str = match prop_name.to_string() {
+                Some(option) => option.to_ascii_uppercase(),
+                None => break,
+            };
             match option_str.as_str() {
                 "XX" => {
                     options.xx = true;
@@ -82,29 +84,50 @@ impl ZAddOptions {
 }
 
 impl SortedValue {
-    pub fn parse(args: &mut IntoIter<RespDataType>) -> Option<Vec<SortedValue>> {
+    pub fn parse(args: &mut IntoIter<RespDataType>) -> Result<Vec<SortedValue>, RedisError> {
+        if args.len() == 0 {
+            return Err(RedisError::WrongArity("zadd"));
+        }
+        if !args.len().is_multiple_of(2) {
+            return Err(RedisError::Syntax);
+        }
         let mut sorted_values: Vec<SortedValue> = Vec::new();
         while let (Some(score_arg), Some(member_arg)) = (args.next(), args.next()) {
-            if let (Some(score), Some(member)) = (score_arg.to_float(), member_arg.to_string()) {
-                sorted_values.push(SortedValue { member, score });
-            } else {
-                return None;
-            }
-        }
-        if sorted_values.is_empty() {
-            None
-        } else {
-            Some(sorted_values)
+            let score = score_arg
+                .to_float()
+                .filter(|score| !score.is_nan())
+                .ok_or(RedisError::NotFloat)?;
+            let member = member_arg.into_bytes().ok_or(RedisError::Syntax)?;
+            sorted_values.push(SortedValue { member, score });
         }
+        Ok(sorted_values)
     }
 }
 
+impl DescribedCommand for ZAddCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "zadd",
+        summary: "Adds one or more members to a sorted set, or updates their scores. Creates the key if it doesn't exist.",
+        since: "1.2.0",
+        arity: -4,
+        flags: &[CommandFlag::Write, CommandFlag::DenyOom, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[
+            AclCategory::Write,
+            AclCategory::SortedSet,
+            AclCategory::Fast,
+        ],
+    };
+}
+
 impl ParseableCommand for ZAddCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "ZADD command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("zadd"))?;
 
         let options = ZAddOptions::parse(args);
-        let values = SortedValue::parse(args).ok_or_else(|| "cant parse values".to_string())?;
+        let values = SortedValue::parse(args)?;
 
         Ok(ZAddCommand {
             key,
@@ -117,18 +140,23 @@ impl ParseableCommand for ZAddCommand {
 impl RunnableCommand for ZAddCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_sorted_set(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_sorted_set(&self.key).await {
             Some(mut ss) => {
                 let mut added = 0;
                 for value in self.values.clone() {
+                    let member = value.member.clone();
                     let count = ss.replace(value);
+                    if count > 0 {
+                        server.store.grow(SortedSet::member_usage(&member));
+                    }
                     added += count;
                 }
-                Some(RespDataType::Integer(added))
+                drop(ss);
+                server.store.notify_key_modified(&self.key).await;
+                Ok(RespDataType::Integer(added))
             }
             None => {
                 let mut ss = SortedSet::new();
@@ -137,10 +165,12 @@ impl RunnableCommand for ZAddCommand {
                     let count = ss.replace(value);
                     added += count;
                 }
-                match store.create(&self.key, RedisType::ZSet(ss)).await {
-                    KeyResult::Created => Some(RespDataType::Integer(added)),
-                    KeyResult::Updated => Some(RespDataType::error("Sorted Set already exists")),
-                    KeyResult::Error(s) => Some(RespDataType::error(&s)),
+                match server.store.create(&self.key, RedisType::ZSet(ss)).await {
+                    KeyResult::Error(e) => Err(e),
+                    _ => {
+                        server.store.notify_key_modified(&self.key).await;
+                        Ok(RespDataType::Integer(added))
+                    }
                 }
             }
         }
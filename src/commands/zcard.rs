@@ -1,7 +1,9 @@
-use tokio::sync::Notify;
-
-use super::traits::ParseableCommand;
-use crate::{commands::traits::RunnableCommand, resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand};
+use crate::{
+    client::RedisClient, commands::traits::RunnableCommand, error::RedisError, resp::RespDataType,
+    server::RedisServer,
+};
 use std::{sync::Arc, vec::IntoIter};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -9,9 +11,23 @@ pub struct ZCardCommand {
     pub key: String,
 }
 
+impl DescribedCommand for ZCardCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "zcard",
+        summary: "Returns the number of members in a sorted set.",
+        since: "1.2.0",
+        arity: 2,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::SortedSet, AclCategory::Fast],
+    };
+}
+
 impl ParseableCommand for ZCardCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "ZCARD command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("zcard"))?;
 
         Ok(ZCardCommand { key })
     }
@@ -20,13 +36,12 @@ impl ParseableCommand for ZCardCommand {
 impl RunnableCommand for ZCardCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_sorted_set(&self.key).await {
-            Some(ss) => return Some(RespDataType::Integer(ss.len())),
-            None => Some(RespDataType::Integer(0)),
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_sorted_set(&self.key).await {
+            Some(ss) => return Ok(RespDataType::Integer(ss.len())),
+            None => Ok(RespDataType::Integer(0)),
         }
     }
 }
@@ -1,10 +1,10 @@
-use super::traits::ParseableCommand;
-use crate::resp::RespDataType;
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand};
+use crate::error::RedisError;
+use crate::resp::{ProtocolVersion, RespDataType};
 use std::vec::IntoIter;
 
-use tokio::sync::Notify;
-
-use crate::{commands::traits::RunnableCommand, store::RedisStore};
+use crate::{client::RedisClient, commands::traits::RunnableCommand, server::RedisServer};
 use std::sync::Arc;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -12,27 +12,47 @@ pub struct ZRangeCommand {
     pub key: String,
     pub start: i64,
     pub end: i64,
+    pub with_scores: bool,
+}
+
+impl DescribedCommand for ZRangeCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "zrange",
+        summary: "Returns members in a sorted set within a range of indexes.",
+        since: "1.2.0",
+        arity: -4,
+        flags: &[CommandFlag::ReadOnly],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::SortedSet, AclCategory::Slow],
+    };
 }
 
 impl ParseableCommand for ZRangeCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "ZRANGE command requires a key")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("zrange"))?;
 
-        let start_arg = args
-            .next()
-            .ok_or_else(|| "Expected values for ZRANGE start and end".to_string())?;
-        let end_arg = args
-            .next()
-            .ok_or_else(|| "Expected values for ZRANGE start and end".to_string())?;
+        let start_arg = args.next().ok_or(RedisError::WrongArity("zrange"))?;
+        let end_arg = args.next().ok_or(RedisError::WrongArity("zrange"))?;
 
-        let start = start_arg
-            .to_int()
-            .ok_or_else(|| "Expected integer values for ZRANGE start and end".to_string())?;
-        let end = end_arg
-            .to_int()
-            .ok_or_else(|| "Expected integer values for ZRANGE start and end".to_string())?;
+        let start = start_arg.to_int().ok_or(RedisError::NotInteger)?;
+        let end = end_arg.to_int().ok_or(RedisError::NotInteger)?;
 
-        Ok(ZRangeCommand { key, start, end })
+        let mut with_scores = false;
+        for option in args {
+            match option.to_string() {
+                Some(option) if option.eq_ignore_ascii_case("WITHSCORES") => with_scores = true,
+                _ => return Err(RedisError::Syntax),
+            }
+        }
+
+        Ok(ZRangeCommand {
+            key,
+            start,
+            end,
+            with_scores,
+        })
     }
 }
 
@@ -67,23 +87,43 @@ impl ZRangeCommand {
 impl RunnableCommand for ZRangeCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_sorted_set(&self.key).await {
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_sorted_set(&self.key).await {
             Some(ss) => {
                 let (start, end) = match self.clone().treat_bounds(ss.len()) {
                     Some(value) => value,
-                    None => return Some(RespDataType::Array(vec![])),
+                    None => return Ok(RespDataType::Array(vec![])),
+                };
+                let members = ss.range(start, end);
+                let result_list = if !self.with_scores {
+                    members
+                        .map(|v| RespDataType::bulk_string(&v.member))
+                        .collect()
+                } else if client.protocol == ProtocolVersion::Resp3 {
+                    // RESP3 clients get a [member, score] pair per element.
+                    members
+                        .map(|v| {
+                            RespDataType::Array(vec![
+                                RespDataType::bulk_string(&v.member),
+                                RespDataType::Double(v.score),
+                            ])
+                        })
+                        .collect()
+                } else {
+                    members
+                        .flat_map(|v| {
+                            [
+                                RespDataType::bulk_string(&v.member),
+                                RespDataType::Double(v.score),
+                            ]
+                        })
+                        .collect()
                 };
-                let result_list = ss
-                    .range(start, end)
-                    .map(|v| RespDataType::bulk_string(&v.member))
-                    .collect();
-                return Some(RespDataType::Array(result_list));
+                return Ok(RespDataType::Array(result_list));
             }
-            None => Some(RespDataType::Array(vec![])),
+            None => Ok(RespDataType::Array(vec![])),
         }
     }
 }
@@ -1,18 +1,36 @@
-use super::traits::ParseableCommand;
-use crate::{commands::traits::RunnableCommand, resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand};
+use crate::{
+    client::RedisClient, commands::traits::RunnableCommand, error::RedisError, resp::RespDataType,
+    server::RedisServer,
+};
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ZRankCommand {
     pub key: String,
-    pub member: String,
+    pub member: Bytes,
+}
+
+impl DescribedCommand for ZRankCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "zrank",
+        summary: "Returns the index of a member in a sorted set ordered by ascending scores.",
+        since: "2.0.0",
+        arity: -3,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::SortedSet, AclCategory::Fast],
+    };
 }
 
 impl ParseableCommand for ZRankCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "ZADD command requires a key")?;
-        let member = Self::get_arg_as_string(args, "ZADD command requires a member")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("zrank"))?;
+        let member = Self::get_arg_as_bytes(args, RedisError::WrongArity("zrank"))?;
 
         Ok(ZRankCommand { key, member })
     }
@@ -21,16 +39,15 @@ impl ParseableCommand for ZRankCommand {
 impl RunnableCommand for ZRankCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_sorted_set(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_sorted_set(&self.key).await {
             Some(ss) => match ss.get_rank_by_member(&self.member) {
-                Some(val) => Some(RespDataType::Integer(val)),
-                None => Some(RespDataType::Null),
+                Some(val) => Ok(RespDataType::Integer(val)),
+                None => Ok(RespDataType::Null),
             },
-            None => Some(RespDataType::Null),
+            None => Ok(RespDataType::Null),
         }
     }
 }
@@ -1,18 +1,40 @@
-use super::traits::ParseableCommand;
-use crate::{commands::traits::RunnableCommand, resp::RespDataType, store::RedisStore};
+use super::table::{AclCategory, CommandFlag, CommandSpec};
+use super::traits::{DescribedCommand, ParseableCommand};
+use crate::{
+    client::RedisClient, commands::traits::RunnableCommand, error::RedisError, resp::RespDataType,
+    server::RedisServer, types::sorted_set::SortedSet,
+};
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ZRemCommand {
     pub key: String,
-    pub member: String,
+    pub member: Bytes,
+}
+
+impl DescribedCommand for ZRemCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "zrem",
+        summary: "Removes one or more members from a sorted set.",
+        since: "1.2.0",
+        arity: -3,
+        flags: &[CommandFlag::Write, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[
+            AclCategory::Write,
+            AclCategory::SortedSet,
+            AclCategory::Fast,
+        ],
+    };
 }
 
 impl ParseableCommand for ZRemCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "ZREM command requires a key")?;
-        let member = Self::get_arg_as_string(args, "ZREM command requires a member")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("zrem"))?;
+        let member = Self::get_arg_as_bytes(args, RedisError::WrongArity("zrem"))?;
 
         Ok(ZRemCommand { key, member })
     }
@@ -21,16 +43,20 @@ impl ParseableCommand for ZRemCommand {
 impl RunnableCommand for ZRemCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_sorted_set(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_sorted_set(&self.key).await {
             Some(mut ss) => {
                 let value = ss.remove_by_member(&self.member);
-                return Some(RespDataType::Integer(value));
+                drop(ss);
+                if value > 0 {
+                    server.store.shrink(SortedSet::member_usage(&self.member));
+                    server.store.notify_key_modified(&self.key).await;
+                }
+                return Ok(RespDataType::Integer(value));
             }
-            None => Some(RespDataType::Integer(0)),
+            None => Ok(RespDataType::Integer(0)),
         }
     }
 }
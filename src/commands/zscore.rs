@@ -1,21 +1,38 @@
+use super::table::{AclCategory, CommandFlag, CommandSpec};
 use crate::{
-    commands::traits::{ParseableCommand, RunnableCommand},
+    client::RedisClient,
+    commands::traits::{DescribedCommand, ParseableCommand, RunnableCommand},
+    error::RedisError,
     resp::RespDataType,
-    store::RedisStore,
+    server::RedisServer,
 };
+use bytes::Bytes;
 use std::{sync::Arc, vec::IntoIter};
-use tokio::sync::Notify;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ZScoreCommand {
     pub key: String,
-    pub member: String,
+    pub member: Bytes,
+}
+
+impl DescribedCommand for ZScoreCommand {
+    const SPEC: CommandSpec = CommandSpec {
+        name: "zscore",
+        summary: "Returns the score of a member in a sorted set.",
+        since: "1.2.0",
+        arity: 3,
+        flags: &[CommandFlag::ReadOnly, CommandFlag::Fast],
+        first_key: 1,
+        last_key: 1,
+        step: 1,
+        acl_categories: &[AclCategory::Read, AclCategory::SortedSet, AclCategory::Fast],
+    };
 }
 
 impl ParseableCommand for ZScoreCommand {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String> {
-        let key = Self::get_arg_as_string(args, "ZSCORE command requires a key")?;
-        let member = Self::get_arg_as_string(args, "ZSCORE command requires a member")?;
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError> {
+        let key = Self::get_arg_as_string(args, RedisError::WrongArity("zscore"))?;
+        let member = Self::get_arg_as_bytes(args, RedisError::WrongArity("zscore"))?;
 
         Ok(ZScoreCommand { key, member })
     }
@@ -24,16 +41,15 @@ impl ParseableCommand for ZScoreCommand {
 impl RunnableCommand for ZScoreCommand {
     async fn execute(
         &self,
-        _client_id: &str,
-        store: &Arc<RedisStore>,
-        _client_notifier: &Arc<Notify>,
-    ) -> Option<RespDataType> {
-        match store.get_sorted_set(&self.key).await {
+        _client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Result<RespDataType, RedisError> {
+        match server.store.get_sorted_set(&self.key).await {
             Some(ss) => match ss.get_score_by_member(&self.member) {
-                Some(value) => Some(RespDataType::bulk_string(&value.to_string())),
-                None => Some(RespDataType::Null),
+                Some(value) => Ok(RespDataType::Double(value)),
+                None => Ok(RespDataType::Null),
             },
-            None => Some(RespDataType::Null),
+            None => Ok(RespDataType::Null),
         }
     }
 }
@@ -0,0 +1,1035 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+use crate::resp;
+
+/// How deep `include` directives may nest before we assume a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Fatal error, can't open config file '{path}': {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error(
+        "Reading the configuration file '{origin}', at line {line}\n>>> '{directive}'\n{reason}"
+    )]
+    Directive {
+        origin: String,
+        line: usize,
+        directive: String,
+        reason: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppendFsync {
+    Always,
+    EverySec,
+    No,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Verbose,
+    Notice,
+    Warning,
+}
+
+/// Server configuration, built from the command line and `redis.conf`-style files.
+///
+/// Every parameter is registered in `PARAMETERS` with the functions used to read and write it
+/// by name, which is what the config file loader and `CONFIG GET`/`CONFIG SET` go through.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub bind: Vec<String>,
+    pub port: u16,
+    pub dir: PathBuf,
+    pub dbfilename: String,
+    pub requirepass: Option<String>,
+    pub maxmemory: u64,
+    pub maxmemory_policy: String,
+    pub replicaof: Option<(String, u16)>,
+    pub replica_read_only: bool,
+    pub repl_backlog_size: u64,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    pub appenddirname: String,
+    pub appendfsync: AppendFsync,
+    pub aof_load_truncated: bool,
+    pub aof_use_rdb_preamble: bool,
+    pub auto_aof_rewrite_percentage: u64,
+    pub auto_aof_rewrite_min_size: u64,
+    /// `save <seconds> <changes>` rules.
+    pub save: Vec<(u64, u64)>,
+    pub loglevel: LogLevel,
+    pub logfile: String,
+    pub busy_reply_threshold: u64,
+    /// The file the configuration was loaded from, if any.
+    pub config_file: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: vec!["127.0.0.1".to_string()],
+            port: 6379,
+            dir: PathBuf::from("."),
+            dbfilename: "dump.rdb".to_string(),
+            requirepass: None,
+            maxmemory: 0,
+            maxmemory_policy: "noeviction".to_string(),
+            replicaof: None,
+            replica_read_only: true,
+            repl_backlog_size: 1024 * 1024,
+            appendonly: false,
+            appendfilename: "appendonly.aof".to_string(),
+            appenddirname: "appendonlydir".to_string(),
+            appendfsync: AppendFsync::EverySec,
+            aof_load_truncated: true,
+            aof_use_rdb_preamble: true,
+            auto_aof_rewrite_percentage: 100,
+            auto_aof_rewrite_min_size: 64 * 1024 * 1024,
+            save: vec![(3600, 1), (300, 100), (60, 10000)],
+            loglevel: LogLevel::Notice,
+            logfile: String::new(),
+            busy_reply_threshold: 5000,
+            config_file: None,
+        }
+    }
+}
+
+/// Side effects of changing a parameter at runtime, run by `CONFIG SET` once every new value has
+/// been validated. Parameters without one are simply read from the config when needed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apply {
+    /// Rebind the listening sockets.
+    Listeners,
+    /// Evict keys until the dataset fits in `maxmemory` again.
+    Eviction,
+    /// Start or stop appending to the AOF.
+    Aof,
+    /// Connect to the new master, or stop replicating.
+    Replication,
+    /// Resize the replication backlog.
+    Backlog,
+}
+
+/// A configuration parameter that can be read and written by name.
+pub struct Parameter {
+    pub name: &'static str,
+    pub alias: Option<&'static str>,
+    /// Takes several arguments (`save 900 1`, `replicaof host port`), joined with spaces.
+    multi_arg: bool,
+    /// Only settable at startup, `CONFIG SET` refuses it.
+    pub immutable: bool,
+    /// What has to happen on a running server when the value changes.
+    pub apply: Option<Apply>,
+    pub get: fn(&Config) -> String,
+    pub set: fn(&mut Config, &str) -> Result<(), String>,
+}
+
+const MAXMEMORY_POLICIES: &[&str] = &[
+    "volatile-lru",
+    "volatile-lfu",
+    "volatile-random",
+    "volatile-ttl",
+    "allkeys-lru",
+    "allkeys-lfu",
+    "allkeys-random",
+    "noeviction",
+];
+
+pub const PARAMETERS: &[Parameter] = &[
+    Parameter {
+        name: "bind",
+        alias: None,
+        multi_arg: true,
+        immutable: false,
+        apply: Some(Apply::Listeners),
+        get: |c| c.bind.join(" "),
+        set: |c, v| {
+            let addresses: Vec<String> = v.split_whitespace().map(str::to_string).collect();
+            if addresses.is_empty() {
+                return Err("Too many bind addresses specified.".to_string());
+            }
+            c.bind = addresses;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "port",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: Some(Apply::Listeners),
+        get: |c| c.port.to_string(),
+        set: |c, v| {
+            c.port = v.parse().map_err(|_| "Invalid port".to_string())?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "dir",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| c.dir.display().to_string(),
+        set: |c, v| {
+            if !Path::new(v).is_dir() {
+                return Err(format!("No such directory: {}", v));
+            }
+            c.dir = PathBuf::from(v);
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "dbfilename",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| c.dbfilename.clone(),
+        set: |c, v| {
+            c.dbfilename = parse_file_name(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "requirepass",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| c.requirepass.clone().unwrap_or_default(),
+        set: |c, v| {
+            c.requirepass = (!v.is_empty()).then(|| v.to_string());
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "maxmemory",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: Some(Apply::Eviction),
+        get: |c| c.maxmemory.to_string(),
+        set: |c, v| {
+            c.maxmemory = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "maxmemory-policy",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: Some(Apply::Eviction),
+        get: |c| c.maxmemory_policy.clone(),
+        set: |c, v| {
+            c.maxmemory_policy = parse_enum(v, MAXMEMORY_POLICIES)?.to_string();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "replicaof",
+        alias: Some("slaveof"),
+        multi_arg: true,
+        immutable: false,
+        apply: Some(Apply::Replication),
+        get: |c| match &c.replicaof {
+            Some((host, port)) => format!("{} {}", host, port),
+            None => String::new(),
+        },
+        set: |c, v| {
+            c.replicaof = parse_replicaof(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "replica-read-only",
+        alias: Some("slave-read-only"),
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| format_bool(c.replica_read_only),
+        set: |c, v| {
+            c.replica_read_only = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "repl-backlog-size",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: Some(Apply::Backlog),
+        get: |c| c.repl_backlog_size.to_string(),
+        set: |c, v| {
+            c.repl_backlog_size = parse_memory(v)?.max(1);
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appendonly",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: Some(Apply::Aof),
+        get: |c| format_bool(c.appendonly),
+        set: |c, v| {
+            c.appendonly = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appendfilename",
+        alias: None,
+        multi_arg: false,
+        immutable: true,
+        apply: None,
+        get: |c| c.appendfilename.clone(),
+        set: |c, v| {
+            c.appendfilename = parse_file_name(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appenddirname",
+        alias: None,
+        multi_arg: false,
+        immutable: true,
+        apply: None,
+        get: |c| c.appenddirname.clone(),
+        set: |c, v| {
+            c.appenddirname = parse_file_name(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "appendfsync",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| {
+            match c.appendfsync {
+                AppendFsync::Always => "always",
+                AppendFsync::EverySec => "everysec",
+                AppendFsync::No => "no",
+            }
+            .to_string()
+        },
+        set: |c, v| {
+            c.appendfsync = match parse_enum(v, &["always", "everysec", "no"])? {
+                "always" => AppendFsync::Always,
+                "everysec" => AppendFsync::EverySec,
+                _ => AppendFsync::No,
+            };
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "aof-load-truncated",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| format_bool(c.aof_load_truncated),
+        set: |c, v| {
+            c.aof_load_truncated = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "aof-use-rdb-preamble",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| format_bool(c.aof_use_rdb_preamble),
+        set: |c, v| {
+            c.aof_use_rdb_preamble = parse_bool(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "auto-aof-rewrite-percentage",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| c.auto_aof_rewrite_percentage.to_string(),
+        set: |c, v| {
+            c.auto_aof_rewrite_percentage = parse_integer(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "auto-aof-rewrite-min-size",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| c.auto_aof_rewrite_min_size.to_string(),
+        set: |c, v| {
+            c.auto_aof_rewrite_min_size = parse_memory(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "save",
+        alias: None,
+        multi_arg: true,
+        immutable: false,
+        apply: None,
+        get: |c| {
+            c.save
+                .iter()
+                .map(|(seconds, changes)| format!("{} {}", seconds, changes))
+                .collect::<Vec<String>>()
+                .join(" ")
+        },
+        set: |c, v| {
+            c.save = parse_save_rules(v)?;
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "loglevel",
+        alias: None,
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| {
+            match c.loglevel {
+                LogLevel::Debug => "debug",
+                LogLevel::Verbose => "verbose",
+                LogLevel::Notice => "notice",
+                LogLevel::Warning => "warning",
+            }
+            .to_string()
+        },
+        set: |c, v| {
+            c.loglevel = match parse_enum(v, &["debug", "verbose", "notice", "warning"])? {
+                "debug" => LogLevel::Debug,
+                "verbose" => LogLevel::Verbose,
+                "notice" => LogLevel::Notice,
+                _ => LogLevel::Warning,
+            };
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "logfile",
+        alias: None,
+        multi_arg: false,
+        immutable: true,
+        apply: None,
+        get: |c| c.logfile.clone(),
+        set: |c, v| {
+            c.logfile = v.to_string();
+            Ok(())
+        },
+    },
+    Parameter {
+        name: "busy-reply-threshold",
+        alias: Some("lua-time-limit"),
+        multi_arg: false,
+        immutable: false,
+        apply: None,
+        get: |c| c.busy_reply_threshold.to_string(),
+        set: |c, v| {
+            c.busy_reply_threshold = parse_integer(v)?;
+            Ok(())
+        },
+    },
+];
+
+impl Parameter {
+    /// Looks a parameter up by name or alias, ignoring case.
+    pub fn lookup(name: &str) -> Option<&'static Parameter> {
+        PARAMETERS.iter().find(|p| {
+            p.name.eq_ignore_ascii_case(name)
+                || p.alias.is_some_and(|a| a.eq_ignore_ascii_case(name))
+        })
+    }
+}
+
+impl Config {
+    /// Builds the configuration from the process arguments, Redis style:
+    /// `redis-server [/path/to/redis.conf] [--name value ...]`. Options given on the command
+    /// line are applied after the config file, so they take precedence.
+    pub fn from_args(args: Vec<String>) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut args = args.into_iter().peekable();
+        let mut save_loaded = false;
+
+        if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+            let path = PathBuf::from(path);
+            config.load_file(&path, 0, &mut save_loaded)?;
+            config.config_file = Some(fs::canonicalize(&path).unwrap_or(path));
+        }
+
+        let mut directives: Vec<Vec<String>> = Vec::new();
+        for arg in args {
+            match arg.strip_prefix("--") {
+                Some(name) => directives.push(vec![name.to_string()]),
+                None => match directives.last_mut() {
+                    Some(directive) => directive.push(arg),
+                    None => directives.push(vec![arg]),
+                },
+            }
+        }
+        for (line, directive) in directives.into_iter().enumerate() {
+            config
+                .apply_directive(&directive, 0, &mut save_loaded)
+                .map_err(|reason| ConfigError::Directive {
+                    origin: "command line".to_string(),
+                    line: line + 1,
+                    directive: directive.join(" "),
+                    reason,
+                })?;
+        }
+        Ok(config)
+    }
+
+    fn load_file(
+        &mut self,
+        path: &Path,
+        depth: usize,
+        save_loaded: &mut bool,
+    ) -> Result<(), ConfigError> {
+        let content = fs::read_to_string(path).map_err(|source| ConfigError::Io {
+            path: path.display().to_string(),
+            source,
+        })?;
+        self.load_str(&content, &path.display().to_string(), depth, save_loaded)
+    }
+
+    /// Applies every directive of a config file. `origin` is only used in error messages.
+    /// `save_loaded` tells whether a `save` directive was applied already, by this file or an
+    /// earlier one: the first replaces the default rules, the next ones add to them.
+    pub fn load_str(
+        &mut self,
+        content: &str,
+        origin: &str,
+        depth: usize,
+        save_loaded: &mut bool,
+    ) -> Result<(), ConfigError> {
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |reason: String| ConfigError::Directive {
+                origin: origin.to_string(),
+                line: index + 1,
+                directive: line.to_string(),
+                reason,
+            };
+
+            let directive: Vec<String> = resp::split_inline_args(line.as_bytes())
+                .map_err(|_| error("Unbalanced quotes in configuration line".to_string()))?
+                .into_iter()
+                .map(|arg| String::from_utf8_lossy(&arg).into_owned())
+                .collect();
+
+            self.apply_directive(&directive, depth, save_loaded)
+                .map_err(error)?;
+        }
+        Ok(())
+    }
+
+    fn apply_directive(
+        &mut self,
+        directive: &[String],
+        depth: usize,
+        save_loaded: &mut bool,
+    ) -> Result<(), String> {
+        let name = &directive[0];
+        if name.eq_ignore_ascii_case("include") {
+            if directive.len() != 2 {
+                return Err("Bad directive or wrong number of arguments".to_string());
+            }
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err("Too many nested include directives".to_string());
+            }
+            return self
+                .load_file(Path::new(&directive[1]), depth + 1, save_loaded)
+                .map_err(|e| e.to_string());
+        }
+
+        let parameter =
+            Parameter::lookup(name).ok_or("Bad directive or wrong number of arguments")?;
+        let values = &directive[1..];
+        // Clearing a value is written with an explicit empty argument, e.g. `save ""`.
+        if values.is_empty() || (!parameter.multi_arg && values.len() != 1) {
+            return Err("wrong number of arguments".to_string());
+        }
+        // Each `save` line adds a rule, `save ""` clears them.
+        if parameter.name == "save" && *save_loaded {
+            let rules = parse_save_rules(&values.join(" "))?;
+            match rules.is_empty() {
+                true => self.save.clear(),
+                false => self.save.extend(rules),
+            }
+            return Ok(());
+        }
+        *save_loaded |= parameter.name == "save";
+        (parameter.set)(self, &values.join(" "))
+    }
+
+    /// Sets a parameter by name, validating the value.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let parameter = Parameter::lookup(name).ok_or("Unknown option or number of arguments")?;
+        (parameter.set)(self, value)
+    }
+
+    /// Addresses to listen on, with Redis' `*` and `-` (optional address) notations resolved.
+    pub fn listen_addresses(&self) -> Vec<(String, bool)> {
+        self.bind
+            .iter()
+            .map(|address| {
+                let (address, optional) = match address.strip_prefix('-') {
+                    Some(address) => (address, true),
+                    None => (address.as_str(), false),
+                };
+                let address = match address {
+                    "*" => "0.0.0.0",
+                    "::*" => "::",
+                    other => other,
+                };
+                (format!("{}:{}", address, self.port), optional)
+            })
+            .collect()
+    }
+
+    pub fn rdb_path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+
+    /// Where the AOF files and their manifest live.
+    pub fn aof_dir(&self) -> PathBuf {
+        self.dir.join(&self.appenddirname)
+    }
+
+    /// Writes the current configuration back to the file it was loaded from, for
+    /// `CONFIG REWRITE`.
+    pub fn rewrite(&self) -> Result<(), String> {
+        let path = self
+            .config_file
+            .as_ref()
+            .ok_or("The server is running without a config file")?;
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Rewriting config file: {}", e)),
+        };
+
+        // Write next to the original and rename, so a crash never leaves a truncated file.
+        let temp = path.with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&temp, self.rewrite_str(&content))
+            .and_then(|_| fs::rename(&temp, path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp);
+                format!("Rewriting config file: {}", e)
+            })
+    }
+
+    /// Rewrites the content of a config file with the current values. Comments, unknown
+    /// directives and `include`s are kept as they are, the first line of each parameter is
+    /// replaced in place and later duplicates are dropped. Parameters the file doesn't mention
+    /// are appended only when they differ from the default.
+    fn rewrite_str(&self, content: &str) -> String {
+        let mut written: Vec<&str> = Vec::new();
+        let mut lines: Vec<String> = Vec::new();
+
+        for line in content.lines() {
+            let trimmed = line.trim();
+            let name = match resp::split_inline_args(trimmed.as_bytes()) {
+                Ok(args) if !trimmed.starts_with('#') && !args.is_empty() => {
+                    String::from_utf8_lossy(&args[0]).into_owned()
+                }
+                _ => {
+                    lines.push(line.to_string());
+                    continue;
+                }
+            };
+            match Parameter::lookup(&name) {
+                Some(parameter) if written.contains(&parameter.name) => {}
+                Some(parameter) => {
+                    written.push(parameter.name);
+                    lines.extend(self.config_lines(parameter));
+                }
+                None => lines.push(line.to_string()),
+            }
+        }
+
+        let default = Config::default();
+        let mut generated = PARAMETERS
+            .iter()
+            .filter(|p| !written.contains(&p.name) && (p.get)(self) != (p.get)(&default))
+            .flat_map(|p| self.config_lines(p))
+            .peekable();
+        if generated.peek().is_some() {
+            while lines.last().is_some_and(|line| line.trim().is_empty()) {
+                lines.pop();
+            }
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            lines.push("# Generated by CONFIG REWRITE".to_string());
+            lines.extend(generated);
+        }
+
+        let mut content = lines.join("\n");
+        content.push('\n');
+        content
+    }
+
+    /// The config file lines for a parameter, quoted so that loading them gives the same
+    /// value: a single one, except for `save` that has one per rule. Empty values are only
+    /// written when the default isn't empty, otherwise leaving the line out is enough.
+    fn config_lines(&self, parameter: &Parameter) -> Vec<String> {
+        let value = (parameter.get)(self);
+        let args: Vec<&str> = if parameter.multi_arg {
+            value.split_whitespace().collect()
+        } else {
+            vec![value.as_str()]
+        };
+        if args.iter().all(|arg| arg.is_empty()) {
+            if (parameter.get)(&Config::default()).is_empty() {
+                return Vec::new();
+            }
+            return vec![format!("{} \"\"", parameter.name)];
+        }
+        let args: Vec<String> = args.into_iter().map(quote_arg).collect();
+        match parameter.name {
+            "save" => args
+                .chunks(2)
+                .map(|rule| format!("save {}", rule.join(" ")))
+                .collect(),
+            name => vec![format!("{} {}", name, args.join(" "))],
+        }
+    }
+}
+
+/// Quotes an argument for a config file line when it wouldn't be read back as a single word.
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .bytes()
+            .all(|b| b.is_ascii_graphic() && !matches!(b, b'"' | b'\'' | b'\\'))
+    {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for b in arg.bytes() {
+        match b {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            b' '..=b'~' => quoted.push(b as char),
+            _ => quoted.push_str(&format!("\\x{:02x}", b)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "yes" => Ok(true),
+        "no" => Ok(false),
+        _ => Err("argument must be 'yes' or 'no'".to_string()),
+    }
+}
+
+fn format_bool(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn parse_integer(value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|_| "argument couldn't be parsed into an integer".to_string())
+}
+
+fn parse_enum<'a>(value: &str, allowed: &[&'a str]) -> Result<&'a str, String> {
+    allowed
+        .iter()
+        .find(|option| option.eq_ignore_ascii_case(value))
+        .copied()
+        .ok_or_else(|| {
+            format!(
+                "argument(s) must be one of the following: {}",
+                allowed.join(", ")
+            )
+        })
+}
+
+fn parse_file_name(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.contains('/') || value.contains('\\') {
+        return Err("File name can't be a path, just a filename".to_string());
+    }
+    Ok(value.to_string())
+}
+
+/// Parses memory amounts like Redis: `1k` is 1000 bytes, `1kb` is 1024, and so on up to `gb`.
+pub fn parse_memory(value: &str) -> Result<u64, String> {
+    let lower = value.to_ascii_lowercase();
+    let digits_end = lower
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(lower.len());
+    let (number, unit) = lower.split_at(digits_end);
+    let multiplier = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return Err("argument must be a memory value".to_string()),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| "argument must be a memory value".to_string())
+}
+
+fn parse_replicaof(value: &str) -> Result<Option<(String, u16)>, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    match parts.as_slice() {
+        [no, one] if no.eq_ignore_ascii_case("no") && one.eq_ignore_ascii_case("one") => Ok(None),
+        [host, port] => {
+            let port = port
+                .parse()
+                .map_err(|_| "Invalid master port".to_string())?;
+            Ok(Some((host.to_string(), port)))
+        }
+        _ => Err("wrong number of arguments".to_string()),
+    }
+}
+
+fn parse_save_rules(value: &str) -> Result<Vec<(u64, u64)>, String> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if !parts.len().is_multiple_of(2) {
+        return Err("Invalid save parameters".to_string());
+    }
+    parts
+        .chunks(2)
+        .map(|rule| match (rule[0].parse(), rule[1].parse()) {
+            (Ok(seconds), Ok(changes)) => Ok((seconds, changes)),
+            _ => Err("Invalid save parameters".to_string()),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn test_config_from_command_line() {
+        let config = Config::from_args(args(&[
+            "--port",
+            "6380",
+            "--dir",
+            "/tmp",
+            "--dbfilename",
+            "test.rdb",
+            "--replicaof",
+            "localhost 6379",
+            "--maxmemory",
+            "2mb",
+            "--requirepass",
+            "secret",
+        ]))
+        .unwrap();
+
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.rdb_path(), PathBuf::from("/tmp/test.rdb"));
+        assert_eq!(config.replicaof, Some(("localhost".to_string(), 6379)));
+        assert_eq!(config.maxmemory, 2 * 1024 * 1024);
+        assert_eq!(config.requirepass, Some("secret".to_string()));
+        assert_eq!(
+            config.listen_addresses(),
+            vec![("127.0.0.1:6380".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn test_config_replicaof_as_separate_arguments() {
+        let config = Config::from_args(args(&["--replicaof", "localhost", "6379"])).unwrap();
+        assert_eq!(config.replicaof, Some(("localhost".to_string(), 6379)));
+    }
+
+    #[test]
+    fn test_config_file_with_include() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let included = dir.join("included.conf");
+        fs::write(&included, "appendonly yes\nsave \"\"\n").unwrap();
+        let main = dir.join("redis.conf");
+        fs::write(
+            &main,
+            format!(
+                "# comment\n\nport 7000\ninclude {}\nrequirepass \"with space\"\nslaveof 10.0.0.1 6379\n",
+                included.display()
+            ),
+        )
+        .unwrap();
+
+        let config = Config::from_args(args(&[main.to_str().unwrap(), "--port", "7001"])).unwrap();
+        assert_eq!(config.port, 7001);
+        assert!(config.appendonly);
+        assert!(config.save.is_empty());
+        assert_eq!(config.requirepass, Some("with space".to_string()));
+        assert_eq!(config.replicaof, Some(("10.0.0.1".to_string(), 6379)));
+        assert!(config.config_file.is_some());
+    }
+
+    #[test]
+    fn test_config_save_lines() {
+        // The first line replaces the default rules, the next ones add to them.
+        let mut config = Config::default();
+        let mut save_loaded = false;
+        config
+            .load_str(
+                "save 900 1\nsave 300 10\n",
+                "redis.conf",
+                0,
+                &mut save_loaded,
+            )
+            .unwrap();
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+        let content = "# Redis config\nsave 900 1\nport 6379\nsave 300 10\n";
+        assert_eq!(
+            config.rewrite_str(content),
+            "# Redis config\nsave 900 1\nsave 300 10\nport 6379\n"
+        );
+        config
+            .load_str(
+                "save \"\"\nsave 60 10000\n",
+                "redis.conf",
+                0,
+                &mut save_loaded,
+            )
+            .unwrap();
+        assert_eq!(config.save, vec![(60, 10000)]);
+
+        let config = Config::from_args(args(&["--save", "900", "1", "--save", "300", "10"]));
+        assert_eq!(config.unwrap().save, vec![(900, 1), (300, 10)]);
+    }
+
+    #[test]
+    fn test_config_errors() {
+        let error = Config::from_args(args(&["--port", "abc"])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Reading the configuration file 'command line', at line 1\n>>> 'port abc'\nInvalid port"
+        );
+
+        let mut config = Config::default();
+        let error = config
+            .load_str("port 6379\nnope 1\n", "redis.conf", 0, &mut false)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Reading the configuration file 'redis.conf', at line 2\n>>> 'nope 1'\nBad directive or wrong number of arguments"
+        );
+
+        let error = config
+            .load_str("appendonly maybe\n", "redis.conf", 0, &mut false)
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .ends_with("argument must be 'yes' or 'no'"));
+
+        assert!(Config::from_args(args(&["/does/not/exist.conf"])).is_err());
+    }
+
+    #[test]
+    fn test_config_get_and_set_by_name() {
+        let mut config = Config::default();
+        let get = |config: &Config, name| Parameter::lookup(name).map(|p| (p.get)(config));
+        assert_eq!(get(&config, "port"), Some("6379".to_string()));
+        assert_eq!(get(&config, "slaveof"), Some(String::new()));
+        assert_eq!(get(&config, "nope"), None);
+
+        config.set("save", "900 1 300 10").unwrap();
+        assert_eq!(config.save, vec![(900, 1), (300, 10)]);
+        assert_eq!(get(&config, "save"), Some("900 1 300 10".to_string()));
+
+        config.set("appendfsync", "ALWAYS").unwrap();
+        assert_eq!(config.appendfsync, AppendFsync::Always);
+        assert!(config.set("appendfsync", "sometimes").is_err());
+        assert!(config.set("dbfilename", "../dump.rdb").is_err());
+    }
+
+    #[test]
+    fn test_config_rewrite_keeps_comments() {
+        let mut config = Config::default();
+        let content = "# Redis config\n\nPORT 7000\ninclude other.conf\nsave 900 1\nslaveof 10.0.0.1 6379\nport 7001\n\n";
+        config
+            .load_str("port 7000\nsave 900 1", "redis.conf", 0, &mut false)
+            .unwrap();
+        config.set("port", "7002").unwrap();
+        config.set("requirepass", "pa ss\"word").unwrap();
+        config.set("save", "").unwrap();
+        config.set("appendonly", "yes").unwrap();
+
+        let rewritten = config.rewrite_str(content);
+        assert_eq!(
+            rewritten,
+            "# Redis config\n\nport 7002\ninclude other.conf\nsave \"\"\n\n\
+             # Generated by CONFIG REWRITE\nrequirepass \"pa ss\\\"word\"\nappendonly yes\n"
+        );
+
+        let mut reloaded = Config::default();
+        reloaded
+            .load_str(
+                &rewritten.replace("include other.conf", ""),
+                "redis.conf",
+                0,
+                &mut false,
+            )
+            .unwrap();
+        assert_eq!(reloaded, config);
+    }
+
+    #[test]
+    fn test_config_rewrite_without_file() {
+        assert_eq!(
+            Config::default().rewrite(),
+            Err("The server is running without a config file".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_memory() {
+        assert_eq!(parse_memory("100"), Ok(100));
+        assert_eq!(parse_memory("1k"), Ok(1000));
+        assert_eq!(parse_memory("1KB"), Ok(1024));
+        assert_eq!(parse_memory("3gb"), Ok(3 * 1024 * 1024 * 1024));
+        assert!(parse_memory("12x").is_err());
+        assert!(parse_memory("mb").is_err());
+    }
+}
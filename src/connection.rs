@@ -1,35 +1,135 @@
+use bytes::{Buf, Bytes, BytesMut};
+use thiserror::Error;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use crate::resp::RespDataType;
+use crate::resp::{Decoder, ProtocolError, ProtocolVersion, RespDataType};
 
-const BUFFER_SIZE: usize = 512;
+const BUFFER_SIZE: usize = 4 * 1024;
+
+/// `Decoder::decode` or `decode_request`: a frame and the bytes it used, if complete.
+type Decode = fn(&mut Decoder, &[u8]) -> Result<Option<(RespDataType, usize)>, ProtocolError>;
+
+#[derive(Debug, Error)]
+pub enum ConnectionError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("Protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+}
 
 #[derive(Debug)]
 pub struct Connection<T: AsyncRead + AsyncWrite + Unpin + Send> {
     stream: T,
-    buffer: [u8; BUFFER_SIZE],
+    buffer: BytesMut,
+    /// How far the frame at the start of `buffer` was decoded.
+    decoder: Decoder,
 }
 
 impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection<T> {
     pub fn new(stream: T) -> Self {
         Self {
             stream,
-            buffer: [0; BUFFER_SIZE],
+            buffer: BytesMut::with_capacity(BUFFER_SIZE),
+            decoder: Decoder::default(),
         }
     }
 
-    pub async fn read_request(&mut self) -> std::io::Result<Option<Vec<u8>>> {
-        match self.stream.read(&mut self.buffer).await {
-            Ok(0) => Ok(None),
-            Ok(n) => Ok(Some(self.buffer[0..n].to_vec())),
-            Err(e) => Err(e),
+    /// Reads the next complete frame sent by the peer.
+    ///
+    /// Bytes are accumulated until a whole frame is available, so requests split across several
+    /// TCP segments or bigger than a single read are handled. Each read only decodes the bytes
+    /// that arrived with it. Returns `Ok(None)` when the peer closed the connection cleanly
+    /// between frames.
+    pub async fn read_request(&mut self) -> Result<Option<RespDataType>, ConnectionError> {
+        let frame = self.read_frame(Decoder::decode_request).await?;
+        Ok(frame.map(|(value, _)| value))
+    }
+
+    /// Like `read_request`, also returning the bytes of the frame as received.
+    pub async fn read_request_raw(
+        &mut self,
+    ) -> Result<Option<(RespDataType, Bytes)>, ConnectionError> {
+        self.read_frame(Decoder::decode_request).await
+    }
+
+    /// Reads the next reply, when the peer is a server this end sent requests to.
+    pub async fn read_reply(&mut self) -> Result<Option<RespDataType>, ConnectionError> {
+        let frame = self.read_frame(Decoder::decode).await?;
+        Ok(frame.map(|(value, _)| value))
+    }
+
+    async fn read_frame(
+        &mut self,
+        decode: Decode,
+    ) -> Result<Option<(RespDataType, Bytes)>, ConnectionError> {
+        loop {
+            if let Some((value, used)) = decode(&mut self.decoder, &self.buffer)? {
+                return Ok(Some((value, self.buffer.split_to(used).freeze())));
+            }
+            if !self.fill_buffer().await? {
+                return Ok(None);
+            }
         }
     }
 
-    pub async fn write_response(&mut self, value: &Option<RespDataType>) {
+    /// Reads more bytes into the buffer. Returns false when the peer closed the connection with
+    /// nothing left to read, and fails if it closed it in the middle of a frame.
+    async fn fill_buffer(&mut self) -> Result<bool, ConnectionError> {
+        if self.stream.read_buf(&mut self.buffer).await? > 0 {
+            return Ok(true);
+        }
+        if self.buffer.is_empty() {
+            return Ok(false);
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::ConnectionReset,
+            "connection closed in the middle of a frame",
+        )
+        .into())
+    }
+
+    /// Reads the snapshot a master sends after `+FULLRESYNC`: `$<len>\r\n` then the raw bytes,
+    /// without a CRLF after them. The newlines a master sends while it prepares the snapshot are
+    /// skipped.
+    pub async fn read_bulk_payload(&mut self) -> Result<Bytes, ConnectionError> {
+        let len = loop {
+            while self.buffer.first() == Some(&b'\n') {
+                self.buffer.advance(1);
+            }
+            if let Some(end) = self.buffer.windows(2).position(|w| w == b"\r\n") {
+                let header = self.buffer.split_to(end + 2);
+                let len = header
+                    .strip_prefix(b"$")
+                    .and_then(|len| std::str::from_utf8(&len[..len.len() - 2]).ok())
+                    .and_then(|len| len.parse::<usize>().ok())
+                    .ok_or(ProtocolError::InvalidBulkLength)?;
+                break len;
+            }
+            if !self.fill_buffer().await? {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+        };
+        while self.buffer.len() < len {
+            if !self.fill_buffer().await? {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+        Ok(self.buffer.split_to(len).freeze())
+    }
+
+    /// Writes raw bytes, e.g. a payload that isn't a RESP frame.
+    pub async fn write_bytes(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(data).await
+    }
+
+    pub async fn write_response(
+        &mut self,
+        value: &Option<RespDataType>,
+        protocol: ProtocolVersion,
+    ) {
         let response_bytes = match value {
-            Some(val) => val.serialize(),
-            None => RespDataType::Null.serialize(),
+            Some(val) => val.encode(protocol),
+            None => RespDataType::Null.encode(protocol),
         };
 
         if self.stream.write_all(&response_bytes).await.is_err() {
@@ -37,3 +137,103 @@ impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_request_split_across_writes() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server_stream);
+
+        let reader = tokio::spawn(async move { connection.read_request().await });
+        client_stream
+            .write_all(b"*2\r\n$4\r\nECHO\r\n$5\r\nhel")
+            .await
+            .unwrap();
+        tokio::task::yield_now().await;
+        client_stream.write_all(b"lo\r\n").await.unwrap();
+
+        let result = reader.await.unwrap().unwrap();
+        assert_eq!(result, Some(RespDataType::new_array(vec!["ECHO", "hello"])));
+    }
+
+    #[tokio::test]
+    async fn test_read_request_larger_than_buffer() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(64 * 1024);
+        let mut connection = Connection::new(server_stream);
+        let value = "x".repeat(3 * BUFFER_SIZE);
+
+        let request = RespDataType::new_array(vec!["SET", "key", &value]).serialize();
+        client_stream.write_all(&request).await.unwrap();
+
+        let result = connection.read_request().await.unwrap();
+        assert_eq!(
+            result,
+            Some(RespDataType::new_array(vec!["SET", "key", &value]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_request_pipelined_frames() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server_stream);
+
+        client_stream
+            .write_all(b"*1\r\n$4\r\nPING\r\n*1\r\n$4\r\nPING\r\n")
+            .await
+            .unwrap();
+        drop(client_stream);
+
+        let ping = Some(RespDataType::new_array(vec!["PING"]));
+        assert_eq!(connection.read_request().await.unwrap(), ping);
+        assert_eq!(connection.read_request().await.unwrap(), ping);
+        assert_eq!(connection.read_request().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_read_request_eof_mid_frame() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server_stream);
+
+        client_stream.write_all(b"*1\r\n$4\r\nPI").await.unwrap();
+        drop(client_stream);
+
+        assert!(matches!(
+            connection.read_request().await,
+            Err(ConnectionError::Io(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_bulk_payload() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server_stream);
+
+        client_stream
+            .write_all(b"\n\n$5\r\nREDIS*1\r\n$4\r\nPING\r\n")
+            .await
+            .unwrap();
+        drop(client_stream);
+
+        assert_eq!(&connection.read_bulk_payload().await.unwrap()[..], b"REDIS");
+        let (request, raw) = connection.read_request_raw().await.unwrap().unwrap();
+        assert_eq!(request, RespDataType::new_array(vec!["PING"]));
+        assert_eq!(&raw[..], b"*1\r\n$4\r\nPING\r\n");
+        assert!(connection.read_bulk_payload().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_request_protocol_error() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let mut connection = Connection::new(server_stream);
+
+        client_stream.write_all(b"*1\r\n$-5\r\n").await.unwrap();
+
+        let error = connection.read_request().await.unwrap_err();
+        assert_eq!(error.to_string(), "Protocol error: invalid bulk length");
+    }
+}
@@ -0,0 +1,121 @@
+use thiserror::Error;
+
+use crate::resp::{ProtocolError, RespDataType};
+
+/// Errors returned to clients. The `Display` output is the exact reply line Redis sends,
+/// including the error code prefix clients match on (`ERR`, `WRONGTYPE`, ...).
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum RedisError {
+    #[error("ERR syntax error")]
+    Syntax,
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(&'static str),
+    #[error("ERR unknown command '{0}', with args beginning with: {1}")]
+    UnknownCommand(String, String),
+    #[error("ERR unknown subcommand '{0}'. Try {1} HELP.")]
+    UnknownSubcommand(String, &'static str),
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR value is out of range, must be positive")]
+    OutOfRange,
+    #[error("ERR timeout is not a float or out of range")]
+    InvalidTimeout,
+    #[error("ERR timeout is negative")]
+    NegativeTimeout,
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireTime(&'static str),
+    #[error("NOAUTH Authentication required.")]
+    NoAuth,
+    #[error("WRONGPASS invalid username-password pair or user is disabled.")]
+    WrongPass,
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    OutOfMemory,
+    #[error("NOPROTO sorry, this protocol version is not supported.")]
+    NoProto,
+    #[error("BUSYKEY Target key name already exists.")]
+    BusyKey,
+    #[error("READONLY You can't write against a read only replica.")]
+    ReadOnly,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("NOSCRIPT No matching script. Please use EVAL.")]
+    NoScript,
+    #[error(
+        "BUSY Redis is busy running a script. You can only call SCRIPT KILL or SHUTDOWN NOSAVE."
+    )]
+    Busy,
+    #[error("NOTBUSY No scripts in execution right now.")]
+    NotBusy,
+    #[error("UNKILLABLE Sorry the script already executed write commands against the dataset. You can either wait the script termination or kill the server in a hard way using the SHUTDOWN NOSAVE command.")]
+    Unkillable,
+    #[error("ERR Protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+    /// Any other `ERR` reply, the message is sent after the `ERR ` prefix.
+    #[error("ERR {0}")]
+    Custom(String),
+}
+
+impl RedisError {
+    pub fn custom(message: impl Into<String>) -> Self {
+        RedisError::Custom(message.into())
+    }
+
+    /// Builds the `unknown command` error, quoting the first arguments like Redis does.
+    pub fn unknown_command(name: &str, args: &[RespDataType]) -> Self {
+        let args = args
+            .iter()
+            .take(16)
+            .map(|arg| match arg.to_string() {
+                Some(arg) => format!("'{}' ", arg),
+                None => "'' ".to_string(),
+            })
+            .collect();
+        RedisError::UnknownCommand(name.to_string(), args)
+    }
+}
+
+impl From<RedisError> for RespDataType {
+    fn from(error: RedisError) -> Self {
+        RespDataType::Error(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_replies() {
+        assert_eq!(
+            RespDataType::from(RedisError::WrongType).serialize(),
+            b"-WRONGTYPE Operation against a key holding the wrong kind of value\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::from(RedisError::WrongArity("get")).serialize(),
+            b"-ERR wrong number of arguments for 'get' command\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::from(RedisError::custom("something failed")).serialize(),
+            b"-ERR something failed\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_unknown_command_quotes_args() {
+        let error = RedisError::unknown_command(
+            "foo",
+            &[
+                RespDataType::bulk_string("bar"),
+                RespDataType::bulk_string("baz"),
+            ],
+        );
+        assert_eq!(
+            error.to_string(),
+            "ERR unknown command 'foo', with args beginning with: 'bar' 'baz' "
+        );
+    }
+}
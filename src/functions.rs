@@ -0,0 +1,378 @@
+//! Function libraries for `FUNCTION LOAD` and `FCALL`: Lua code starting with a
+//! `#!lua name=<library>` line that registers functions with `redis.register_function`. Only
+//! the code and what it registered are kept, each `FCALL` runs the library again in a fresh
+//! interpreter before calling the function.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use mlua::{Lua, MultiValue, RegistryKey, Table, Value};
+
+use crate::{error::RedisError, scripting};
+
+/// Flags a function may declare. Only `no-writes` makes a difference here.
+const FLAGS: &[&str] = &[
+    "no-writes",
+    "allow-oom",
+    "allow-stale",
+    "no-cluster",
+    "allow-cross-slot-keys",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub flags: Vec<String>,
+}
+
+impl FunctionInfo {
+    /// Functions flagged `no-writes` may run with `FCALL_RO`, and never write.
+    pub fn no_writes(&self) -> bool {
+        self.flags.iter().any(|flag| flag == "no-writes")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Library {
+    pub name: String,
+    pub code: String,
+    pub functions: Vec<FunctionInfo>,
+}
+
+/// What `FUNCTION RESTORE` does with the libraries already loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RestorePolicy {
+    /// Fails if a restored library already exists.
+    Append,
+    /// Restored libraries replace the existing ones of the same name.
+    Replace,
+    /// Deletes every library first.
+    Flush,
+}
+
+/// The loaded libraries, by name.
+#[derive(Debug, Default)]
+pub struct Functions {
+    libraries: Mutex<BTreeMap<String, Library>>,
+}
+
+impl Functions {
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, Library>> {
+        self.libraries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Loads a library and returns its name. An existing library of the same name is only
+    /// replaced with `replace`.
+    pub fn load(&self, code: &str, replace: bool) -> Result<String, RedisError> {
+        let library = compile(code)?;
+        let name = library.name.clone();
+        let mut libraries = self.lock();
+        Self::check(&libraries, &library, replace)?;
+        libraries.insert(name.clone(), library);
+        Ok(name)
+    }
+
+    /// Whether `library` can be added: no library of the same name unless it is replaced, and
+    /// no function of the same name in another library.
+    fn check(
+        libraries: &BTreeMap<String, Library>,
+        library: &Library,
+        replace: bool,
+    ) -> Result<(), RedisError> {
+        if !replace && libraries.contains_key(&library.name) {
+            return Err(RedisError::custom(format!(
+                "Library '{}' already exists",
+                library.name
+            )));
+        }
+        let others = libraries
+            .values()
+            .filter(|other| other.name != library.name);
+        for other in others {
+            if let Some(function) = library
+                .functions
+                .iter()
+                .find(|function| other.functions.iter().any(|f| f.name == function.name))
+            {
+                return Err(RedisError::custom(format!(
+                    "Function {} already exists",
+                    function.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), RedisError> {
+        match self.lock().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(RedisError::custom("Library not found")),
+        }
+    }
+
+    pub fn flush(&self) {
+        self.lock().clear();
+    }
+
+    pub fn libraries(&self) -> Vec<Library> {
+        self.lock().values().cloned().collect()
+    }
+
+    /// The code of every library, what RDB files and `FUNCTION DUMP` keep.
+    pub fn codes(&self) -> Vec<String> {
+        self.lock()
+            .values()
+            .map(|library| library.code.clone())
+            .collect()
+    }
+
+    /// A function with the code of its library.
+    pub fn find(&self, name: &str) -> Option<(String, FunctionInfo)> {
+        self.lock().values().find_map(|library| {
+            library
+                .functions
+                .iter()
+                .find(|function| function.name == name)
+                .map(|function| (library.code.clone(), function.clone()))
+        })
+    }
+
+    /// Loads libraries read from an RDB file or a `FUNCTION DUMP` payload: all of them or,
+    /// on the first error, none.
+    pub fn restore(&self, codes: &[String], policy: RestorePolicy) -> Result<(), RedisError> {
+        let restored = codes
+            .iter()
+            .map(|code| compile(code))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut libraries = self.lock();
+        let mut updated = match policy {
+            RestorePolicy::Flush => BTreeMap::new(),
+            _ => libraries.clone(),
+        };
+        for library in restored {
+            Self::check(&updated, &library, policy != RestorePolicy::Append)?;
+            updated.insert(library.name.clone(), library);
+        }
+        *libraries = updated;
+        Ok(())
+    }
+}
+
+/// Runs a library's code in an interpreter of its own to learn its name and functions.
+fn compile(code: &str) -> Result<Library, RedisError> {
+    let lua = scripting::new_lua()?;
+    let setup = || -> mlua::Result<()> {
+        lua.globals().set("redis", lua.create_table()?)?;
+        scripting::protect_globals(&lua)
+    };
+    setup().map_err(|e| RedisError::custom(e.to_string()))?;
+    let (name, functions) = register_library(&lua, code)?;
+    Ok(Library {
+        name,
+        code: code.to_string(),
+        functions: functions.into_iter().map(|(info, _)| info).collect(),
+    })
+}
+
+/// The library name from the `#!<engine> name=<library>` first line.
+fn metadata(code: &str) -> Result<String, RedisError> {
+    let first_line = code.lines().next().unwrap_or_default();
+    let mut fields = first_line
+        .strip_prefix("#!")
+        .ok_or_else(|| RedisError::custom("Missing library metadata"))?
+        .split_whitespace();
+    let engine = fields.next().unwrap_or_default();
+    if !engine.eq_ignore_ascii_case("lua") {
+        return Err(RedisError::custom(format!("Engine '{}' not found", engine)));
+    }
+    let mut name = None;
+    for field in fields {
+        match field.strip_prefix("name=") {
+            Some(value) => name = Some(value.to_string()),
+            None => {
+                return Err(RedisError::custom(format!(
+                    "Invalid metadata value given: {}",
+                    field
+                )))
+            }
+        }
+    }
+    let name = name.ok_or_else(|| RedisError::custom("Library name was not given"))?;
+    if !valid_name(&name) {
+        return Err(RedisError::custom(
+            "Library names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+        ));
+    }
+    Ok(name)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+type Registered = Vec<(FunctionInfo, RegistryKey)>;
+
+/// Runs a library's code with `redis.register_function` added to the `redis` table, returning
+/// the library name and the functions it registered with their callbacks.
+pub(crate) fn register_library(lua: &Lua, code: &str) -> Result<(String, Registered), RedisError> {
+    let name = metadata(code)?;
+    let registered: Arc<Mutex<Registered>> = Arc::default();
+    let register = {
+        let registered = Arc::clone(&registered);
+        lua.create_function(move |lua, args: MultiValue| {
+            let (info, callback) = function_arguments(args)?;
+            let mut registered = registered.lock().unwrap_or_else(|e| e.into_inner());
+            if registered.iter().any(|(other, _)| other.name == info.name) {
+                return Err(mlua::Error::runtime(
+                    "Function already exists in the library",
+                ));
+            }
+            registered.push((info, lua.create_registry_value(callback)?));
+            Ok(())
+        })
+    };
+    let run = || -> mlua::Result<()> {
+        let redis: Table = lua.globals().get("redis")?;
+        redis.set("register_function", register?)?;
+        // The metadata line isn't Lua, it is blanked out keeping the line numbers.
+        let body = code.find('\n').map_or("", |i| &code[i..]);
+        lua.load(body).set_name("@user_function").exec()
+    };
+    run().map_err(|e| {
+        RedisError::custom(format!(
+            "Error registering functions: {}",
+            scripting::error_message(&e)
+        ))
+    })?;
+    let registered = std::mem::take(&mut *registered.lock().unwrap_or_else(|e| e.into_inner()));
+    if registered.is_empty() {
+        return Err(RedisError::custom("No functions registered"));
+    }
+    Ok((name, registered))
+}
+
+/// `redis.register_function(name, callback)`, or with a table of named arguments to also give
+/// flags and a description.
+fn function_arguments(args: MultiValue) -> mlua::Result<(FunctionInfo, mlua::Function)> {
+    let mut args = args.into_iter();
+    let (name, callback, flags, description) = match (args.next(), args.next(), args.next()) {
+        (Some(Value::String(name)), Some(Value::Function(callback)), None) => {
+            (name.to_str()?.to_string(), callback, Vec::new(), None)
+        }
+        (Some(Value::Table(table)), None, None) => {
+            let mut flags = Vec::new();
+            if let Some(values) = table.get::<_, Option<Table>>("flags")? {
+                for flag in values.sequence_values::<String>() {
+                    let flag = flag?;
+                    if !FLAGS.contains(&flag.as_str()) {
+                        return Err(mlua::Error::runtime("unknown flag given"));
+                    }
+                    flags.push(flag);
+                }
+            }
+            let callback = table
+                .get::<_, Option<mlua::Function>>("callback")?
+                .ok_or_else(|| mlua::Error::runtime("callback argument given is not a function"))?;
+            let name = table
+                .get::<_, Option<String>>("function_name")?
+                .unwrap_or_default();
+            (name, callback, flags, table.get("description")?)
+        }
+        _ => {
+            return Err(mlua::Error::runtime(
+                "wrong number of arguments to redis.register_function",
+            ))
+        }
+    };
+    if !valid_name(&name) {
+        return Err(mlua::Error::runtime(
+            "Function names can only contain letters, numbers, or underscores(_) and must be at least one character long",
+        ));
+    }
+    let info = FunctionInfo {
+        name,
+        description,
+        flags,
+    };
+    Ok((info, callback))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_libraries() {
+        let functions = Functions::default();
+        let code = "#!lua name=mylib\n\
+                    redis.register_function('hello', function(keys, args) return 1 end)\n\
+                    redis.register_function{function_name = 'get', flags = {'no-writes'},\n\
+                        callback = function(keys) return redis.call('GET', keys[1]) end}";
+        assert_eq!(functions.load(code, false), Ok("mylib".to_string()));
+        let (_, get) = functions.find("get").unwrap();
+        assert!(get.no_writes());
+        assert!(!functions.find("hello").unwrap().1.no_writes());
+        assert_eq!(
+            functions.load(code, false),
+            Err(RedisError::custom("Library 'mylib' already exists"))
+        );
+        assert_eq!(functions.load(code, true), Ok("mylib".to_string()));
+        let other = "#!lua name=other\nredis.register_function('hello', function() end)";
+        assert_eq!(
+            functions.load(other, false),
+            Err(RedisError::custom("Function hello already exists"))
+        );
+
+        for (code, error) in [
+            ("return 1", "Missing library metadata"),
+            ("#!js name=x\n", "Engine 'js' not found"),
+            ("#!lua name=x foo=bar\n", "Invalid metadata value given: foo=bar"),
+            ("#!lua name=x\nlocal a = 1", "No functions registered"),
+            (
+                "#!lua name=x\nredis.register_function{function_name = 'f', \
+                 callback = function() end, flags = {'bad'}}",
+                "Error registering functions: unknown flag given",
+            ),
+            (
+                "#!lua name=x\nredis.register_function('f', function() end)\nx = 1",
+                "Error registering functions: user_function:3: Script attempted to create global variable 'x'",
+            ),
+        ] {
+            assert_eq!(functions.load(code, false), Err(RedisError::custom(error)));
+        }
+
+        functions.delete("mylib").unwrap();
+        assert_eq!(
+            functions.delete("mylib"),
+            Err(RedisError::custom("Library not found"))
+        );
+    }
+
+    #[test]
+    fn test_restore() {
+        let functions = Functions::default();
+        let lib = |name: &str, function: &str| {
+            format!(
+                "#!lua name={}\nredis.register_function('{}', function() end)",
+                name, function
+            )
+        };
+        functions.load(&lib("a", "f"), false).unwrap();
+        let codes = vec![lib("a", "f"), lib("b", "g")];
+        assert_eq!(
+            functions.restore(&codes, RestorePolicy::Append),
+            Err(RedisError::custom("Library 'a' already exists"))
+        );
+        // Nothing was restored.
+        assert!(functions.find("g").is_none());
+        functions.restore(&codes, RestorePolicy::Replace).unwrap();
+        assert!(functions.find("g").is_some());
+        functions
+            .restore(&codes[1..], RestorePolicy::Flush)
+            .unwrap();
+        assert_eq!(functions.codes(), vec![lib("b", "g")]);
+    }
+}
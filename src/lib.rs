@@ -0,0 +1,24 @@
+#![allow(unused_imports)]
+// The modules are only public for the binaries in this package, not as a library API.
+#![allow(
+    async_fn_in_trait,
+    clippy::new_without_default,
+    clippy::len_without_is_empty
+)]
+pub mod aof;
+pub mod client;
+pub mod commands;
+pub mod config;
+pub mod connection;
+pub mod error;
+pub mod functions;
+mod macros;
+pub mod pubsub;
+pub mod rdb;
+pub mod replication;
+pub mod resp;
+pub mod scripting;
+pub mod server;
+pub mod store;
+pub mod types;
+pub mod utils;
@@ -1,33 +1,60 @@
-/// Macro para analisar o nome de um comando e despachá-lo para o comando
-/// e struct de parse corretos.
+/// Declara o registro de comandos a partir de uma única lista `VARIANTE => Struct`.
+///
+/// Gera o enum `RedisCommand`, a tabela `COMMAND_TABLE` com a `CommandSpec` de cada comando,
+/// o despacho de parse por nome e o despacho de `execute`. Nome, aridade e flags vêm da
+/// `SPEC` declarada junto de cada comando, então não há outra lista para manter em sincronia.
 #[macro_export]
-macro_rules! command_parser {
+macro_rules! command_registry {
     (
-        $command_name:expr, // O nome do comando (String/&str)
-        $args:expr,         // O iterador de argumentos (mut args)
-        $commands_vec:expr, // O vetor para adicionar o comando parseado (mut commands)
         $(
-            $match_command_name:expr => (
-                $command_enum:ident,
-                $command_struct:ty
-            )
+            $command_enum:ident => $command_struct:ty
         ),* $(,)?
     ) => {
-        match $command_name {
-            $(
-                $match_command_name => {
-                    // Note: Você precisará garantir que $args seja um &mut impl Iterator
-                    // e que parse seja um método disponível para $command_struct
-                    match <$command_struct>::parse(&mut $args) {
-                        Ok(cmd) => {
-                            $commands_vec.push(RedisCommand::$command_enum(cmd));
-                        },
-                        Err(e) => return Err(e), // Propaga o erro do parse
+        #[derive(Debug, PartialEq, Clone)]
+        pub enum RedisCommand {
+            $( $command_enum($command_struct), )*
+        }
+
+        /// Every command known by the server, in declaration order.
+        pub const COMMAND_TABLE: &[&CommandSpec] = &[
+            $( &<$command_struct as DescribedCommand>::SPEC, )*
+        ];
+
+        impl RedisCommand {
+            /// Parses the arguments of the command described by `spec`. The arity has already
+            /// been checked by the caller.
+            fn parse_with_spec(
+                spec: &'static CommandSpec,
+                args: &mut IntoIter<RespDataType>,
+            ) -> Result<RedisCommand, RedisError> {
+                $(
+                    if spec.name == <$command_struct as DescribedCommand>::SPEC.name {
+                        return <$command_struct>::parse(args).map(RedisCommand::$command_enum);
                     }
+                )*
+                unreachable!("{} is in COMMAND_TABLE", spec.name)
+            }
+
+            pub fn spec(&self) -> &'static CommandSpec {
+                match self {
+                    $(
+                        RedisCommand::$command_enum(_) => {
+                            &<$command_struct as DescribedCommand>::SPEC
+                        }
+                    )*
+                }
+            }
+        }
+
+        impl RunnableCommand for RedisCommand {
+            async fn execute(
+                &self,
+                client: &mut RedisClient,
+                server: &Arc<RedisServer>,
+            ) -> Result<RespDataType, RedisError> {
+                match self {
+                    $( RedisCommand::$command_enum(cmd) => cmd.execute(client, server).await, )*
                 }
-            )*
-            _ => {
-                return Err("command not found".to_string());
             }
         }
     };
@@ -1,20 +1,36 @@
-#![allow(unused_imports)]
-mod client;
-mod commands;
-mod connection;
-mod macros;
-mod resp;
-mod server;
-mod store;
-mod types;
-mod utils;
-
-use server::RedisServer;
+use codecrafters_redis::{
+    config::{Config, LogLevel},
+    server::{RedisServer, REDIS_VERSION},
+};
+use std::sync::Arc;
 use tokio::io::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let server = RedisServer::new(String::from("127.0.0.1:6379"));
+    let config = match Config::from_args(std::env::args().skip(1).collect()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!(
+                "\n*** FATAL CONFIG FILE ERROR (Redis {}) ***",
+                REDIS_VERSION
+            );
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let server = Arc::new(RedisServer::new(config));
+    let appendonly = server.config().appendonly;
+    let loaded = match appendonly {
+        true => server.load_aof().await.map_err(|e| e.to_string()),
+        false => server.load_rdb().await.map_err(|e| e.to_string()),
+    };
+    if let Err(e) = loaded {
+        server.log(
+            LogLevel::Warning,
+            format!("Fatal error loading the DB: {}. Exiting.", e),
+        );
+        std::process::exit(1);
+    }
     server.run().await;
     Ok(())
 }
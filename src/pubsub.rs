@@ -0,0 +1,244 @@
+//! Pub/Sub: the channels and patterns each client subscribed to. Messages are sent to the
+//! `notifier` of each subscriber, and written out by its connection task as they come.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard},
+};
+
+use bytes::Bytes;
+
+use crate::{
+    client::{Notifier, RedisClient},
+    resp::RespDataType,
+    utils,
+};
+
+/// The notifiers of the clients subscribed to each channel or pattern, by client ID.
+type Subscribers = HashMap<Bytes, HashMap<u64, Notifier>>;
+
+#[derive(Debug, Default)]
+pub struct PubSub {
+    channels: Mutex<Subscribers>,
+    patterns: Mutex<Subscribers>,
+}
+
+/// Whether a subscription is to a channel, or to the channels matching a glob pattern.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Channel,
+    Pattern,
+}
+
+impl PubSub {
+    fn subscribers(&self, kind: Kind) -> MutexGuard<'_, Subscribers> {
+        let subscribers = match kind {
+            Kind::Channel => &self.channels,
+            Kind::Pattern => &self.patterns,
+        };
+        subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Subscribes the client, confirming each name with a push holding the number of
+    /// subscriptions the client has then. It is sent with the subscribers locked, so that it
+    /// comes before any message published to it.
+    pub fn subscribe(&self, client: &mut RedisClient, kind: Kind, names: &[Bytes]) {
+        for name in names {
+            let mut subscribers = self.subscribers(kind);
+            if client.subscriptions(kind).insert(name.clone()) {
+                subscribers
+                    .entry(name.clone())
+                    .or_default()
+                    .insert(client.id, client.notifier.clone());
+            }
+            let kind = match kind {
+                Kind::Channel => "subscribe",
+                Kind::Pattern => "psubscribe",
+            };
+            Self::confirm(client, kind, Some(name));
+        }
+    }
+
+    /// Unsubscribes the client from `names`, or from everything of that kind when there are
+    /// none, confirming each name like `subscribe`.
+    pub fn unsubscribe(&self, client: &mut RedisClient, kind: Kind, names: &[Bytes]) {
+        let reply = match kind {
+            Kind::Channel => "unsubscribe",
+            Kind::Pattern => "punsubscribe",
+        };
+        let names = match names.is_empty() {
+            true => client.subscriptions(kind).iter().cloned().collect(),
+            false => names.to_vec(),
+        };
+        if names.is_empty() {
+            return Self::confirm(client, reply, None);
+        }
+        for name in names {
+            let mut subscribers = self.subscribers(kind);
+            client.subscriptions(kind).remove(&name);
+            Self::remove(&mut subscribers, &name, client.id);
+            Self::confirm(client, reply, Some(&name));
+        }
+    }
+
+    /// Drops every subscription of the client without confirming them, on `RESET` or when
+    /// it disconnects.
+    pub fn unsubscribe_all(&self, client: &mut RedisClient) {
+        let id = client.id;
+        for kind in [Kind::Channel, Kind::Pattern] {
+            let mut subscribers = self.subscribers(kind);
+            for name in client.subscriptions(kind).drain() {
+                Self::remove(&mut subscribers, &name, id);
+            }
+        }
+    }
+
+    fn remove(subscribers: &mut Subscribers, name: &Bytes, id: u64) {
+        if let Some(clients) = subscribers.get_mut(name) {
+            clients.remove(&id);
+            if clients.is_empty() {
+                subscribers.remove(name);
+            }
+        }
+    }
+
+    fn confirm(client: &RedisClient, kind: &str, name: Option<&Bytes>) {
+        client.notifier.send(RespDataType::Push(vec![
+            RespDataType::bulk_string(kind),
+            name.map_or(RespDataType::Null, RespDataType::bulk_string),
+            RespDataType::Integer(client.subscription_count() as i64),
+        ]));
+    }
+
+    /// Sends a message to the subscribers of the channel and of the patterns it matches,
+    /// returning how many got it. A client subscribed both ways gets it once for each.
+    pub fn publish(&self, channel: &Bytes, message: &Bytes) -> usize {
+        let mut receivers = 0;
+        if let Some(clients) = self.subscribers(Kind::Channel).get(channel) {
+            let push = RespDataType::Push(vec![
+                RespDataType::bulk_string("message"),
+                RespDataType::bulk_string(channel),
+                RespDataType::bulk_string(message),
+            ]);
+            for sender in clients.values() {
+                receivers += sender.send(push.clone()) as usize;
+            }
+        }
+        for (pattern, clients) in self.subscribers(Kind::Pattern).iter() {
+            if !utils::glob_match(pattern, channel, false) {
+                continue;
+            }
+            let push = RespDataType::Push(vec![
+                RespDataType::bulk_string("pmessage"),
+                RespDataType::bulk_string(pattern),
+                RespDataType::bulk_string(channel),
+                RespDataType::bulk_string(message),
+            ]);
+            for sender in clients.values() {
+                receivers += sender.send(push.clone()) as usize;
+            }
+        }
+        receivers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::client::NOTIFICATIONS_LIMIT;
+
+    fn push(values: Vec<RespDataType>) -> RespDataType {
+        RespDataType::Push(values)
+    }
+
+    #[test]
+    fn test_publish() {
+        let pubsub = PubSub::default();
+        let (mut client, mut other) = (RedisClient::new(), RedisClient::new());
+        let mut pushes = client.notifications.take().unwrap();
+        let (news, weather) = (Bytes::from("news"), Bytes::from("weather"));
+        pubsub.subscribe(&mut client, Kind::Channel, &[news.clone(), weather.clone()]);
+        pubsub.subscribe(&mut client, Kind::Pattern, &[Bytes::from("n*")]);
+        pubsub.subscribe(&mut other, Kind::Channel, std::slice::from_ref(&news));
+        for (kind, name, count) in [
+            ("subscribe", "news", 1),
+            ("subscribe", "weather", 2),
+            ("psubscribe", "n*", 3),
+        ] {
+            assert_eq!(
+                pushes.try_recv().unwrap(),
+                push(vec![
+                    RespDataType::bulk_string(kind),
+                    RespDataType::bulk_string(name),
+                    RespDataType::Integer(count),
+                ])
+            );
+        }
+
+        assert_eq!(pubsub.publish(&news, &Bytes::from("hi")), 3);
+        assert_eq!(
+            pushes.try_recv().unwrap(),
+            push(vec![
+                RespDataType::bulk_string("message"),
+                RespDataType::bulk_string("news"),
+                RespDataType::bulk_string("hi"),
+            ])
+        );
+        assert_eq!(
+            pushes.try_recv().unwrap(),
+            push(vec![
+                RespDataType::bulk_string("pmessage"),
+                RespDataType::bulk_string("n*"),
+                RespDataType::bulk_string("news"),
+                RespDataType::bulk_string("hi"),
+            ])
+        );
+        assert_eq!(
+            pubsub.publish(&Bytes::from("sports"), &Bytes::from("hi")),
+            0
+        );
+
+        // Without names, from every channel.
+        pubsub.unsubscribe(&mut client, Kind::Channel, &[]);
+        assert_eq!(client.subscription_count(), 1);
+        for count in [2, 1] {
+            assert!(matches!(
+                pushes.try_recv().unwrap(),
+                RespDataType::Push(values) if values[2] == RespDataType::Integer(count)
+            ));
+        }
+
+        pubsub.unsubscribe_all(&mut client);
+        assert!(pushes.try_recv().is_err());
+        pubsub.unsubscribe(&mut client, Kind::Pattern, &[]);
+        assert_eq!(
+            pushes.try_recv().unwrap(),
+            push(vec![
+                RespDataType::bulk_string("punsubscribe"),
+                RespDataType::Null,
+                RespDataType::Integer(0),
+            ])
+        );
+        assert_eq!(pubsub.publish(&news, &Bytes::from("hi")), 1);
+    }
+
+    #[tokio::test]
+    async fn test_publish_over_limit() {
+        let pubsub = PubSub::default();
+        let mut client = RedisClient::new();
+        let pushes = client.notifications.take().unwrap();
+        let news = Bytes::from("news");
+        pubsub.subscribe(&mut client, Kind::Channel, std::slice::from_ref(&news));
+        for _ in 1..NOTIFICATIONS_LIMIT {
+            assert_eq!(pubsub.publish(&news, &Bytes::from("hi")), 1);
+        }
+        assert_eq!(pubsub.publish(&news, &Bytes::from("hi")), 0);
+        // The connection task is told to disconnect it.
+        tokio::time::timeout(Duration::from_secs(1), client.notifier.overflowed())
+            .await
+            .unwrap();
+        assert_eq!(pushes.len(), NOTIFICATIONS_LIMIT);
+    }
+}
@@ -0,0 +1,735 @@
+//! The RDB snapshot format: a `REDIS` + version header, a stream of opcodes (auxiliary fields,
+//! database selectors, expires) and typed key/value pairs, an EOF opcode and a CRC64 trailer.
+
+mod encodings;
+mod lzf;
+mod reader;
+mod writer;
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use bytes::Bytes;
+use thiserror::Error;
+
+use crate::types::RedisType;
+use reader::RdbReader;
+use writer::RdbWriter;
+
+/// Newest format version we understand, written by Redis 7.4.
+const MAX_VERSION: u32 = 12;
+
+/// Version we write, the one of Redis 7.2.
+const VERSION: u32 = 11;
+
+const OPCODE_SLOT_INFO: u8 = 0xf4;
+const OPCODE_FUNCTION_PRE_GA: u8 = 0xf5;
+const OPCODE_FUNCTION2: u8 = 0xf6;
+const OPCODE_MODULE_AUX: u8 = 0xf7;
+const OPCODE_IDLE: u8 = 0xf8;
+const OPCODE_FREQ: u8 = 0xf9;
+const OPCODE_AUX: u8 = 0xfa;
+const OPCODE_RESIZEDB: u8 = 0xfb;
+const OPCODE_EXPIRETIME_MS: u8 = 0xfc;
+const OPCODE_EXPIRETIME: u8 = 0xfd;
+const OPCODE_SELECTDB: u8 = 0xfe;
+const OPCODE_EOF: u8 = 0xff;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_MODULE_PRE_GA: u8 = 6;
+const TYPE_MODULE_2: u8 = 7;
+const TYPE_HASH_ZIPMAP: u8 = 9;
+const TYPE_LIST_ZIPLIST: u8 = 10;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_ZSET_ZIPLIST: u8 = 12;
+const TYPE_HASH_ZIPLIST: u8 = 13;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+const TYPE_STREAM_LISTPACKS: u8 = 15;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_STREAM_LISTPACKS_2: u8 = 19;
+const TYPE_SET_LISTPACK: u8 = 20;
+const TYPE_STREAM_LISTPACKS_3: u8 = 21;
+
+/// Special string encodings, in the low bits of a length byte starting with `11`.
+const ENC_INT8: u8 = 0;
+const ENC_INT16: u8 = 1;
+const ENC_INT32: u8 = 2;
+const ENC_LZF: u8 = 3;
+
+const CRC64: crc::Crc<u64> = crc::Crc::<u64>::new(&crc::CRC_64_REDIS);
+
+#[derive(Debug, Error)]
+pub enum RdbError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Wrong signature trying to load DB from file")]
+    BadSignature,
+    #[error("Can't handle RDB format version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Unexpected EOF reading RDB file")]
+    UnexpectedEof,
+    #[error("Unknown RDB type or opcode {0}")]
+    UnknownType(u8),
+    #[error("{0} are not supported")]
+    Unsupported(&'static str),
+    #[error("Wrong RDB checksum expected: ({expected:x}) got: ({actual:x})")]
+    Checksum { expected: u64, actual: u64 },
+    #[error("{0}")]
+    Corrupt(String),
+    #[error("DUMP payload version or checksum are wrong")]
+    BadPayload,
+}
+
+/// A key read from an RDB file.
+#[derive(Debug, PartialEq)]
+pub struct RdbEntry {
+    pub db: u64,
+    pub key: Bytes,
+    pub value: RedisType,
+    pub expire_at_millis: Option<u128>,
+}
+
+/// Content of an RDB file.
+#[derive(Debug, Default, PartialEq)]
+pub struct Rdb {
+    pub version: u32,
+    /// Auxiliary fields such as `redis-ver` and `ctime`.
+    pub aux: Vec<(Bytes, Bytes)>,
+    pub entries: Vec<RdbEntry>,
+    /// Code of the function libraries.
+    pub functions: Vec<String>,
+}
+
+impl Rdb {
+    pub fn aux(&self, name: &str) -> Option<&Bytes> {
+        self.aux
+            .iter()
+            .find(|(field, _)| field == name.as_bytes())
+            .map(|(_, value)| value)
+    }
+}
+
+/// Reads an RDB file, `None` if it doesn't exist.
+pub fn load(path: &Path) -> Result<Option<Rdb>, RdbError> {
+    match fs::read(path) {
+        Ok(data) => parse(&data).map(Some),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<Rdb, RdbError> {
+    parse_prefix(data).map(|(rdb, _)| rdb)
+}
+
+/// Parses an RDB payload at the start of `data`, also returning its length: the AOF keeps
+/// commands after it.
+pub fn parse_prefix(data: &[u8]) -> Result<(Rdb, usize), RdbError> {
+    parse_prefix_with_offset(data).map_err(|(e, _)| e)
+}
+
+/// Like `parse_prefix`, but on error also returns the offset the reader had reached, to point
+/// at the corruption.
+pub fn parse_prefix_with_offset(data: &[u8]) -> Result<(Rdb, usize), (RdbError, usize)> {
+    let mut reader = RdbReader::new(data);
+    match read_rdb(&mut reader, data) {
+        Ok(rdb) => Ok((rdb, reader.position())),
+        Err(e) => Err((e, reader.position())),
+    }
+}
+
+fn read_rdb(reader: &mut RdbReader, data: &[u8]) -> Result<Rdb, RdbError> {
+    if reader.bytes(5).map_err(|_| RdbError::BadSignature)? != b"REDIS" {
+        return Err(RdbError::BadSignature);
+    }
+    let version = std::str::from_utf8(reader.bytes(4)?)
+        .ok()
+        .and_then(|version| version.parse().ok())
+        .ok_or(RdbError::BadSignature)?;
+    if !(1..=MAX_VERSION).contains(&version) {
+        return Err(RdbError::UnsupportedVersion(version));
+    }
+
+    let mut rdb = Rdb {
+        version,
+        ..Rdb::default()
+    };
+    let mut db = 0;
+    let mut expire_at_millis = None;
+    loop {
+        match reader.u8()? {
+            OPCODE_EOF => break,
+            OPCODE_SELECTDB => db = reader.length()?,
+            OPCODE_RESIZEDB => {
+                // Hash table sizes, only a hint for preallocation.
+                reader.length()?;
+                reader.length()?;
+            }
+            OPCODE_SLOT_INFO => {
+                for _ in 0..3 {
+                    reader.length()?;
+                }
+            }
+            OPCODE_AUX => {
+                let field = reader.string()?;
+                let value = reader.string()?;
+                rdb.aux.push((field, value));
+            }
+            OPCODE_EXPIRETIME => expire_at_millis = Some(reader.u32_le()? as u128 * 1000),
+            OPCODE_EXPIRETIME_MS => expire_at_millis = Some(reader.u64_le()? as u128),
+            // Eviction hints for the next key, we don't track access times.
+            OPCODE_IDLE => {
+                reader.length()?;
+            }
+            OPCODE_FREQ => {
+                reader.u8()?;
+            }
+            OPCODE_FUNCTION2 => {
+                let code = reader.string()?;
+                rdb.functions
+                    .push(String::from_utf8_lossy(&code).into_owned());
+            }
+            OPCODE_FUNCTION_PRE_GA => return Err(RdbError::Unsupported("Pre-release functions")),
+            OPCODE_MODULE_AUX => return Err(RdbError::Unsupported("Module auxiliary data")),
+            value_type => {
+                let key = reader.string()?;
+                let value = reader.object(value_type)?;
+                rdb.entries.push(RdbEntry {
+                    db,
+                    key,
+                    value,
+                    expire_at_millis: expire_at_millis.take(),
+                });
+            }
+        }
+    }
+
+    // Checksums were added in version 5, zero means the server had them disabled.
+    if version >= 5 {
+        let end = reader.position();
+        let expected = reader.u64_le()?;
+        let actual = CRC64.checksum(&data[..end]);
+        if expected != 0 && expected != actual {
+            return Err(RdbError::Checksum { expected, actual });
+        }
+    }
+    Ok(rdb)
+}
+
+/// Serializes a dataset as database 0, after the code of the function libraries. Keys already
+/// expired at `now_millis` are left out.
+pub fn encode(
+    data: &HashMap<String, RedisType>,
+    functions: &[String],
+    now_millis: u128,
+) -> Vec<u8> {
+    let live: Vec<_> = data
+        .iter()
+        .filter(|(_, value)| writer::value_type(value).is_some())
+        .map(|(key, value)| {
+            let expire_at_millis = match value {
+                RedisType::String(kv) => kv.expired_at_millis,
+                _ => None,
+            };
+            (key, value, expire_at_millis)
+        })
+        .filter(|(_, _, expire_at_millis)| expire_at_millis.is_none_or(|at| at > now_millis))
+        .collect();
+    let expires = live.iter().filter(|(_, _, at)| at.is_some()).count();
+    let used_mem: usize = live
+        .iter()
+        .map(|(key, value, _)| key.len() + value.memory_usage())
+        .sum();
+
+    let mut writer = RdbWriter::new();
+    writer.bytes(format!("REDIS{:04}", VERSION).as_bytes());
+    let aux = [
+        ("redis-ver", crate::server::REDIS_VERSION.to_string()),
+        ("redis-bits", (usize::BITS).to_string()),
+        ("ctime", (now_millis / 1000).to_string()),
+        ("used-mem", used_mem.to_string()),
+        ("aof-base", "0".to_string()),
+    ];
+    for (field, value) in aux {
+        writer.u8(OPCODE_AUX);
+        writer.string(field.as_bytes());
+        writer.string(value.as_bytes());
+    }
+    for code in functions {
+        writer.u8(OPCODE_FUNCTION2);
+        writer.string(code.as_bytes());
+    }
+
+    writer.u8(OPCODE_SELECTDB);
+    writer.length(0);
+    writer.u8(OPCODE_RESIZEDB);
+    writer.length(live.len() as u64);
+    writer.length(expires as u64);
+    for (key, value, expire_at_millis) in live {
+        if let Some(at) = expire_at_millis {
+            writer.u8(OPCODE_EXPIRETIME_MS);
+            writer.bytes(&(at as u64).to_le_bytes());
+        }
+        writer.u8(writer::value_type(value).expect("filtered out above"));
+        writer.string(key.as_bytes());
+        writer.object(value);
+    }
+    writer.u8(OPCODE_EOF);
+
+    let mut data = writer.into_inner();
+    let checksum = CRC64.checksum(&data);
+    data.extend_from_slice(&checksum.to_le_bytes());
+    data
+}
+
+/// Serializes a single value like `DUMP` does: its RDB type and encoding, followed by the RDB
+/// version (2 bytes) and a CRC64 of everything before it (8 bytes), both little endian.
+/// `None` for `RedisType::None`.
+pub fn dump(value: &RedisType) -> Option<Vec<u8>> {
+    let mut writer = RdbWriter::new();
+    writer.u8(writer::value_type(value)?);
+    writer.object(value);
+    Some(with_footer(writer))
+}
+
+/// Serializes function libraries like `FUNCTION DUMP` does: the code of each as in an RDB
+/// file, with the same footer as `DUMP`.
+pub fn dump_functions(codes: &[String]) -> Vec<u8> {
+    let mut writer = RdbWriter::new();
+    for code in codes {
+        writer.u8(OPCODE_FUNCTION2);
+        writer.string(code.as_bytes());
+    }
+    with_footer(writer)
+}
+
+fn with_footer(mut writer: RdbWriter) -> Vec<u8> {
+    writer.bytes(&(VERSION as u16).to_le_bytes());
+    let mut data = writer.into_inner();
+    let checksum = CRC64.checksum(&data);
+    data.extend_from_slice(&checksum.to_le_bytes());
+    data
+}
+
+/// The body of a `DUMP` or `FUNCTION DUMP` payload. Payloads from an RDB version we can't read
+/// or with a wrong checksum fail with `BadPayload`.
+fn payload_body(payload: &[u8]) -> Result<&[u8], RdbError> {
+    let body_len = payload.len().checked_sub(10).ok_or(RdbError::BadPayload)?;
+    let (body, footer) = payload.split_at(body_len);
+    let version = u16::from_le_bytes([footer[0], footer[1]]) as u32;
+    let checksum = u64::from_le_bytes(footer[2..].try_into().expect("8 bytes"));
+    if version > MAX_VERSION || checksum != CRC64.checksum(&payload[..body_len + 2]) {
+        return Err(RdbError::BadPayload);
+    }
+    Ok(body)
+}
+
+/// Reads a `DUMP` payload back. The footer is checked before anything else.
+pub fn restore(payload: &[u8]) -> Result<RedisType, RdbError> {
+    let body = payload_body(payload)?;
+    let mut reader = RdbReader::new(body);
+    let value_type = reader.u8()?;
+    let value = reader.object(value_type)?;
+    if reader.position() != body.len() {
+        return Err(RdbError::Corrupt(
+            "Trailing bytes after the DUMP payload value".to_string(),
+        ));
+    }
+    Ok(value)
+}
+
+/// Reads a `FUNCTION DUMP` payload back into the code of each library.
+pub fn restore_functions(payload: &[u8]) -> Result<Vec<String>, RdbError> {
+    let body = payload_body(payload)?;
+    let mut reader = RdbReader::new(body);
+    let mut codes = Vec::new();
+    while reader.position() < body.len() {
+        match reader.u8()? {
+            OPCODE_FUNCTION2 => {
+                let code = reader.string()?;
+                codes.push(String::from_utf8_lossy(&code).into_owned());
+            }
+            opcode => return Err(RdbError::UnknownType(opcode)),
+        }
+    }
+    Ok(codes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet, VecDeque};
+
+    use super::*;
+    use crate::types::{
+        key_value::KeyValue,
+        sorted_set::{SortedSet, SortedValue},
+        stream::{RedisStream, StreamEntry},
+    };
+
+    fn with_checksum(mut data: Vec<u8>) -> Vec<u8> {
+        let checksum = CRC64.checksum(&data);
+        data.extend_from_slice(&checksum.to_le_bytes());
+        data
+    }
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut data = vec![value.len() as u8];
+        data.extend_from_slice(value.as_bytes());
+        data
+    }
+
+    #[test]
+    fn test_parse_strings_and_expires() {
+        let mut data = b"REDIS0011".to_vec();
+        data.push(OPCODE_AUX);
+        data.extend(string("redis-ver"));
+        data.extend(string("7.2.0"));
+        data.push(OPCODE_AUX);
+        data.extend(string("redis-bits"));
+        data.extend([0xc0, 0x40]);
+        data.extend([OPCODE_SELECTDB, 0x00, OPCODE_RESIZEDB, 0x03, 0x02]);
+        data.push(TYPE_STRING);
+        data.extend(string("foobar"));
+        data.extend(string("bazqux"));
+        data.push(OPCODE_EXPIRETIME_MS);
+        data.extend(1713824559637u64.to_le_bytes());
+        data.push(TYPE_STRING);
+        data.extend(string("foo"));
+        data.extend([0xc1, 0x39, 0x30]);
+        data.push(OPCODE_EXPIRETIME);
+        data.extend(1714089298u32.to_le_bytes());
+        data.push(TYPE_STRING);
+        data.extend(string("baz"));
+        // "aaaaaaaaaa" LZF compressed.
+        data.extend([0xc3, 0x05, 0x0a, 0x00, b'a', 0xe0, 0x00, 0x00]);
+        data.push(OPCODE_EOF);
+        let data = with_checksum(data);
+
+        let rdb = parse(&data).unwrap();
+        assert_eq!(rdb.version, 11);
+        assert_eq!(rdb.aux("redis-ver"), Some(&Bytes::from("7.2.0")));
+        assert_eq!(rdb.aux("redis-bits"), Some(&Bytes::from("64")));
+        let string = |value: &str| {
+            RedisType::String(KeyValue {
+                value: Bytes::from(value.to_string()),
+                expired_at_millis: None,
+            })
+        };
+        assert_eq!(
+            rdb.entries,
+            vec![
+                RdbEntry {
+                    db: 0,
+                    key: Bytes::from("foobar"),
+                    value: string("bazqux"),
+                    expire_at_millis: None,
+                },
+                RdbEntry {
+                    db: 0,
+                    key: Bytes::from("foo"),
+                    value: string("12345"),
+                    expire_at_millis: Some(1713824559637),
+                },
+                RdbEntry {
+                    db: 0,
+                    key: Bytes::from("baz"),
+                    value: string("aaaaaaaaaa"),
+                    expire_at_millis: Some(1714089298000),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_collections() {
+        let mut data = b"REDIS0011".to_vec();
+        data.extend([OPCODE_SELECTDB, 0x02]);
+        data.push(TYPE_LIST_QUICKLIST_2);
+        data.extend(string("list"));
+        // One packed node with "a" and 1.
+        data.extend([0x01, 0x02, 0x0c]);
+        data.extend([0x0c, 0, 0, 0, 2, 0, 0x81, b'a', 0x02, 0x01, 0x01, 0xff]);
+        data.push(TYPE_SET_INTSET);
+        data.extend(string("set"));
+        data.extend([0x0c, 2, 0, 0, 0, 2, 0, 0, 0, 0x01, 0x00, 0x02, 0x00]);
+        data.push(TYPE_ZSET_2);
+        data.extend(string("zset"));
+        data.push(0x01);
+        data.extend(string("m"));
+        data.extend(1.5f64.to_le_bytes());
+        data.push(TYPE_HASH);
+        data.extend(string("hash"));
+        data.push(0x01);
+        data.extend(string("f"));
+        data.extend(string("v"));
+        data.push(OPCODE_EOF);
+        // A zero checksum is not verified.
+        data.extend([0; 8]);
+
+        let rdb = parse(&data).unwrap();
+        let values: Vec<(u64, RedisType)> = rdb
+            .entries
+            .into_iter()
+            .map(|entry| (entry.db, entry.value))
+            .collect();
+        let mut zset = SortedSet::new();
+        zset.replace(SortedValue {
+            member: Bytes::from("m"),
+            score: 1.5,
+        });
+        assert_eq!(
+            values,
+            vec![
+                (
+                    2,
+                    RedisType::List(VecDeque::from([Bytes::from("a"), Bytes::from("1")]))
+                ),
+                (
+                    2,
+                    RedisType::Set(HashSet::from([Bytes::from("1"), Bytes::from("2")]))
+                ),
+                (2, RedisType::ZSet(zset)),
+                (
+                    2,
+                    RedisType::Hash(HashMap::from([(Bytes::from("f"), Bytes::from("v"))]))
+                ),
+            ]
+        );
+    }
+
+    /// An RDB file holding a stream with master ID 1-0 and a single listpack.
+    fn stream_rdb(listpack: &[u8]) -> Vec<u8> {
+        let mut data = b"REDIS0011".to_vec();
+        data.push(TYPE_STREAM_LISTPACKS_3);
+        data.extend(string("stream"));
+        data.push(0x01);
+        data.push(0x10);
+        data.extend(1u64.to_be_bytes());
+        data.extend(0u64.to_be_bytes());
+        data.push((listpack.len() + 7) as u8);
+        data.extend(((listpack.len() + 7) as u32).to_le_bytes());
+        data.extend([10, 0]);
+        data.extend(listpack);
+        data.push(0xff);
+        // Length, last ID, first ID, max deleted ID, entries added, no consumer groups.
+        data.extend([1, 6, 0, 1, 0, 0, 0, 1, 0]);
+        data.push(OPCODE_EOF);
+        with_checksum(data)
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let listpack = [
+            0x01, 0x01, // count 1
+            0x00, 0x01, // deleted 0
+            0x01, 0x01, // one master field
+            0x81, b'f', 0x02, // "f"
+            0x00, 0x01, // master entry terminator
+            0x02, 0x01, // flags: same fields
+            0x05, 0x01, // ms delta 5
+            0x00, 0x01, // seq delta 0
+            0x81, b'v', 0x02, // "v"
+            0x03, 0x01, // lp-count
+        ];
+        let data = stream_rdb(&listpack);
+
+        let mut stream = RedisStream::new();
+        stream.add_entry(
+            "6-0".to_string(),
+            StreamEntry::new(HashMap::from([("f".to_string(), "v".to_string())])),
+        );
+        let rdb = parse(&data).unwrap();
+        assert_eq!(rdb.entries[0].value, RedisType::Stream(stream));
+
+        // Entry counts that overflow or are negative.
+        for count in [i64::MAX, -1] {
+            let mut listpack = vec![0xf4];
+            listpack.extend(count.to_le_bytes());
+            listpack.extend([0x09, 0x01, 0x01, 0x00, 0x01, 0x00, 0x01]);
+            assert!(matches!(
+                parse(&stream_rdb(&listpack)),
+                Err(RdbError::Corrupt(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(matches!(parse(b"RADIS0011"), Err(RdbError::BadSignature)));
+        assert!(matches!(
+            parse(b"REDIS0099"),
+            Err(RdbError::UnsupportedVersion(99))
+        ));
+        assert!(matches!(
+            parse(b"REDIS0011\x00\x03foo"),
+            Err(RdbError::UnexpectedEof)
+        ));
+        assert!(matches!(
+            parse(b"REDIS0011\x08\x03foo"),
+            Err(RdbError::UnknownType(8))
+        ));
+
+        let mut data = with_checksum(b"REDIS0011\xff".to_vec());
+        data[10] ^= 0xff;
+        assert!(matches!(parse(&data), Err(RdbError::Checksum { .. })));
+        assert!(matches!(
+            parse_prefix_with_offset(b"REDIS0011\xfe\x00\x08\x03foo"),
+            Err((RdbError::UnknownType(8), 16))
+        ));
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        let now = 1_700_000_000_000;
+        let kv = |value: &str, expired_at_millis| {
+            RedisType::String(KeyValue {
+                value: Bytes::from(value.to_string()),
+                expired_at_millis,
+            })
+        };
+        let mut zset = SortedSet::new();
+        zset.replace(SortedValue {
+            member: Bytes::from("a"),
+            score: 1.5,
+        });
+        zset.replace(SortedValue {
+            member: Bytes::from("b"),
+            score: f64::NEG_INFINITY,
+        });
+        // More entries than fit in one node, IDs that don't sort as strings and fields that
+        // change from one entry to the next.
+        let mut stream = RedisStream::new();
+        for i in 1..=150u64 {
+            let field = if i % 7 == 0 { "other" } else { "f" };
+            stream.add_entry(
+                format!("{}-{}", i * 10, i % 3),
+                StreamEntry::new(HashMap::from([
+                    (field.to_string(), format!("v{}", i)),
+                    ("n".to_string(), i.to_string()),
+                ])),
+            );
+        }
+
+        let mut data = HashMap::new();
+        data.insert("plain".to_string(), kv("hello", None));
+        data.insert("number".to_string(), kv("-70000", None));
+        data.insert(
+            "expiring".to_string(),
+            kv(&"x".repeat(100), Some(now + 1000)),
+        );
+        data.insert("expired".to_string(), kv("gone", Some(now)));
+        data.insert(
+            "list".to_string(),
+            RedisType::List(VecDeque::from([Bytes::from("a"), Bytes::from("12")])),
+        );
+        data.insert(
+            "set".to_string(),
+            RedisType::Set(HashSet::from([Bytes::from("m"), Bytes::from("3")])),
+        );
+        data.insert(
+            "hash".to_string(),
+            RedisType::Hash(HashMap::from([(Bytes::from("f"), Bytes::from("v"))])),
+        );
+        data.insert("zset".to_string(), RedisType::ZSet(zset));
+        data.insert("stream".to_string(), RedisType::Stream(stream));
+
+        let string_value = |value: &Bytes| {
+            RedisType::String(KeyValue {
+                value: value.clone(),
+                expired_at_millis: None,
+            })
+        };
+        let functions = vec!["#!lua name=lib\nredis.register_function('f', f)".to_string()];
+        let rdb = parse(&encode(&data, &functions, now)).unwrap();
+        assert_eq!(rdb.version, VERSION);
+        assert_eq!(rdb.functions, functions);
+        assert_eq!(rdb.aux("redis-bits"), Some(&Bytes::from("64")));
+        assert_eq!(rdb.aux("ctime"), Some(&Bytes::from("1700000000")));
+        assert_eq!(rdb.entries.len(), data.len() - 1);
+        for entry in rdb.entries {
+            let key = String::from_utf8(entry.key.to_vec()).unwrap();
+            match &data[&key] {
+                // The expire is read separately from the value.
+                RedisType::String(kv) => {
+                    assert_eq!(entry.expire_at_millis, kv.expired_at_millis);
+                    assert_eq!(entry.value, string_value(&kv.value));
+                }
+                value => {
+                    assert_eq!(entry.expire_at_millis, None);
+                    assert_eq!(&entry.value, value, "{}", key);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_dump_and_restore() {
+        let mut zset = SortedSet::new();
+        zset.replace(SortedValue {
+            member: Bytes::from("m"),
+            score: 2.5,
+        });
+        let mut stream = RedisStream::new();
+        stream.add_entry(
+            "1-1".to_string(),
+            StreamEntry::new(HashMap::from([("f".to_string(), "v".to_string())])),
+        );
+        let values = [
+            RedisType::String(KeyValue {
+                value: Bytes::from("12345"),
+                expired_at_millis: None,
+            }),
+            RedisType::List(VecDeque::from([Bytes::from("a"), Bytes::from("b")])),
+            RedisType::Set(HashSet::from([Bytes::from("m")])),
+            RedisType::ZSet(zset),
+            RedisType::Hash(HashMap::from([(Bytes::from("f"), Bytes::from("v"))])),
+            RedisType::Stream(stream),
+        ];
+        for value in values {
+            let payload = dump(&value).unwrap();
+            assert_eq!(restore(&payload).unwrap(), value);
+        }
+        assert_eq!(dump(&RedisType::None), None);
+
+        // `SET mykey 10` then `DUMP mykey` on Redis 7.0, which writes RDB version 10.
+        let payload = b"\x00\xc0\n\n\x00n\x9fWE\x0e\xaec\xbb";
+        assert_eq!(
+            restore(payload).unwrap(),
+            RedisType::String(KeyValue {
+                value: Bytes::from("10"),
+                expired_at_millis: None,
+            })
+        );
+
+        let mut corrupt = payload.to_vec();
+        corrupt[2] = b'1';
+        assert!(matches!(restore(&corrupt), Err(RdbError::BadPayload)));
+        let mut newer = payload[..payload.len() - 8].to_vec();
+        newer[3] = 99;
+        assert!(matches!(
+            restore(&with_checksum(newer)),
+            Err(RdbError::BadPayload)
+        ));
+        assert!(matches!(restore(b"\x00"), Err(RdbError::BadPayload)));
+        let mut trailing = b"\x00\x03barx".to_vec();
+        trailing.extend((VERSION as u16).to_le_bytes());
+        assert!(matches!(
+            restore(&with_checksum(trailing)),
+            Err(RdbError::Corrupt(_))
+        ));
+
+        let codes = vec!["#!lua name=a\n".to_string(), "#!lua name=b\n".to_string()];
+        let payload = dump_functions(&codes);
+        assert_eq!(restore_functions(&payload).unwrap(), codes);
+        assert!(matches!(
+            restore_functions(&payload[1..]),
+            Err(RdbError::BadPayload)
+        ));
+    }
+}
@@ -0,0 +1,341 @@
+//! Compact encodings Redis uses for small collections, stored in the RDB file as one string
+//! blob. Integers are returned in their decimal form, like Redis does when it reads them.
+//! Only listpacks are written back, streams have no other encoding.
+
+use bytes::Bytes;
+
+use super::{reader::RdbReader, RdbError};
+
+const END: u8 = 0xff;
+
+fn corrupt(encoding: &str) -> RdbError {
+    RdbError::Corrupt(format!("Invalid {} encoding", encoding))
+}
+
+fn integer(value: i64) -> Bytes {
+    Bytes::from(value.to_string())
+}
+
+/// Sign-extends the low `bits` bits of `value`.
+fn signed(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// Ziplist: a header (total bytes, tail offset, count), then entries made of the previous
+/// entry length, an encoding byte telling a string length or an integer type, and the data.
+pub fn ziplist(data: &[u8]) -> Result<Vec<Bytes>, RdbError> {
+    let mut reader = RdbReader::new(data);
+    reader.u32_le()?;
+    reader.u32_le()?;
+    reader.u16_le()?;
+
+    let mut items = Vec::new();
+    loop {
+        match reader.u8()? {
+            END => break,
+            // Previous entry length over 253 bytes, in the next 4 bytes.
+            0xfe => {
+                reader.bytes(4)?;
+            }
+            _ => {}
+        }
+        let encoding = reader.u8()?;
+        let item = match encoding >> 6 {
+            0 => Bytes::copy_from_slice(reader.bytes((encoding & 0x3f) as usize)?),
+            1 => {
+                let len = (((encoding & 0x3f) as usize) << 8) | reader.u8()? as usize;
+                Bytes::copy_from_slice(reader.bytes(len)?)
+            }
+            2 => {
+                let len = reader.u32_be()? as usize;
+                Bytes::copy_from_slice(reader.bytes(len)?)
+            }
+            _ => integer(match encoding {
+                0xc0 => reader.u16_le()? as i16 as i64,
+                0xd0 => reader.u32_le()? as i32 as i64,
+                0xe0 => reader.u64_le()? as i64,
+                0xf0 => {
+                    let bytes = reader.bytes(3)?;
+                    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+                    signed(value as u64, 24)
+                }
+                0xfe => reader.u8()? as i8 as i64,
+                // 4 bit immediate between 0 and 12, stored as 1 to 13.
+                0xf1..=0xfd => (encoding & 0x0f) as i64 - 1,
+                _ => return Err(corrupt("ziplist")),
+            }),
+        };
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Listpack: a header (total bytes, count), then entries made of an encoding byte, the data
+/// and the entry length written backwards, so the list can be walked from the tail.
+pub fn listpack(data: &[u8]) -> Result<Vec<Bytes>, RdbError> {
+    let mut reader = RdbReader::new(data);
+    reader.u32_le()?;
+    reader.u16_le()?;
+
+    let mut items = Vec::new();
+    loop {
+        let encoding = reader.u8()?;
+        if encoding == END {
+            break;
+        }
+        let start = reader.position() - 1;
+        let item = if encoding & 0x80 == 0 {
+            integer((encoding & 0x7f) as i64)
+        } else if encoding & 0xc0 == 0x80 {
+            Bytes::copy_from_slice(reader.bytes((encoding & 0x3f) as usize)?)
+        } else if encoding & 0xe0 == 0xc0 {
+            let value = (((encoding & 0x1f) as u64) << 8) | reader.u8()? as u64;
+            integer(signed(value, 13))
+        } else if encoding & 0xf0 == 0xe0 {
+            let len = (((encoding & 0x0f) as usize) << 8) | reader.u8()? as usize;
+            Bytes::copy_from_slice(reader.bytes(len)?)
+        } else {
+            match encoding {
+                0xf0 => {
+                    let len = reader.u32_le()? as usize;
+                    Bytes::copy_from_slice(reader.bytes(len)?)
+                }
+                0xf1 => integer(reader.u16_le()? as i16 as i64),
+                0xf2 => {
+                    let bytes = reader.bytes(3)?;
+                    let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]);
+                    integer(signed(value as u64, 24))
+                }
+                0xf3 => integer(reader.u32_le()? as i32 as i64),
+                0xf4 => integer(reader.u64_le()? as i64),
+                _ => return Err(corrupt("listpack")),
+            }
+        };
+        reader.bytes(backlen_size(reader.position() - start))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+/// Builds a listpack. Items that are the decimal form of an integer are stored as integers,
+/// like `lpAppend` does.
+pub struct ListpackBuilder {
+    data: Vec<u8>,
+    count: usize,
+}
+
+impl ListpackBuilder {
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: &[u8]) {
+        match canonical_integer(item) {
+            Some(value) => self.push_int(value),
+            None => {
+                let mut entry = Vec::with_capacity(item.len() + 5);
+                let len = item.len();
+                if len < 64 {
+                    entry.push(0x80 | len as u8);
+                } else if len < 4096 {
+                    entry.extend([0xe0 | (len >> 8) as u8, len as u8]);
+                } else {
+                    entry.push(0xf0);
+                    entry.extend((len as u32).to_le_bytes());
+                }
+                entry.extend_from_slice(item);
+                self.push_entry(entry);
+            }
+        }
+    }
+
+    pub fn push_int(&mut self, value: i64) {
+        let mut entry = Vec::with_capacity(9);
+        match value {
+            0..=127 => entry.push(value as u8),
+            -4096..=4095 => {
+                let value = (value as u64) & 0x1fff;
+                entry.extend([0xc0 | (value >> 8) as u8, value as u8]);
+            }
+            -32768..=32767 => {
+                entry.push(0xf1);
+                entry.extend((value as i16).to_le_bytes());
+            }
+            -8388608..=8388607 => {
+                entry.push(0xf2);
+                entry.extend(&(value as i32).to_le_bytes()[..3]);
+            }
+            -2147483648..=2147483647 => {
+                entry.push(0xf3);
+                entry.extend((value as i32).to_le_bytes());
+            }
+            _ => {
+                entry.push(0xf4);
+                entry.extend(value.to_le_bytes());
+            }
+        }
+        self.push_entry(entry);
+    }
+
+    /// Appends an encoded entry followed by its length written backwards: 7 bits per byte,
+    /// most significant first, every byte but the first one with the high bit set.
+    fn push_entry(&mut self, entry: Vec<u8>) {
+        let len = entry.len() as u64;
+        self.data.extend(entry);
+        let groups = backlen_size(len as usize) as u64;
+        for i in (0..groups).rev() {
+            let byte = ((len >> (7 * i)) & 0x7f) as u8;
+            self.data
+                .push(if i == groups - 1 { byte } else { byte | 0x80 });
+        }
+        self.count += 1;
+    }
+
+    /// The listpack with its header: total size and item count, saturated when it doesn't fit.
+    pub fn finish(self) -> Vec<u8> {
+        let total = (self.data.len() + 7) as u32;
+        let count = self.count.min(u16::MAX as usize) as u16;
+        let mut listpack = Vec::with_capacity(total as usize);
+        listpack.extend(total.to_le_bytes());
+        listpack.extend(count.to_le_bytes());
+        listpack.extend(self.data);
+        listpack.push(END);
+        listpack
+    }
+}
+
+/// The integer `item` is the decimal form of, without sign or leading zero variants.
+pub fn canonical_integer(item: &[u8]) -> Option<i64> {
+    if item.is_empty() || item.len() > 20 {
+        return None;
+    }
+    let value: i64 = std::str::from_utf8(item).ok()?.parse().ok()?;
+    (value.to_string().as_bytes() == item).then_some(value)
+}
+
+/// Bytes taken by the backwards length of a listpack entry of `entry_len` bytes.
+fn backlen_size(entry_len: usize) -> usize {
+    match entry_len {
+        0..=127 => 1,
+        128..=16382 => 2,
+        16383..=2097150 => 3,
+        2097151..=268435454 => 4,
+        _ => 5,
+    }
+}
+
+/// Intset: the integer width in bytes, the count, then the sorted integers.
+pub fn intset(data: &[u8]) -> Result<Vec<Bytes>, RdbError> {
+    let mut reader = RdbReader::new(data);
+    let width = reader.u32_le()?;
+    let mut items = Vec::new();
+    for _ in 0..reader.u32_le()? {
+        let value = match width {
+            2 => reader.u16_le()? as i16 as i64,
+            4 => reader.u32_le()? as i32 as i64,
+            8 => reader.u64_le()? as i64,
+            _ => return Err(corrupt("intset")),
+        };
+        items.push(integer(value));
+    }
+    Ok(items)
+}
+
+/// Zipmap, the hash encoding before Redis 2.6: a count byte, then pairs of length-prefixed
+/// field and value, each value followed by a number of unused bytes to skip.
+pub fn zipmap(data: &[u8]) -> Result<Vec<Bytes>, RdbError> {
+    fn length(reader: &mut RdbReader) -> Result<Option<usize>, RdbError> {
+        Ok(match reader.u8()? {
+            END => None,
+            254 => Some(reader.u32_le()? as usize),
+            len => Some(len as usize),
+        })
+    }
+
+    let mut reader = RdbReader::new(data);
+    reader.u8()?;
+    let mut items = Vec::new();
+    while let Some(len) = length(&mut reader)? {
+        items.push(Bytes::copy_from_slice(reader.bytes(len)?));
+        let len = length(&mut reader)?.ok_or_else(|| corrupt("zipmap"))?;
+        let free = reader.u8()? as usize;
+        items.push(Bytes::copy_from_slice(reader.bytes(len)?));
+        reader.bytes(free)?;
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<Bytes> {
+        items
+            .iter()
+            .map(|item| Bytes::from(item.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_ziplist() {
+        let data = [
+            0x1a, 0, 0, 0, 0x14, 0, 0, 0, 4, 0, // header
+            0x00, 0x02, b'h', b'i', // "hi"
+            0x04, 0xf2, // 1
+            0x02, 0xfe, 0x9c, // -100
+            0x03, 0xc0, 0x39, 0x30, // 12345
+            0xff,
+        ];
+        assert_eq!(
+            ziplist(&data).unwrap(),
+            strings(&["hi", "1", "-100", "12345"])
+        );
+        assert!(ziplist(&data[..data.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_listpack() {
+        let data = [
+            0x15, 0, 0, 0, 4, 0, // header
+            0x82, b'h', b'i', 0x03, // "hi"
+            0x07, 0x01, // 7
+            0xdf, 0xff, 0x02, // -1 as a 13 bit integer
+            0xf1, 0x39, 0x30, 0x03, // 12345
+            0xff,
+        ];
+        assert_eq!(
+            listpack(&data).unwrap(),
+            strings(&["hi", "7", "-1", "12345"])
+        );
+        assert!(listpack(&[0x07, 0, 0, 0, 1, 0, 0xf9, 0x01, 0xff]).is_err());
+    }
+
+    #[test]
+    fn test_listpack_builder() {
+        let mut builder = ListpackBuilder::new();
+        for item in ["hi", "7", "-1", "12345", "007", "-70000", "9999999999"] {
+            builder.push(item.as_bytes());
+        }
+        builder.push(&[b'x'; 200]);
+        let data = builder.finish();
+        assert_eq!(&data[..4], (data.len() as u32).to_le_bytes());
+        assert_eq!(&data[6..10], [0x82, b'h', b'i', 0x03]);
+
+        let mut expected = strings(&["hi", "7", "-1", "12345", "007", "-70000", "9999999999"]);
+        expected.push(Bytes::from(vec![b'x'; 200]));
+        assert_eq!(listpack(&data).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_intset_and_zipmap() {
+        let data = [2, 0, 0, 0, 2, 0, 0, 0, 0xff, 0xff, 0x05, 0x00];
+        assert_eq!(intset(&data).unwrap(), strings(&["-1", "5"]));
+
+        let data = [1, 1, b'a', 2, 0, b'b', b'c', 0xff];
+        assert_eq!(zipmap(&data).unwrap(), strings(&["a", "bc"]));
+    }
+}
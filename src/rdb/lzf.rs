@@ -0,0 +1,66 @@
+use super::RdbError;
+
+/// Decompresses an LZF block, the compression Redis uses for long strings in RDB files.
+///
+/// The input is a sequence of chunks, each starting with a control byte: below 32 it is a run
+/// of `ctrl + 1` literal bytes, otherwise a back reference into the output written so far,
+/// with the length in the top 3 bits (7 meaning "add the next byte") and the offset in the
+/// low 5 bits plus the next byte.
+pub fn decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>, RdbError> {
+    let corrupt = || RdbError::Corrupt("Invalid LZF compressed string".to_string());
+    let mut output: Vec<u8> = Vec::new();
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 32 {
+            let literal = input.get(i..i + ctrl + 1).ok_or_else(corrupt)?;
+            output.extend_from_slice(literal);
+            i += ctrl + 1;
+        } else {
+            let mut len = ctrl >> 5;
+            if len == 7 {
+                len += *input.get(i).ok_or_else(corrupt)? as usize;
+                i += 1;
+            }
+            len += 2;
+            let offset = ((ctrl & 0x1f) << 8) + *input.get(i).ok_or_else(corrupt)? as usize + 1;
+            i += 1;
+            if offset > output.len() {
+                return Err(corrupt());
+            }
+            // Byte by byte: the reference may overlap what it is producing.
+            let start = output.len() - offset;
+            for k in 0..len {
+                output.push(output[start + k]);
+            }
+        }
+        if output.len() > expected_len {
+            return Err(corrupt());
+        }
+    }
+    if output.len() != expected_len {
+        return Err(corrupt());
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decompress() {
+        // "a", then a reference to it repeated 9 times.
+        let compressed = [0x00, b'a', 0xe0, 0x00, 0x00];
+        assert_eq!(decompress(&compressed, 10).unwrap(), b"aaaaaaaaaa");
+
+        // "abc" literal, then "abcabc" from offset 3.
+        let compressed = [0x02, b'a', b'b', b'c', 0x80, 0x02];
+        assert_eq!(decompress(&compressed, 9).unwrap(), b"abcabcabc");
+
+        assert!(decompress(&compressed, 8).is_err());
+        assert!(decompress(&[0x05, b'a'], 6).is_err());
+        assert!(decompress(&[0x20, 0x05], 3).is_err());
+    }
+}
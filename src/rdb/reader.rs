@@ -0,0 +1,397 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bytes::Bytes;
+
+use super::{encodings, lzf, RdbError};
+use super::{
+    ENC_INT16, ENC_INT32, ENC_INT8, ENC_LZF, TYPE_HASH, TYPE_HASH_LISTPACK, TYPE_HASH_ZIPLIST,
+    TYPE_HASH_ZIPMAP, TYPE_LIST, TYPE_LIST_QUICKLIST, TYPE_LIST_QUICKLIST_2, TYPE_LIST_ZIPLIST,
+    TYPE_MODULE_2, TYPE_MODULE_PRE_GA, TYPE_SET, TYPE_SET_INTSET, TYPE_SET_LISTPACK,
+    TYPE_STREAM_LISTPACKS, TYPE_STREAM_LISTPACKS_2, TYPE_STREAM_LISTPACKS_3, TYPE_STRING,
+    TYPE_ZSET, TYPE_ZSET_2, TYPE_ZSET_LISTPACK, TYPE_ZSET_ZIPLIST,
+};
+use crate::types::{
+    key_value::KeyValue,
+    sorted_set::{SortedSet, SortedValue},
+    stream::{RedisStream, StreamEntry},
+    RedisType,
+};
+
+/// Quicklist node holding a single large element instead of a listpack.
+const QUICKLIST_NODE_PLAIN: u64 = 1;
+
+/// Stream entry flags, stored in front of each entry of a stream listpack.
+const STREAM_ITEM_FLAG_DELETED: i64 = 1;
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+/// A length prefix: either a plain length or the marker of a specially encoded string.
+enum Length {
+    Plain(u64),
+    Encoded(u8),
+}
+
+/// Cursor over an in-memory RDB payload, also used for the compact encodings embedded in it.
+pub struct RdbReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> RdbReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], RdbError> {
+        if self.data.len() - self.position < len {
+            return Err(RdbError::UnexpectedEof);
+        }
+        let bytes = &self.data[self.position..self.position + len];
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], RdbError> {
+        Ok(self.bytes(N)?.try_into().expect("slice has N bytes"))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, RdbError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn u16_le(&mut self) -> Result<u16, RdbError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32_le(&mut self) -> Result<u32, RdbError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64_le(&mut self) -> Result<u64, RdbError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32_be(&mut self) -> Result<u32, RdbError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    /// The top two bits of the first byte select the format: 6 bit, 14 bit or 32/64 bit big
+    /// endian lengths, or `11` for a string stored as an integer or LZF compressed.
+    fn length_or_encoding(&mut self) -> Result<Length, RdbError> {
+        let first = self.u8()?;
+        Ok(match first >> 6 {
+            0 => Length::Plain((first & 0x3f) as u64),
+            1 => Length::Plain((((first & 0x3f) as u64) << 8) | self.u8()? as u64),
+            2 if first == 0x80 => Length::Plain(self.u32_be()? as u64),
+            2 if first == 0x81 => Length::Plain(u64::from_be_bytes(self.array()?)),
+            2 => {
+                return Err(RdbError::Corrupt(format!(
+                    "Unknown length encoding {} in rdbLoadLen()",
+                    first
+                )))
+            }
+            _ => Length::Encoded(first & 0x3f),
+        })
+    }
+
+    pub fn length(&mut self) -> Result<u64, RdbError> {
+        match self.length_or_encoding()? {
+            Length::Plain(len) => Ok(len),
+            Length::Encoded(_) => Err(RdbError::Corrupt(
+                "Unexpected string encoding where a length was expected".to_string(),
+            )),
+        }
+    }
+
+    fn length_usize(&mut self) -> Result<usize, RdbError> {
+        usize::try_from(self.length()?).map_err(|_| RdbError::UnexpectedEof)
+    }
+
+    pub fn string(&mut self) -> Result<Bytes, RdbError> {
+        match self.length_or_encoding()? {
+            Length::Plain(len) => {
+                let len = usize::try_from(len).map_err(|_| RdbError::UnexpectedEof)?;
+                Ok(Bytes::copy_from_slice(self.bytes(len)?))
+            }
+            Length::Encoded(ENC_INT8) => Ok(Bytes::from((self.u8()? as i8).to_string())),
+            Length::Encoded(ENC_INT16) => Ok(Bytes::from((self.u16_le()? as i16).to_string())),
+            Length::Encoded(ENC_INT32) => Ok(Bytes::from((self.u32_le()? as i32).to_string())),
+            Length::Encoded(ENC_LZF) => {
+                let compressed_len = self.length_usize()?;
+                let len = self.length_usize()?;
+                let compressed = self.bytes(compressed_len)?;
+                Ok(Bytes::from(lzf::decompress(compressed, len)?))
+            }
+            Length::Encoded(encoding) => Err(RdbError::Corrupt(format!(
+                "Unknown RDB string encoding type {}",
+                encoding
+            ))),
+        }
+    }
+
+    /// A length followed by that many strings. Lengths come from the file, so nothing is
+    /// allocated upfront from them: a corrupt one must fail on EOF, not on allocation.
+    fn strings(&mut self) -> Result<Vec<Bytes>, RdbError> {
+        let mut strings = Vec::new();
+        for _ in 0..self.length()? {
+            strings.push(self.string()?);
+        }
+        Ok(strings)
+    }
+
+    /// Old zset scores, a length byte followed by the score as text, with the lengths 253 to
+    /// 255 reserved for NaN and the infinities.
+    fn string_double(&mut self) -> Result<f64, RdbError> {
+        match self.u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_double(self.bytes(len as usize)?),
+        }
+    }
+
+    fn binary_double(&mut self) -> Result<f64, RdbError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+
+    /// Reads a value of the given RDB type.
+    pub fn object(&mut self, value_type: u8) -> Result<RedisType, RdbError> {
+        Ok(match value_type {
+            TYPE_STRING => RedisType::String(KeyValue {
+                value: self.string()?,
+                expired_at_millis: None,
+            }),
+            TYPE_LIST => RedisType::List(self.strings()?.into()),
+            TYPE_SET => RedisType::Set(self.strings()?.into_iter().collect()),
+            TYPE_ZSET | TYPE_ZSET_2 => {
+                let mut zset = SortedSet::new();
+                for _ in 0..self.length()? {
+                    let member = self.string()?;
+                    let score = if value_type == TYPE_ZSET_2 {
+                        self.binary_double()?
+                    } else {
+                        self.string_double()?
+                    };
+                    zset.replace(SortedValue { member, score });
+                }
+                RedisType::ZSet(zset)
+            }
+            TYPE_HASH => {
+                let mut hash = HashMap::new();
+                for _ in 0..self.length()? {
+                    hash.insert(self.string()?, self.string()?);
+                }
+                RedisType::Hash(hash)
+            }
+            TYPE_LIST_ZIPLIST => RedisType::List(encodings::ziplist(&self.string()?)?.into()),
+            TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2 => {
+                let mut list = VecDeque::new();
+                for _ in 0..self.length()? {
+                    let container = match value_type {
+                        TYPE_LIST_QUICKLIST_2 => self.length()?,
+                        _ => 0,
+                    };
+                    let node = self.string()?;
+                    match value_type {
+                        TYPE_LIST_QUICKLIST => list.extend(encodings::ziplist(&node)?),
+                        _ if container == QUICKLIST_NODE_PLAIN => list.push_back(node),
+                        _ => list.extend(encodings::listpack(&node)?),
+                    }
+                }
+                RedisType::List(list)
+            }
+            TYPE_SET_INTSET => {
+                RedisType::Set(encodings::intset(&self.string()?)?.into_iter().collect())
+            }
+            TYPE_SET_LISTPACK => {
+                let members: HashSet<Bytes> =
+                    encodings::listpack(&self.string()?)?.into_iter().collect();
+                RedisType::Set(members)
+            }
+            TYPE_ZSET_ZIPLIST | TYPE_ZSET_LISTPACK => {
+                let blob = self.string()?;
+                let items = match value_type {
+                    TYPE_ZSET_ZIPLIST => encodings::ziplist(&blob)?,
+                    _ => encodings::listpack(&blob)?,
+                };
+                let mut zset = SortedSet::new();
+                for pair in pairs(items)? {
+                    let (member, score) = pair;
+                    zset.replace(SortedValue {
+                        member,
+                        score: parse_double(&score)?,
+                    });
+                }
+                RedisType::ZSet(zset)
+            }
+            TYPE_HASH_ZIPMAP | TYPE_HASH_ZIPLIST | TYPE_HASH_LISTPACK => {
+                let blob = self.string()?;
+                let items = match value_type {
+                    TYPE_HASH_ZIPMAP => encodings::zipmap(&blob)?,
+                    TYPE_HASH_ZIPLIST => encodings::ziplist(&blob)?,
+                    _ => encodings::listpack(&blob)?,
+                };
+                RedisType::Hash(pairs(items)?.into_iter().collect())
+            }
+            TYPE_STREAM_LISTPACKS | TYPE_STREAM_LISTPACKS_2 | TYPE_STREAM_LISTPACKS_3 => {
+                RedisType::Stream(self.stream(value_type)?)
+            }
+            TYPE_MODULE_PRE_GA | TYPE_MODULE_2 => {
+                return Err(RdbError::Unsupported("Module values"))
+            }
+            other => return Err(RdbError::UnknownType(other)),
+        })
+    }
+
+    /// Streams are a radix tree of listpacks keyed by the ID of their first ("master") entry,
+    /// followed by metadata and consumer groups. `RedisStream` only keeps the entries, so the
+    /// rest is read and dropped.
+    fn stream(&mut self, value_type: u8) -> Result<RedisStream, RdbError> {
+        let mut stream = RedisStream::new();
+        for _ in 0..self.length()? {
+            let master_id = self.string()?;
+            if master_id.len() != 16 {
+                return Err(RdbError::Corrupt(
+                    "Stream node key entry is not the size of a stream ID".to_string(),
+                ));
+            }
+            let master_ms = u64::from_be_bytes(master_id[..8].try_into().expect("8 bytes"));
+            let master_seq = u64::from_be_bytes(master_id[8..].try_into().expect("8 bytes"));
+            let items = encodings::listpack(&self.string()?)?;
+            stream_entries(&mut stream, master_ms, master_seq, items)?;
+        }
+
+        // Length and last ID, then first ID, max deleted ID and entries added since v2.
+        let metadata = if value_type == TYPE_STREAM_LISTPACKS {
+            3
+        } else {
+            8
+        };
+        for _ in 0..metadata {
+            self.length()?;
+        }
+
+        for _ in 0..self.length()? {
+            self.string()?;
+            self.length()?;
+            self.length()?;
+            if value_type != TYPE_STREAM_LISTPACKS {
+                // entries_read
+                self.length()?;
+            }
+            for _ in 0..self.length()? {
+                // ID, delivery time and delivery count of each pending entry.
+                self.bytes(16)?;
+                self.bytes(8)?;
+                self.length()?;
+            }
+            for _ in 0..self.length()? {
+                self.string()?;
+                self.bytes(8)?;
+                if value_type == TYPE_STREAM_LISTPACKS_3 {
+                    // active_time
+                    self.bytes(8)?;
+                }
+                for _ in 0..self.length()? {
+                    self.bytes(16)?;
+                }
+            }
+        }
+        Ok(stream)
+    }
+}
+
+/// Decodes the entries of one stream listpack. It starts with the master entry: entry and
+/// deleted counts followed by the master field names, then each entry stores its flags, its ID
+/// as a delta from the master ID and its fields, with only the values when the fields are the
+/// same as the master's.
+fn stream_entries(
+    stream: &mut RedisStream,
+    master_ms: u64,
+    master_seq: u64,
+    items: Vec<Bytes>,
+) -> Result<(), RdbError> {
+    let mut items = items.into_iter();
+    let count = next_int(&mut items)?;
+    let deleted = next_int(&mut items)?;
+    let mut master_fields = Vec::new();
+    for _ in 0..next_int(&mut items)? {
+        master_fields.push(next_item(&mut items)?);
+    }
+    // Terminator of the master entry.
+    next_item(&mut items)?;
+
+    let total = match (count, deleted) {
+        (0.., 0..) => count.checked_add(deleted),
+        _ => None,
+    }
+    .ok_or_else(|| RdbError::Corrupt("Invalid stream listpack entry count".to_string()))?;
+
+    let text = |item: &Bytes| String::from_utf8_lossy(item).into_owned();
+    for _ in 0..total {
+        let flags = next_int(&mut items)?;
+        let ms = master_ms.wrapping_add(next_int(&mut items)? as u64);
+        let seq = master_seq.wrapping_add(next_int(&mut items)? as u64);
+        let mut values = HashMap::new();
+        if flags & STREAM_ITEM_FLAG_SAMEFIELDS != 0 {
+            for field in &master_fields {
+                values.insert(text(field), text(&next_item(&mut items)?));
+            }
+        } else {
+            for _ in 0..next_int(&mut items)? {
+                let field = next_item(&mut items)?;
+                values.insert(text(&field), text(&next_item(&mut items)?));
+            }
+        }
+        // Number of items in the entry, used to walk the listpack backwards.
+        next_item(&mut items)?;
+
+        if flags & STREAM_ITEM_FLAG_DELETED == 0 {
+            stream.add_entry(format!("{}-{}", ms, seq), StreamEntry::new(values));
+        }
+    }
+    Ok(())
+}
+
+fn next_item(items: &mut std::vec::IntoIter<Bytes>) -> Result<Bytes, RdbError> {
+    items
+        .next()
+        .ok_or_else(|| RdbError::Corrupt("Truncated stream listpack".to_string()))
+}
+
+fn next_int(items: &mut std::vec::IntoIter<Bytes>) -> Result<i64, RdbError> {
+    let item = next_item(items)?;
+    std::str::from_utf8(&item)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| RdbError::Corrupt("Expected an integer in a stream listpack".to_string()))
+}
+
+/// Groups a flat list of items into consecutive pairs.
+fn pairs(items: Vec<Bytes>) -> Result<Vec<(Bytes, Bytes)>, RdbError> {
+    if !items.len().is_multiple_of(2) {
+        return Err(RdbError::Corrupt(
+            "Odd number of elements in a hash or zset".to_string(),
+        ));
+    }
+    let mut items = items.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(first), Some(second)) = (items.next(), items.next()) {
+        pairs.push((first, second));
+    }
+    Ok(pairs)
+}
+
+fn parse_double(bytes: &[u8]) -> Result<f64, RdbError> {
+    std::str::from_utf8(bytes)
+        .ok()
+        .and_then(|s| match s {
+            "inf" | "+inf" => Some(f64::INFINITY),
+            "-inf" => Some(f64::NEG_INFINITY),
+            s => s.parse().ok(),
+        })
+        .ok_or_else(|| RdbError::Corrupt("Invalid double value".to_string()))
+}
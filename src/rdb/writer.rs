@@ -0,0 +1,221 @@
+use super::encodings::{canonical_integer, ListpackBuilder};
+use super::{
+    ENC_INT16, ENC_INT32, ENC_INT8, TYPE_HASH, TYPE_LIST, TYPE_SET, TYPE_STREAM_LISTPACKS_3,
+    TYPE_STRING, TYPE_ZSET_2,
+};
+use crate::types::{stream::RedisStream, RedisType};
+
+/// Entries per stream listpack, Redis' default `stream-node-max-entries`.
+const STREAM_NODE_MAX_ENTRIES: usize = 100;
+
+/// Stream entry flag: the fields are the master entry's, only the values are stored.
+const STREAM_ITEM_FLAG_SAMEFIELDS: i64 = 2;
+
+/// A stream entry ready to be written: its ID and its fields sorted by name.
+type StreamItem<'a> = ((u64, u64), Vec<(&'a String, &'a String)>);
+
+/// Builds an RDB payload in memory, the counterpart of `RdbReader`. Collections use the plain
+/// encodings, one string per element, which every Redis version still loads. Streams only
+/// exist as listpacks.
+pub struct RdbWriter {
+    data: Vec<u8>,
+}
+
+impl RdbWriter {
+    pub fn new() -> Self {
+        Self { data: Vec::new() }
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    /// 6 bits, 14 bits, or a marker byte followed by a big endian 32 or 64 bit length.
+    pub fn length(&mut self, len: u64) {
+        if len < 1 << 6 {
+            self.u8(len as u8);
+        } else if len < 1 << 14 {
+            self.bytes(&[0x40 | (len >> 8) as u8, len as u8]);
+        } else if len <= u32::MAX as u64 {
+            self.u8(0x80);
+            self.bytes(&(len as u32).to_be_bytes());
+        } else {
+            self.u8(0x81);
+            self.bytes(&len.to_be_bytes());
+        }
+    }
+
+    /// Short strings holding a 32 bit integer are stored as the integer, like Redis does.
+    pub fn string(&mut self, bytes: &[u8]) {
+        let integer = match bytes.len() {
+            1..=11 => canonical_integer(bytes),
+            _ => None,
+        };
+        match integer {
+            Some(value) if i8::try_from(value).is_ok() => {
+                self.bytes(&[0xc0 | ENC_INT8, value as i8 as u8]);
+            }
+            Some(value) if i16::try_from(value).is_ok() => {
+                self.u8(0xc0 | ENC_INT16);
+                self.bytes(&(value as i16).to_le_bytes());
+            }
+            Some(value) if i32::try_from(value).is_ok() => {
+                self.u8(0xc0 | ENC_INT32);
+                self.bytes(&(value as i32).to_le_bytes());
+            }
+            _ => {
+                self.length(bytes.len() as u64);
+                self.bytes(bytes);
+            }
+        }
+    }
+
+    /// Writes a value in the encoding announced by `value_type`.
+    pub fn object(&mut self, value: &RedisType) {
+        match value {
+            RedisType::None => {}
+            RedisType::String(kv) => self.string(&kv.value),
+            RedisType::List(list) => {
+                self.length(list.len() as u64);
+                list.iter().for_each(|item| self.string(item));
+            }
+            RedisType::Set(set) => {
+                self.length(set.len() as u64);
+                set.iter().for_each(|member| self.string(member));
+            }
+            RedisType::ZSet(zset) => {
+                self.length(zset.len() as u64);
+                for value in zset.iter() {
+                    self.string(&value.member);
+                    self.bytes(&value.score.to_le_bytes());
+                }
+            }
+            RedisType::Hash(hash) => {
+                self.length(hash.len() as u64);
+                for (field, value) in hash {
+                    self.string(field);
+                    self.string(value);
+                }
+            }
+            RedisType::Stream(stream) => self.stream(stream),
+        }
+    }
+
+    /// Listpacks of up to `STREAM_NODE_MAX_ENTRIES` entries keyed by their first ID, then the
+    /// metadata, with no consumer groups. Entries whose ID isn't `<ms>-<seq>` can't be
+    /// represented and are left out.
+    fn stream(&mut self, stream: &RedisStream) {
+        let mut entries: Vec<StreamItem> = stream
+            .entries()
+            .filter_map(|(id, entry)| {
+                let mut fields: Vec<_> = entry.values().iter().collect();
+                fields.sort();
+                Some((parse_stream_id(id)?, fields))
+            })
+            .collect();
+        // Stored by their string form, which doesn't sort numerically.
+        entries.sort_by_key(|(id, _)| *id);
+
+        self.length(entries.len().div_ceil(STREAM_NODE_MAX_ENTRIES) as u64);
+        for node in entries.chunks(STREAM_NODE_MAX_ENTRIES) {
+            let (master_ms, master_seq) = node[0].0;
+            let mut key = master_ms.to_be_bytes().to_vec();
+            key.extend(master_seq.to_be_bytes());
+            self.string(&key);
+            self.string(&stream_listpack(node));
+        }
+
+        let first = entries.first().map_or((0, 0), |(id, _)| *id);
+        let last = entries.last().map_or((0, 0), |(id, _)| *id);
+        self.length(entries.len() as u64);
+        self.length(last.0);
+        self.length(last.1);
+        self.length(first.0);
+        self.length(first.1);
+        // Max deleted entry ID and entries added.
+        self.length(0);
+        self.length(0);
+        self.length(entries.len() as u64);
+        // Consumer groups.
+        self.length(0);
+    }
+}
+
+/// The RDB type `RdbWriter::object` encodes a value with, `None` for `RedisType::None` that
+/// can't be stored.
+pub fn value_type(value: &RedisType) -> Option<u8> {
+    Some(match value {
+        RedisType::None => return None,
+        RedisType::String(_) => TYPE_STRING,
+        RedisType::List(_) => TYPE_LIST,
+        RedisType::Set(_) => TYPE_SET,
+        RedisType::ZSet(_) => TYPE_ZSET_2,
+        RedisType::Hash(_) => TYPE_HASH,
+        RedisType::Stream(_) => TYPE_STREAM_LISTPACKS_3,
+    })
+}
+
+/// One stream node: the master entry (count, deleted count, field names of the first entry
+/// and a terminator), then each entry with its ID relative to the master one.
+fn stream_listpack(node: &[StreamItem]) -> Vec<u8> {
+    let ((master_ms, master_seq), master_fields) = &node[0];
+    let mut listpack = ListpackBuilder::new();
+    listpack.push_int(node.len() as i64);
+    listpack.push_int(0);
+    listpack.push_int(master_fields.len() as i64);
+    for (field, _) in master_fields {
+        listpack.push(field.as_bytes());
+    }
+    listpack.push_int(0);
+
+    for ((ms, seq), fields) in node {
+        let same_fields = fields.len() == master_fields.len()
+            && fields
+                .iter()
+                .zip(master_fields)
+                .all(|((field, _), (master, _))| field == master);
+        let flags = if same_fields {
+            STREAM_ITEM_FLAG_SAMEFIELDS
+        } else {
+            0
+        };
+        listpack.push_int(flags);
+        listpack.push_int(ms.wrapping_sub(*master_ms) as i64);
+        listpack.push_int(seq.wrapping_sub(*master_seq) as i64);
+        if same_fields {
+            for (_, value) in fields {
+                listpack.push(value.as_bytes());
+            }
+        } else {
+            listpack.push_int(fields.len() as i64);
+            for (field, value) in fields {
+                listpack.push(field.as_bytes());
+                listpack.push(value.as_bytes());
+            }
+        }
+        // Number of items in the entry, used to walk the listpack backwards.
+        let count = if same_fields {
+            fields.len() + 3
+        } else {
+            fields.len() * 2 + 4
+        };
+        listpack.push_int(count as i64);
+    }
+    listpack.finish()
+}
+
+/// `<ms>-<seq>`, or just `<ms>` for sequence 0.
+fn parse_stream_id(id: &str) -> Option<(u64, u64)> {
+    match id.split_once('-') {
+        Some((ms, seq)) => Some((ms.parse().ok()?, seq.parse().ok()?)),
+        None => Some((id.parse().ok()?, 0)),
+    }
+}
@@ -0,0 +1,565 @@
+//! Replication state shared by both roles. A replica attaches to its master with `PSYNC`, gets
+//! a snapshot of the dataset, then every write command in the order the master propagated them.
+//! The replication offset counts the bytes of that stream, so a replica acknowledging an offset
+//! has everything up to it. A replica forwards the stream as is to its own replicas.
+
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use thiserror::Error;
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+    },
+    task::JoinHandle,
+    time::Instant,
+};
+
+use crate::{
+    connection::ConnectionError,
+    rdb::RdbError,
+    resp::{ProtocolVersion, RespDataType},
+    utils,
+};
+
+const REPLID_ALPHABET: [char; 16] = [
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'a', 'b', 'c', 'd', 'e', 'f',
+];
+
+/// A new random replication ID, 40 hex characters like Redis'.
+pub fn new_replid() -> String {
+    nanoid::nanoid!(40, &REPLID_ALPHABET)
+}
+
+/// A replica attached to this master.
+#[derive(Debug)]
+pub struct Replica {
+    pub client_id: u64,
+    pub ip: String,
+    /// Port the replica listens on, from `REPLCONF listening-port`.
+    pub listening_port: u16,
+    /// Set once the snapshot is sent, the stream of commands follows.
+    online: AtomicBool,
+    /// Offset from the last `REPLCONF ACK`.
+    pub ack_offset: AtomicU64,
+    /// Offset the replica has fsynced to its AOF, from the `FACK` of the last `REPLCONF ACK`.
+    pub aof_ack_offset: AtomicU64,
+    /// Unix time in milliseconds of the last `REPLCONF ACK`.
+    pub ack_time: AtomicU64,
+    sender: UnboundedSender<Bytes>,
+}
+
+impl Replica {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.ip, self.listening_port)
+    }
+
+    pub fn state(&self) -> &'static str {
+        match self.online.load(Ordering::SeqCst) {
+            true => "online",
+            false => "send_bulk",
+        }
+    }
+
+    pub fn set_online(&self) {
+        self.online.store(true, Ordering::SeqCst);
+    }
+
+    pub fn ack(&self, offset: u64, aof_offset: Option<u64>) {
+        self.ack_offset.fetch_max(offset, Ordering::SeqCst);
+        if let Some(aof_offset) = aof_offset {
+            self.aof_ack_offset.fetch_max(aof_offset, Ordering::SeqCst);
+        }
+        self.ack_time
+            .store(utils::now_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+/// Why the link to the master failed. The replica reconnects after logging it.
+#[derive(Debug, Error)]
+pub enum ReplicationError {
+    #[error("Error condition on socket for SYNC: {0}")]
+    Io(#[from] io::Error),
+    #[error("Error condition on socket for SYNC: {0}")]
+    Connection(#[from] ConnectionError),
+    #[error("Error reply to {0} from master: '{1}'")]
+    Reply(&'static str, String),
+    #[error("Unexpected reply to PSYNC from master: {0}")]
+    Psync(String),
+    #[error("Failed trying to load the MASTER synchronization DB: {0}")]
+    Rdb(#[from] RdbError),
+    #[error("MASTER closed the connection")]
+    Closed,
+    #[error("MASTER timeout: no data nor PING received...")]
+    Timeout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkState {
+    /// Connecting, or waiting to reconnect.
+    Connect,
+    /// Running the `PING`, `REPLCONF` and `PSYNC` handshake.
+    Handshake,
+    /// Receiving and loading the snapshot.
+    Transfer,
+    /// Applying the command stream.
+    Connected,
+}
+
+/// The replica side: the master this server replicates, see `RedisServer::replicate`.
+#[derive(Debug)]
+pub struct MasterLink {
+    pub host: String,
+    pub port: u16,
+    state: Mutex<LinkState>,
+    /// Unix time in milliseconds of the last read from the master, 0 before the first one.
+    pub last_io: AtomicU64,
+}
+
+impl MasterLink {
+    pub fn new(host: String, port: u16) -> Self {
+        Self {
+            host,
+            port,
+            state: Mutex::new(LinkState::Connect),
+            last_io: AtomicU64::new(0),
+        }
+    }
+
+    pub fn state(&self) -> LinkState {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_state(&self, state: LinkState) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = state;
+    }
+
+    pub fn touch(&self) {
+        self.last_io
+            .store(utils::now_millis() as u64, Ordering::Relaxed);
+    }
+}
+
+/// The link to the master and the task that runs it.
+type Master = (Arc<MasterLink>, JoinHandle<()>);
+
+/// Shown for `master_replid2` while there is no previous history.
+const NO_REPLID: &str = "0000000000000000000000000000000000000000";
+
+/// The end of the command stream, for replicas resuming with `PSYNC <replid> <offset>`.
+#[derive(Debug)]
+struct Backlog {
+    data: VecDeque<u8>,
+    size: usize,
+}
+
+impl Backlog {
+    fn new(size: usize) -> Self {
+        Self {
+            data: VecDeque::new(),
+            size,
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.data.extend(bytes);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        let excess = self.data.len().saturating_sub(self.size);
+        self.data.drain(..excess);
+    }
+}
+
+/// Everything that must change together when a command is fed.
+#[derive(Debug)]
+struct Shared {
+    /// ID of the history of the dataset, the master's one on a replica.
+    replid: String,
+    /// The history this one continues, and the first offset that isn't part of it.
+    replid2: Option<(String, u64)>,
+    replicas: Vec<Arc<Replica>>,
+    /// Created with the first replica, or by a replica's first sync.
+    backlog: Option<Backlog>,
+}
+
+#[derive(Debug)]
+pub struct Replication {
+    shared: Mutex<Shared>,
+    /// Bytes of the command stream since the replication ID was created: sent to replicas on a
+    /// master, applied on a replica. Only changed with `shared` locked.
+    offset: AtomicU64,
+    /// Set while this server is a replica.
+    master: Mutex<Option<Master>>,
+    /// Notified when a replica acknowledges an offset or the AOF is fsynced, for `WAIT`.
+    progress: broadcast::Sender<()>,
+}
+
+impl Replication {
+    pub fn new() -> Self {
+        Self {
+            shared: Mutex::new(Shared {
+                replid: new_replid(),
+                replid2: None,
+                replicas: Vec::new(),
+                backlog: None,
+            }),
+            offset: AtomicU64::new(0),
+            master: Mutex::new(None),
+            progress: broadcast::channel(1).0,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_master(&self) -> MutexGuard<'_, Option<Master>> {
+        self.master.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn replid(&self) -> String {
+        self.lock().replid.clone()
+    }
+
+    /// `master_replid2` and `second_repl_offset` as shown by `INFO`.
+    pub fn replid2(&self) -> (String, i64) {
+        match &self.lock().replid2 {
+            Some((replid, offset)) => (replid.clone(), *offset as i64),
+            None => (NO_REPLID.to_string(), -1),
+        }
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset.load(Ordering::SeqCst)
+    }
+
+    /// Whether there is a history to resume from: the replication ID and offset of the last
+    /// byte are worth a `PSYNC` to a master.
+    pub fn has_backlog(&self) -> bool {
+        self.lock().backlog.is_some()
+    }
+
+    /// Size, offset of the first byte and length of the backlog, if there is one.
+    pub fn backlog_info(&self) -> Option<(usize, u64, usize)> {
+        let shared = self.lock();
+        let backlog = shared.backlog.as_ref()?;
+        let first = self.offset() + 1 - backlog.data.len() as u64;
+        Some((backlog.size, first, backlog.data.len()))
+    }
+
+    pub fn resize_backlog(&self, size: usize) {
+        if let Some(backlog) = self.lock().backlog.as_mut() {
+            backlog.size = size;
+            backlog.trim();
+        }
+    }
+
+    /// Follows the history of a master after a full resync: its ID, its offset, and a backlog
+    /// that starts there.
+    pub fn set_replid(&self, replid: String, offset: u64, backlog_size: usize) {
+        let mut shared = self.lock();
+        shared.replid = replid;
+        shared.replid2 = None;
+        shared.backlog = Some(Backlog::new(backlog_size));
+        self.offset.store(offset, Ordering::SeqCst);
+    }
+
+    /// Starts a new history that continues the current one, e.g. when a replica is promoted.
+    /// Replicas of the previous one can still resume from it up to the current offset.
+    pub fn shift_replid(&self, replid: String) {
+        let mut shared = self.lock();
+        let old = std::mem::replace(&mut shared.replid, replid);
+        shared.replid2 = Some((old, self.offset() + 1));
+    }
+
+    pub fn master_link(&self) -> Option<Arc<MasterLink>> {
+        self.lock_master()
+            .as_ref()
+            .map(|(link, _)| Arc::clone(link))
+    }
+
+    /// Replaces the link to the master, stopping the previous one. Returns whether there was
+    /// one.
+    pub fn set_master(&self, master: Option<Master>) -> bool {
+        let old = std::mem::replace(&mut *self.lock_master(), master);
+        match old {
+            Some((_, task)) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn replicas(&self) -> Vec<Arc<Replica>> {
+        self.lock().replicas.clone()
+    }
+
+    /// Sends a command that changed the dataset to every replica. It is counted even without
+    /// any, so that clients can wait for their writes with `WAIT` and `WAITAOF`.
+    pub fn feed(&self, argv: &[Bytes]) {
+        let mut shared = self.lock();
+        let command = RespDataType::Array(argv.iter().map(RespDataType::bulk_string).collect())
+            .encode(ProtocolVersion::Resp2);
+        Self::append(&mut shared, &self.offset, Bytes::from(command));
+    }
+
+    /// Forwards data received from the master to the replicas of this replica. Counted even
+    /// without any, it is the offset acknowledged to the master.
+    pub fn proxy(&self, data: Bytes) {
+        Self::append(&mut self.lock(), &self.offset, data);
+    }
+
+    fn append(shared: &mut Shared, offset: &AtomicU64, data: Bytes) {
+        offset.fetch_add(data.len() as u64, Ordering::SeqCst);
+        if let Some(backlog) = shared.backlog.as_mut() {
+            backlog.push(&data);
+        }
+        for replica in shared.replicas.iter() {
+            // A closed channel means the replica is being detached.
+            let _ = replica.sender.send(data.clone());
+        }
+    }
+
+    fn new_replica(
+        client_id: u64,
+        ip: String,
+        listening_port: u16,
+    ) -> (Arc<Replica>, UnboundedReceiver<Bytes>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let replica = Arc::new(Replica {
+            client_id,
+            ip,
+            listening_port,
+            online: AtomicBool::new(false),
+            ack_offset: AtomicU64::new(0),
+            aof_ack_offset: AtomicU64::new(0),
+            ack_time: AtomicU64::new(utils::now_millis() as u64),
+            sender,
+        });
+        (replica, receiver)
+    }
+
+    /// Registers a replica for a full resync. Commands fed from now on are queued for it, so
+    /// the caller must send the dataset as of this point first, with the replication ID and
+    /// offset read after this call.
+    pub fn attach(
+        &self,
+        client_id: u64,
+        ip: String,
+        listening_port: u16,
+        backlog_size: usize,
+    ) -> (Arc<Replica>, UnboundedReceiver<Bytes>) {
+        let (replica, receiver) = Self::new_replica(client_id, ip, listening_port);
+        let mut shared = self.lock();
+        if shared.backlog.is_none() {
+            shared.backlog = Some(Backlog::new(backlog_size));
+        }
+        shared.replicas.push(Arc::clone(&replica));
+        (replica, receiver)
+    }
+
+    /// Registers a replica that asks to resume `replid` from `offset`, the first byte it
+    /// misses. The backlog from there is queued for it. Fails with the reason when the history
+    /// differs or the backlog doesn't go back that far.
+    pub fn attach_continue(
+        &self,
+        client_id: u64,
+        ip: String,
+        listening_port: u16,
+        replid: &str,
+        offset: u64,
+    ) -> Result<(Arc<Replica>, UnboundedReceiver<Bytes>), String> {
+        let mut shared = self.lock();
+        let master_offset = self.offset();
+        let same_history = shared.replid == replid
+            || shared
+                .replid2
+                .as_ref()
+                .is_some_and(|(replid2, until)| replid2 == replid && offset <= *until);
+        if !same_history {
+            return Err(format!(
+                "Replication ID mismatch (Replica asked for '{}', my replication IDs are '{}' and '{}')",
+                replid,
+                shared.replid,
+                shared.replid2.as_ref().map_or(NO_REPLID, |(replid2, _)| replid2),
+            ));
+        }
+        let Some(backlog) = shared.backlog.as_ref() else {
+            return Err("no backlog".to_string());
+        };
+        let first = master_offset + 1 - backlog.data.len() as u64;
+        if offset < first || offset > master_offset + 1 {
+            return Err(format!(
+                "Requested offset {} is outside of the backlog range {}..{}",
+                offset,
+                first,
+                master_offset + 1
+            ));
+        }
+        let missed: Vec<u8> = backlog
+            .data
+            .range((offset - first) as usize..)
+            .copied()
+            .collect();
+        let (replica, receiver) = Self::new_replica(client_id, ip, listening_port);
+        if !missed.is_empty() {
+            let _ = replica.sender.send(Bytes::from(missed));
+        }
+        replica.set_online();
+        shared.replicas.push(Arc::clone(&replica));
+        Ok((replica, receiver))
+    }
+
+    /// Replicas that acknowledged `offset`, or fsynced it to their AOF.
+    pub fn count_acked(&self, offset: u64, aof: bool) -> usize {
+        self.lock()
+            .replicas
+            .iter()
+            .filter(|replica| match aof {
+                true => replica.aof_ack_offset.load(Ordering::SeqCst) >= offset,
+                false => replica.ack_offset.load(Ordering::SeqCst) >= offset,
+            })
+            .count()
+    }
+
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<()> {
+        self.progress.subscribe()
+    }
+
+    pub fn notify_progress(&self) {
+        let _ = self.progress.send(());
+    }
+
+    /// Waits until `reached` holds, checking again whenever a replica acknowledges an offset or
+    /// the AOF is fsynced. Gives up after `timeout`, never with `None`.
+    pub async fn wait_progress(&self, timeout: Option<Duration>, reached: impl Fn() -> bool) {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            // Subscribed before checking, so no notification falls in between.
+            let mut progress = self.subscribe_progress();
+            if reached() {
+                return;
+            }
+            match deadline {
+                Some(deadline) => tokio::select! {
+                    _ = progress.recv() => {}
+                    _ = tokio::time::sleep_until(deadline) => return,
+                },
+                None => {
+                    let _ = progress.recv().await;
+                }
+            }
+        }
+    }
+
+    pub fn detach(&self, client_id: u64) {
+        self.lock()
+            .replicas
+            .retain(|replica| replica.client_id != client_id);
+    }
+
+    /// Drops every replica: their links close and they have to synchronize again.
+    pub fn detach_all(&self) {
+        self.lock().replicas.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKLOG_SIZE: usize = 1024;
+
+    #[test]
+    fn test_feed_replicas() {
+        let replication = Replication::new();
+        assert_eq!(replication.replid().len(), 40);
+        assert!(replication
+            .replid()
+            .chars()
+            .all(|c| REPLID_ALPHABET.contains(&c)));
+
+        let argv = [Bytes::from("SET"), Bytes::from("k"), Bytes::from("v")];
+        let command = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+        replication.feed(&argv);
+        assert_eq!(replication.offset(), command.len() as u64);
+        assert!(!replication.has_backlog());
+
+        let (replica, mut receiver) =
+            replication.attach(1, "127.0.0.1".to_string(), 6380, BACKLOG_SIZE);
+        replication.feed(&argv);
+        assert_eq!(&receiver.try_recv().unwrap()[..], command);
+        assert_eq!(replication.offset(), 2 * command.len() as u64);
+        assert_eq!(
+            replication.backlog_info(),
+            Some((BACKLOG_SIZE, command.len() as u64 + 1, command.len()))
+        );
+
+        replica.ack(10, None);
+        replica.ack(5, Some(3));
+        assert_eq!(replica.ack_offset.load(Ordering::SeqCst), 10);
+        assert_eq!(replication.count_acked(10, false), 1);
+        assert_eq!(replication.count_acked(10, true), 0);
+        replication.detach(1);
+        assert!(replication.replicas().is_empty());
+    }
+
+    #[test]
+    fn test_proxy_counts_without_replicas() {
+        let replication = Replication::new();
+        replication.set_replid("a".repeat(40), 100, BACKLOG_SIZE);
+        replication.proxy(Bytes::from("*1\r\n$4\r\nPING\r\n"));
+        assert_eq!(replication.offset(), 114);
+        assert_eq!(replication.backlog_info(), Some((BACKLOG_SIZE, 101, 14)));
+
+        let (_, mut receiver) = replication.attach(1, "127.0.0.1".to_string(), 6380, 1);
+        assert_eq!(replication.replid(), "a".repeat(40));
+        replication.proxy(Bytes::from("raw"));
+        assert_eq!(&receiver.try_recv().unwrap()[..], b"raw");
+        replication.detach_all();
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(replication.offset(), 117);
+    }
+
+    #[test]
+    fn test_attach_continue() {
+        let replication = Replication::new();
+        replication.set_replid("a".repeat(40), 0, 8);
+        replication.proxy(Bytes::from("0123456789"));
+        assert_eq!(replication.backlog_info(), Some((8, 3, 8)));
+
+        let resume = |replid: &str, offset| {
+            replication
+                .attach_continue(1, "127.0.0.1".to_string(), 6380, replid, offset)
+                .map(|(_, mut receiver)| receiver.try_recv().ok())
+        };
+        assert_eq!(resume(&"a".repeat(40), 5).unwrap().unwrap(), "456789");
+        assert_eq!(resume(&"a".repeat(40), 11).unwrap(), None);
+        assert!(resume(&"a".repeat(40), 2).is_err());
+        assert!(resume(&"a".repeat(40), 12).is_err());
+        assert!(resume(&"b".repeat(40), 5).is_err());
+
+        // After a promotion the old ID is accepted up to where the new history starts.
+        replication.shift_replid("b".repeat(40));
+        replication.proxy(Bytes::from("ab"));
+        assert_eq!(replication.replid2(), ("a".repeat(40), 11));
+        assert_eq!(resume(&"a".repeat(40), 11).unwrap().unwrap(), "ab");
+        assert!(resume(&"a".repeat(40), 12).is_err());
+        assert_eq!(resume(&"b".repeat(40), 12).unwrap().unwrap(), "b");
+
+        replication.resize_backlog(1);
+        assert_eq!(replication.backlog_info(), Some((1, 12, 1)));
+    }
+}
@@ -1,77 +1,217 @@
+use bytes::Bytes;
+use thiserror::Error;
+
 const SYMBOL_SIMPLE_STRING: char = '+';
 const SYMBOL_ERROR: char = '-';
 const SYMBOL_INTEGER: char = ':';
 const SYMBOL_BULK_STRING: char = '$';
 const SYMBOL_ARRAY: char = '*';
-const SYMBOL_END_COMMAND: &'static str = "\r\n";
+const SYMBOL_NULL: char = '_';
+const SYMBOL_BOOLEAN: char = '#';
+const SYMBOL_DOUBLE: char = ',';
+const SYMBOL_BIG_NUMBER: char = '(';
+const SYMBOL_VERBATIM_STRING: char = '=';
+const SYMBOL_MAP: char = '%';
+const SYMBOL_SET: char = '~';
+const SYMBOL_PUSH: char = '>';
+const SYMBOL_ATTRIBUTE: char = '|';
+const SYMBOL_END_COMMAND: &str = "\r\n";
+
+/// Same limits real Redis applies to client requests (`proto-max-bulk-len` and the
+/// multibulk/inline caps), so a hostile header can't make us buffer unbounded data.
+const MAX_BULK_LENGTH: i64 = 512 * 1024 * 1024;
+const MAX_MULTIBULK_LENGTH: i64 = 1024 * 1024;
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+const MAX_NESTING_DEPTH: usize = 128;
+
+/// Malformed input that can't be recovered from; the connection must be closed after replying.
+#[derive(Debug, Error, PartialEq, Clone)]
+pub enum ProtocolError {
+    #[error("invalid multibulk length")]
+    InvalidMultibulkLength,
+    #[error("invalid bulk length")]
+    InvalidBulkLength,
+    #[error("invalid integer")]
+    InvalidInteger,
+    #[error("invalid UTF-8 in simple string")]
+    InvalidUtf8,
+    #[error("expected '\\r\\n' after bulk string")]
+    MissingTerminator,
+    #[error("too big request line")]
+    LineTooLong,
+    #[error("too deeply nested aggregate")]
+    TooDeeplyNested,
+    #[error("invalid boolean")]
+    InvalidBoolean,
+    #[error("invalid double")]
+    InvalidDouble,
+    #[error("invalid verbatim string format")]
+    InvalidVerbatimFormat,
+    #[error("unexpected '{0}'")]
+    UnexpectedByte(char),
+    #[error("too big inline request")]
+    InlineTooLong,
+    #[error("unbalanced quotes in request")]
+    UnbalancedQuotes,
+}
+
+/// Wire protocol spoken by a client, negotiated through `HELLO`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProtocolVersion {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl ProtocolVersion {
+    pub fn from_number(version: i64) -> Option<Self> {
+        match version {
+            2 => Some(ProtocolVersion::Resp2),
+            3 => Some(ProtocolVersion::Resp3),
+            _ => None,
+        }
+    }
 
-#[derive(Debug, PartialEq)]
+    pub fn number(&self) -> i64 {
+        match self {
+            ProtocolVersion::Resp2 => 2,
+            ProtocolVersion::Resp3 => 3,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub enum RespDataType {
     SimpleString(String),
     Error(String),
     Integer(i64),
-    BulkString(Vec<u8>),
+    BulkString(Bytes),
     Array(Vec<RespDataType>),
     NullArray,
     Null,
+    // RESP3 only types, downgraded to their RESP2 equivalent when the client didn't negotiate
+    // RESP3 with HELLO.
+    Map(Vec<(RespDataType, RespDataType)>),
+    Set(Vec<RespDataType>),
+    Double(f64),
+    Boolean(bool),
+    BigNumber(String),
+    VerbatimString(String, Vec<u8>),
+    Push(Vec<RespDataType>),
+    Attribute(Vec<(RespDataType, RespDataType)>),
 }
 
 impl RespDataType {
+    /// Serializes using RESP2, which every client understands.
+    #[cfg(test)]
     pub fn serialize(&self) -> Vec<u8> {
+        self.encode(ProtocolVersion::Resp2)
+    }
+
+    pub fn encode(&self, protocol: ProtocolVersion) -> Vec<u8> {
+        let mut result = Vec::new();
+        self.encode_into(&mut result, protocol);
+        result
+    }
+
+    fn encode_into(&self, result: &mut Vec<u8>, protocol: ProtocolVersion) {
+        let resp3 = protocol == ProtocolVersion::Resp3;
         match self {
-            RespDataType::SimpleString(s) => {
-                format!("{}{}{}", SYMBOL_SIMPLE_STRING, s, SYMBOL_END_COMMAND).into_bytes()
+            RespDataType::SimpleString(s) => write_text(result, SYMBOL_SIMPLE_STRING, s),
+            RespDataType::Error(s) => write_text(result, SYMBOL_ERROR, s),
+            RespDataType::Integer(s) => write_line(result, SYMBOL_INTEGER, s),
+            RespDataType::BulkString(s) => write_blob(result, SYMBOL_BULK_STRING, s),
+            RespDataType::Array(arr) => write_aggregate(result, SYMBOL_ARRAY, arr, protocol),
+            RespDataType::NullArray | RespDataType::Null if resp3 => {
+                write_line(result, SYMBOL_NULL, "")
             }
-            RespDataType::Error(s) => {
-                format!("{}{}{}", SYMBOL_ERROR, s, SYMBOL_END_COMMAND).into_bytes()
+            RespDataType::NullArray => write_line(result, SYMBOL_ARRAY, -1),
+            RespDataType::Null => write_line(result, SYMBOL_BULK_STRING, -1),
+            RespDataType::Map(pairs) | RespDataType::Attribute(pairs) => {
+                let symbol = match self {
+                    RespDataType::Map(_) => SYMBOL_MAP,
+                    _ => SYMBOL_ATTRIBUTE,
+                };
+                if resp3 {
+                    write_line(result, symbol, pairs.len());
+                } else if symbol == SYMBOL_MAP {
+                    write_line(result, SYMBOL_ARRAY, pairs.len() * 2);
+                } else {
+                    // RESP2 has no way to carry out-of-band attributes, they are just dropped.
+                    return;
+                }
+                for (key, value) in pairs {
+                    key.encode_into(result, protocol);
+                    value.encode_into(result, protocol);
+                }
             }
-            RespDataType::Integer(s) => {
-                format!("{}{}{}", SYMBOL_INTEGER, s, SYMBOL_END_COMMAND).into_bytes()
+            RespDataType::Set(arr) if resp3 => write_aggregate(result, SYMBOL_SET, arr, protocol),
+            RespDataType::Push(arr) if resp3 => write_aggregate(result, SYMBOL_PUSH, arr, protocol),
+            RespDataType::Set(arr) | RespDataType::Push(arr) => {
+                write_aggregate(result, SYMBOL_ARRAY, arr, protocol)
             }
-            RespDataType::BulkString(s) => {
-                let mut result =
-                    format!("{}{}{}", SYMBOL_BULK_STRING, s.len(), SYMBOL_END_COMMAND).into_bytes();
-                result.extend_from_slice(s);
-                result.extend_from_slice(SYMBOL_END_COMMAND.as_bytes());
-                result
+            RespDataType::Double(value) if resp3 => {
+                write_line(result, SYMBOL_DOUBLE, format_double(*value))
             }
-            RespDataType::Array(arr) => {
-                let mut result =
-                    format!("{}{}{}", SYMBOL_ARRAY, arr.len(), SYMBOL_END_COMMAND).into_bytes();
-                for elem in arr {
-                    result.extend(elem.serialize());
-                }
-                result
+            RespDataType::Double(value) => {
+                write_blob(result, SYMBOL_BULK_STRING, format_double(*value).as_bytes())
+            }
+            RespDataType::Boolean(value) if resp3 => {
+                write_line(result, SYMBOL_BOOLEAN, if *value { "t" } else { "f" })
             }
-            RespDataType::NullArray => {
-                format!("{}-1{}", SYMBOL_ARRAY, SYMBOL_END_COMMAND).into_bytes()
+            RespDataType::Boolean(value) => write_line(result, SYMBOL_INTEGER, *value as i64),
+            RespDataType::BigNumber(value) if resp3 => write_line(result, SYMBOL_BIG_NUMBER, value),
+            RespDataType::BigNumber(value) => {
+                write_blob(result, SYMBOL_BULK_STRING, value.as_bytes())
             }
-            _ => format!("{}-1{}", SYMBOL_BULK_STRING, SYMBOL_END_COMMAND).into_bytes(),
+            RespDataType::VerbatimString(format, data) if resp3 => {
+                let mut content = format!("{}:", format).into_bytes();
+                content.extend_from_slice(data);
+                write_blob(result, SYMBOL_VERBATIM_STRING, &content)
+            }
+            RespDataType::VerbatimString(_, data) => write_blob(result, SYMBOL_BULK_STRING, data),
         }
     }
 
+    /// Tries to decode a single frame from the start of `buf`.
+    ///
+    /// Returns the frame and how many bytes it used, or `Ok(None)` when `buf` only holds part of
+    /// a frame and the caller must wait for more data before trying again.
+    pub fn decode(buf: &[u8]) -> Result<Option<(RespDataType, usize)>, ProtocolError> {
+        Decoder::default().decode(buf)
+    }
+
+    /// Decodes a request sent by a client.
+    ///
+    /// Requests normally are arrays of bulk strings, but anything not starting with `*` is taken
+    /// as an inline command (`SET foo bar\r\n`, as typed through telnet or `nc`) and split into
+    /// an array of bulk strings with the same quoting rules as redis-cli. An empty inline line
+    /// decodes to an empty array.
+    pub fn decode_request(buf: &[u8]) -> Result<Option<(RespDataType, usize)>, ProtocolError> {
+        Decoder::default().decode_request(buf)
+    }
+
+    /// Decodes every complete frame in `values`, ignoring a trailing partial frame.
+    #[cfg(test)]
     pub fn parse(values: Vec<u8>) -> Vec<RespDataType> {
-        let mut values_iter = values.into_iter();
         let mut results: Vec<RespDataType> = Vec::new();
+        let mut remaining = values.as_slice();
 
-        while !values_iter.as_slice().is_empty() {
-            _parse(&mut values_iter, &mut results);
+        while let Ok(Some((value, used))) = Self::decode(remaining) {
+            results.push(value);
+            remaining = &remaining[used..];
         }
         return results;
     }
 
-    pub fn bulk_string(value: &str) -> RespDataType {
-        return RespDataType::BulkString(String::from(value).into_bytes());
+    pub fn bulk_string<T: AsRef<[u8]>>(value: T) -> RespDataType {
+        return RespDataType::BulkString(Bytes::copy_from_slice(value.as_ref()));
     }
 
     pub fn simple_string(value: &str) -> RespDataType {
         return RespDataType::SimpleString(String::from(value));
     }
 
-    pub fn error(value: &str) -> RespDataType {
-        return RespDataType::SimpleString(String::from(value));
-    }
-
     pub fn ok() -> RespDataType {
         return RespDataType::simple_string("OK");
     }
@@ -90,12 +230,23 @@ impl RespDataType {
         }
     }
 
+    /// Takes the raw bytes of the value, without requiring them to be valid UTF-8.
+    pub fn into_bytes(self) -> Option<Bytes> {
+        match self {
+            RespDataType::SimpleString(val) => Some(Bytes::from(val)),
+            RespDataType::Error(val) => Some(Bytes::from(val)),
+            RespDataType::Integer(val) => Some(Bytes::from(val.to_string())),
+            RespDataType::BulkString(val) => Some(val),
+            _ => None,
+        }
+    }
+
     pub fn to_int(&self) -> Option<i64> {
         match self {
             RespDataType::SimpleString(val) => val.parse().ok(),
             RespDataType::Error(val) => val.parse().ok(),
             RespDataType::Integer(val) => Some(*val),
-            RespDataType::BulkString(val) => String::from_utf8(val.to_vec()).unwrap().parse().ok(),
+            RespDataType::BulkString(val) => std::str::from_utf8(val).ok()?.parse().ok(),
             _ => None,
         }
     }
@@ -105,79 +256,442 @@ impl RespDataType {
             RespDataType::SimpleString(val) => val.parse().ok(),
             RespDataType::Error(val) => val.parse().ok(),
             RespDataType::Integer(val) => Some(*val as f64),
-            RespDataType::BulkString(val) => String::from_utf8(val.to_vec()).unwrap().parse().ok(),
+            RespDataType::BulkString(val) => std::str::from_utf8(val).ok()?.parse().ok(),
             _ => None,
         }
     }
 
-    #[cfg(test)]
     pub fn new_array(values: Vec<&str>) -> RespDataType {
-        let bulk_string = values.iter().map(|x| Self::bulk_string(x)).collect();
+        let bulk_string = values.iter().map(Self::bulk_string).collect();
         RespDataType::Array(bulk_string)
     }
 }
 
-fn read_next_word(iter: &mut std::vec::IntoIter<u8>) -> Vec<u8> {
-    let mut word = Vec::new();
-    while let Some(byte) = iter.next() {
-        if byte == b'\r' {
-            iter.next();
-            break;
+fn write_line(result: &mut Vec<u8>, symbol: char, content: impl std::fmt::Display) {
+    result.extend(format!("{}{}{}", symbol, content, SYMBOL_END_COMMAND).into_bytes());
+}
+
+/// Writes a line of text, like an error message that quotes the arguments of the client: a
+/// line break in it would end the reply early and start another, so it becomes a space.
+fn write_text(result: &mut Vec<u8>, symbol: char, text: &str) {
+    write_line(result, symbol, text.replace(['\r', '\n'], " "));
+}
+
+fn write_blob(result: &mut Vec<u8>, symbol: char, content: &[u8]) {
+    write_line(result, symbol, content.len());
+    result.extend_from_slice(content);
+    result.extend_from_slice(SYMBOL_END_COMMAND.as_bytes());
+}
+
+fn write_aggregate(
+    result: &mut Vec<u8>,
+    symbol: char,
+    elements: &[RespDataType],
+    protocol: ProtocolVersion,
+) {
+    write_line(result, symbol, elements.len());
+    for elem in elements {
+        elem.encode_into(result, protocol);
+    }
+}
+
+/// Formats a double the way Redis replies with it (`inf`, `-inf` and `nan` included).
+pub fn format_double(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Finds the next CRLF-terminated line starting at `pos`, advancing `pos` past the terminator.
+/// Returns `None` when the terminator hasn't arrived yet.
+fn read_line<'a>(buf: &'a [u8], pos: &mut usize) -> Result<Option<&'a [u8]>, ProtocolError> {
+    let start = *pos;
+    let end = match buf[start..]
+        .windows(2)
+        .position(|w| w == SYMBOL_END_COMMAND.as_bytes())
+    {
+        Some(offset) => start + offset,
+        None if buf.len() - start > MAX_LINE_LENGTH => return Err(ProtocolError::LineTooLong),
+        None => return Ok(None),
+    };
+    *pos = end + SYMBOL_END_COMMAND.len();
+    Ok(Some(&buf[start..end]))
+}
+
+fn parse_line_as_int(line: &[u8], error: ProtocolError) -> Result<i64, ProtocolError> {
+    std::str::from_utf8(line)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(error)
+}
+
+fn parse_line_as_string(line: &[u8]) -> Result<String, ProtocolError> {
+    String::from_utf8(line.to_vec()).map_err(|_| ProtocolError::InvalidUtf8)
+}
+
+/// Reads a length-prefixed payload and its trailing CRLF.
+fn read_blob(buf: &[u8], pos: &mut usize, length: usize) -> Result<Option<Vec<u8>>, ProtocolError> {
+    let end = *pos + length;
+    if buf.len() < end + SYMBOL_END_COMMAND.len() {
+        return Ok(None);
+    }
+    if &buf[end..end + SYMBOL_END_COMMAND.len()] != SYMBOL_END_COMMAND.as_bytes() {
+        return Err(ProtocolError::MissingTerminator);
+    }
+    let content = buf[*pos..end].to_vec();
+    *pos = end + SYMBOL_END_COMMAND.len();
+    Ok(Some(content))
+}
+
+/// Decodes an inline request, looking for its end from `scanned`: the bytes before it were
+/// already searched.
+fn decode_inline(
+    buf: &[u8],
+    scanned: usize,
+) -> Result<Option<(RespDataType, usize)>, ProtocolError> {
+    let end = match buf[scanned..].iter().position(|b| *b == b'\n') {
+        Some(offset) => scanned + offset,
+        None if buf.len() > MAX_LINE_LENGTH => return Err(ProtocolError::InlineTooLong),
+        None => return Ok(None),
+    };
+    // Like Redis, a bare `\n` is accepted as the line terminator.
+    let line = buf[..end].strip_suffix(b"\r").unwrap_or(&buf[..end]);
+    let args = split_inline_args(line)?
+        .into_iter()
+        .map(|arg| RespDataType::BulkString(Bytes::from(arg)))
+        .collect();
+    Ok(Some((RespDataType::Array(args), end + 1)))
+}
+
+/// Splits an inline command line into arguments, following `sdssplitargs` from Redis: arguments
+/// are separated by whitespace, double quotes support `\n`, `\r`, `\t`, `\b`, `\a`, `\xHH`
+/// and escaped characters, single quotes only support `\'`. A closing quote must be followed by
+/// whitespace or the end of the line.
+pub fn split_inline_args(line: &[u8]) -> Result<Vec<Vec<u8>>, ProtocolError> {
+    let mut args = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        while pos < line.len() && line[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos == line.len() {
+            return Ok(args);
+        }
+
+        let mut current = Vec::new();
+        let mut in_double_quotes = false;
+        let mut in_single_quotes = false;
+        loop {
+            let byte = line.get(pos).copied();
+            if in_double_quotes {
+                match byte {
+                    None => return Err(ProtocolError::UnbalancedQuotes),
+                    Some(b'\\') if pos + 3 < line.len() && line[pos + 1] == b'x' => {
+                        match std::str::from_utf8(&line[pos + 2..pos + 4])
+                            .ok()
+                            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                        {
+                            Some(value) => {
+                                current.push(value);
+                                pos += 3;
+                            }
+                            None => current.push(b'\\'),
+                        }
+                    }
+                    Some(b'\\') if pos + 1 < line.len() => {
+                        pos += 1;
+                        current.push(match line[pos] {
+                            b'n' => b'\n',
+                            b'r' => b'\r',
+                            b't' => b'\t',
+                            b'b' => 0x08,
+                            b'a' => 0x07,
+                            other => other,
+                        });
+                    }
+                    Some(b'"') => {
+                        if line.get(pos + 1).is_some_and(|b| !b.is_ascii_whitespace()) {
+                            return Err(ProtocolError::UnbalancedQuotes);
+                        }
+                        in_double_quotes = false;
+                    }
+                    Some(other) => current.push(other),
+                }
+            } else if in_single_quotes {
+                match byte {
+                    None => return Err(ProtocolError::UnbalancedQuotes),
+                    Some(b'\\') if line.get(pos + 1) == Some(&b'\'') => {
+                        pos += 1;
+                        current.push(b'\'');
+                    }
+                    Some(b'\'') => {
+                        if line.get(pos + 1).is_some_and(|b| !b.is_ascii_whitespace()) {
+                            return Err(ProtocolError::UnbalancedQuotes);
+                        }
+                        in_single_quotes = false;
+                    }
+                    Some(other) => current.push(other),
+                }
+            } else {
+                match byte {
+                    None => break,
+                    Some(b) if b.is_ascii_whitespace() => break,
+                    Some(b'"') => in_double_quotes = true,
+                    Some(b'\'') => in_single_quotes = true,
+                    Some(other) => current.push(other),
+                }
+            }
+            pos += 1;
         }
-        word.push(byte);
+        args.push(current);
     }
-    word
 }
 
-fn _parse(values_iter: &mut std::vec::IntoIter<u8>, results: &mut Vec<RespDataType>) {
-    let first_byte = values_iter.next().unwrap();
-    match first_byte as char {
-        SYMBOL_SIMPLE_STRING => {
-            let content_bytes = read_next_word(values_iter);
-            let content = String::from_utf8(content_bytes).unwrap();
-            results.push(RespDataType::SimpleString(content));
+/// Decoding state kept between reads of a connection, so that a frame arriving in pieces is
+/// parsed once: each call goes on from the bytes already decoded instead of the frame start.
+///
+/// The buffer given to each call must start with the same frame, only with more bytes after the
+/// ones seen before. After an error the decoder must not be used anymore.
+#[derive(Debug, Default)]
+pub struct Decoder {
+    /// Bytes of the current frame already decoded, or scanned for the end of an inline request.
+    pos: usize,
+    /// The current frame is an inline request.
+    inline: bool,
+    /// Symbol and length of a bulk or verbatim string whose header was read.
+    blob: Option<(char, usize)>,
+    /// Aggregates whose header was read, the innermost last.
+    aggregates: Vec<PartialAggregate>,
+}
+
+#[derive(Debug)]
+struct PartialAggregate {
+    symbol: char,
+    length: usize,
+    elements: Vec<RespDataType>,
+}
+
+/// What the first line of a frame holds.
+enum Header {
+    Value(RespDataType),
+    Blob(char, usize),
+    Aggregate(char, usize),
+}
+
+impl Decoder {
+    /// Tries to decode a frame from the start of `buf`, like `RespDataType::decode`. On
+    /// `Ok(None)` the progress made is kept for the next call.
+    pub fn decode(&mut self, buf: &[u8]) -> Result<Option<(RespDataType, usize)>, ProtocolError> {
+        loop {
+            let value = match self.blob {
+                Some((symbol, length)) => match read_blob(buf, &mut self.pos, length)? {
+                    Some(content) => {
+                        self.blob = None;
+                        blob_value(symbol, content)?
+                    }
+                    None => return Ok(None),
+                },
+                None => {
+                    if self.aggregates.len() > MAX_NESTING_DEPTH {
+                        return Err(ProtocolError::TooDeeplyNested);
+                    }
+                    let Some(&first_byte) = buf.get(self.pos) else {
+                        return Ok(None);
+                    };
+                    let mut pos = self.pos + 1;
+                    let Some(line) = read_line(buf, &mut pos)? else {
+                        return Ok(None);
+                    };
+                    let header = decode_header(first_byte as char, line)?;
+                    self.pos = pos;
+                    match header {
+                        Header::Value(value) => value,
+                        Header::Blob(symbol, length) => {
+                            self.blob = Some((symbol, length));
+                            continue;
+                        }
+                        Header::Aggregate(symbol, length) => {
+                            self.aggregates.push(PartialAggregate {
+                                symbol,
+                                length,
+                                elements: Vec::with_capacity(length),
+                            });
+                            match self.complete_aggregates() {
+                                Some(value) => value,
+                                None => continue,
+                            }
+                        }
+                    }
+                }
+            };
+            if let Some(value) = self.add_element(value) {
+                let used = std::mem::take(&mut self.pos);
+                return Ok(Some((value, used)));
+            }
         }
-        SYMBOL_ERROR => {
-            let content_bytes = read_next_word(values_iter);
-            let content = String::from_utf8(content_bytes).unwrap();
-            results.push(RespDataType::Error(content));
+    }
+
+    /// Decodes a request sent by a client, like `RespDataType::decode_request`.
+    pub fn decode_request(
+        &mut self,
+        buf: &[u8],
+    ) -> Result<Option<(RespDataType, usize)>, ProtocolError> {
+        if self.pos == 0 {
+            match buf.first() {
+                None => return Ok(None),
+                Some(byte) => self.inline = *byte as char != SYMBOL_ARRAY,
+            }
         }
-        SYMBOL_INTEGER => {
-            let content_bytes = read_next_word(values_iter);
-            let content_str = String::from_utf8(content_bytes).unwrap();
-            let number: i64 = content_str.parse().unwrap();
-            results.push(RespDataType::Integer(number));
+        if !self.inline {
+            return self.decode(buf);
         }
-        SYMBOL_BULK_STRING => {
-            let length_bytes = read_next_word(values_iter);
-            let length_str = String::from_utf8(length_bytes).unwrap();
-            let length: i64 = length_str.parse().unwrap();
-            if length == -1 {
-                results.push(RespDataType::Null);
-            } else {
-                let content_bytes = read_next_word(values_iter);
-                results.push(RespDataType::BulkString(content_bytes));
+        match decode_inline(buf, self.pos)? {
+            Some(frame) => {
+                self.pos = 0;
+                Ok(Some(frame))
+            }
+            None => {
+                self.pos = buf.len();
+                Ok(None)
             }
         }
-        SYMBOL_ARRAY => {
-            let length_bytes = read_next_word(values_iter);
-            let length_str = String::from_utf8(length_bytes).unwrap();
-            let length: i64 = length_str.parse().unwrap();
+    }
 
-            if length == -1 {
-                results.push(RespDataType::Null);
-            } else {
-                let mut current_array_elements: Vec<RespDataType> = Vec::new();
-                for _ in 0..length {
-                    _parse(values_iter, &mut current_array_elements);
+    /// Adds a decoded value to the innermost aggregate, returning the whole frame once it is
+    /// complete.
+    fn add_element(&mut self, value: RespDataType) -> Option<RespDataType> {
+        match self.aggregates.last_mut() {
+            Some(aggregate) => {
+                aggregate.elements.push(value);
+                self.complete_aggregates()
+            }
+            None => Some(value),
+        }
+    }
+
+    /// Closes the innermost aggregates that got all their elements, returning the frame when
+    /// the outermost one is closed.
+    fn complete_aggregates(&mut self) -> Option<RespDataType> {
+        while self
+            .aggregates
+            .last()
+            .is_some_and(|aggregate| aggregate.elements.len() == aggregate.length)
+        {
+            let aggregate = self.aggregates.pop()?;
+            let value = aggregate.into_value();
+            match self.aggregates.last_mut() {
+                Some(parent) => parent.elements.push(value),
+                None => return Some(value),
+            }
+        }
+        None
+    }
+}
+
+impl PartialAggregate {
+    fn into_value(self) -> RespDataType {
+        let pairs = |elements: Vec<RespDataType>| {
+            let mut elements = elements.into_iter();
+            let mut pairs = Vec::new();
+            while let (Some(key), Some(value)) = (elements.next(), elements.next()) {
+                pairs.push((key, value));
+            }
+            pairs
+        };
+        match self.symbol {
+            SYMBOL_SET => RespDataType::Set(self.elements),
+            SYMBOL_PUSH => RespDataType::Push(self.elements),
+            SYMBOL_MAP => RespDataType::Map(pairs(self.elements)),
+            SYMBOL_ATTRIBUTE => RespDataType::Attribute(pairs(self.elements)),
+            _ => RespDataType::Array(self.elements),
+        }
+    }
+}
+
+/// Decodes the first line of a frame, without its type byte.
+fn decode_header(symbol: char, line: &[u8]) -> Result<Header, ProtocolError> {
+    match symbol {
+        SYMBOL_SIMPLE_STRING => Ok(Header::Value(RespDataType::SimpleString(
+            parse_line_as_string(line)?,
+        ))),
+        SYMBOL_ERROR => Ok(Header::Value(RespDataType::Error(parse_line_as_string(
+            line,
+        )?))),
+        SYMBOL_INTEGER => Ok(Header::Value(RespDataType::Integer(parse_line_as_int(
+            line,
+            ProtocolError::InvalidInteger,
+        )?))),
+        SYMBOL_BULK_STRING | SYMBOL_VERBATIM_STRING => {
+            let length = parse_line_as_int(line, ProtocolError::InvalidBulkLength)?;
+            if length == -1 && symbol == SYMBOL_BULK_STRING {
+                return Ok(Header::Value(RespDataType::Null));
+            }
+            if !(0..=MAX_BULK_LENGTH).contains(&length) {
+                return Err(ProtocolError::InvalidBulkLength);
+            }
+            Ok(Header::Blob(symbol, length as usize))
+        }
+        SYMBOL_ARRAY | SYMBOL_SET | SYMBOL_PUSH | SYMBOL_MAP | SYMBOL_ATTRIBUTE => {
+            let length = parse_line_as_int(line, ProtocolError::InvalidMultibulkLength)?;
+            if length == -1 && symbol == SYMBOL_ARRAY {
+                return Ok(Header::Value(RespDataType::Null));
+            }
+            // Maps and attributes are followed by a key and a value for each entry.
+            let length = match symbol {
+                SYMBOL_MAP | SYMBOL_ATTRIBUTE => length.checked_mul(2),
+                _ => Some(length),
+            };
+            match length {
+                Some(length) if (0..=MAX_MULTIBULK_LENGTH).contains(&length) => {
+                    Ok(Header::Aggregate(symbol, length as usize))
                 }
-                results.push(RespDataType::Array(current_array_elements));
+                _ => Err(ProtocolError::InvalidMultibulkLength),
             }
         }
-        _ => (),
+        SYMBOL_NULL if line.is_empty() => Ok(Header::Value(RespDataType::Null)),
+        SYMBOL_BOOLEAN => match line {
+            b"t" => Ok(Header::Value(RespDataType::Boolean(true))),
+            b"f" => Ok(Header::Value(RespDataType::Boolean(false))),
+            _ => Err(ProtocolError::InvalidBoolean),
+        },
+        SYMBOL_DOUBLE => {
+            let value = std::str::from_utf8(line)
+                .ok()
+                .and_then(|s| s.parse::<f64>().ok())
+                .ok_or(ProtocolError::InvalidDouble)?;
+            Ok(Header::Value(RespDataType::Double(value)))
+        }
+        SYMBOL_BIG_NUMBER => {
+            let value = parse_line_as_string(line)?;
+            let digits = value.strip_prefix('-').unwrap_or(&value);
+            if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ProtocolError::InvalidInteger);
+            }
+            Ok(Header::Value(RespDataType::BigNumber(value)))
+        }
+        other => Err(ProtocolError::UnexpectedByte(other)),
     }
 }
 
+/// Builds a bulk or verbatim string from its payload.
+fn blob_value(symbol: char, mut content: Vec<u8>) -> Result<RespDataType, ProtocolError> {
+    if symbol == SYMBOL_BULK_STRING {
+        return Ok(RespDataType::BulkString(Bytes::from(content)));
+    }
+    // The payload is prefixed by a three letters format, e.g. `txt:`.
+    if content.len() < 4 || content[3] != b':' {
+        return Err(ProtocolError::InvalidVerbatimFormat);
+    }
+    let format = parse_line_as_string(&content[..3])?;
+    content.drain(..4);
+    Ok(RespDataType::VerbatimString(format, content))
+}
+
 // A anotação `#[cfg(test)]` diz ao compilador para só incluir
 // este código quando executamos `cargo test`.
 #[cfg(test)]
@@ -199,6 +713,14 @@ mod tests {
         assert_eq!(result, expected)
     }
 
+    #[test]
+    fn test_serialize_line_breaks() {
+        let result = RespDataType::Error(String::from("ERR 'a\r\n+OK'")).serialize();
+        assert_eq!(result, b"-ERR 'a  +OK'\r\n".to_vec());
+        let result = RespDataType::SimpleString(String::from("a\n+OK")).serialize();
+        assert_eq!(result, b"+a +OK\r\n".to_vec());
+    }
+
     #[test]
     fn test_serialize_integer() {
         let result = RespDataType::Integer(128).serialize();
@@ -208,14 +730,14 @@ mod tests {
 
     #[test]
     fn test_serialize_bulk_string() {
-        let result = RespDataType::BulkString(b"foobar".to_vec()).serialize();
+        let result = RespDataType::BulkString(Bytes::from_static(b"foobar")).serialize();
         let expected = b"$6\r\nfoobar\r\n".to_vec();
         assert_eq!(result, expected);
     }
 
     #[test]
     fn test_serialize_empty_bulk_string() {
-        let result = RespDataType::BulkString(b"".to_vec()).serialize();
+        let result = RespDataType::BulkString(Bytes::from_static(b"")).serialize();
         let expected = b"$0\r\n\r\n".to_vec();
         assert_eq!(result, expected);
     }
@@ -231,13 +753,153 @@ mod tests {
     fn test_serialize_empty_array() {
         let result = RespDataType::Array(vec![
             RespDataType::Integer(128),
-            RespDataType::BulkString(b"foobar".to_vec()),
+            RespDataType::BulkString(Bytes::from_static(b"foobar")),
         ])
         .serialize();
         let expected = b"*2\r\n:128\r\n$6\r\nfoobar\r\n".to_vec();
         assert_eq!(result, expected);
     }
 
+    #[test]
+    fn test_encode_resp3_types() {
+        let resp3 = ProtocolVersion::Resp3;
+        assert_eq!(RespDataType::Null.encode(resp3), b"_\r\n".to_vec());
+        assert_eq!(RespDataType::NullArray.encode(resp3), b"_\r\n".to_vec());
+        assert_eq!(
+            RespDataType::Double(1.5).encode(resp3),
+            b",1.5\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Double(f64::NEG_INFINITY).encode(resp3),
+            b",-inf\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Boolean(true).encode(resp3),
+            b"#t\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::BigNumber("-12345678901234567890".to_string()).encode(resp3),
+            b"(-12345678901234567890\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::VerbatimString("txt".to_string(), b"hi".to_vec()).encode(resp3),
+            b"=6\r\ntxt:hi\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Map(vec![(
+                RespDataType::simple_string("a"),
+                RespDataType::Integer(1)
+            )])
+            .encode(resp3),
+            b"%1\r\n+a\r\n:1\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Set(vec![RespDataType::Integer(1)]).encode(resp3),
+            b"~1\r\n:1\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Push(vec![RespDataType::bulk_string("message")]).encode(resp3),
+            b">1\r\n$7\r\nmessage\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Attribute(vec![(
+                RespDataType::simple_string("ttl"),
+                RespDataType::Integer(3)
+            )])
+            .encode(resp3),
+            b"|1\r\n+ttl\r\n:3\r\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_encode_resp3_types_downgraded_to_resp2() {
+        assert_eq!(
+            RespDataType::Double(40.2).serialize(),
+            b"$4\r\n40.2\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Double(f64::INFINITY).serialize(),
+            b"$3\r\ninf\r\n".to_vec()
+        );
+        assert_eq!(RespDataType::Boolean(false).serialize(), b":0\r\n".to_vec());
+        assert_eq!(
+            RespDataType::BigNumber("123".to_string()).serialize(),
+            b"$3\r\n123\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::VerbatimString("txt".to_string(), b"hi".to_vec()).serialize(),
+            b"$2\r\nhi\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Map(vec![(
+                RespDataType::simple_string("a"),
+                RespDataType::Integer(1)
+            )])
+            .serialize(),
+            b"*2\r\n+a\r\n:1\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Set(vec![RespDataType::Integer(1)]).serialize(),
+            b"*1\r\n:1\r\n".to_vec()
+        );
+        assert_eq!(
+            RespDataType::Attribute(vec![(
+                RespDataType::simple_string("ttl"),
+                RespDataType::Integer(3)
+            )])
+            .serialize(),
+            b"".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_parse_resp3_types() {
+        let result = RespDataType::parse(
+            b"_\r\n#t\r\n,-2.5\r\n,inf\r\n(3492890328409238509324850943850943825024385\r\n=8\r\ntxt:Some\r\n%1\r\n+key\r\n~2\r\n:1\r\n:2\r\n>1\r\n+pushed\r\n|1\r\n+a\r\n:1\r\n"
+                .to_vec(),
+        );
+        assert_eq!(
+            result,
+            vec![
+                RespDataType::Null,
+                RespDataType::Boolean(true),
+                RespDataType::Double(-2.5),
+                RespDataType::Double(f64::INFINITY),
+                RespDataType::BigNumber("3492890328409238509324850943850943825024385".to_string()),
+                RespDataType::VerbatimString("txt".to_string(), b"Some".to_vec()),
+                RespDataType::Map(vec![(
+                    RespDataType::simple_string("key"),
+                    RespDataType::Set(vec![RespDataType::Integer(1), RespDataType::Integer(2)])
+                )]),
+                RespDataType::Push(vec![RespDataType::simple_string("pushed")]),
+                RespDataType::Attribute(vec![(
+                    RespDataType::simple_string("a"),
+                    RespDataType::Integer(1)
+                )]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_invalid_resp3_frames() {
+        assert_eq!(
+            RespDataType::decode(b"#x\r\n"),
+            Err(ProtocolError::InvalidBoolean)
+        );
+        assert_eq!(
+            RespDataType::decode(b",abc\r\n"),
+            Err(ProtocolError::InvalidDouble)
+        );
+        assert_eq!(
+            RespDataType::decode(b"(12a\r\n"),
+            Err(ProtocolError::InvalidInteger)
+        );
+        assert_eq!(
+            RespDataType::decode(b"=2\r\nhi\r\n"),
+            Err(ProtocolError::InvalidVerbatimFormat)
+        );
+    }
+
     #[test]
     fn test_parse_null() {
         let result = RespDataType::parse(b"$-1\r\n".to_vec());
@@ -268,14 +930,14 @@ mod tests {
     #[test]
     fn test_parse_bulk_string() {
         let result = RespDataType::parse(b"$6\r\nfoobar\r\n".to_vec());
-        let expected = vec![RespDataType::BulkString(b"foobar".to_vec())];
+        let expected = vec![RespDataType::BulkString(Bytes::from_static(b"foobar"))];
         assert_eq!(result, expected);
     }
 
     #[test]
     fn test_parse_empty_bulk_string() {
         let result = RespDataType::parse(b"$0\r\n\r\n".to_vec());
-        let expected = vec![RespDataType::BulkString(b"".to_vec())];
+        let expected = vec![RespDataType::BulkString(Bytes::from_static(b""))];
         assert_eq!(result, expected);
     }
 
@@ -297,7 +959,7 @@ mod tests {
         assert_eq!(
             result,
             vec![RespDataType::Array(vec![RespDataType::BulkString(
-                b"foobar".to_vec()
+                Bytes::from_static(b"foobar")
             )])]
         );
     }
@@ -337,10 +999,243 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_decode_incomplete_frames() {
+        assert_eq!(RespDataType::decode(b""), Ok(None));
+        assert_eq!(RespDataType::decode(b"+OK"), Ok(None));
+        assert_eq!(RespDataType::decode(b"$6\r\nfoo"), Ok(None));
+        assert_eq!(RespDataType::decode(b"$6\r\nfoobar"), Ok(None));
+        assert_eq!(RespDataType::decode(b"*2\r\n$3\r\nfoo\r\n"), Ok(None));
+    }
+
+    #[test]
+    fn test_decode_returns_consumed_length() {
+        let result = RespDataType::decode(b"*1\r\n$3\r\nfoo\r\n*1\r\n");
+        assert_eq!(
+            result,
+            Ok(Some((
+                RespDataType::Array(vec![RespDataType::BulkString(Bytes::from_static(b"foo"))]),
+                13
+            )))
+        );
+    }
+
+    #[test]
+    fn test_decode_bulk_string_by_declared_length() {
+        let result = RespDataType::decode(b"$8\r\nfoo\r\nbar\r\n");
+        assert_eq!(
+            result,
+            Ok(Some((
+                RespDataType::BulkString(Bytes::from_static(b"foo\r\nbar")),
+                14
+            )))
+        );
+    }
+
+    #[test]
+    fn test_decoder_resumes_byte_by_byte() {
+        let frames: [&[u8]; 4] = [
+            b"*2\r\n$3\r\nfoo\r\n*2\r\n:1\r\n$-1\r\n",
+            b"%1\r\n+key\r\n~1\r\n=7\r\ntxt:abc\r\n",
+            b"*0\r\n",
+            b"*1\r\n*0\r\n",
+        ];
+        for frame in frames {
+            let mut decoder = Decoder::default();
+            for end in 0..frame.len() {
+                assert_eq!(decoder.decode(&frame[..end]), Ok(None));
+            }
+            let expected = RespDataType::decode(frame).unwrap();
+            assert_eq!(decoder.decode(frame).unwrap(), expected);
+            assert_eq!(expected.unwrap().1, frame.len());
+        }
+
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.decode_request(b"SET k"), Ok(None));
+        assert_eq!(
+            decoder.decode_request(b"SET k v\r\n"),
+            Ok(Some((RespDataType::new_array(vec!["SET", "k", "v"]), 9)))
+        );
+    }
+
+    #[test]
+    fn test_decoder_skips_decoded_bytes() {
+        let mut decoder = Decoder::default();
+        assert_eq!(decoder.decode(b"*2\r\n$3\r\nfoo\r\n$3\r\nba"), Ok(None));
+        // The elements read before are kept, not decoded again.
+        assert_eq!(
+            decoder.decode(b"!!!!!!!!!!!!!!!!!bar\r\n"),
+            Ok(Some((RespDataType::new_array(vec!["foo", "bar"]), 22)))
+        );
+        assert_eq!(
+            decoder.decode(b"+OK\r\n"),
+            Ok(Some((RespDataType::ok(), 5)))
+        );
+    }
+
+    #[test]
+    fn test_decode_truncated_frames_wait_for_more_data() {
+        assert_eq!(
+            RespDataType::decode(b"*3\r\n$3\r\nSET\r\n$1\r\nk"),
+            Ok(None)
+        );
+        assert_eq!(RespDataType::decode(b"$-"), Ok(None));
+        assert_eq!(RespDataType::decode(b":12"), Ok(None));
+    }
+
+    #[test]
+    fn test_decode_negative_lengths() {
+        assert_eq!(
+            RespDataType::decode(b"$-2\r\n"),
+            Err(ProtocolError::InvalidBulkLength)
+        );
+        assert_eq!(
+            RespDataType::decode(b"*-2\r\n"),
+            Err(ProtocolError::InvalidMultibulkLength)
+        );
+    }
+
+    #[test]
+    fn test_decode_non_numeric_headers() {
+        assert_eq!(
+            RespDataType::decode(b"$abc\r\nfoo\r\n"),
+            Err(ProtocolError::InvalidBulkLength)
+        );
+        assert_eq!(
+            RespDataType::decode(b"*x\r\n"),
+            Err(ProtocolError::InvalidMultibulkLength)
+        );
+        assert_eq!(
+            RespDataType::decode(b":1a\r\n"),
+            Err(ProtocolError::InvalidInteger)
+        );
+        assert_eq!(
+            RespDataType::decode(b"$\xff\r\n"),
+            Err(ProtocolError::InvalidBulkLength)
+        );
+    }
+
+    #[test]
+    fn test_decode_oversized_headers() {
+        assert_eq!(
+            RespDataType::decode(b"$536870913\r\n"),
+            Err(ProtocolError::InvalidBulkLength)
+        );
+        assert_eq!(
+            RespDataType::decode(b"*1048577\r\n"),
+            Err(ProtocolError::InvalidMultibulkLength)
+        );
+        let mut no_terminator = b"+".to_vec();
+        no_terminator.extend(vec![b'a'; MAX_LINE_LENGTH + 1]);
+        assert_eq!(
+            RespDataType::decode(&no_terminator),
+            Err(ProtocolError::LineTooLong)
+        );
+    }
+
+    #[test]
+    fn test_decode_malformed_frames() {
+        assert_eq!(
+            RespDataType::decode(b"$3\r\nfoobar\r\n"),
+            Err(ProtocolError::MissingTerminator)
+        );
+        assert_eq!(
+            RespDataType::decode(b"+\xff\r\n"),
+            Err(ProtocolError::InvalidUtf8)
+        );
+        assert_eq!(
+            RespDataType::decode(b"!oops\r\n"),
+            Err(ProtocolError::UnexpectedByte('!'))
+        );
+        let nested = b"*1\r\n".repeat(MAX_NESTING_DEPTH + 2);
+        assert_eq!(
+            RespDataType::decode(&nested),
+            Err(ProtocolError::TooDeeplyNested)
+        );
+    }
+
+    #[test]
+    fn test_decode_request_inline() {
+        assert_eq!(
+            RespDataType::decode_request(b"SET foo bar\r\nGET"),
+            Ok(Some((
+                RespDataType::new_array(vec!["SET", "foo", "bar"]),
+                13
+            )))
+        );
+        assert_eq!(
+            RespDataType::decode_request(b"  ping\n"),
+            Ok(Some((RespDataType::new_array(vec!["ping"]), 7)))
+        );
+        assert_eq!(
+            RespDataType::decode_request(b"\r\n"),
+            Ok(Some((RespDataType::Array(vec![]), 2)))
+        );
+        assert_eq!(RespDataType::decode_request(b"SET foo"), Ok(None));
+        assert_eq!(
+            RespDataType::decode_request(b"*1\r\n$4\r\nPING\r\n"),
+            Ok(Some((RespDataType::new_array(vec!["PING"]), 14)))
+        );
+    }
+
+    #[test]
+    fn test_decode_request_inline_quoting() {
+        let result = RespDataType::decode_request(
+            b"SET \"hello world\" 'it\\'s' \"a\\x41\\n\\\"\" \"\"\r\n",
+        );
+        assert_eq!(
+            result,
+            Ok(Some((
+                RespDataType::new_array(vec!["SET", "hello world", "it's", "aA\n\"", ""]),
+                42
+            )))
+        );
+        assert_eq!(
+            RespDataType::decode_request(b"SET \"foo\r\n"),
+            Err(ProtocolError::UnbalancedQuotes)
+        );
+        assert_eq!(
+            RespDataType::decode_request(b"SET 'foo'bar\r\n"),
+            Err(ProtocolError::UnbalancedQuotes)
+        );
+        assert_eq!(
+            RespDataType::decode_request(&vec![b'a'; MAX_LINE_LENGTH + 1]),
+            Err(ProtocolError::InlineTooLong)
+        );
+    }
+
+    #[test]
+    fn test_parse_binary_bulk_string() {
+        let result = RespDataType::parse(b"*2\r\n$4\r\nECHO\r\n$5\r\n\xff\r\n\x00a\r\n".to_vec());
+        assert_eq!(
+            result,
+            vec![RespDataType::Array(vec![
+                RespDataType::bulk_string("ECHO"),
+                RespDataType::BulkString(Bytes::from_static(b"\xff\r\n\x00a")),
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_into_bytes_keeps_non_utf8() {
+        assert_eq!(
+            RespDataType::BulkString(Bytes::from_static(b"\xff\xfe")).into_bytes(),
+            Some(Bytes::from_static(b"\xff\xfe"))
+        );
+        assert_eq!(
+            RespDataType::Integer(12).into_bytes(),
+            Some(Bytes::from("12"))
+        );
+        assert_eq!(RespDataType::Null.into_bytes(), None);
+    }
+
     #[test]
     fn test_bulk_string_helper() {
         let result = RespDataType::bulk_string("hello");
-        assert_eq!(result, RespDataType::BulkString(b"hello".to_vec()));
+        assert_eq!(
+            result,
+            RespDataType::BulkString(Bytes::from_static(b"hello"))
+        );
     }
 
     #[test]
@@ -376,7 +1271,7 @@ mod tests {
             Some("123".to_string())
         );
         assert_eq!(
-            RespDataType::BulkString(b"bulk".to_vec()).to_string(),
+            RespDataType::BulkString(Bytes::from_static(b"bulk")).to_string(),
             Some("bulk".to_string())
         );
         assert_eq!(RespDataType::Array(vec![]).to_string(), None);
@@ -394,10 +1289,17 @@ mod tests {
         assert_eq!(RespDataType::Error("456".to_string()).to_int(), Some(456));
         assert_eq!(RespDataType::Integer(789).to_int(), Some(789));
         assert_eq!(
-            RespDataType::BulkString(b"101".to_vec()).to_int(),
+            RespDataType::BulkString(Bytes::from_static(b"101")).to_int(),
             Some(101)
         );
-        assert_eq!(RespDataType::BulkString(b"xyz".to_vec()).to_int(), None);
+        assert_eq!(
+            RespDataType::BulkString(Bytes::from_static(b"xyz")).to_int(),
+            None
+        );
+        assert_eq!(
+            RespDataType::BulkString(Bytes::from_static(b"\xff1")).to_int(),
+            None
+        );
         assert_eq!(RespDataType::Array(vec![]).to_int(), None);
         assert_eq!(RespDataType::Null.to_int(), None);
         assert_eq!(RespDataType::NullArray.to_int(), None);
@@ -419,10 +1321,17 @@ mod tests {
         );
         assert_eq!(RespDataType::Integer(789).to_float(), Some(789.0));
         assert_eq!(
-            RespDataType::BulkString(b"101.12".to_vec()).to_float(),
+            RespDataType::BulkString(Bytes::from_static(b"101.12")).to_float(),
             Some(101.12)
         );
-        assert_eq!(RespDataType::BulkString(b"xyz".to_vec()).to_float(), None);
+        assert_eq!(
+            RespDataType::BulkString(Bytes::from_static(b"xyz")).to_float(),
+            None
+        );
+        assert_eq!(
+            RespDataType::BulkString(Bytes::from_static(b"\xff")).to_float(),
+            None
+        );
         assert_eq!(RespDataType::Array(vec![]).to_float(), None);
         assert_eq!(RespDataType::Null.to_float(), None);
         assert_eq!(RespDataType::NullArray.to_float(), None);
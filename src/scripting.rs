@@ -0,0 +1,529 @@
+//! Lua scripting for `EVAL` and friends. Each call gets a fresh interpreter with the `redis`
+//! library, `KEYS` and `ARGV`, and runs on a blocking thread while the caller holds the server
+//! exclusively: no other command runs until the script returns, except those allowed once it
+//! runs past `busy-reply-threshold`, like `SCRIPT KILL`.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use mlua::{ChunkMode, HookTriggers, Lua, LuaOptions, MultiValue, StdLib, Table, Value};
+use thiserror::Error;
+use tokio::runtime::Handle;
+
+use crate::{
+    client::RedisClient, config::LogLevel, error::RedisError, functions, resp::RespDataType,
+    server::RedisServer,
+};
+
+/// Instructions between two checks for `SCRIPT KILL`.
+const KILL_CHECK_PERIOD: u32 = 10_000;
+
+/// Scripts known to the server and the one running, if any.
+#[derive(Debug, Default)]
+pub struct Scripting {
+    /// Bodies by SHA1 digest, added by `SCRIPT LOAD` and `EVAL`.
+    cache: Mutex<HashMap<String, String>>,
+    running: Mutex<Option<Arc<RunningScript>>>,
+}
+
+/// What a script call runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Program {
+    /// The body of an `EVAL` script.
+    Script(String),
+    /// A function of a library loaded with `FUNCTION LOAD`, with the code of the library.
+    Function { name: String, code: String },
+}
+
+#[derive(Debug)]
+pub struct RunningScript {
+    started: Instant,
+    /// The function running, `None` for an `EVAL` script.
+    pub function: Option<String>,
+    /// Arguments of the command that runs it.
+    pub command: Vec<Bytes>,
+    /// Set by `EVAL_RO`, `EVALSHA_RO` and functions flagged `no-writes`: write commands are
+    /// refused.
+    pub read_only: bool,
+    /// Whether its writes are propagated wrapped in `MULTI` and `EXEC`. Not inside a
+    /// transaction, `EXEC` wraps them already.
+    pub wrap: bool,
+    /// Set once it ran a write command, it can't be killed anymore.
+    pub wrote: AtomicBool,
+    killed: AtomicBool,
+}
+
+/// An error reply of `redis.call`, replied as is when the script doesn't catch it.
+#[derive(Debug, Error)]
+#[error("{0}")]
+struct CallError(String);
+
+impl Scripting {
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a script to the cache and returns its digest.
+    pub fn cache(&self, body: &str) -> String {
+        let sha = sha1hex(body.as_bytes());
+        Self::lock(&self.cache).insert(sha.clone(), body.to_string());
+        sha
+    }
+
+    pub fn get(&self, sha: &str) -> Option<String> {
+        Self::lock(&self.cache)
+            .get(&sha.to_ascii_lowercase())
+            .cloned()
+    }
+
+    pub fn flush(&self) {
+        Self::lock(&self.cache).clear();
+    }
+
+    pub fn start(
+        &self,
+        program: &Program,
+        command: Vec<Bytes>,
+        read_only: bool,
+        wrap: bool,
+    ) -> Arc<RunningScript> {
+        let function = match program {
+            Program::Script(_) => None,
+            Program::Function { name, .. } => Some(name.clone()),
+        };
+        let script = Arc::new(RunningScript {
+            started: Instant::now(),
+            function,
+            command,
+            read_only,
+            wrap,
+            wrote: AtomicBool::new(false),
+            killed: AtomicBool::new(false),
+        });
+        *Self::lock(&self.running) = Some(Arc::clone(&script));
+        script
+    }
+
+    pub fn running(&self) -> Option<Arc<RunningScript>> {
+        Self::lock(&self.running).clone()
+    }
+
+    pub fn finish(&self) {
+        *Self::lock(&self.running) = None;
+    }
+
+    /// Whether a script runs for longer than `threshold`: other clients get `-BUSY`.
+    pub fn busy(&self, threshold: Duration) -> bool {
+        Self::lock(&self.running)
+            .as_ref()
+            .is_some_and(|script| script.started.elapsed() >= threshold)
+    }
+
+    /// Stops the running script at its next check, unless it already wrote.
+    pub fn kill(&self) -> Result<(), RedisError> {
+        let running = Self::lock(&self.running);
+        let script = running.as_ref().ok_or(RedisError::NotBusy)?;
+        if script.wrote.load(Ordering::SeqCst) {
+            return Err(RedisError::Unkillable);
+        }
+        script.killed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+impl RunningScript {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+pub fn sha1hex(data: &[u8]) -> String {
+    sha1_smol::Sha1::from(data).digest().to_string()
+}
+
+/// Checks that a script compiles, for `SCRIPT LOAD`.
+pub fn compile(body: &str) -> Result<(), RedisError> {
+    let lua = new_lua()?;
+    lua.load(body)
+        .set_name("@user_script")
+        .into_function()
+        .map_err(|e| compile_error(&e))?;
+    Ok(())
+}
+
+pub(crate) fn new_lua() -> Result<Lua, RedisError> {
+    let libs = StdLib::TABLE | StdLib::STRING | StdLib::MATH;
+    Lua::new_with(libs, LuaOptions::new()).map_err(|e| RedisError::custom(e.to_string()))
+}
+
+fn compile_error(error: &mlua::Error) -> RedisError {
+    RedisError::custom(format!(
+        "Error compiling script (new function): {}",
+        error_message(error)
+    ))
+}
+
+/// Runs a script on the current thread, calling back into `handle` for the commands it runs.
+/// Meant for a blocking thread: a script can take as long as it wants. `EVAL` scripts get the
+/// keys and arguments as `KEYS` and `ARGV`, functions as their two parameters.
+pub fn run(
+    server: Arc<RedisServer>,
+    handle: Handle,
+    script: Arc<RunningScript>,
+    client: RedisClient,
+    program: &Program,
+    keys: Vec<Bytes>,
+    args: Vec<Bytes>,
+) -> Result<RespDataType, RedisError> {
+    let lua = new_lua()?;
+    register_redis(&lua, server, handle, Arc::clone(&script), client)
+        .map_err(|e| RedisError::custom(e.to_string()))?;
+    let setup = || -> mlua::Result<_> {
+        for name in ["loadfile", "dofile", "load"] {
+            lua.globals().set(name, Value::Nil)?;
+        }
+        lua.globals()
+            .set("loadstring", lua.create_function(loadstring)?)?;
+        Ok((sequence(&lua, keys)?, sequence(&lua, args)?))
+    };
+    let (keys, args) = setup().map_err(|e| RedisError::custom(e.to_string()))?;
+    let (function, call_args) = match program {
+        Program::Script(body) => {
+            let function = lua
+                .load(body)
+                .set_name("@user_script")
+                .into_function()
+                .map_err(|e| compile_error(&e))?;
+            let globals = lua.globals();
+            let setup = || -> mlua::Result<()> {
+                globals.set("KEYS", keys)?;
+                globals.set("ARGV", args)?;
+                protect_globals(&lua)
+            };
+            setup().map_err(|e| RedisError::custom(e.to_string()))?;
+            (function, MultiValue::new())
+        }
+        Program::Function { name, code } => {
+            protect_globals(&lua).map_err(|e| RedisError::custom(e.to_string()))?;
+            let (_, functions) = functions::register_library(&lua, code)?;
+            let (_, callback) = functions
+                .iter()
+                .find(|(info, _)| &info.name == name)
+                .ok_or_else(|| RedisError::custom("Function not found"))?;
+            let function = lua
+                .registry_value(callback)
+                .map_err(|e| RedisError::custom(e.to_string()))?;
+            let call_args = MultiValue::from_vec(vec![Value::Table(keys), Value::Table(args)]);
+            (function, call_args)
+        }
+    };
+    lua.set_hook(
+        HookTriggers::new().every_nth_instruction(KILL_CHECK_PERIOD),
+        move |_, _| match script.killed.load(Ordering::SeqCst) {
+            true => Err(mlua::Error::external(CallError(
+                "ERR Script killed by user with SCRIPT KILL...".to_string(),
+            ))),
+            false => Ok(()),
+        },
+    );
+
+    let result = match function.call::<_, Value>(call_args) {
+        Ok(value) => Ok(from_lua(value)),
+        Err(e) => match call_error(&e) {
+            Some(CallError(message)) => Ok(RespDataType::Error(message.clone())),
+            None => {
+                let message = error_message(&e);
+                Err(RedisError::custom(match program {
+                    Program::Script(body) => format!(
+                        "Error running script (call to f_{}): {}",
+                        sha1hex(body.as_bytes()),
+                        message
+                    ),
+                    Program::Function { name, .. } => format!("{} script: {}", message, name),
+                }))
+            }
+        },
+    };
+    result
+}
+
+/// `loadstring` refusing precompiled chunks, like Redis: Lua 5.1 doesn't verify bytecode, so a
+/// crafted one could corrupt the memory of the server. `load` accepts them too and is removed.
+fn loadstring<'lua>(
+    lua: &'lua Lua,
+    (chunk, name): (mlua::String<'lua>, Option<String>),
+) -> mlua::Result<(Option<mlua::Function<'lua>>, Option<String>)> {
+    let chunk = chunk.as_bytes();
+    if chunk.first() == Some(&0x1b) {
+        return Ok((
+            None,
+            Some("loadstring: binary chunks are not allowed".to_string()),
+        ));
+    }
+    let name = name.unwrap_or_else(|| String::from_utf8_lossy(chunk).into_owned());
+    match lua
+        .load(chunk)
+        .set_name(name)
+        .set_mode(ChunkMode::Text)
+        .into_function()
+    {
+        Ok(function) => Ok((Some(function), None)),
+        Err(e) => Ok((None, Some(error_message(&e)))),
+    }
+}
+
+/// Scripts can't define globals, and reading an undefined one is an error rather than `nil`.
+pub(crate) fn protect_globals(lua: &Lua) -> mlua::Result<()> {
+    lua.load(PROTECT_GLOBALS).exec()
+}
+
+const PROTECT_GLOBALS: &str = r#"
+setmetatable(_G, {
+    __newindex = function(_, name)
+        error("Script attempted to create global variable '" .. tostring(name) .. "'", 2)
+    end,
+    __index = function(_, name)
+        error("Script attempted to access nonexistent global variable '" .. tostring(name) .. "'", 2)
+    end,
+})
+"#;
+
+/// The message of a Lua error without its traceback: the reply is a single line.
+pub(crate) fn error_message(error: &mlua::Error) -> String {
+    let message = match error {
+        mlua::Error::SyntaxError { message, .. } | mlua::Error::RuntimeError(message) => {
+            message.clone()
+        }
+        mlua::Error::CallbackError { cause, .. } => return error_message(cause),
+        e => e.to_string(),
+    };
+    message.lines().next().unwrap_or_default().to_string()
+}
+
+fn call_error(error: &mlua::Error) -> Option<&CallError> {
+    match error {
+        mlua::Error::CallbackError { cause, .. } => call_error(cause),
+        mlua::Error::ExternalError(e) => e.downcast_ref(),
+        _ => None,
+    }
+}
+
+fn sequence(lua: &Lua, values: Vec<Bytes>) -> mlua::Result<Table<'_>> {
+    let table = lua.create_table()?;
+    for value in values {
+        table.raw_push(lua.create_string(&value)?)?;
+    }
+    Ok(table)
+}
+
+/// The `redis` library: `call` raises errors while `pcall` returns them as `{err = ...}`.
+fn register_redis(
+    lua: &Lua,
+    server: Arc<RedisServer>,
+    handle: Handle,
+    script: Arc<RunningScript>,
+    client: RedisClient,
+) -> mlua::Result<()> {
+    let redis = lua.create_table()?;
+    let client = Arc::new(Mutex::new(client));
+    for (name, raise) in [("call", true), ("pcall", false)] {
+        let (server, handle) = (Arc::clone(&server), handle.clone());
+        let (script, client) = (Arc::clone(&script), Arc::clone(&client));
+        let call = lua.create_function(move |lua, args: MultiValue| {
+            let reply = match arguments(args) {
+                Ok(argv) => {
+                    let mut client = Scripting::lock(&client);
+                    handle.block_on(RedisServer::call_from_script(
+                        &server,
+                        &mut client,
+                        &script,
+                        argv,
+                    ))
+                }
+                Err(e) => RespDataType::from(e),
+            };
+            match reply {
+                RespDataType::Error(message) if raise => {
+                    Err(mlua::Error::external(CallError(message)))
+                }
+                reply => to_lua(lua, reply),
+            }
+        })?;
+        redis.set(name, call)?;
+    }
+    redis.set(
+        "error_reply",
+        lua.create_function(|lua, message: mlua::String| reply_table(lua, "err", message))?,
+    )?;
+    redis.set(
+        "status_reply",
+        lua.create_function(|lua, message: mlua::String| reply_table(lua, "ok", message))?,
+    )?;
+    redis.set(
+        "sha1hex",
+        lua.create_function(|_, data: mlua::String| Ok(sha1hex(data.as_bytes())))?,
+    )?;
+    let levels = [
+        ("LOG_DEBUG", LogLevel::Debug),
+        ("LOG_VERBOSE", LogLevel::Verbose),
+        ("LOG_NOTICE", LogLevel::Notice),
+        ("LOG_WARNING", LogLevel::Warning),
+    ];
+    for (i, (name, _)) in levels.iter().enumerate() {
+        redis.set(*name, i)?;
+    }
+    redis.set(
+        "log",
+        lua.create_function(move |_, (level, message): (usize, mlua::String)| {
+            let (_, level) = levels
+                .get(level)
+                .ok_or_else(|| mlua::Error::runtime("Invalid debug level."))?;
+            server.log(*level, message.to_string_lossy());
+            Ok(())
+        })?,
+    )?;
+    lua.globals().set("redis", redis)
+}
+
+fn reply_table<'lua>(
+    lua: &'lua Lua,
+    kind: &str,
+    message: mlua::String,
+) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set(kind, message)?;
+    Ok(table)
+}
+
+/// Arguments of `redis.call`: strings, or numbers sent as their decimal representation.
+fn arguments(args: MultiValue) -> Result<Vec<Bytes>, RedisError> {
+    if args.is_empty() {
+        return Err(RedisError::custom(
+            "Please specify at least one argument for this redis lib call",
+        ));
+    }
+    args.into_iter()
+        .map(|arg| match arg {
+            Value::String(s) => Ok(Bytes::copy_from_slice(s.as_bytes())),
+            Value::Integer(i) => Ok(Bytes::from(i.to_string())),
+            Value::Number(n) => Ok(Bytes::from(n.to_string())),
+            _ => Err(RedisError::custom(
+                "Lua redis lib command arguments must be strings or integers",
+            )),
+        })
+        .collect()
+}
+
+/// Converts a command reply the way Redis does with RESP2: status and error replies become
+/// tables with an `ok` or `err` field, nulls become `false`.
+fn to_lua(lua: &Lua, reply: RespDataType) -> mlua::Result<Value<'_>> {
+    let array = |items: Vec<RespDataType>| -> mlua::Result<Value<'_>> {
+        let table = lua.create_table()?;
+        for item in items {
+            table.raw_push(to_lua(lua, item)?)?;
+        }
+        Ok(Value::Table(table))
+    };
+    let string = |data: &[u8]| lua.create_string(data).map(Value::String);
+    match reply {
+        RespDataType::Integer(i) => Ok(Value::Integer(i)),
+        RespDataType::BulkString(data) => string(&data),
+        RespDataType::VerbatimString(_, data) => string(&data),
+        RespDataType::SimpleString(s) => {
+            reply_table(lua, "ok", lua.create_string(&s)?).map(Value::Table)
+        }
+        RespDataType::Error(s) => reply_table(lua, "err", lua.create_string(&s)?).map(Value::Table),
+        RespDataType::Null | RespDataType::NullArray => Ok(Value::Boolean(false)),
+        RespDataType::Array(items) | RespDataType::Set(items) | RespDataType::Push(items) => {
+            array(items)
+        }
+        RespDataType::Map(pairs) | RespDataType::Attribute(pairs) => array(
+            pairs
+                .into_iter()
+                .flat_map(|(key, value)| [key, value])
+                .collect(),
+        ),
+        RespDataType::Double(d) => string(d.to_string().as_bytes()),
+        RespDataType::BigNumber(n) => string(n.as_bytes()),
+        RespDataType::Boolean(true) => Ok(Value::Integer(1)),
+        RespDataType::Boolean(false) => Ok(Value::Boolean(false)),
+    }
+}
+
+/// Converts what a script returns: numbers are truncated to integers, tables with an `ok` or
+/// `err` field become status and error replies, other tables arrays up to their first `nil`.
+fn from_lua(value: Value) -> RespDataType {
+    match value {
+        Value::Boolean(true) => RespDataType::Integer(1),
+        Value::Integer(i) => RespDataType::Integer(i),
+        Value::Number(n) => RespDataType::Integer(n as i64),
+        Value::String(s) => RespDataType::bulk_string(s.as_bytes()),
+        Value::Table(table) => {
+            if let Ok(Value::String(err)) = table.raw_get("err") {
+                return RespDataType::Error(err.to_string_lossy().into_owned());
+            }
+            if let Ok(Value::String(ok)) = table.raw_get("ok") {
+                return RespDataType::SimpleString(ok.to_string_lossy().into_owned());
+            }
+            let mut items = Vec::new();
+            for i in 1.. {
+                match table.raw_get::<_, Value>(i) {
+                    Ok(Value::Nil) | Err(_) => break,
+                    Ok(value) => items.push(from_lua(value)),
+                }
+            }
+            RespDataType::Array(items)
+        }
+        _ => RespDataType::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_conversions() {
+        let lua = Lua::new();
+        let reply = RespDataType::Array(vec![
+            RespDataType::Integer(1),
+            RespDataType::bulk_string("a"),
+            RespDataType::Null,
+            RespDataType::simple_string("OK"),
+            RespDataType::Error("ERR oops".to_string()),
+        ]);
+        let value = to_lua(&lua, reply.clone()).unwrap();
+        assert_eq!(from_lua(value), reply);
+        // `nil` ends an array.
+        let script = "return {1.9, 'x', {ok = 'fine'}, true, nil, 2}";
+        let value = lua.load(script).eval().unwrap();
+        assert_eq!(
+            from_lua(value),
+            RespDataType::Array(vec![
+                RespDataType::Integer(1),
+                RespDataType::bulk_string("x"),
+                RespDataType::simple_string("fine"),
+                RespDataType::Integer(1),
+            ])
+        );
+        let value = lua.load("return {err = 'ERR bad'}").eval().unwrap();
+        assert_eq!(from_lua(value), RespDataType::Error("ERR bad".to_string()));
+    }
+
+    #[test]
+    fn test_sha1hex_and_compile() {
+        assert_eq!(sha1hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert!(compile("return 1").is_ok());
+        assert!(matches!(
+            compile("return ("),
+            Err(RedisError::Custom(message)) if message.starts_with("Error compiling script")
+        ));
+    }
+}
@@ -3,246 +3,3802 @@ use std::{
         hash_map::Entry::{Occupied, Vacant},
         BTreeSet, HashMap, VecDeque,
     },
-    sync::Arc,
-    time::Duration,
+    fmt::Display,
+    fs::OpenOptions,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::UnboundedReceiver, Mutex, Notify, RwLock as AsyncRwLock,
+        RwLockReadGuard as AsyncRwLockReadGuard,
+    },
+    task::JoinHandle,
+};
+
+use crate::{
+    aof::{self, Aof, AofContent, AofError, AofFile, Manifest},
+    client::{self, RedisClient},
+    commands::{table::CommandFlag, traits::RunnableCommand, zadd::ZAddCommand, RedisCommand},
+    config::{AppendFsync, Apply, Config, LogLevel},
+    connection::{Connection, ConnectionError},
+    error::RedisError,
+    functions::{Functions, RestorePolicy},
+    pubsub::PubSub,
+    rdb::{self, Rdb, RdbError},
+    replication::{self, LinkState, MasterLink, Replica, Replication, ReplicationError},
+    resp::{ProtocolVersion, RespDataType},
+    scripting::{self, Program, RunningScript, Scripting},
+    store::RedisStore,
+    types::{key_value::KeyValue, RedisType},
+    utils,
 };
 
-use tokio::{
-    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
-    net::{TcpListener, TcpStream},
-    sync::{Mutex, Notify},
-};
+/// Version reported to clients, e.g. in the `HELLO` reply.
+pub const REDIS_VERSION: &str = "7.2.0";
+
+/// Server counters, cleared by `CONFIG RESETSTAT`.
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    pub total_connections_received: AtomicU64,
+    pub total_commands_processed: AtomicU64,
+    pub evicted_keys: AtomicU64,
+}
+
+impl ServerStats {
+    pub fn reset(&self) {
+        self.total_connections_received.store(0, Ordering::Relaxed);
+        self.total_commands_processed.store(0, Ordering::Relaxed);
+        self.evicted_keys.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A master pings its replicas this often, so they can tell a quiet master from a dead one.
+const REPL_PING_PERIOD: Duration = Duration::from_secs(10);
+
+/// A replica drops the link to a master it heard nothing from for this long.
+const REPL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Failed background saves triggered by a `save` rule are retried after this many seconds.
+const BGSAVE_RETRY_DELAY: u64 = 5;
+
+/// RDB snapshot and AOF rewrite bookkeeping.
+#[derive(Debug)]
+pub struct Persistence {
+    /// Changes to the dataset since the last successful save.
+    pub dirty: AtomicU64,
+    /// Unix time in seconds of the last successful save, or of the start.
+    pub lastsave: AtomicU64,
+    /// Set while a `SAVE` or `BGSAVE` runs, only one can write the file at a time.
+    pub saving: AtomicBool,
+    pub last_bgsave_ok: AtomicBool,
+    last_bgsave_try: AtomicU64,
+    /// Set while the AOF is rewritten.
+    pub aof_rewriting: AtomicBool,
+    /// A rewrite to start once the running one is done, e.g. to turn the AOF on.
+    pub aof_rewrite_scheduled: AtomicBool,
+    /// Replication offset of the stream written to the AOF, waiting for the next fsync with
+    /// `appendfsync everysec`.
+    aof_written_offset: AtomicU64,
+    /// Replication offset of the stream known to be on disk, for `WAITAOF`.
+    pub aof_fsynced_offset: AtomicU64,
+}
+
+impl Persistence {
+    fn new() -> Self {
+        Self {
+            dirty: AtomicU64::new(0),
+            lastsave: AtomicU64::new(unix_time()),
+            saving: AtomicBool::new(false),
+            last_bgsave_ok: AtomicBool::new(true),
+            last_bgsave_try: AtomicU64::new(0),
+            aof_rewriting: AtomicBool::new(false),
+            aof_rewrite_scheduled: AtomicBool::new(false),
+            aof_written_offset: AtomicU64::new(0),
+            aof_fsynced_offset: AtomicU64::new(0),
+        }
+    }
+}
+
+fn unix_time() -> u64 {
+    (utils::now_millis() / 1000) as u64
+}
+
+#[derive(Debug)]
+pub struct RedisServer {
+    config: RwLock<Config>,
+    pub store: Arc<RedisStore>,
+    pub stats: ServerStats,
+    pub persistence: Persistence,
+    pub replication: Replication,
+    pub started_at: Instant,
+    pub scripting: Scripting,
+    pub functions: Functions,
+    pub pubsub: PubSub,
+    /// The AOF while `appendonly` is on.
+    aof: std::sync::Mutex<Option<Aof>>,
+    /// Held shared while a command runs and exclusively to capture the dataset as of a precise
+    /// point of the command stream, e.g. to rewrite the AOF.
+    exec_lock: AsyncRwLock<()>,
+    /// Accept loops of the listening sockets, `None` until `run` is called.
+    listeners: Mutex<Option<Vec<JoinHandle<()>>>>,
+}
+
+impl RedisServer {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config: RwLock::new(config),
+            store: Arc::new(RedisStore::new()),
+            stats: ServerStats::default(),
+            persistence: Persistence::new(),
+            replication: Replication::new(),
+            started_at: Instant::now(),
+            scripting: Scripting::default(),
+            functions: Functions::default(),
+            pubsub: PubSub::default(),
+            aof: std::sync::Mutex::new(None),
+            exec_lock: AsyncRwLock::new(()),
+            listeners: Mutex::new(None),
+        }
+    }
+
+    /// The current configuration. The guard must not be held across an `.await`.
+    pub fn config(&self) -> RwLockReadGuard<'_, Config> {
+        self.config.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn config_mut(&self) -> RwLockWriteGuard<'_, Config> {
+        self.config.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The AOF, `None` while it is off. Appends are quick, the guard is never held across an
+    /// `.await`.
+    fn aof(&self) -> MutexGuard<'_, Option<Aof>> {
+        self.aof.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Size of the AOF and of its base as of the last rewrite, `None` while it is off.
+    pub fn aof_sizes(&self) -> Option<(u64, u64)> {
+        self.aof().as_ref().map(|aof| (aof.size, aof.base_size))
+    }
+
+    /// Writes a log line when `level` is enabled. Level and file are read from the config on
+    /// every call, so changing them with `CONFIG SET` applies right away.
+    pub fn log(&self, level: LogLevel, message: impl Display) {
+        let (logfile, role) = {
+            let config = self.config();
+            if level < config.loglevel {
+                return;
+            }
+            let role = match config.replicaof {
+                Some(_) => 'S',
+                None => 'M',
+            };
+            (config.logfile.clone(), role)
+        };
+        let symbol = match level {
+            LogLevel::Debug => '.',
+            LogLevel::Verbose => '-',
+            LogLevel::Notice => '*',
+            LogLevel::Warning => '#',
+        };
+        let line = format!(
+            "{}:{} {} {} {}",
+            std::process::id(),
+            role,
+            utils::format_log_time(utils::now_millis()),
+            symbol,
+            message
+        );
+        if logfile.is_empty() {
+            println!("{}", line);
+        } else if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&logfile) {
+            let _ = writeln!(file, "{}", line);
+        }
+    }
+
+    /// Loads the RDB file at `dir`/`dbfilename` into the store, if there is one. Keys that
+    /// already expired are dropped, and only strings keep their expire time.
+    pub async fn load_rdb(&self) -> Result<(), RdbError> {
+        let path = self.config().rdb_path();
+        let started = Instant::now();
+        let Some(rdb) = rdb::load(&path)? else {
+            return Ok(());
+        };
+        self.load_rdb_entries(rdb).await;
+        self.log(
+            LogLevel::Notice,
+            format!(
+                "DB loaded from disk: {:.3} seconds",
+                started.elapsed().as_secs_f64()
+            ),
+        );
+        Ok(())
+    }
+
+    /// Inserts the keys of an RDB file or AOF preamble into the store, and loads its function
+    /// libraries.
+    pub async fn load_rdb_entries(&self, rdb: Rdb) {
+        if let Some(version) = rdb.aux("redis-ver") {
+            self.log(
+                LogLevel::Notice,
+                format!(
+                    "Loading RDB produced by version {}",
+                    String::from_utf8_lossy(version)
+                ),
+            );
+        }
+        if let Err(e) = self
+            .functions
+            .restore(&rdb.functions, RestorePolicy::Replace)
+        {
+            self.log(
+                LogLevel::Warning,
+                format!("Failed loading the function libraries: {}", e),
+            );
+        }
+
+        let now = utils::now_millis();
+        let (mut loaded, mut expired, mut skipped) = (0, 0, 0);
+        let (mut binary, mut volatile) = (0, 0);
+        for entry in rdb.entries {
+            if entry.db != 0 {
+                skipped += 1;
+                continue;
+            }
+            if entry.expire_at_millis.is_some_and(|at| at <= now) {
+                expired += 1;
+                continue;
+            }
+            // Keys are kept as strings: a lossy conversion could make two keys collide.
+            let Ok(key) = String::from_utf8(entry.key.to_vec()) else {
+                binary += 1;
+                continue;
+            };
+            let value = match entry.value {
+                RedisType::String(kv) => RedisType::String(KeyValue {
+                    expired_at_millis: entry.expire_at_millis,
+                    ..kv
+                }),
+                // Only strings can expire: loaded without its TTL it would never go away.
+                _ if entry.expire_at_millis.is_some() => {
+                    volatile += 1;
+                    continue;
+                }
+                value => value,
+            };
+            self.store.insert(key, value).await;
+            loaded += 1;
+        }
+
+        if skipped > 0 {
+            self.log(
+                LogLevel::Warning,
+                format!("Skipped {} keys stored in databases other than 0", skipped),
+            );
+        }
+        if binary > 0 {
+            self.log(
+                LogLevel::Warning,
+                format!("Skipped {} keys that are not valid UTF-8", binary),
+            );
+        }
+        if volatile > 0 {
+            self.log(
+                LogLevel::Warning,
+                format!(
+                    "Skipped {} keys with an expire that are not strings, only strings can expire",
+                    volatile
+                ),
+            );
+        }
+        self.log(
+            LogLevel::Notice,
+            format!(
+                "Done loading RDB, keys loaded: {}, keys expired: {}.",
+                loaded, expired
+            ),
+        );
+    }
+
+    /// Rebuilds the dataset from the files listed in the AOF manifest and keeps appending to
+    /// the last one. A single AOF from before the manifest, at `dir`/`appendfilename`, becomes
+    /// the base file. Without any AOF, the RDB file is loaded instead and written as the base
+    /// of a new one.
+    pub async fn load_aof(self: &Arc<Self>) -> Result<(), AofError> {
+        let (dir, name, legacy, load_truncated) = {
+            let config = self.config();
+            (
+                config.aof_dir(),
+                config.appendfilename.clone(),
+                config.dir.join(&config.appendfilename),
+                config.aof_load_truncated,
+            )
+        };
+        let manifest = match Manifest::load(&dir, &name)? {
+            Some(manifest) => manifest,
+            None if legacy.exists() => {
+                self.log(
+                    LogLevel::Notice,
+                    format!(
+                        "Moving the old AOF file {} into {}",
+                        legacy.display(),
+                        dir.display()
+                    ),
+                );
+                std::fs::create_dir_all(&dir)?;
+                std::fs::rename(&legacy, dir.join(&name))?;
+                let manifest = Manifest {
+                    base: Some(AofFile {
+                        name: name.clone(),
+                        seq: 1,
+                    }),
+                    incrs: vec![],
+                };
+                manifest.persist(&dir, &name)?;
+                manifest
+            }
+            None => {
+                self.load_rdb().await?;
+                self.log(
+                    LogLevel::Notice,
+                    format!("Creating AOF files in {}", dir.display()),
+                );
+                return self.rewrite_aof().await;
+            }
+        };
+
+        let started = Instant::now();
+        let files: Vec<AofFile> = manifest.files().cloned().collect();
+        let mut size = 0;
+        for (i, file) in files.iter().enumerate() {
+            let path = dir.join(&file.name);
+            let data = match std::fs::read(&path) {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(AofError::Missing(file.name.clone()))
+                }
+                Err(e) => return Err(e.into()),
+            };
+            let content = aof::parse(&data)?;
+            let (truncated, valid_len) = (content.truncated, content.valid_len);
+            // A crash can only cut the file being appended to.
+            if truncated && (i + 1 < files.len() || !load_truncated) {
+                return Err(AofError::Truncated);
+            }
+            self.load_aof_content(content).await?;
+            if truncated {
+                self.log(
+                    LogLevel::Warning,
+                    format!(
+                        "!!! Warning: short read while loading the AOF file {}!!!",
+                        file.name
+                    ),
+                );
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(&path)?
+                    .set_len(valid_len as u64)?;
+                self.log(
+                    LogLevel::Warning,
+                    "AOF loaded anyway because aof-load-truncated is enabled",
+                );
+            }
+            size += valid_len as u64;
+        }
+
+        *self.aof() = Some(Aof::open(&dir, &name, manifest, size)?);
+        self.log(
+            LogLevel::Notice,
+            format!(
+                "DB loaded from append only file: {:.3} seconds",
+                started.elapsed().as_secs_f64()
+            ),
+        );
+        Ok(())
+    }
+
+    /// Loads one AOF file: the keys of its RDB preamble, then its commands, executed directly:
+    /// nothing is counted, checked against `maxmemory` or propagated.
+    pub async fn load_aof_content(self: &Arc<Self>, content: AofContent) -> Result<(), AofError> {
+        if let Some(rdb) = content.preamble {
+            self.log(LogLevel::Notice, "Reading RDB base file on AOF loading...");
+            self.load_rdb_entries(rdb).await;
+        }
+        let mut client = RedisClient::new();
+        client.authenticated = true;
+        for request in content.commands {
+            for command in RedisCommand::build(vec![request]).map_err(AofError::Command)? {
+                let _ = command.execute(&mut client, self).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a new base file from the current dataset and drops the files it replaces. While
+    /// the AOF is on, commands go to a new incremental file from the point the dataset is
+    /// captured on, so none is lost. Turns the AOF on if `appendonly` is set and it is off; if
+    /// that fails, `appendonly` is reset.
+    async fn rewrite_aof(&self) -> Result<(), AofError> {
+        let (dir, name, use_rdb_preamble) = {
+            let config = self.config();
+            (
+                config.aof_dir(),
+                config.appendfilename.clone(),
+                config.aof_use_rdb_preamble,
+            )
+        };
+        let enabling = self.config().appendonly && self.aof().is_none();
+        let result = self.write_aof_base(&dir, &name, use_rdb_preamble).await;
+        if enabling {
+            match &result {
+                Ok(()) => self.log(LogLevel::Notice, "Append only file enabled"),
+                Err(e) => {
+                    *self.aof() = None;
+                    self.config_mut().appendonly = false;
+                    self.log(
+                        LogLevel::Warning,
+                        format!("Can't enable the append only file: {}", e),
+                    );
+                }
+            }
+        }
+        result
+    }
+
+    async fn write_aof_base(
+        &self,
+        dir: &std::path::Path,
+        name: &str,
+        use_rdb_preamble: bool,
+    ) -> Result<(), AofError> {
+        let (first_incr, manifest, (data, functions, now), offset) = {
+            let _exclusive = self.exec_lock.write().await;
+            let (first_incr, manifest) = {
+                let mut aof = self.aof();
+                match aof.as_mut() {
+                    Some(aof) => (Some(aof.start_incr()?), aof.manifest().clone()),
+                    // `CONFIG SET` may have turned it off again while this waited for the lock.
+                    None if self.config().appendonly => {
+                        let manifest = Manifest::load(dir, name)?.unwrap_or_default();
+                        let new = aof.insert(Aof::create(dir, name, manifest)?);
+                        (Some(new.incr_seq()), new.manifest().clone())
+                    }
+                    None => (None, Manifest::load(dir, name)?.unwrap_or_default()),
+                }
+            };
+            let functions = self.functions.codes();
+            let data = (self.store.snapshot().await, functions, utils::now_millis());
+            (first_incr, manifest, data, self.replication.offset())
+        };
+
+        let dir_created = dir.to_path_buf();
+        let (content, ext) = tokio::task::spawn_blocking(move || {
+            aof::base_content(&data, &functions, now, use_rdb_preamble)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        let base = manifest.next_base(name, ext);
+        let path = dir.join(&base.name);
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&dir_created)?;
+            utils::write_atomically(&path, &content)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+
+        let mut aof = self.aof();
+        match (aof.as_mut(), first_incr) {
+            (Some(aof), Some(first_incr)) => aof.finish_rewrite(base, first_incr)?,
+            // The AOF is off, or was turned off meanwhile: the base alone is the whole AOF.
+            _ => manifest.clone().replace_base(dir, name, base, None)?,
+        }
+        drop(aof);
+        // The base holds the whole stream up to the snapshot.
+        self.aof_fsynced(offset);
+        Ok(())
+    }
+
+    /// Appends a command that changed the dataset to the AOF, if it is on, and sends it to the
+    /// replicas. A replica forwards its master's stream to them instead, see `replicate`.
+    fn propagate(&self, argv: &[Bytes]) {
+        if argv.is_empty() {
+            return;
+        }
+        let fsync = self.config().appendfsync;
+        if let Some(aof) = self.aof().as_mut() {
+            if let Err(e) = aof.append(argv, fsync) {
+                self.log(
+                    LogLevel::Warning,
+                    format!("Error writing to the AOF file: {}", e),
+                );
+            }
+        }
+        if self.config().replicaof.is_none() {
+            self.replication.feed(argv);
+            self.aof_written();
+        }
+    }
+
+    /// Records that the AOF holds the stream up to the current replication offset. It is on
+    /// disk already with `appendfsync always`, and `no` leaves that to the OS: only `everysec`
+    /// waits for `fsync_aof`.
+    fn aof_written(&self) {
+        if self.aof().is_none() {
+            return;
+        }
+        let offset = self.replication.offset();
+        match self.config().appendfsync {
+            AppendFsync::EverySec => {
+                let written = &self.persistence.aof_written_offset;
+                written.fetch_max(offset, Ordering::SeqCst);
+            }
+            _ => self.aof_fsynced(offset),
+        }
+    }
+
+    fn aof_fsynced(&self, offset: u64) {
+        let fsynced = &self.persistence.aof_fsynced_offset;
+        fsynced.fetch_max(offset, Ordering::SeqCst);
+        self.replication.notify_progress();
+    }
+
+    /// With `appendfsync everysec`, flushes the AOF to disk from a blocking thread.
+    fn fsync_aof(self: &Arc<Self>) {
+        if self.config().appendfsync != AppendFsync::EverySec {
+            return;
+        }
+        let Some(file) = self.aof().as_mut().and_then(Aof::fsync_due) else {
+            return;
+        };
+        let offset = self.persistence.aof_written_offset.load(Ordering::SeqCst);
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            let synced =
+                tokio::task::spawn_blocking(move || file.and_then(|file| file.sync_data())).await;
+            if let Ok(Ok(())) = synced {
+                server.aof_fsynced(offset);
+            }
+        });
+    }
+
+    /// Writes the dataset to the RDB file, waiting for it. Fails if another save is running.
+    pub async fn save(&self) -> Result<(), RedisError> {
+        if self.persistence.saving.swap(true, Ordering::SeqCst) {
+            return Err(RedisError::custom("Background save already in progress"));
+        }
+        let result = self.write_rdb().await;
+        self.persistence.saving.store(false, Ordering::SeqCst);
+        match result {
+            Ok(()) => {
+                self.log(LogLevel::Notice, "DB saved on disk");
+                Ok(())
+            }
+            Err(e) => {
+                self.log(LogLevel::Warning, format!("Failed saving the DB: {}", e));
+                Err(RedisError::Custom(format!("Failed saving the DB: {}", e)))
+            }
+        }
+    }
+
+    /// Starts writing the dataset to the RDB file in a separate task and returns right away.
+    pub fn bgsave(self: &Arc<Self>) -> Result<(), RedisError> {
+        if self.persistence.saving.swap(true, Ordering::SeqCst) {
+            return Err(RedisError::custom("Background save already in progress"));
+        }
+        self.persistence
+            .last_bgsave_try
+            .store(unix_time(), Ordering::Relaxed);
+        self.log(LogLevel::Notice, "Background saving started");
+
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            let result = server.write_rdb().await;
+            server
+                .persistence
+                .last_bgsave_ok
+                .store(result.is_ok(), Ordering::Relaxed);
+            match result {
+                Ok(()) => server.log(
+                    LogLevel::Notice,
+                    "Background saving terminated with success",
+                ),
+                Err(e) => server.log(LogLevel::Warning, format!("Background saving error: {}", e)),
+            }
+            server.persistence.saving.store(false, Ordering::SeqCst);
+        });
+        Ok(())
+    }
+
+    /// Copies the dataset, then serializes and writes it on a blocking thread: clients only
+    /// wait for the copy.
+    async fn write_rdb(&self) -> Result<(), String> {
+        let dirty = self.persistence.dirty.load(Ordering::SeqCst);
+        let functions = self.functions.codes();
+        let (data, now) = (self.store.snapshot().await, utils::now_millis());
+        let path = self.config().rdb_path();
+        tokio::task::spawn_blocking(move || {
+            utils::write_atomically(&path, &rdb::encode(&data, &functions, now))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+        // Changes made while the file was written are still pending.
+        self.persistence.dirty.fetch_sub(dirty, Ordering::SeqCst);
+        self.persistence
+            .lastsave
+            .store(unix_time(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Starts a background save when one of the `save <seconds> <changes>` rules is met.
+    fn check_save_rules(self: &Arc<Self>) {
+        let persistence = &self.persistence;
+        if persistence.saving.load(Ordering::SeqCst) {
+            return;
+        }
+        let now = unix_time();
+        let dirty = persistence.dirty.load(Ordering::SeqCst);
+        let since_save = now.saturating_sub(persistence.lastsave.load(Ordering::Relaxed));
+        let can_retry = persistence.last_bgsave_ok.load(Ordering::Relaxed)
+            || now.saturating_sub(persistence.last_bgsave_try.load(Ordering::Relaxed))
+                > BGSAVE_RETRY_DELAY;
+        let rules = self.config().save.clone();
+        let rule = rules
+            .into_iter()
+            .find(|(seconds, changes)| dirty >= *changes && since_save > *seconds);
+        if let Some((seconds, changes)) = rule.filter(|_| can_retry) {
+            self.log(
+                LogLevel::Notice,
+                format!("{} changes in {} seconds. Saving...", changes, seconds),
+            );
+            let _ = self.bgsave();
+        }
+    }
+
+    /// Starts rewriting the AOF in a separate task and returns right away.
+    pub fn bgrewriteaof(self: &Arc<Self>) -> Result<(), RedisError> {
+        if self.start_aof_rewrite() {
+            Ok(())
+        } else {
+            Err(RedisError::custom(
+                "Background append only file rewriting already in progress",
+            ))
+        }
+    }
+
+    /// Returns false if a rewrite is already running.
+    fn start_aof_rewrite(self: &Arc<Self>) -> bool {
+        let persistence = &self.persistence;
+        if persistence.aof_rewriting.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        persistence
+            .aof_rewrite_scheduled
+            .store(false, Ordering::SeqCst);
+        self.log(
+            LogLevel::Notice,
+            "Background append only file rewriting started",
+        );
+
+        let server = Arc::clone(self);
+        tokio::spawn(async move {
+            match server.rewrite_aof().await {
+                Ok(()) => server.log(
+                    LogLevel::Notice,
+                    "Background AOF rewrite finished successfully",
+                ),
+                Err(e) => server.log(
+                    LogLevel::Warning,
+                    format!("Background AOF rewrite failed: {}", e),
+                ),
+            }
+            server
+                .persistence
+                .aof_rewriting
+                .store(false, Ordering::SeqCst);
+        });
+        true
+    }
+
+    /// Starts a scheduled rewrite, or one due to `auto-aof-rewrite-percentage`: the AOF grew
+    /// by that much since the last rewrite and is over `auto-aof-rewrite-min-size`.
+    fn check_aof_rewrite(self: &Arc<Self>) {
+        let persistence = &self.persistence;
+        if persistence.aof_rewriting.load(Ordering::SeqCst) {
+            return;
+        }
+        if persistence.aof_rewrite_scheduled.load(Ordering::SeqCst) {
+            self.start_aof_rewrite();
+            return;
+        }
+        let (percentage, min_size) = {
+            let config = self.config();
+            (
+                config.auto_aof_rewrite_percentage,
+                config.auto_aof_rewrite_min_size,
+            )
+        };
+        let growth = match self.aof().as_ref() {
+            Some(aof) if percentage > 0 && aof.size > min_size => {
+                let base = aof.base_size.max(1);
+                aof.size.saturating_sub(base) * 100 / base
+            }
+            _ => return,
+        };
+        if growth >= percentage {
+            self.log(
+                LogLevel::Notice,
+                format!("Starting automatic rewriting of AOF on {}% growth", growth),
+            );
+            self.start_aof_rewrite();
+        }
+    }
+
+    /// Periodic housekeeping, like Redis' `serverCron`.
+    async fn cron(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        let mut last_ping = Instant::now();
+        loop {
+            interval.tick().await;
+            self.check_save_rules();
+            self.check_aof_rewrite();
+            self.fsync_aof();
+            if last_ping.elapsed() >= REPL_PING_PERIOD {
+                last_ping = Instant::now();
+                // A replica forwards its master's pings instead.
+                if self.config().replicaof.is_none() {
+                    self.replication.feed(&[Bytes::from_static(b"PING")]);
+                }
+            }
+        }
+    }
+
+    pub async fn run(self: Arc<Self>) {
+        match self.bind_listeners().await {
+            Ok(accept_loops) => *self.listeners.lock().await = Some(accept_loops),
+            Err(e) => {
+                self.log(LogLevel::Warning, e);
+                return;
+            }
+        }
+        tokio::spawn(Arc::clone(&self).cron());
+        if self.config().replicaof.is_some() {
+            self.update_replication();
+        }
+        std::future::pending::<()>().await;
+    }
+
+    /// Binds every configured address and spawns its accept loop. Optional addresses (`-`
+    /// prefix) that fail are skipped, any other failure closes what was already bound.
+    async fn bind_listeners(self: &Arc<Self>) -> Result<Vec<JoinHandle<()>>, String> {
+        let addresses = self.config().listen_addresses();
+        let mut accept_loops = Vec::new();
+        for (addr, optional) in addresses {
+            match TcpListener::bind(&addr).await {
+                Ok(listener) => {
+                    self.log(LogLevel::Notice, format!("Listening on {}", addr));
+                    accept_loops.push(self.spawn_accept_loop(listener));
+                }
+                Err(e) if optional => self.log(
+                    LogLevel::Notice,
+                    format!("Skipping optional address {}: {}", addr, e),
+                ),
+                Err(e) => {
+                    accept_loops.iter().for_each(JoinHandle::abort);
+                    return Err(format!("Failed to bind to address {}: {}", addr, e));
+                }
+            }
+        }
+        Ok(accept_loops)
+    }
+
+    /// Not async on purpose: `accept_loop` ends up calling `bind_listeners` again through
+    /// `CONFIG SET`, and the compiler can't prove the recursive futures `Send` otherwise.
+    fn spawn_accept_loop(self: &Arc<Self>, listener: TcpListener) -> JoinHandle<()> {
+        tokio::spawn(Arc::clone(self).accept_loop(listener))
+    }
+
+    /// Runs the side effect of a parameter changed with `CONFIG SET`.
+    pub async fn apply_config(self: &Arc<Self>, apply: Apply) -> Result<(), String> {
+        match apply {
+            Apply::Listeners => {
+                let mut listeners = self.listeners.lock().await;
+                let Some(old) = listeners.take() else {
+                    // Not serving yet, `run` will bind with the new values.
+                    return Ok(());
+                };
+                // Close the old sockets first, the new addresses may reuse the same port.
+                old.iter().for_each(JoinHandle::abort);
+                for accept_loop in old {
+                    let _ = accept_loop.await;
+                }
+                match self.bind_listeners().await {
+                    Ok(accept_loops) => {
+                        *listeners = Some(accept_loops);
+                        Ok(())
+                    }
+                    Err(e) => {
+                        // Still serving, so that rolling back rebinds the old addresses.
+                        *listeners = Some(vec![]);
+                        Err(e)
+                    }
+                }
+            }
+            Apply::Eviction => {
+                if !self.enforce_maxmemory().await {
+                    self.log(
+                        LogLevel::Warning,
+                        "WARNING: the new maxmemory value set via CONFIG SET is smaller than the current memory usage",
+                    );
+                }
+                Ok(())
+            }
+            Apply::Aof => {
+                if !self.config().appendonly {
+                    if let Some(mut aof) = self.aof().take() {
+                        aof.fsync().map_err(|e| e.to_string())?;
+                        self.log(LogLevel::Notice, "Append only file disabled");
+                    }
+                    return Ok(());
+                }
+                // The rewrite turns it on. It needs every running command to finish, this one
+                // included, so it can't be waited for here.
+                if self.aof().is_none() && !self.start_aof_rewrite() {
+                    self.persistence
+                        .aof_rewrite_scheduled
+                        .store(true, Ordering::SeqCst);
+                }
+                Ok(())
+            }
+            Apply::Replication => {
+                self.update_replication();
+                Ok(())
+            }
+            Apply::Backlog => {
+                let size = self.config().repl_backlog_size;
+                self.replication.resize_backlog(size as usize);
+                Ok(())
+            }
+        }
+    }
+
+    /// Replicates the master in `replicaof`, or turns this server into a master when it is
+    /// unset.
+    fn update_replication(self: &Arc<Self>) {
+        let master = self.config().replicaof.clone();
+        let link = master.map(|(host, port)| {
+            let link = Arc::new(MasterLink::new(host, port));
+            let task = tokio::spawn(Arc::clone(self).replicate(Arc::clone(&link)));
+            (link, task)
+        });
+        let replica = link.is_some();
+        let was_replica = self.replication.set_master(link);
+        if replica {
+            // They follow a history the new master is about to replace.
+            self.replication.detach_all();
+        } else if was_replica {
+            // Replicas of the old master can still resume from this one up to here.
+            self.replication.shift_replid(replication::new_replid());
+        }
+    }
+
+    /// The replica side: connects to the master, loads its snapshot and applies the commands
+    /// it streams. Reconnects a second after the link drops, until `update_replication`
+    /// replaces it.
+    async fn replicate(self: Arc<Self>, link: Arc<MasterLink>) {
+        loop {
+            link.set_state(LinkState::Connect);
+            self.log(
+                LogLevel::Notice,
+                format!("Connecting to MASTER {}:{}", link.host, link.port),
+            );
+            if let Err(e) = self.sync_with_master(&link).await {
+                self.log(LogLevel::Warning, e);
+            }
+            link.set_state(LinkState::Connect);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn sync_with_master(self: &Arc<Self>, link: &MasterLink) -> Result<(), ReplicationError> {
+        let stream = TcpStream::connect((link.host.as_str(), link.port)).await?;
+        let mut connection = Connection::new(stream);
+        link.set_state(LinkState::Handshake);
+        self.log(LogLevel::Notice, "MASTER <-> REPLICA sync started");
+
+        match Self::master_request(&mut connection, &["PING"]).await? {
+            // A master with `requirepass` is still reachable, as for Redis.
+            RespDataType::Error(e) if !e.starts_with("NOAUTH") => {
+                return Err(ReplicationError::Reply("PING", e));
+            }
+            _ => self.log(
+                LogLevel::Notice,
+                "Master replied to PING, replication can continue...",
+            ),
+        }
+        let port = self.config().port.to_string();
+        for args in [
+            ["REPLCONF", "listening-port", port.as_str()],
+            ["REPLCONF", "capa", "psync2"],
+        ] {
+            if let RespDataType::Error(e) = Self::master_request(&mut connection, &args).await? {
+                self.log(
+                    LogLevel::Notice,
+                    format!(
+                        "(Non critical) Master does not understand {} {}: {}",
+                        args[0], args[1], e
+                    ),
+                );
+            }
+        }
+
+        // With a history of its own, from a previous master or from before it was turned into a
+        // replica, it may only need what it missed.
+        let (cached_replid, cached_offset) = match self.replication.has_backlog() {
+            true => (
+                self.replication.replid(),
+                (self.replication.offset() + 1).to_string(),
+            ),
+            false => ("?".to_string(), "-1".to_string()),
+        };
+        let psync = ["PSYNC", cached_replid.as_str(), cached_offset.as_str()];
+        let reply = match Self::master_request(&mut connection, &psync).await? {
+            RespDataType::SimpleString(reply) | RespDataType::Error(reply) => reply,
+            reply => format!("{:?}", reply),
+        };
+        let (replid, offset) = match reply.split(' ').collect::<Vec<_>>()[..] {
+            ["FULLRESYNC", replid, offset] => match offset.parse::<u64>() {
+                Ok(offset) => (replid.to_string(), offset),
+                Err(_) => return Err(ReplicationError::Psync(reply)),
+            },
+            ["CONTINUE", ..] => {
+                self.continue_with_master(reply.split(' ').nth(1));
+                link.touch();
+                link.set_state(LinkState::Connected);
+                return self.apply_master_stream(&mut connection, link).await;
+            }
+            _ => return Err(ReplicationError::Psync(reply)),
+        };
+        self.log(
+            LogLevel::Notice,
+            format!("Full resync from master: {}:{}", replid, offset),
+        );
+
+        link.set_state(LinkState::Transfer);
+        let payload = connection.read_bulk_payload().await?;
+        link.touch();
+        self.log(
+            LogLevel::Notice,
+            format!(
+                "MASTER <-> REPLICA sync: receiving {} bytes from master",
+                payload.len()
+            ),
+        );
+        let snapshot = rdb::parse(&payload)?;
+        {
+            let _exclusive = self.exec_lock.write().await;
+            self.log(
+                LogLevel::Notice,
+                "MASTER <-> REPLICA sync: Flushing old data",
+            );
+            self.store.clear().await;
+            self.functions.flush();
+            self.log(
+                LogLevel::Notice,
+                "MASTER <-> REPLICA sync: Loading DB in memory",
+            );
+            self.load_rdb_entries(snapshot).await;
+            let backlog_size = self.config().repl_backlog_size as usize;
+            self.replication.set_replid(replid, offset, backlog_size);
+            self.replication.detach_all();
+        }
+        self.log(
+            LogLevel::Notice,
+            "MASTER <-> REPLICA sync: Finished with success",
+        );
+        // The AOF holds the old dataset, rewrite it from the new one.
+        if self.aof().is_some() && !self.start_aof_rewrite() {
+            self.persistence
+                .aof_rewrite_scheduled
+                .store(true, Ordering::SeqCst);
+        }
+
+        link.set_state(LinkState::Connected);
+        self.apply_master_stream(&mut connection, link).await
+    }
+
+    /// The master accepted a partial resynchronization: the stream resumes right after the
+    /// offset this replica has. A new replication ID means the master was promoted.
+    fn continue_with_master(&self, replid: Option<&str>) {
+        self.log(
+            LogLevel::Notice,
+            "MASTER <-> REPLICA sync: Master accepted a Partial Resynchronization.",
+        );
+        let Some(replid) = replid.filter(|replid| *replid != self.replication.replid()) else {
+            return;
+        };
+        self.log(
+            LogLevel::Notice,
+            format!("Master replication ID changed to {}", replid),
+        );
+        self.replication.shift_replid(replid.to_string());
+        // They have to learn the new ID, they can resume with the old one.
+        self.replication.detach_all();
+    }
+
+    /// Sends a handshake request to the master and reads its reply.
+    async fn master_request(
+        connection: &mut Connection<TcpStream>,
+        args: &[&str],
+    ) -> Result<RespDataType, ReplicationError> {
+        connection
+            .write_bytes(&RespDataType::new_array(args.to_vec()).encode(ProtocolVersion::Resp2))
+            .await?;
+        connection
+            .read_reply()
+            .await?
+            .ok_or(ReplicationError::Closed)
+    }
+
+    /// Executes the commands the master streams without replying to them, except
+    /// `REPLCONF GETACK`. The offset is acknowledged every second too.
+    async fn apply_master_stream(
+        self: &Arc<Self>,
+        connection: &mut Connection<TcpStream>,
+        link: &MasterLink,
+    ) -> Result<(), ReplicationError> {
+        let mut client = RedisClient::new();
+        client.authenticated = true;
+        client.from_master = true;
+        let mut ack_interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            let frame = tokio::select! {
+                frame = connection.read_request_raw() => frame?,
+                _ = ack_interval.tick() => {
+                    let last_io = link.last_io.load(Ordering::Relaxed) as u128;
+                    if utils::now_millis().saturating_sub(last_io) > REPL_TIMEOUT.as_millis() {
+                        return Err(ReplicationError::Timeout);
+                    }
+                    self.send_ack(connection).await?;
+                    continue;
+                }
+            };
+            let Some((request, raw)) = frame else {
+                return Err(ReplicationError::Closed);
+            };
+            link.touch();
+            client.argv = match &request {
+                RespDataType::Array(args) => args
+                    .iter()
+                    .filter_map(|arg| arg.clone().into_bytes())
+                    .collect(),
+                _ => Vec::new(),
+            };
+            match RedisCommand::build(vec![request]) {
+                Ok(commands) => {
+                    for command in commands {
+                        match &command {
+                            RedisCommand::REPLCONF(replconf) if replconf.getack() => {
+                                self.send_ack(connection).await?;
+                            }
+                            _ => {
+                                Self::process_command(command, &mut client, self).await;
+                            }
+                        }
+                    }
+                }
+                Err(e) => self.log(
+                    LogLevel::Verbose,
+                    format!("Skipping command from MASTER: {}", e),
+                ),
+            }
+            // Counted once applied: a `GETACK` acknowledges what came before it.
+            self.replication.proxy(raw);
+            self.aof_written();
+        }
+    }
+
+    async fn send_ack(
+        &self,
+        connection: &mut Connection<TcpStream>,
+    ) -> Result<(), ReplicationError> {
+        let offset = self.replication.offset().to_string();
+        let mut ack = vec!["REPLCONF", "ACK", &offset];
+        // With the AOF on, also what is on disk, for `WAITAOF` on the master.
+        let fsynced = self.persistence.aof_fsynced_offset.load(Ordering::SeqCst);
+        let fsynced = fsynced.to_string();
+        if self.aof().is_some() {
+            ack.extend(["FACK", &fsynced]);
+        }
+        let ack = RespDataType::new_array(ack);
+        connection
+            .write_bytes(&ack.encode(ProtocolVersion::Resp2))
+            .await?;
+        Ok(())
+    }
+
+    /// Evicts keys when the dataset is over `maxmemory`. Returns false if it still doesn't fit.
+    async fn enforce_maxmemory(&self) -> bool {
+        let (maxmemory, policy) = {
+            let config = self.config();
+            (config.maxmemory, config.maxmemory_policy.clone())
+        };
+        if maxmemory == 0 {
+            return true;
+        }
+        match self.store.evict(maxmemory as usize, &policy).await {
+            Some(evicted) => {
+                self.stats
+                    .evicted_keys
+                    .fetch_add(evicted as u64, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    async fn accept_loop(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let stream = listener.accept().await;
+            match stream {
+                Ok((stream, addr)) => {
+                    self.log(LogLevel::Verbose, "accepted new connection");
+                    self.stats
+                        .total_connections_received
+                        .fetch_add(1, Ordering::Relaxed);
+                    let server = Arc::clone(&self);
+                    let connection: Connection<TcpStream> = Connection::new(stream);
+                    let mut client = RedisClient::new();
+                    client.addr = Some(addr);
+                    tokio::spawn(async move {
+                        Self::client_process(connection, client, server).await;
+                    });
+                }
+                Err(e) => {
+                    self.log(
+                        LogLevel::Warning,
+                        format!("Accepting client connection: {}", e),
+                    );
+                }
+            }
+        }
+    }
+
+    async fn client_process<T: AsyncRead + AsyncWrite + Unpin + Send>(
+        mut connection: Connection<T>,
+        mut client: RedisClient,
+        server: Arc<RedisServer>,
+    ) {
+        let Some(mut notifications) = client.notifications.take() else {
+            return;
+        };
+        loop {
+            let request = tokio::select! {
+                request = connection.read_request() => request,
+                Some(push) = notifications.recv() => {
+                    connection.write_response(&Some(push), client.protocol).await;
+                    continue;
+                }
+                _ = client.notifier.overflowed() => {
+                    server.log(
+                        LogLevel::Warning,
+                        format!(
+                            "Client {} scheduled to be closed for reaching the limit of {} \
+                             pending messages",
+                            client.id,
+                            client::NOTIFICATIONS_LIMIT
+                        ),
+                    );
+                    server.pubsub.unsubscribe_all(&mut client);
+                    return;
+                }
+            };
+            if let Ok(Some(request)) = &request {
+                // Bulk strings share their bytes with the request, so no data is copied.
+                client.argv = match request {
+                    RespDataType::Array(args) => args
+                        .iter()
+                        .cloned()
+                        .filter_map(RespDataType::into_bytes)
+                        .collect(),
+                    _ => Vec::new(),
+                };
+            }
+            match request {
+                Ok(None) => break,
+                Ok(Some(request)) => match RedisCommand::build(vec![request]) {
+                    Ok(received_commands) => {
+                        for command in received_commands {
+                            let quit = matches!(command, RedisCommand::QUIT(_));
+                            let response =
+                                Self::process_command(command, &mut client, &server).await;
+                            if client.psync.is_some() {
+                                server.pubsub.unsubscribe_all(&mut client);
+                                return Self::sync_replica(connection, client, server).await;
+                            }
+                            // What the command pushed, like the replies of `SUBSCRIBE`, and
+                            // the messages before it go first.
+                            while let Ok(push) = notifications.try_recv() {
+                                connection
+                                    .write_response(&Some(push), client.protocol)
+                                    .await;
+                            }
+                            if let Some(response) = response {
+                                server.log(
+                                    LogLevel::Debug,
+                                    format!(
+                                        "Response Generated for client:{} {:?}",
+                                        client.id, response
+                                    ),
+                                );
+                                connection
+                                    .write_response(&Some(response), client.protocol)
+                                    .await;
+                            }
+                            if quit {
+                                server.pubsub.unsubscribe_all(&mut client);
+                                return;
+                            }
+                        }
+                    }
+                    Err(error) => {
+                        if let Some(multi) = client.multi.as_mut() {
+                            multi.aborted = true;
+                        }
+                        server.log(
+                            LogLevel::Debug,
+                            format!("Response Generated for client:{} {}", client.id, error),
+                        );
+                        connection
+                            .write_response(&Some(error.into()), client.protocol)
+                            .await;
+                    }
+                },
+                Err(ConnectionError::Protocol(e)) => {
+                    // Like Redis, reply with the reason and drop the client: the rest of the
+                    // buffer can't be trusted to start at a frame boundary anymore.
+                    let error = RedisError::from(e);
+                    server.log(
+                        LogLevel::Verbose,
+                        format!("Closing client:{} {}", client.id, error),
+                    );
+                    connection
+                        .write_response(&Some(error.into()), client.protocol)
+                        .await;
+                    break;
+                }
+                Err(ConnectionError::Io(e)) => {
+                    server.log(LogLevel::Verbose, format!("Reading from client: {}", e));
+                    break;
+                }
+            }
+        }
+        server.pubsub.unsubscribe_all(&mut client);
+    }
+
+    /// Turns the connection into a replication link after `PSYNC`. The replica resumes from the
+    /// backlog when it still holds the offset it asks for, or gets the dataset as an RDB
+    /// payload. Then it gets every command propagated since, and reports the offset it
+    /// processed with `REPLCONF ACK`.
+    async fn sync_replica<T: AsyncRead + AsyncWrite + Unpin + Send>(
+        mut connection: Connection<T>,
+        client: RedisClient,
+        server: Arc<RedisServer>,
+    ) {
+        let ip = client
+            .addr
+            .map_or_else(|| "?".to_string(), |addr| addr.ip().to_string());
+        let port = client.replica_listening_port.unwrap_or(0);
+        server.log(
+            LogLevel::Notice,
+            format!("Replica {}:{} asks for synchronization", ip, port),
+        );
+
+        let (replid, offset) = client.psync.clone().unwrap_or(("?".to_string(), -1));
+        let resumed = match replid.as_str() {
+            "?" => Err(String::new()),
+            _ => server.replication.attach_continue(
+                client.id,
+                ip.clone(),
+                port,
+                &replid,
+                offset.max(0) as u64,
+            ),
+        };
+        let (replica, mut stream, mut sent) = match resumed {
+            Ok((replica, stream)) => {
+                server.log(
+                    LogLevel::Notice,
+                    format!(
+                        "Partial resynchronization request from {} accepted, resuming from offset {}.",
+                        replica.addr(),
+                        offset
+                    ),
+                );
+                let reply = format!("+CONTINUE {}\r\n", server.replication.replid());
+                let sent = connection.write_bytes(reply.as_bytes()).await.is_ok();
+                (replica, stream, sent)
+            }
+            Err(reason) => {
+                if !reason.is_empty() {
+                    server.log(
+                        LogLevel::Notice,
+                        format!("Partial resynchronization not accepted: {}", reason),
+                    );
+                }
+                Self::full_resync(&mut connection, &client, ip, port, &server).await
+            }
+        };
+
+        while sent {
+            tokio::select! {
+                command = stream.recv() => match command {
+                    Some(command) => sent = connection.write_bytes(&command).await.is_ok(),
+                    None => break,
+                },
+                request = connection.read_request() => match request {
+                    Ok(Some(request)) => {
+                        let commands = RedisCommand::build(vec![request]).unwrap_or_default();
+                        for command in commands {
+                            if let RedisCommand::REPLCONF(replconf) = command {
+                                if let Some(offset) = replconf.ack() {
+                                    replica.ack(offset, replconf.fack());
+                                    server.replication.notify_progress();
+                                }
+                            }
+                        }
+                    }
+                    Ok(None) | Err(_) => break,
+                },
+            }
+        }
+        server.replication.detach(client.id);
+        server.log(
+            LogLevel::Notice,
+            format!("Connection with replica {} lost.", replica.addr()),
+        );
+    }
+
+    /// Sends the dataset to a replica, attached while no command runs so that its stream starts
+    /// right after the snapshot. Returns whether the payload was sent.
+    async fn full_resync<T: AsyncRead + AsyncWrite + Unpin + Send>(
+        connection: &mut Connection<T>,
+        client: &RedisClient,
+        ip: String,
+        port: u16,
+        server: &Arc<RedisServer>,
+    ) -> (Arc<Replica>, UnboundedReceiver<Bytes>, bool) {
+        let backlog_size = server.config().repl_backlog_size as usize;
+        let (replica, stream, replid, offset, (data, functions, now)) = {
+            let _exclusive = server.exec_lock.write().await;
+            let functions = server.functions.codes();
+            let data = (
+                server.store.snapshot().await,
+                functions,
+                utils::now_millis(),
+            );
+            let (replica, stream) = server.replication.attach(client.id, ip, port, backlog_size);
+            let replid = server.replication.replid();
+            (replica, stream, replid, server.replication.offset(), data)
+        };
+        let payload = tokio::task::spawn_blocking(move || rdb::encode(&data, &functions, now))
+            .await
+            .unwrap_or_default();
+        server.log(
+            LogLevel::Notice,
+            format!("Full resync requested by replica {}", replica.addr()),
+        );
+        let header = format!(
+            "+FULLRESYNC {} {}\r\n${}\r\n",
+            replid,
+            offset,
+            payload.len()
+        );
+        // The payload isn't a bulk string: no CRLF after it.
+        let sent = connection.write_bytes(header.as_bytes()).await.is_ok()
+            && connection.write_bytes(&payload).await.is_ok();
+        if sent {
+            replica.set_online();
+            server.log(
+                LogLevel::Notice,
+                format!("Synchronization with replica {} succeeded", replica.addr()),
+            );
+        }
+        (replica, stream, sent)
+    }
+
+    /// Runs a command, or queues it while the client is in a transaction. `EXEC`, `DISCARD`,
+    /// `WATCH` and `MULTI` itself always run right away.
+    async fn process_command(
+        command: RedisCommand,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Option<RespDataType> {
+        let Some(multi) = client.multi.as_ref() else {
+            return Self::handle_command(command, client, server).await;
+        };
+        match command {
+            RedisCommand::EXEC(_) if multi.aborted => {
+                client.multi = None;
+                client.unwatch(&server.store);
+                Some(RedisError::ExecAbort.into())
+            }
+            RedisCommand::EXEC(_) => Some(Self::exec_transaction(client, server).await),
+            RedisCommand::DISCARD(_) | RedisCommand::MULTI(_) | RedisCommand::WATCH(_) => {
+                Self::handle_command(command, client, server).await
+            }
+            _ => {
+                let checked = match command.has_flag(CommandFlag::NoMulti) {
+                    true => Err(RedisError::custom(
+                        "Command not allowed inside a transaction",
+                    )),
+                    false => Self::check_command(&command, client, server).await,
+                };
+                let multi = client.multi.as_mut()?;
+                match checked {
+                    Ok(()) => {
+                        multi.commands.push((command, client.argv.clone()));
+                        Some(RespDataType::simple_string("QUEUED"))
+                    }
+                    Err(error) => {
+                        multi.aborted = true;
+                        Some(error.into())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Runs the queued commands with no command of another client in between, and sends them
+    /// to the AOF and the replicas wrapped in `MULTI` and `EXEC` so that they are applied
+    /// the same way. Blocking commands don't wait inside a transaction, and the clients
+    /// blocked on the keys it changes are only woken up once it is done. Nothing runs if a
+    /// watched key changed or expired since `WATCH`.
+    async fn exec_transaction(client: &mut RedisClient, server: &Arc<RedisServer>) -> RespDataType {
+        let commands = match client.multi.as_mut() {
+            Some(multi) => std::mem::take(&mut multi.commands),
+            None => Vec::new(),
+        };
+        let _exclusive = server.exec_lock.write().await;
+        let mut touched = client.watch_touched.load(Ordering::SeqCst);
+        for (key, since) in &client.watched_keys {
+            touched = touched || server.store.expired_since(key, *since).await;
+        }
+        client.unwatch(&server.store);
+        if touched {
+            client.multi = None;
+            return RespDataType::NullArray;
+        }
+        let mut replies = Vec::with_capacity(commands.len());
+        let mut wrapped = false;
+        server.store.defer_wakeups();
+        for (command, argv) in commands {
+            server
+                .stats
+                .total_commands_processed
+                .fetch_add(1, Ordering::Relaxed);
+            // What a script writes is propagated as the commands it runs.
+            let scripting = command.spec().has_category("scripting");
+            if (command.has_flag(CommandFlag::Write) || scripting) && !wrapped {
+                server.propagate(&[Bytes::from_static(b"MULTI")]);
+                wrapped = true;
+            }
+            client.argv = argv;
+            replies.push(Self::run_command(&command, client, server).await);
+        }
+        if wrapped {
+            server.propagate(&[Bytes::from_static(b"EXEC")]);
+            client.woff = server.replication.offset();
+        }
+        server.store.resume_wakeups().await;
+        client.multi = None;
+        RespDataType::Array(replies)
+    }
+
+    async fn handle_command(
+        command: RedisCommand,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Option<RespDataType> {
+        if let Err(error) = Self::check_command(&command, client, server).await {
+            return Some(error.into());
+        }
+        server
+            .stats
+            .total_commands_processed
+            .fetch_add(1, Ordering::Relaxed);
+
+        // Blocking commands take it themselves for each attempt, not to hold it while they
+        // wait, and scripts take it exclusively.
+        let skip = command.has_flag(CommandFlag::Blocking)
+            || (command.spec().has_category("scripting") && !command.has_flag(CommandFlag::Write));
+        let _exec = match skip {
+            true => None,
+            false => Some(server.exec_shared().await),
+        };
+        let response = Self::run_command(&command, client, server).await;
+        // Subscriptions already sent their replies through the notifier.
+        match response {
+            RespDataType::Error(_) => Some(response),
+            _ if command.is_subscription() => None,
+            _ => Some(response),
+        }
+    }
+
+    /// Whether the client may run the command now: no script running for too long,
+    /// authenticated, not writing to a read-only replica, within `maxmemory`, and not in
+    /// Pub/Sub mode.
+    async fn check_command(
+        command: &RedisCommand,
+        client: &RedisClient,
+        server: &RedisServer,
+    ) -> Result<(), RedisError> {
+        let threshold = Duration::from_millis(server.config().busy_reply_threshold);
+        if !client.in_script
+            && !command.has_flag(CommandFlag::AllowBusy)
+            && server.scripting.busy(threshold)
+        {
+            return Err(RedisError::Busy);
+        }
+        if !client.authenticated
+            && server.config().requirepass.is_some()
+            && !command.has_flag(CommandFlag::NoAuth)
+        {
+            return Err(RedisError::NoAuth);
+        }
+        if command.has_flag(CommandFlag::Write) && !client.from_master {
+            let config = server.config();
+            if config.replicaof.is_some() && config.replica_read_only {
+                return Err(RedisError::ReadOnly);
+            }
+        }
+        // A replica applies what its master did, whatever its own memory.
+        if command.has_flag(CommandFlag::DenyOom)
+            && !client.from_master
+            && !server.enforce_maxmemory().await
+        {
+            return Err(RedisError::OutOfMemory);
+        }
+        if client.in_pubsub_mode()
+            && !command.is_subscription()
+            && !matches!(
+                command,
+                RedisCommand::PING(_) | RedisCommand::QUIT(_) | RedisCommand::RESET(_)
+            )
+        {
+            return Err(RedisError::custom(format!(
+                "Can't execute '{}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET \
+                 are allowed in this context",
+                command.spec().name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Executes a command and propagates it if it changed the dataset.
+    async fn run_command(
+        command: &RedisCommand,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> RespDataType {
+        match command.execute(client, server).await {
+            Ok(response) => {
+                if command.has_flag(CommandFlag::Write) {
+                    server.persistence.dirty.fetch_add(1, Ordering::SeqCst);
+                    server.propagate_write(client);
+                }
+                response
+            }
+            Err(error) => error.into(),
+        }
+    }
+
+    /// Propagates the arguments of a write of the client, unless the command cleared them as
+    /// there is nothing to propagate, and records the offset `WAIT` waits for.
+    pub(crate) fn propagate_write(&self, client: &mut RedisClient) {
+        if client.argv.is_empty() {
+            return;
+        }
+        self.propagate(&client.argv);
+        client.woff = self.replication.offset();
+    }
+
+    /// Held while a command runs, so that the dataset isn't captured in the middle of it.
+    pub(crate) async fn exec_shared(&self) -> AsyncRwLockReadGuard<'_, ()> {
+        self.exec_lock.read().await
+    }
+
+    /// Runs a script with no command of another client in between, not even of a blocked client
+    /// woken up by its writes. Its writes are propagated as the commands it ran, wrapped in
+    /// `MULTI` and `EXEC` so that they are applied the same way.
+    pub async fn run_script(
+        self: &Arc<Self>,
+        client: &mut RedisClient,
+        program: Program,
+        keys: Vec<Bytes>,
+        args: Vec<Bytes>,
+        read_only: bool,
+    ) -> Result<RespDataType, RedisError> {
+        // A transaction holds it already.
+        let _exclusive = match client.multi {
+            Some(_) => None,
+            None => Some(self.exec_lock.write().await),
+        };
+        let mut script_client = RedisClient::new();
+        script_client.authenticated = client.authenticated;
+        script_client.from_master = client.from_master;
+        script_client.in_script = true;
+        let wrap = client.multi.is_none();
+        if wrap {
+            self.store.defer_wakeups();
+        }
+        let script = self
+            .scripting
+            .start(&program, client.argv.clone(), read_only, wrap);
+        let (server, handle) = (Arc::clone(self), tokio::runtime::Handle::current());
+        let running = Arc::clone(&script);
+        let result = tokio::task::spawn_blocking(move || {
+            scripting::run(server, handle, running, script_client, &program, keys, args)
+        })
+        .await
+        .unwrap_or_else(|e| Err(RedisError::custom(e.to_string())));
+        self.scripting.finish();
+        if script.wrap && script.wrote.load(Ordering::SeqCst) {
+            self.propagate(&[Bytes::from_static(b"EXEC")]);
+        }
+        if wrap {
+            self.store.resume_wakeups().await;
+        }
+        client.woff = self.replication.offset();
+        result
+    }
+
+    /// Runs a command for `redis.call`, on the script's own client.
+    pub(crate) async fn call_from_script(
+        server: &Arc<RedisServer>,
+        client: &mut RedisClient,
+        script: &RunningScript,
+        argv: Vec<Bytes>,
+    ) -> RespDataType {
+        let request = RespDataType::Array(argv.iter().map(RespDataType::bulk_string).collect());
+        let command = match RedisCommand::build(vec![request]).map(|mut commands| commands.pop()) {
+            Ok(Some(command)) => command,
+            Ok(None) => return RedisError::WrongArity("redis.call").into(),
+            Err(RedisError::UnknownCommand(..)) => {
+                return RedisError::custom("Unknown Redis command called from script").into()
+            }
+            Err(error) => return error.into(),
+        };
+        if command.has_flag(CommandFlag::NoScript) {
+            return RedisError::custom("This Redis command is not allowed from script").into();
+        }
+        let write = command.has_flag(CommandFlag::Write);
+        if write && script.read_only {
+            return RedisError::custom("Write commands are not allowed from read-only scripts.")
+                .into();
+        }
+        if let Err(error) = Self::check_command(&command, client, server).await {
+            return error.into();
+        }
+        server
+            .stats
+            .total_commands_processed
+            .fetch_add(1, Ordering::Relaxed);
+        if write && !script.wrote.swap(true, Ordering::SeqCst) && script.wrap {
+            server.propagate(&[Bytes::from_static(b"MULTI")]);
+        }
+        client.argv = argv;
+        Self::run_command(&command, client, server).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use tokio::io::DuplexStream;
+    use tokio::sync::Mutex;
+
+    use super::*;
+    use crate::commands::auth::AuthCommand;
+    use crate::commands::bgsave::BgSaveCommand;
+    use crate::commands::command::{CommandCommand, CommandListFilter, CommandSubcommand};
+    use crate::commands::config::{ConfigCommand, ConfigSubcommand};
+    use crate::commands::hello::HelloCommand;
+    use crate::commands::key_type::KeyTypeCommand;
+    use crate::commands::lastsave::LastSaveCommand;
+    use crate::commands::lpop;
+    use crate::commands::save::SaveCommand;
+    use crate::commands::zadd::ZAddOptions;
+    use crate::commands::{
+        blpop::BLPopCommand, echo::EchoCommand, get::GetCommand, llen::LLenCommand,
+        lpop::LPopCommand, lpush::LPushCommand, lrange::LRangeCommand, ping::PingCommand,
+        rpush::RPushCommand, set::SetCommand, zadd::ZAddCommand, zcard::ZCardCommand,
+        zrange::ZRangeCommand, zrank::ZRankCommand, zrem::ZRemCommand, zscore::ZScoreCommand,
+    };
+    use crate::config::AppendFsync;
+    use crate::resp::ProtocolVersion;
+    use crate::resp::RespDataType;
+    use crate::types::key_value::KeyValue;
+    use crate::types::sorted_set::{SortedSet, SortedValue};
+    use crate::types::RedisType;
+    use crate::utils;
+    use bytes::Bytes;
+
+    fn new_server_for_test() -> Arc<RedisServer> {
+        Arc::new(RedisServer::new(Config {
+            port: 0,
+            ..Config::default()
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_client_process_protocol_error_closes_connection() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            new_server_for_test(),
+        ));
+
+        client_stream
+            .write_all(b"*1\r\n$4\r\nPING\r\n*1\r\n$-7\r\n")
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            b"+PONG\r\n-ERR Protocol error: invalid bulk length\r\n".to_vec()
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_process_error_replies() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            new_server_for_test(),
+        ));
+
+        client_stream
+            .write_all(b"SET foo bar\r\nRPUSH foo a\r\nGET\r\nFOO a b\r\n")
+            .await
+            .unwrap();
+        client_stream.shutdown().await.unwrap();
+
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "+OK\r\n\
+             -WRONGTYPE Operation against a key holding the wrong kind of value\r\n\
+             -ERR wrong number of arguments for 'get' command\r\n\
+             -ERR unknown command 'FOO', with args beginning with: 'a' 'b' \r\n"
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_process_transactions() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(4096);
+        let server = new_server_for_test();
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+
+        client_stream
+            .write_all(
+                b"MULTI\r\nSET a 1\r\nRPUSH a x\r\nBLPOP l 0\r\nGET a\r\nMULTI\r\nEXEC\r\n\
+                  EXEC\r\nDISCARD\r\n\
+                  MULTI\r\nSET a 2\r\nDISCARD\r\nGET a\r\n\
+                  MULTI\r\nSET a 3\r\nGET\r\nPSYNC ? -1\r\nEXEC\r\nGET a\r\n",
+            )
+            .await
+            .unwrap();
+        client_stream.shutdown().await.unwrap();
+
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "+OK\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n+QUEUED\r\n\
+             -ERR MULTI calls can not be nested\r\n\
+             *4\r\n+OK\r\n\
+             -WRONGTYPE Operation against a key holding the wrong kind of value\r\n\
+             *-1\r\n$1\r\n1\r\n\
+             -ERR EXEC without MULTI\r\n-ERR DISCARD without MULTI\r\n\
+             +OK\r\n+QUEUED\r\n+OK\r\n$1\r\n1\r\n\
+             +OK\r\n+QUEUED\r\n\
+             -ERR wrong number of arguments for 'get' command\r\n\
+             -ERR Command not allowed inside a transaction\r\n\
+             -EXECABORT Transaction discarded because of previous errors.\r\n\
+             $1\r\n1\r\n"
+        );
+        handle.await.unwrap();
+
+        // Only the transaction that ran is propagated, wrapped in MULTI and EXEC.
+        let propagated = [vec!["MULTI"], vec!["SET", "a", "1"], vec!["EXEC"]]
+            .into_iter()
+            .map(|argv| {
+                RespDataType::new_array(argv)
+                    .encode(ProtocolVersion::Resp2)
+                    .len()
+            })
+            .sum::<usize>();
+        assert_eq!(server.replication.offset(), propagated as u64);
+    }
+
+    #[tokio::test]
+    async fn test_client_process_transaction_defers_blocked_clients() {
+        let server = new_server_for_test();
+        let (server_stream, mut blocked) = tokio::io::duplex(4096);
+        tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+        blocked.write_all(b"BLPOP q 0\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The blocked client doesn't pop in the middle of the transaction.
+        let (server_stream, mut client_stream) = tokio::io::duplex(4096);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+        let mut request = b"MULTI\r\nRPUSH q a\r\n".to_vec();
+        for _ in 0..100 {
+            request.extend(b"LLEN q\r\n");
+        }
+        request.extend(b"EXEC\r\n");
+        client_stream.write_all(&request).await.unwrap();
+        client_stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        handle.await.unwrap();
+        let expected = format!(
+            "+OK\r\n{}*101\r\n{}",
+            "+QUEUED\r\n".repeat(101),
+            ":1\r\n".repeat(101)
+        );
+        assert_eq!(String::from_utf8(response).unwrap(), expected);
+
+        let expected = "*2\r\n$1\r\nq\r\n$1\r\na\r\n";
+        let mut reply = vec![0; expected.len()];
+        blocked.read_exact(&mut reply).await.unwrap();
+        assert_eq!(String::from_utf8(reply).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_client_process_script_defers_blocked_clients() {
+        let server = new_server_for_test();
+        let (server_stream, mut blocked) = tokio::io::duplex(4096);
+        tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+        blocked.write_all(b"BLPOP q 0\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut client = RedisClient::new();
+        let script = "redis.call('RPUSH', KEYS[1], 'a') \
+                      for i = 1, 100 do if redis.call('LLEN', KEYS[1]) ~= 1 then return i end end \
+                      return 0";
+        let reply = handle_request(vec!["EVAL", script, "1", "q"], &mut client, &server).await;
+        assert_eq!(reply, Some(RespDataType::Integer(0)));
+
+        let expected = "*2\r\n$1\r\nq\r\n$1\r\na\r\n";
+        let mut reply = vec![0; expected.len()];
+        blocked.read_exact(&mut reply).await.unwrap();
+        assert_eq!(String::from_utf8(reply).unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_client_process_inline_commands() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            new_server_for_test(),
+        ));
+
+        client_stream
+            .write_all(b"SET foo \"bar baz\"\r\n\r\nget foo\nECHO 'oops\r\n")
+            .await
+            .unwrap();
+
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            response,
+            b"+OK\r\n$7\r\nbar baz\r\n-ERR Protocol error: unbalanced quotes in request\r\n"
+                .to_vec()
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_process_error_with_line_break() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            new_server_for_test(),
+        ));
+
+        // Quoted in the error, the name can't forge another reply.
+        client_stream
+            .write_all(b"*2\r\n$8\r\nfoo\r\n+OK\r\n$1\r\nx\r\n")
+            .await
+            .unwrap();
+        client_stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "-ERR unknown command 'foo  +OK', with args beginning with: 'x' \r\n"
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_process_script_replies_with_line_break() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            new_server_for_test(),
+        ));
+
+        let mut request = Vec::new();
+        for script in [
+            "return redis.error_reply('a\\r\\n+PWNED')",
+            "return redis.status_reply('a\\n+PWNED')",
+            "return {err='a\\r+PWNED'}",
+        ] {
+            let argv = vec!["EVAL", script, "0"];
+            request.extend(RespDataType::new_array(argv).encode(ProtocolVersion::Resp2));
+        }
+        client_stream.write_all(&request).await.unwrap();
+        client_stream.shutdown().await.unwrap();
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        assert_eq!(
+            String::from_utf8(response).unwrap(),
+            "-a  +PWNED\r\n+a +PWNED\r\n-a +PWNED\r\n"
+        );
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_process_pubsub() {
+        async fn read(stream: &mut DuplexStream, expected: &str) {
+            let mut reply = vec![0; expected.len()];
+            stream.read_exact(&mut reply).await.unwrap();
+            assert_eq!(String::from_utf8(reply).unwrap(), expected);
+        }
+        let server = new_server_for_test();
+        let (server_stream, mut subscriber) = tokio::io::duplex(4096);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+
+        // Only subscription commands, PING, QUIT and RESET while subscribed.
+        subscriber
+            .write_all(b"SUBSCRIBE news weather\r\nGET a\r\nPING\r\n")
+            .await
+            .unwrap();
+        read(
+            &mut subscriber,
+            "*3\r\n$9\r\nsubscribe\r\n$4\r\nnews\r\n:1\r\n\
+             *3\r\n$9\r\nsubscribe\r\n$7\r\nweather\r\n:2\r\n\
+             -ERR Can't execute 'get': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / \
+             RESET are allowed in this context\r\n\
+             *2\r\n$4\r\npong\r\n$0\r\n\r\n",
+        )
+        .await;
+
+        // Delivered while the subscriber isn't sending anything.
+        let (server_stream, mut publisher) = tokio::io::duplex(4096);
+        tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+        publisher
+            .write_all(b"PUBLISH news hi\r\nPUBLISH sports hi\r\nQUIT\r\n")
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        publisher.read_to_end(&mut response).await.unwrap();
+        assert_eq!(String::from_utf8(response).unwrap(), ":1\r\n:0\r\n+OK\r\n");
+        read(
+            &mut subscriber,
+            "*3\r\n$7\r\nmessage\r\n$4\r\nnews\r\n$2\r\nhi\r\n",
+        )
+        .await;
+
+        subscriber
+            .write_all(b"UNSUBSCRIBE news\r\nRESET\r\nGET a\r\n")
+            .await
+            .unwrap();
+        read(
+            &mut subscriber,
+            "*3\r\n$11\r\nunsubscribe\r\n$4\r\nnews\r\n:1\r\n+RESET\r\n$-1\r\n",
+        )
+        .await;
+        assert_eq!(
+            server
+                .pubsub
+                .publish(&Bytes::from("weather"), &Bytes::from("hi")),
+            0
+        );
+        subscriber.shutdown().await.unwrap();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_process_switches_to_resp3_after_hello() {
+        let (server_stream, mut client_stream) = tokio::io::duplex(1024);
+        let server = new_server_for_test();
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+
+        let mut zset = SortedSet::new();
+        zset.replace(SortedValue {
+            member: Bytes::from("foo"),
+            score: 1.5,
+        });
+        server
+            .store
+            .create(&"zset_key".to_string(), RedisType::ZSet(zset))
+            .await;
+
+        client_stream
+            .write_all(b"*3\r\n$6\r\nZSCORE\r\n$8\r\nzset_key\r\n$3\r\nfoo\r\n")
+            .await
+            .unwrap();
+        client_stream
+            .write_all(b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n")
+            .await
+            .unwrap();
+        client_stream
+            .write_all(b"*3\r\n$6\r\nZSCORE\r\n$8\r\nzset_key\r\n$3\r\nfoo\r\n")
+            .await
+            .unwrap();
+        client_stream
+            .write_all(b"*3\r\n$6\r\nZSCORE\r\n$8\r\nzset_key\r\n$3\r\nbar\r\n")
+            .await
+            .unwrap();
+        client_stream.shutdown().await.unwrap();
+
+        let mut response = Vec::new();
+        client_stream.read_to_end(&mut response).await.unwrap();
+        let response = String::from_utf8(response).unwrap();
+        assert!(response.starts_with("$3\r\n1.5\r\n%7\r\n$6\r\nserver\r\n"));
+        assert!(response.ends_with(",1.5\r\n_\r\n"));
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handle_command_introspection() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        let run = |subcommand| RedisCommand::COMMAND(CommandCommand { subcommand });
+
+        let result =
+            RedisServer::handle_command(run(CommandSubcommand::Count), &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Integer(
+                crate::commands::COMMAND_TABLE.len() as i64
+            ))
+        );
+
+        let result = RedisServer::handle_command(
+            run(CommandSubcommand::Info(vec![
+                "GET".to_string(),
+                "nope".to_string(),
+            ])),
+            &mut client,
+            &server,
+        )
+        .await
+        .unwrap();
+        let RespDataType::Array(infos) = result else {
+            panic!("Expected an array, got {:?}", result);
+        };
+        assert_eq!(infos[1], RespDataType::Null);
+        let RespDataType::Array(get_info) = &infos[0] else {
+            panic!("Expected an array, got {:?}", infos[0]);
+        };
+        assert_eq!(get_info.len(), 10);
+        assert_eq!(get_info[0], RespDataType::bulk_string("get"));
+        assert_eq!(get_info[1], RespDataType::Integer(2));
+        assert_eq!(
+            get_info[2],
+            RespDataType::Set(vec![
+                RespDataType::simple_string("readonly"),
+                RespDataType::simple_string("fast"),
+            ])
+        );
+        assert_eq!(
+            &get_info[3..6],
+            &[
+                RespDataType::Integer(1),
+                RespDataType::Integer(1),
+                RespDataType::Integer(1),
+            ]
+        );
+
+        let result = RedisServer::handle_command(
+            run(CommandSubcommand::List(Some(CommandListFilter::Pattern(
+                "z*".to_string(),
+            )))),
+            &mut client,
+            &server,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Some(RespDataType::new_array(vec![
+                "zadd", "zrank", "zrange", "zcard", "zscore", "zrem"
+            ]))
+        );
+
+        let result = RedisServer::handle_command(
+            run(CommandSubcommand::List(Some(CommandListFilter::AclCat(
+                "connection".to_string(),
+            )))),
+            &mut client,
+            &server,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Some(RespDataType::new_array(vec![
+                "ping", "echo", "hello", "auth", "command", "wait", "waitaof", "quit", "reset"
+            ]))
+        );
+
+        let result = RedisServer::handle_command(
+            run(CommandSubcommand::GetKeys(vec![
+                Bytes::from("blpop"),
+                Bytes::from("list1"),
+                Bytes::from("0"),
+            ])),
+            &mut client,
+            &server,
+        )
+        .await;
+        assert_eq!(result, Some(RespDataType::new_array(vec!["list1"])));
+
+        let result = RedisServer::handle_command(
+            run(CommandSubcommand::GetKeys(vec![Bytes::from("ping")])),
+            &mut client,
+            &server,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Some(RedisError::custom("The command has no key arguments").into())
+        );
+
+        let result = RedisServer::handle_command(
+            run(CommandSubcommand::Docs(vec!["zadd".to_string()])),
+            &mut client,
+            &server,
+        )
+        .await
+        .unwrap();
+        let RespDataType::Map(docs) = result else {
+            panic!("Expected a map, got {:?}", result);
+        };
+        assert_eq!(docs[0].0, RespDataType::bulk_string("zadd"));
+        let RespDataType::Map(fields) = &docs[0].1 else {
+            panic!("Expected a map, got {:?}", docs[0].1);
+        };
+        assert!(fields.contains(&(
+            RespDataType::bulk_string("group"),
+            RespDataType::bulk_string("sorted-set")
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_handle_hello() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: None,
+            auth: None,
+            client_name: None,
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server)
+            .await
+            .unwrap();
+        assert!(matches!(result, RespDataType::Map(_)));
+        assert_eq!(client.protocol, ProtocolVersion::Resp2);
+
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: Some(4),
+            auth: None,
+            client_name: None,
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::NoProto.into()));
+        assert_eq!(client.protocol, ProtocolVersion::Resp2);
+
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: Some(3),
+            auth: Some(("default".to_string(), "secret".to_string())),
+            client_name: Some("worker-1".to_string()),
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server)
+            .await
+            .unwrap();
+        assert_eq!(client.protocol, ProtocolVersion::Resp3);
+        assert_eq!(client.name, Some("worker-1".to_string()));
+        match result {
+            RespDataType::Map(pairs) => assert!(
+                pairs.contains(&(RespDataType::bulk_string("proto"), RespDataType::Integer(3)))
+            ),
+            other => panic!("Expected a map, got {:?}", other),
+        }
+
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: Some(2),
+            auth: Some(("admin".to_string(), "secret".to_string())),
+            client_name: None,
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::WrongPass.into()));
+        assert_eq!(client.protocol, ProtocolVersion::Resp3);
+
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: None,
+            auth: None,
+            client_name: Some("bad name".to_string()),
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR Client names cannot contain spaces, newlines or special characters."
+                    .to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_requirepass() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server.config_mut().requirepass = Some("secret".to_string());
+
+        let get = RedisCommand::GET(GetCommand {
+            key: "key".to_string(),
+        });
+        let result = RedisServer::handle_command(get.clone(), &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::NoAuth.into()));
+
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: Some(3),
+            auth: None,
+            client_name: None,
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::NoAuth.into()));
+
+        let command = RedisCommand::AUTH(AuthCommand {
+            username: None,
+            password: "wrong".to_string(),
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::WrongPass.into()));
+        assert!(!client.authenticated);
+
+        let command = RedisCommand::AUTH(AuthCommand {
+            username: Some("default".to_string()),
+            password: "secret".to_string(),
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+
+        let result = RedisServer::handle_command(get, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::Null));
+
+        let mut client = RedisClient::new();
+        let command = RedisCommand::HELLO(HelloCommand {
+            protocol: Some(3),
+            auth: Some(("default".to_string(), "secret".to_string())),
+            client_name: None,
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert!(matches!(result, Some(RespDataType::Map(_))));
+        assert!(client.authenticated);
+    }
+
+    #[tokio::test]
+    async fn test_handle_auth_without_password() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+
+        let command = RedisCommand::AUTH(AuthCommand {
+            username: None,
+            password: "secret".to_string(),
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert!(
+            matches!(result, Some(RespDataType::Error(e)) if e.starts_with("ERR AUTH <password> called without"))
+        );
+    }
+
+    fn config_command(subcommand: ConfigSubcommand) -> RedisCommand {
+        RedisCommand::CONFIG(ConfigCommand { subcommand })
+    }
+
+    fn config_set(pairs: &[(&str, &str)]) -> RedisCommand {
+        config_command(ConfigSubcommand::Set(
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_handle_config_get() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+
+        let command = config_command(ConfigSubcommand::Get(vec![
+            "maxmemory*".to_string(),
+            "MAXMEMORY".to_string(),
+            "slave*".to_string(),
+        ]));
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Map(vec![
+                (
+                    RespDataType::bulk_string("maxmemory"),
+                    RespDataType::bulk_string("0")
+                ),
+                (
+                    RespDataType::bulk_string("maxmemory-policy"),
+                    RespDataType::bulk_string("noeviction")
+                ),
+                (
+                    RespDataType::bulk_string("slaveof"),
+                    RespDataType::bulk_string("")
+                ),
+                (
+                    RespDataType::bulk_string("slave-read-only"),
+                    RespDataType::bulk_string("yes")
+                ),
+            ]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_config_set_is_atomic() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+
+        let command = config_set(&[("appendfsync", "always"), ("loglevel", "warning")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        assert_eq!(server.config().appendfsync, AppendFsync::Always);
+        assert_eq!(server.config().loglevel, LogLevel::Warning);
+
+        let command = config_set(&[("appendfsync", "no"), ("maxmemory", "lots")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert!(matches!(
+            result,
+            Some(RespDataType::Error(e)) if e.starts_with("ERR CONFIG SET failed (possibly related to argument 'maxmemory')")
+        ));
+        assert_eq!(server.config().appendfsync, AppendFsync::Always);
+
+        let command = config_set(&[("replicaof", "no one"), ("slaveof", "no one")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR CONFIG SET failed (possibly related to argument 'slaveof') - duplicate parameter"
+                    .to_string()
+            ))
+        );
+
+        let command = config_set(&[("appenddirname", "other")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR CONFIG SET failed (possibly related to argument 'appenddirname') - can't set immutable config"
+                    .to_string()
+            ))
+        );
+
+        let command = config_set(&[("nope", "1")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR Unknown option or number of arguments for CONFIG SET - 'nope'".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_used_memory_follows_writes() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        let requests: Vec<Vec<&str>> = vec![
+            vec!["SET", "s", "value"],
+            vec!["SET", "s", "longer value"],
+            vec!["RPUSH", "l", "a", "bb", "ccc"],
+            vec!["LPUSH", "l", "dddd"],
+            vec!["LPOP", "l", "2"],
+            vec!["BLPOP", "l", "0"],
+            vec!["ZADD", "z", "1", "a", "2", "b"],
+            vec!["ZADD", "z", "3", "a", "4", "c"],
+            vec!["ZREM", "z", "b"],
+            vec!["XADD", "x", "1-1", "f", "v"],
+            vec!["XADD", "x", "1-2", "f", "v"],
+        ];
+        for request in requests {
+            handle_request(request, &mut client, &server).await;
+            let used: usize = server
+                .store
+                .snapshot()
+                .await
+                .iter()
+                .map(|(key, value)| key.len() + value.memory_usage())
+                .sum();
+            assert_eq!(server.store.used_memory(), used);
+        }
+        handle_request(vec!["FLUSHALL"], &mut client, &server).await;
+        assert_eq!(server.store.used_memory(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_config_set_maxmemory_evicts() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        for i in 0..10 {
+            let command = RedisCommand::SET(SetCommand {
+                key: format!("key:{}", i),
+                value: KeyValue {
+                    value: Bytes::from(vec![b'x'; 100]),
+                    expired_at_millis: None,
+                },
+            });
+            RedisServer::handle_command(command, &mut client, &server).await;
+        }
+
+        let command = config_set(&[("maxmemory", "1000")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+
+        // With noeviction nothing is removed, but writes are refused.
+        let set = RedisCommand::SET(SetCommand {
+            key: "other".to_string(),
+            value: KeyValue {
+                value: Bytes::from("value"),
+                expired_at_millis: None,
+            },
+        });
+        let result = RedisServer::handle_command(set.clone(), &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::OutOfMemory.into()));
+
+        let command = config_set(&[("maxmemory-policy", "allkeys-random")]);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        assert_eq!(server.stats.evicted_keys.load(Ordering::Relaxed), 1);
+
+        let result = RedisServer::handle_command(set, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+
+        let command = config_command(ConfigSubcommand::ResetStat);
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        assert_eq!(server.stats.evicted_keys.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_handle_config_rewrite() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+
+        let command = config_command(ConfigSubcommand::Rewrite);
+        let result = RedisServer::handle_command(command.clone(), &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR The server is running without a config file".to_string()
+            ))
+        );
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("redis.conf");
+        std::fs::write(&path, "# keep me\nport 0\n").unwrap();
+        server.config_mut().config_file = Some(path.clone());
+        let set = config_set(&[("appendfsync", "always")]);
+        RedisServer::handle_command(set, &mut client, &server).await;
+
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# keep me\nport 0\n\n# Generated by CONFIG REWRITE\nappendfsync always\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_rdb() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let server = Arc::new(RedisServer::new(Config {
+            dir: dir.to_path_buf(),
+            ..Config::default()
+        }));
+
+        // Without a file the store just starts empty.
+        server.load_rdb().await.unwrap();
+
+        let future = utils::now_millis() as u64 + 60_000;
+        let mut data = b"REDIS0011".to_vec();
+        data.extend([0xfc]);
+        data.extend(future.to_le_bytes());
+        data.extend([0x00, 0x04, b'l', b'i', b'v', b'e', 0x01, b'1']);
+        data.extend([0xfc]);
+        data.extend(1000u64.to_le_bytes());
+        data.extend([0x00, 0x04, b'g', b'o', b'n', b'e', 0x01, b'2']);
+        data.extend([0x01, 0x04, b'l', b'i', b's', b't', 0x01, 0x01, b'a']);
+        data.extend([
+            0xfe, 0x01, 0x00, 0x05, b'o', b't', b'h', b'e', b'r', 0x01, b'3',
+        ]);
+        data.extend([0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+        std::fs::write(dir.join("dump.rdb"), data).unwrap();
+
+        server.load_rdb().await.unwrap();
+        let live = server
+            .store
+            .get_key_value(&"live".to_string())
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(live.value, Bytes::from("1"));
+        assert_eq!(live.expired_at_millis, Some(future as u128));
+        assert!(server.store.get_key(&"gone".to_string()).await.is_none());
+        assert!(server.store.get_key(&"other".to_string()).await.is_none());
+        assert_eq!(
+            server
+                .store
+                .get_list(&"list".to_string())
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        std::fs::write(dir.join("dump.rdb"), b"REDIS0011\x00").unwrap();
+        assert!(matches!(
+            server.load_rdb().await,
+            Err(RdbError::UnexpectedEof)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_load_rdb_skips_unloadable_keys() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let logfile = dir.join("redis.log");
+        let server = RedisServer::new(Config {
+            dir: dir.to_path_buf(),
+            logfile: logfile.to_string_lossy().into_owned(),
+            ..Config::default()
+        });
+
+        let future = utils::now_millis() as u64 + 60_000;
+        let mut data = b"REDIS0011".to_vec();
+        data.extend(b"\x01\x04list\x01\x01a");
+        data.extend([0xfc]);
+        data.extend(future.to_le_bytes());
+        data.extend(b"\x01\x08volatile\x01\x01a");
+        data.extend(b"\x01\x04bin\xff\x01\x01a");
+        data.extend(b"\x01\x04bin\xfe\x01\x01a");
+        data.extend([0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+        std::fs::write(dir.join("dump.rdb"), data).unwrap();
+
+        server.load_rdb().await.unwrap();
+        assert!(server.store.get_list(&"list".to_string()).await.is_some());
+        assert!(server
+            .store
+            .get_key(&"volatile".to_string())
+            .await
+            .is_none());
+        assert!(server
+            .store
+            .get_key(&"bin\u{fffd}".to_string())
+            .await
+            .is_none());
+        let log = std::fs::read_to_string(&logfile).unwrap();
+        assert!(log.contains("Skipped 2 keys that are not valid UTF-8"));
+        assert!(log.contains("Skipped 1 keys with an expire that are not strings"));
+    }
+
+    async fn wait_for_bgsave(server: &Arc<RedisServer>) {
+        while server.persistence.saving.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_save_and_bgsave() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut client = RedisClient::new();
+        let server = Arc::new(RedisServer::new(Config {
+            dir: dir.to_path_buf(),
+            ..Config::default()
+        }));
+        server.persistence.lastsave.store(0, Ordering::Relaxed);
+
+        let set = |key: &str| {
+            RedisCommand::SET(SetCommand {
+                key: key.to_string(),
+                value: KeyValue {
+                    value: Bytes::from("value"),
+                    expired_at_millis: None,
+                },
+            })
+        };
+        RedisServer::handle_command(set("a"), &mut client, &server).await;
+        assert_eq!(server.persistence.dirty.load(Ordering::SeqCst), 1);
+
+        let result =
+            RedisServer::handle_command(RedisCommand::SAVE(SaveCommand), &mut client, &server)
+                .await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        assert_eq!(server.persistence.dirty.load(Ordering::SeqCst), 0);
+        let lastsave = RedisServer::handle_command(
+            RedisCommand::LASTSAVE(LastSaveCommand),
+            &mut client,
+            &server,
+        )
+        .await;
+        assert!(matches!(lastsave, Some(RespDataType::Integer(at)) if at > 0));
+
+        RedisServer::handle_command(set("b"), &mut client, &server).await;
+        let result =
+            RedisServer::handle_command(RedisCommand::BGSAVE(BgSaveCommand), &mut client, &server)
+                .await;
+        assert_eq!(
+            result,
+            Some(RespDataType::simple_string("Background saving started"))
+        );
+        // The background task hasn't had a chance to run yet.
+        let result =
+            RedisServer::handle_command(RedisCommand::SAVE(SaveCommand), &mut client, &server)
+                .await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR Background save already in progress".to_string()
+            ))
+        );
+        wait_for_bgsave(&server).await;
+        assert_eq!(server.persistence.dirty.load(Ordering::SeqCst), 0);
+
+        let restarted = Arc::new(RedisServer::new(Config {
+            dir: dir.to_path_buf(),
+            ..Config::default()
+        }));
+        restarted.load_rdb().await.unwrap();
+        assert!(restarted.store.get_key(&"a".to_string()).await.is_some());
+        assert!(restarted.store.get_key(&"b".to_string()).await.is_some());
+
+        temp.close().unwrap();
+        let result =
+            RedisServer::handle_command(RedisCommand::SAVE(SaveCommand), &mut client, &server)
+                .await;
+        assert!(
+            matches!(result, Some(RespDataType::Error(e)) if e.starts_with("ERR Failed saving the DB"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_save_rules() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let server = Arc::new(RedisServer::new(Config {
+            dir: dir.to_path_buf(),
+            save: vec![(60, 2)],
+            ..Config::default()
+        }));
+        server.persistence.dirty.store(1, Ordering::SeqCst);
+        server.persistence.lastsave.store(0, Ordering::Relaxed);
+        server.check_save_rules();
+        assert!(!server.persistence.saving.load(Ordering::SeqCst));
+
+        server.persistence.dirty.store(2, Ordering::SeqCst);
+        server.check_save_rules();
+        assert!(server.persistence.saving.load(Ordering::SeqCst));
+        wait_for_bgsave(&server).await;
+        assert!(dir.join("dump.rdb").exists());
+        assert_eq!(server.persistence.dirty.load(Ordering::SeqCst), 0);
+
+        // Within the period since the last save nothing happens.
+        server.persistence.dirty.store(2, Ordering::SeqCst);
+        server.check_save_rules();
+        assert!(!server.persistence.saving.load(Ordering::SeqCst));
+    }
+
+    /// Runs a command the way `client_process` does, with its arguments kept for propagation.
+    async fn handle_request<A: AsRef<[u8]>>(
+        args: Vec<A>,
+        client: &mut RedisClient,
+        server: &Arc<RedisServer>,
+    ) -> Option<RespDataType> {
+        client.argv = args
+            .iter()
+            .map(|arg| Bytes::copy_from_slice(arg.as_ref()))
+            .collect();
+        let request =
+            RespDataType::Array(client.argv.iter().map(RespDataType::bulk_string).collect());
+        let command = RedisCommand::build(vec![request]).unwrap().remove(0);
+        RedisServer::process_command(command, client, server).await
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let server = new_server_for_test();
+        let (mut client, mut other) = (RedisClient::new(), RedisClient::new());
+        let exec = |set: &'static str| vec![vec!["MULTI"], vec!["SET", "a", set], vec!["EXEC"]];
+        let queued = RespDataType::simple_string("QUEUED");
+
+        // Changed by another client: the transaction doesn't run.
+        handle_request(vec!["WATCH", "a"], &mut client, &server).await;
+        handle_request(vec!["SET", "a", "1"], &mut other, &server).await;
+        let mut replies = Vec::new();
+        for request in exec("2") {
+            replies.push(handle_request(request, &mut client, &server).await.unwrap());
+        }
+        assert_eq!(
+            replies,
+            vec![RespDataType::ok(), queued.clone(), RespDataType::NullArray]
+        );
+        let get = handle_request(vec!["GET", "a"], &mut client, &server).await;
+        assert_eq!(get, Some(RespDataType::bulk_string("1")));
+
+        // `EXEC` forgets the watched keys, whatever the outcome.
+        handle_request(vec!["SET", "a", "1"], &mut other, &server).await;
+        for request in exec("3") {
+            handle_request(request, &mut client, &server).await;
+        }
+        let get = handle_request(vec!["GET", "a"], &mut client, &server).await;
+        assert_eq!(get, Some(RespDataType::bulk_string("3")));
+
+        // So does `UNWATCH`.
+        handle_request(vec!["WATCH", "a", "b"], &mut client, &server).await;
+        handle_request(vec!["UNWATCH"], &mut client, &server).await;
+        handle_request(vec!["LPUSH", "b", "x"], &mut other, &server).await;
+        handle_request(vec!["MULTI"], &mut client, &server).await;
+        assert_eq!(
+            handle_request(vec!["WATCH", "a"], &mut client, &server).await,
+            Some(RedisError::custom("WATCH inside MULTI is not allowed").into())
+        );
+        assert_eq!(
+            handle_request(vec!["EXEC"], &mut client, &server).await,
+            Some(RespDataType::Array(vec![]))
+        );
+    }
+
+    /// Runs an empty transaction, returning what `EXEC` replied.
+    async fn exec_empty(client: &mut RedisClient, server: &Arc<RedisServer>) -> RespDataType {
+        handle_request(vec!["MULTI"], client, server).await;
+        handle_request(vec!["EXEC"], client, server).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watch_expiry() {
+        let server = new_server_for_test();
+        let (mut client, mut other) = (RedisClient::new(), RedisClient::new());
+        handle_request(vec!["SET", "e", "v", "PX", "20"], &mut other, &server).await;
+        handle_request(vec!["WATCH", "e"], &mut client, &server).await;
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(
+            exec_empty(&mut client, &server).await,
+            RespDataType::NullArray
+        );
+
+        // Already expired when watched: expiring doesn't change it anymore.
+        handle_request(vec!["WATCH", "e"], &mut client, &server).await;
+        assert_eq!(
+            exec_empty(&mut client, &server).await,
+            RespDataType::Array(vec![])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch_flushall() {
+        let server = new_server_for_test();
+        let (mut client, mut other) = (RedisClient::new(), RedisClient::new());
+        handle_request(vec!["SET", "a", "1"], &mut other, &server).await;
+        handle_request(vec!["WATCH", "a", "missing"], &mut client, &server).await;
+        handle_request(vec!["FLUSHALL"], &mut other, &server).await;
+        assert_eq!(
+            exec_empty(&mut client, &server).await,
+            RespDataType::NullArray
+        );
+
+        // A key that didn't exist counts as changed too.
+        handle_request(vec!["WATCH", "missing"], &mut client, &server).await;
+        handle_request(vec!["FLUSHALL"], &mut other, &server).await;
+        assert_eq!(
+            exec_empty(&mut client, &server).await,
+            RespDataType::NullArray
+        );
+    }
+
+    #[tokio::test]
+    async fn test_watch_eviction() {
+        let server = Arc::new(RedisServer::new(Config {
+            port: 0,
+            maxmemory: 100,
+            maxmemory_policy: "volatile-ttl".to_string(),
+            ..Config::default()
+        }));
+        let (mut client, mut other) = (RedisClient::new(), RedisClient::new());
+        handle_request(vec!["SET", "w", "v", "PX", "60000"], &mut other, &server).await;
+        handle_request(vec!["WATCH", "w"], &mut client, &server).await;
+        let big = "x".repeat(100);
+        handle_request(vec!["SET", "big", &big], &mut other, &server).await;
+        handle_request(vec!["SET", "small", "v"], &mut other, &server).await;
+        assert!(server.store.get_key(&"w".to_string()).await.is_none());
+        assert_eq!(
+            exec_empty(&mut client, &server).await,
+            RespDataType::NullArray
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eval() {
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        let (_replica, mut stream) = server.replication.attach(0, "r".to_string(), 1, 1024);
+        let eval = |script: &'static str, rest: Vec<&'static str>| {
+            let mut request = vec!["EVAL", script];
+            request.extend(rest);
+            request
+        };
+        let set = "return redis.call('SET', KEYS[1], ARGV[1])";
+        assert_eq!(
+            handle_request(eval(set, vec!["1", "a", "1"]), &mut client, &server).await,
+            Some(RespDataType::ok())
+        );
+        // Propagated as the commands it ran.
+        let mut propagated = Vec::new();
+        while let Ok(data) = stream.try_recv() {
+            propagated.extend_from_slice(&data);
+        }
+        assert_eq!(
+            String::from_utf8(propagated).unwrap(),
+            "*1\r\n$5\r\nMULTI\r\n*3\r\n$3\r\nSET\r\n$1\r\na\r\n$1\r\n1\r\n*1\r\n$4\r\nEXEC\r\n"
+        );
+
+        let script = "return {redis.call('GET', 'a'), 2.7, ARGV[1], false, nil, 3}";
+        assert_eq!(
+            handle_request(eval(script, vec!["0", "x"]), &mut client, &server).await,
+            Some(RespDataType::Array(vec![
+                RespDataType::bulk_string("1"),
+                RespDataType::Integer(2),
+                RespDataType::bulk_string("x"),
+                RespDataType::Null,
+            ]))
+        );
+        // `redis.call` raises error replies, `redis.pcall` returns them.
+        let wrongtype = RespDataType::from(RedisError::WrongType);
+        let script = "return redis.call('LPUSH', 'a', 'x')";
+        assert_eq!(
+            handle_request(eval(script, vec!["0"]), &mut client, &server).await,
+            Some(wrongtype.clone())
+        );
+        let script = "local reply = redis.pcall('LPUSH', 'a', 'x'); return reply.err";
+        assert_eq!(
+            handle_request(eval(script, vec!["0"]), &mut client, &server).await,
+            Some(RespDataType::bulk_string(wrongtype.to_string().unwrap()))
+        );
+        for (script, error) in [
+            (
+                "return redis.call('EVAL', 'return 1', 0)",
+                "ERR This Redis command is not allowed from script",
+            ),
+            (
+                "return redis.call('NOPE')",
+                "ERR Unknown Redis command called from script",
+            ),
+            (
+                "return redis.call()",
+                "ERR Please specify at least one argument for this redis lib call",
+            ),
+        ] {
+            assert_eq!(
+                handle_request(eval(script, vec!["0"]), &mut client, &server).await,
+                Some(RespDataType::Error(error.to_string()))
+            );
+        }
+        let Some(RespDataType::Error(error)) =
+            handle_request(eval("x = 1", vec!["0"]), &mut client, &server).await
+        else {
+            panic!("globals can't be created");
+        };
+        assert!(error.starts_with("ERR Error running script (call to f_"));
+        assert!(error.ends_with("Script attempted to create global variable 'x'"));
+
+        // Read-only scripts can read but not write.
+        let request = vec!["EVAL_RO", set, "1", "a", "2"];
+        assert_eq!(
+            handle_request(request, &mut client, &server).await,
+            Some(
+                RedisError::custom("Write commands are not allowed from read-only scripts.").into()
+            )
+        );
+        let request = vec!["EVAL_RO", "return redis.call('GET', 'a')", "0"];
+        assert_eq!(
+            handle_request(request, &mut client, &server).await,
+            Some(RespDataType::bulk_string("1"))
+        );
+
+        // `EVAL` caches the script for `EVALSHA`, until `SCRIPT FLUSH`.
+        let sha = scripting::sha1hex(set.as_bytes());
+        let request = vec!["EVALSHA", &sha, "1", "a", "3"];
+        assert_eq!(
+            handle_request(request.clone(), &mut client, &server).await,
+            Some(RespDataType::ok())
+        );
+        assert_eq!(
+            handle_request(vec!["SCRIPT", "EXISTS", &sha, "nope"], &mut client, &server).await,
+            Some(RespDataType::Array(vec![
+                RespDataType::Integer(1),
+                RespDataType::Integer(0)
+            ]))
+        );
+        handle_request(vec!["SCRIPT", "FLUSH"], &mut client, &server).await;
+        assert_eq!(
+            handle_request(request, &mut client, &server).await,
+            Some(RedisError::NoScript.into())
+        );
+        assert_eq!(
+            handle_request(vec!["SCRIPT", "LOAD", set], &mut client, &server).await,
+            Some(RespDataType::bulk_string(&sha))
+        );
+        assert_eq!(
+            handle_request(
+                vec!["EVALSHA_RO", &sha, "1", "a", "4"],
+                &mut client,
+                &server
+            )
+            .await,
+            Some(
+                RedisError::custom("Write commands are not allowed from read-only scripts.").into()
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_eval_loadstring() {
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        let script = "return loadstring('return ' .. ARGV[1])()";
+        assert_eq!(
+            handle_request(vec!["EVAL", script, "0", "7"], &mut client, &server).await,
+            Some(RespDataType::Integer(7))
+        );
+        // Precompiled chunks aren't verified by Lua 5.1, so they are refused.
+        let script = "local f, e = loadstring(string.dump(function() return 1 end)) return e";
+        assert_eq!(
+            handle_request(vec!["EVAL", script, "0"], &mut client, &server).await,
+            Some(RespDataType::bulk_string(
+                "loadstring: binary chunks are not allowed"
+            ))
+        );
+        let result = handle_request(vec!["EVAL", "return load", "0"], &mut client, &server).await;
+        assert!(matches!(result, Some(RespDataType::Error(e)) if e.contains("nonexistent global")));
+    }
+
+    #[tokio::test]
+    async fn test_script_busy_and_kill() {
+        let server = Arc::new(RedisServer::new(Config {
+            port: 0,
+            busy_reply_threshold: 50,
+            ..Config::default()
+        }));
+        let mut other = RedisClient::new();
+        assert_eq!(
+            handle_request(vec!["SCRIPT", "KILL"], &mut other, &server).await,
+            Some(RedisError::NotBusy.into())
+        );
+        let running = tokio::spawn({
+            let server = server.clone();
+            async move {
+                let request = vec!["EVAL", "while true do end", "0"];
+                handle_request(request, &mut RedisClient::new(), &server).await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(
+            handle_request(vec!["PING"], &mut other, &server).await,
+            Some(RedisError::Busy.into())
+        );
+        assert_eq!(
+            handle_request(vec!["SCRIPT", "KILL"], &mut other, &server).await,
+            Some(RespDataType::ok())
+        );
+        assert_eq!(
+            running.await.unwrap(),
+            Some(RespDataType::Error(
+                "ERR Script killed by user with SCRIPT KILL...".to_string()
+            ))
+        );
+        assert_eq!(
+            handle_request(vec!["PING"], &mut other, &server).await,
+            Some(RespDataType::simple_string("PONG"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fcall() {
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        let code = "#!lua name=lib\n\
+                    redis.register_function('set', function(keys, args)\n\
+                        return redis.call('SET', keys[1], args[1]) end)\n\
+                    redis.register_function{function_name = 'get', flags = {'no-writes'},\n\
+                        callback = function(keys) return redis.call('GET', keys[1]) end,\n\
+                        description = 'reads a key'}\n\
+                    redis.register_function{function_name = 'sneaky', flags = {'no-writes'},\n\
+                        callback = function(keys) return redis.call('SET', keys[1], 'x') end}";
+        assert_eq!(
+            handle_request(vec!["FUNCTION", "LOAD", code], &mut client, &server).await,
+            Some(RespDataType::bulk_string("lib"))
+        );
+        assert_eq!(
+            handle_request(vec!["FCALL", "set", "1", "a", "1"], &mut client, &server).await,
+            Some(RespDataType::ok())
+        );
+        assert_eq!(
+            handle_request(vec!["FCALL_RO", "get", "1", "a"], &mut client, &server).await,
+            Some(RespDataType::bulk_string("1"))
+        );
+        assert_eq!(
+            handle_request(vec!["FCALL_RO", "set", "1", "a", "2"], &mut client, &server).await,
+            Some(
+                RedisError::custom("Can not execute a script with write flag using *_ro command.")
+                    .into()
+            )
+        );
+        // Functions flagged `no-writes` can't write, whatever the command calling them.
+        assert_eq!(
+            handle_request(vec!["FCALL", "sneaky", "1", "a"], &mut client, &server).await,
+            Some(
+                RedisError::custom("Write commands are not allowed from read-only scripts.").into()
+            )
+        );
+        assert_eq!(
+            handle_request(vec!["FCALL", "nope", "0"], &mut client, &server).await,
+            Some(RedisError::custom("Function not found").into())
+        );
+
+        let list = handle_request(
+            vec!["FUNCTION", "LIST", "LIBRARYNAME", "l*"],
+            &mut client,
+            &server,
+        )
+        .await
+        .unwrap();
+        let RespDataType::Array(libraries) = list else {
+            panic!("FUNCTION LIST replies an array");
+        };
+        let RespDataType::Map(fields) = &libraries[0] else {
+            panic!("each library is a map");
+        };
+        assert_eq!(fields[0].1, RespDataType::bulk_string("lib"));
+        let RespDataType::Array(functions) = &fields[2].1 else {
+            panic!("the functions of a library are an array");
+        };
+        assert_eq!(
+            functions[1],
+            RespDataType::Map(vec![
+                (
+                    RespDataType::bulk_string("name"),
+                    RespDataType::bulk_string("get")
+                ),
+                (
+                    RespDataType::bulk_string("description"),
+                    RespDataType::bulk_string("reads a key")
+                ),
+                (
+                    RespDataType::bulk_string("flags"),
+                    RespDataType::Set(vec![RespDataType::simple_string("no-writes")])
+                ),
+            ])
+        );
+        let list = vec!["FUNCTION", "LIST", "LIBRARYNAME", "other"];
+        assert_eq!(
+            handle_request(list, &mut client, &server).await,
+            Some(RespDataType::Array(vec![]))
+        );
+
+        // Dumped libraries are restored elsewhere, and kept in RDB files.
+        let Some(RespDataType::BulkString(payload)) =
+            handle_request(vec!["FUNCTION", "DUMP"], &mut client, &server).await
+        else {
+            panic!("FUNCTION DUMP replies a bulk string");
+        };
+        let other = new_server_for_test();
+        let restore = vec![b"FUNCTION".to_vec(), b"RESTORE".to_vec(), payload.to_vec()];
+        assert_eq!(
+            handle_request(restore.clone(), &mut client, &other).await,
+            Some(RespDataType::ok())
+        );
+        assert_eq!(
+            handle_request(restore, &mut client, &other).await,
+            Some(RedisError::custom("Library 'lib' already exists").into())
+        );
+        let data = HashMap::new();
+        let payload = rdb::encode(&data, &server.functions.codes(), utils::now_millis());
+        let other = new_server_for_test();
+        other.load_rdb_entries(rdb::parse(&payload).unwrap()).await;
+        assert_eq!(
+            handle_request(vec!["FCALL", "set", "1", "b", "2"], &mut client, &other).await,
+            Some(RespDataType::ok())
+        );
+
+        let stats = handle_request(vec!["FUNCTION", "STATS"], &mut client, &server).await;
+        let counts = RespDataType::Map(vec![
+            (
+                RespDataType::bulk_string("libraries_count"),
+                RespDataType::Integer(1),
+            ),
+            (
+                RespDataType::bulk_string("functions_count"),
+                RespDataType::Integer(3),
+            ),
+        ]);
+        assert_eq!(
+            stats,
+            Some(RespDataType::Map(vec![
+                (
+                    RespDataType::bulk_string("running_script"),
+                    RespDataType::Null
+                ),
+                (
+                    RespDataType::bulk_string("engines"),
+                    RespDataType::Map(vec![(RespDataType::bulk_string("LUA"), counts)])
+                ),
+            ]))
+        );
+        handle_request(vec!["FUNCTION", "DELETE", "lib"], &mut client, &server).await;
+        assert_eq!(
+            handle_request(vec!["FUNCTION", "DELETE", "lib"], &mut client, &server).await,
+            Some(RedisError::custom("Library not found").into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_without_replicas() {
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        let wait = vec!["WAIT", "1", "20"];
+        assert_eq!(
+            handle_request(wait.clone(), &mut client, &server).await,
+            Some(RespDataType::Integer(0))
+        );
+        assert_eq!(
+            handle_request(vec!["WAIT", "0", "0"], &mut client, &server).await,
+            Some(RespDataType::Integer(0))
+        );
+        assert_eq!(
+            handle_request(vec!["WAITAOF", "1", "0", "0"], &mut client, &server).await,
+            Some(
+                RedisError::custom(
+                    "WAITAOF cannot be used when numlocal is set but appendonly is disabled."
+                )
+                .into()
+            )
+        );
+
+        server.config_mut().replicaof = Some(("127.0.0.1".to_string(), 6379));
+        let Some(RespDataType::Error(error)) = handle_request(wait, &mut client, &server).await
+        else {
+            panic!("WAIT should fail on a replica");
+        };
+        assert!(error.starts_with("ERR WAIT cannot be used with replica instances."));
+    }
+
+    #[tokio::test]
+    async fn test_waitaof_local() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let server = Arc::new(RedisServer::new(Config {
+            dir: dir.to_path_buf(),
+            appendonly: true,
+            appendfsync: AppendFsync::Always,
+            ..Config::default()
+        }));
+        server.load_aof().await.unwrap();
+        let mut client = RedisClient::new();
+        let reply = |local, replicas| {
+            Some(RespDataType::Array(vec![
+                RespDataType::Integer(local),
+                RespDataType::Integer(replicas),
+            ]))
+        };
+
+        handle_request(vec!["SET", "a", "1"], &mut client, &server).await;
+        let waitaof = vec!["WAITAOF", "1", "0", "20"];
+        assert_eq!(
+            handle_request(waitaof.clone(), &mut client, &server).await,
+            reply(1, 0)
+        );
+
+        // With `everysec` the write is on disk only after the next fsync.
+        server.config_mut().appendfsync = AppendFsync::EverySec;
+        handle_request(vec!["SET", "b", "2"], &mut client, &server).await;
+        assert_eq!(
+            handle_request(waitaof.clone(), &mut client, &server).await,
+            reply(0, 0)
+        );
+        assert_eq!(
+            handle_request(vec!["WAITAOF", "0", "1", "20"], &mut client, &server).await,
+            reply(0, 0)
+        );
+        let wait = tokio::spawn({
+            let server = server.clone();
+            async move { handle_request(vec!["WAITAOF", "1", "0", "0"], &mut client, &server).await }
+        });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        server.fsync_aof();
+        assert_eq!(wait.await.unwrap(), reply(1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_aof_propagates_and_replays() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = Config {
+            dir: dir.to_path_buf(),
+            appendonly: true,
+            appendfsync: AppendFsync::Always,
+            ..Config::default()
+        };
+        let path = config.aof_dir().join("appendonly.aof.1.incr.aof");
+        let mut client = RedisClient::new();
+        let server = Arc::new(RedisServer::new(config.clone()));
+        server
+            .store
+            .insert("before".to_string(), RedisType::Set(["m".into()].into()))
+            .await;
+        // No AOF yet: it is created with what is already there as its base.
+        server.load_aof().await.unwrap();
+        let base = std::fs::read(config.aof_dir().join("appendonly.aof.1.base.rdb")).unwrap();
+        assert_eq!(
+            aof::parse(&base).unwrap().preamble.unwrap().entries.len(),
+            1
+        );
+
+        handle_request(vec!["SET", "a", "1"], &mut client, &server).await;
+        handle_request(vec!["SET", "b", "2", "PX", "60000"], &mut client, &server).await;
+        handle_request(vec!["RPUSH", "l", "x", "y"], &mut client, &server).await;
+        handle_request(vec!["BLPOP", "l", "0"], &mut client, &server).await;
+        handle_request(vec!["GET", "a"], &mut client, &server).await;
+
+        let content = aof::parse(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(content.preamble.is_none());
+        let commands: Vec<_> = content
+            .commands
+            .iter()
+            .map(|command| match command {
+                RespDataType::Array(args) => args[0].to_string().unwrap(),
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(commands, vec!["SET", "SET", "RPUSH", "LPOP"]);
+        assert_eq!(
+            content.commands[1],
+            RespDataType::Array(vec![
+                RespDataType::bulk_string("SET"),
+                RespDataType::bulk_string("b"),
+                RespDataType::bulk_string("2"),
+                RespDataType::bulk_string("PXAT"),
+                RespDataType::bulk_string(
+                    server
+                        .store
+                        .get_key_value(&"b".to_string())
+                        .await
+                        .unwrap()
+                        .expired_at_millis
+                        .unwrap()
+                        .to_string()
+                ),
+            ])
+        );
+
+        // A command cut short by a crash is dropped, unless that is disabled.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"*3\r\n$3\r\nSET\r\n$1\r\nc").unwrap();
+        let strict = Arc::new(RedisServer::new(Config {
+            aof_load_truncated: false,
+            ..config.clone()
+        }));
+        assert!(matches!(strict.load_aof().await, Err(AofError::Truncated)));
+
+        let restarted = Arc::new(RedisServer::new(config));
+        restarted.load_aof().await.unwrap();
+        for key in ["before", "a", "b"] {
+            assert!(restarted.store.get_key(&key.to_string()).await.is_some());
+        }
+        let list = restarted
+            .store
+            .get_list(&"l".to_string())
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(list, VecDeque::from([Bytes::from("y")]));
+        assert!(restarted.store.get_key(&"c".to_string()).await.is_none());
+        let content = aof::parse(&std::fs::read(&path).unwrap()).unwrap();
+        assert!(!content.truncated);
+    }
 
-use crate::{
-    client::RedisClient,
-    commands::{traits::RunnableCommand, zadd::ZAddCommand, RedisCommand},
-    resp::RespDataType,
-    store::RedisStore,
-    types::RedisType,
-    utils,
-};
+    #[tokio::test]
+    async fn test_handle_config_set_appendonly() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("data");
+        let mut client = RedisClient::new();
+        let server = Arc::new(RedisServer::new(Config {
+            dir: dir.clone(),
+            ..Config::default()
+        }));
+        handle_request(vec!["SET", "a", "1"], &mut client, &server).await;
+        assert!(!dir.exists());
+
+        let set = config_set(&[("appendonly", "yes")]);
+        RedisServer::handle_command(set, &mut client, &server).await;
+        // Started once no command runs anymore.
+        handle_request(vec!["SET", "b", "2"], &mut client, &server).await;
+        wait_for_aof_rewrite(&server).await;
+        handle_request(vec!["SET", "c", "3"], &mut client, &server).await;
+
+        let aof_dir = server.config().aof_dir();
+        let manifest = Manifest::load(&aof_dir, "appendonly.aof").unwrap().unwrap();
+        let base = std::fs::read(aof_dir.join(&manifest.base.unwrap().name)).unwrap();
+        assert_eq!(
+            aof::parse(&base).unwrap().preamble.unwrap().entries.len(),
+            2
+        );
+        let incr = std::fs::read(aof_dir.join(&manifest.incrs[0].name)).unwrap();
+        assert_eq!(
+            aof::parse(&incr).unwrap().commands,
+            vec![RespDataType::new_array(vec!["SET", "c", "3"])]
+        );
 
-#[derive(Debug)]
-pub struct RedisServer {
-    addr: String,
-    store: Arc<RedisStore>,
-}
+        let set = config_set(&[("appendonly", "no")]);
+        RedisServer::handle_command(set, &mut client, &server).await;
+        assert!(server.aof().is_none());
+    }
 
-impl RedisServer {
-    pub fn new(addr: String) -> Self {
-        Self {
-            addr,
-            store: Arc::new(RedisStore::new()),
+    async fn wait_for_aof_rewrite(server: &Arc<RedisServer>) {
+        tokio::task::yield_now().await;
+        while server.persistence.aof_rewriting.load(Ordering::SeqCst) {
+            tokio::time::sleep(Duration::from_millis(5)).await;
         }
     }
 
-    pub async fn run(&self) {
-        let listener = match TcpListener::bind(self.addr.clone()).await {
-            Ok(listener) => listener,
-            Err(e) => {
-                eprintln!("Failed to bind to address {}: {}", self.addr, e);
-                return;
-            }
+    fn aof_files(dir: &std::path::Path) -> Vec<String> {
+        let mut files: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[tokio::test]
+    async fn test_handle_bgrewriteaof() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = Config {
+            dir: dir.to_path_buf(),
+            appendonly: true,
+            aof_use_rdb_preamble: false,
+            auto_aof_rewrite_min_size: 0,
+            ..Config::default()
         };
-        loop {
-            let stream = listener.accept().await;
-            match stream {
-                Ok((stream, _)) => {
-                    println!("accepted new connection");
-                    let store_clone = Arc::clone(&self.store);
-                    let client: RedisClient<TcpStream> = RedisClient::new(stream);
-                    tokio::spawn(async move {
-                        Self::client_process(client, store_clone).await;
-                    });
-                }
-                Err(e) => {
-                    println!("error: {}", e);
-                }
-            }
+        let aof_dir = config.aof_dir();
+        let mut client = RedisClient::new();
+        let server = Arc::new(RedisServer::new(config.clone()));
+        server.load_aof().await.unwrap();
+        for i in 0..10 {
+            handle_request(vec!["RPUSH", "l", &i.to_string()], &mut client, &server).await;
         }
-    }
 
-    async fn client_process<T: AsyncRead + AsyncWrite + Unpin + Send>(
-        mut client: RedisClient<T>,
-        store: Arc<RedisStore>,
-    ) {
-        loop {
-            match client.connection.read_request().await {
-                Ok(None) => break,
-                Ok(Some(request)) => match RedisCommand::parse(request) {
-                    Ok(received_commands) => {
-                        for command in received_commands {
-                            let response = Self::handle_command(command, &client, &store).await;
-                            client.connection.write_response(&response).await;
-                            println!(
-                                "Response Generated for client:{:?} {:?}",
-                                client.id, response
-                            );
-                        }
-                    }
-                    Err(msg) => {
-                        client
-                            .connection
-                            .write_response(&Some(RespDataType::Error(msg.clone())))
-                            .await;
-                        println!("Response Generated for client:{:?} {}", client.id, msg);
-                    }
-                },
-                Err(e) => {
-                    println!("error: {}", e);
-                    return;
-                }
-            }
+        let result = handle_request(vec!["BGREWRITEAOF"], &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::simple_string(
+                "Background append only file rewriting started"
+            ))
+        );
+        let result = handle_request(vec!["BGREWRITEAOF"], &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR Background append only file rewriting already in progress".to_string()
+            ))
+        );
+        wait_for_aof_rewrite(&server).await;
+        handle_request(vec!["SET", "k", "v"], &mut client, &server).await;
+        assert_eq!(
+            aof_files(&aof_dir),
+            vec![
+                "appendonly.aof.2.base.aof",
+                "appendonly.aof.2.incr.aof",
+                "appendonly.aof.manifest"
+            ]
+        );
+        let base = std::fs::read(aof_dir.join("appendonly.aof.2.base.aof")).unwrap();
+        assert_eq!(aof::parse(&base).unwrap().commands.len(), 1);
+
+        // Doubled in size since the rewrite.
+        server.check_aof_rewrite();
+        assert!(!server.persistence.aof_rewriting.load(Ordering::SeqCst));
+        for i in 0..10 {
+            handle_request(vec!["RPUSH", "l", &i.to_string()], &mut client, &server).await;
         }
+        server.check_aof_rewrite();
+        assert!(server.persistence.aof_rewriting.load(Ordering::SeqCst));
+        wait_for_aof_rewrite(&server).await;
+        assert_eq!(
+            aof_files(&aof_dir),
+            vec![
+                "appendonly.aof.3.base.aof",
+                "appendonly.aof.3.incr.aof",
+                "appendonly.aof.manifest"
+            ]
+        );
+
+        let restarted = Arc::new(RedisServer::new(config));
+        restarted.load_aof().await.unwrap();
+        let list = restarted
+            .store
+            .get_list(&"l".to_string())
+            .await
+            .unwrap()
+            .clone();
+        assert_eq!(list.len(), 20);
+        assert!(restarted.store.get_key(&"k".to_string()).await.is_some());
     }
 
-    async fn handle_command(
-        command: RedisCommand,
-        client: &RedisClient<impl AsyncReadExt + AsyncWriteExt + Unpin + Send>,
-        store: &Arc<RedisStore>,
-    ) -> Option<RespDataType> {
-        command.execute(&client.id, store, &client.notifier).await
+    #[tokio::test]
+    async fn test_load_aof_without_manifest() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let config = Config {
+            dir: dir.to_path_buf(),
+            appendonly: true,
+            ..Config::default()
+        };
+        std::fs::write(
+            dir.join("appendonly.aof"),
+            RespDataType::new_array(vec!["SET", "k", "v"]).encode(ProtocolVersion::Resp2),
+        )
+        .unwrap();
+        let server = Arc::new(RedisServer::new(config.clone()));
+        server.load_aof().await.unwrap();
+        assert!(server.store.get_key(&"k".to_string()).await.is_some());
+        assert_eq!(
+            aof_files(&config.aof_dir()),
+            vec![
+                "appendonly.aof",
+                "appendonly.aof.1.incr.aof",
+                "appendonly.aof.manifest"
+            ]
+        );
+
+        // A file the manifest lists must be there.
+        std::fs::remove_file(config.aof_dir().join("appendonly.aof")).unwrap();
+        let server = Arc::new(RedisServer::new(config));
+        assert!(matches!(server.load_aof().await, Err(AofError::Missing(_))));
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-    use std::sync::Arc;
+    #[tokio::test]
+    async fn test_handle_dump_and_restore() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        handle_request(vec!["RPUSH", "l", "a", "b"], &mut client, &server).await;
+        handle_request(vec!["SET", "s", "v"], &mut client, &server).await;
+        assert_eq!(
+            handle_request(vec!["DUMP", "missing"], &mut client, &server).await,
+            Some(RespDataType::Null)
+        );
+        let Some(RespDataType::BulkString(list)) =
+            handle_request(vec!["DUMP", "l"], &mut client, &server).await
+        else {
+            panic!("DUMP replies with a bulk string");
+        };
+        let Some(RespDataType::BulkString(string)) =
+            handle_request(vec!["DUMP", "s"], &mut client, &server).await
+        else {
+            panic!("DUMP replies with a bulk string");
+        };
+        let restore = |args: &[&str], payload: &[u8]| {
+            let mut args: Vec<Bytes> = args
+                .iter()
+                .map(|arg| Bytes::from(arg.to_string()))
+                .collect();
+            args.insert(3, Bytes::copy_from_slice(payload));
+            args
+        };
 
-    use tokio::io::DuplexStream;
-    use tokio::sync::Mutex;
+        let args = restore(&["RESTORE", "copy", "0"], &list);
+        let result = handle_request(args.clone(), &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        assert_eq!(
+            server
+                .store
+                .get_list(&"copy".to_string())
+                .await
+                .unwrap()
+                .clone(),
+            VecDeque::from([Bytes::from("a"), Bytes::from("b")])
+        );
+        let result = handle_request(args, &mut client, &server).await;
+        assert_eq!(result, Some(RedisError::BusyKey.into()));
+
+        // Only strings can have a TTL, even one already passed: the list is left as it was.
+        for ttl in [&["60000", "REPLACE"][..], &["1", "REPLACE", "ABSTTL"]] {
+            let mut args = vec!["RESTORE", "copy"];
+            args.extend(ttl);
+            let result = handle_request(restore(&args, &list), &mut client, &server).await;
+            assert_eq!(
+                result,
+                Some(RespDataType::Error(
+                    "ERR TTL is only supported for string values".to_string()
+                ))
+            );
+            assert_eq!(
+                server
+                    .store
+                    .get_list(&"copy".to_string())
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+        }
 
-    use super::*;
-    use crate::commands::key_type::KeyTypeCommand;
-    use crate::commands::lpop;
-    use crate::commands::zadd::ZAddOptions;
-    use crate::commands::{
-        blpop::BLPopCommand, echo::EchoCommand, get::GetCommand, llen::LLenCommand,
-        lpop::LPopCommand, lpush::LPushCommand, lrange::LRangeCommand, ping::PingCommand,
-        rpush::RPushCommand, set::SetCommand, zadd::ZAddCommand, zcard::ZCardCommand,
-        zrange::ZRangeCommand, zrank::ZRankCommand, zrem::ZRemCommand, zscore::ZScoreCommand,
-    };
-    use crate::resp::RespDataType;
-    use crate::types::key_value::KeyValue;
-    use crate::types::sorted_set::SortedValue;
-    use crate::types::RedisType;
-    use crate::utils;
+        // Relative TTLs are propagated as absolute ones.
+        let args = restore(&["RESTORE", "copy", "60000", "REPLACE"], &string);
+        let result = handle_request(args, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::ok()));
+        let at = server
+            .store
+            .get_key_value(&"copy".to_string())
+            .await
+            .unwrap()
+            .expired_at_millis
+            .unwrap();
+        assert_eq!(client.argv[2], Bytes::from(at.to_string()));
+        assert_eq!(client.argv[5], Bytes::from("ABSTTL"));
+
+        let args = restore(&["RESTORE", "copy", "1", "REPLACE", "ABSTTL"], &string);
+        handle_request(args, &mut client, &server).await;
+        assert!(server.store.get_key(&"copy".to_string()).await.is_none());
+        assert_eq!(client.argv, vec![Bytes::from("DEL"), Bytes::from("copy")]);
+
+        let mut corrupt = string.to_vec();
+        corrupt[2] ^= 1;
+        let args = restore(&["RESTORE", "new", "0"], &corrupt);
+        let result = handle_request(args, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Error(
+                "ERR DUMP payload version or checksum are wrong".to_string()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handle_zrange_withscores() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        let command = RedisCommand::ZADD(ZAddCommand {
+            key: "zset_key".to_string(),
+            options: ZAddOptions::new(),
+            values: vec![
+                SortedValue {
+                    score: 1.0,
+                    member: Bytes::from("foo"),
+                },
+                SortedValue {
+                    score: 2.5,
+                    member: Bytes::from("bar"),
+                },
+            ],
+        });
+        RedisServer::handle_command(command, &mut client, &server).await;
+
+        let command = RedisCommand::ZRANGE(ZRangeCommand {
+            key: "zset_key".to_string(),
+            start: 0,
+            end: -1,
+            with_scores: true,
+        });
+        let result = RedisServer::handle_command(command.clone(), &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Array(vec![
+                RespDataType::bulk_string("foo"),
+                RespDataType::Double(1.0),
+                RespDataType::bulk_string("bar"),
+                RespDataType::Double(2.5),
+            ]))
+        );
 
-    fn new_client_for_test() -> (RedisClient<DuplexStream>, DuplexStream) {
-        let (server_stream, client_stream) = tokio::io::duplex(1024);
-        (RedisClient::mock_new(server_stream), client_stream)
+        client.protocol = ProtocolVersion::Resp3;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(
+            result,
+            Some(RespDataType::Array(vec![
+                RespDataType::Array(vec![
+                    RespDataType::bulk_string("foo"),
+                    RespDataType::Double(1.0),
+                ]),
+                RespDataType::Array(vec![
+                    RespDataType::bulk_string("bar"),
+                    RespDataType::Double(2.5),
+                ]),
+            ]))
+        );
     }
 
     #[tokio::test]
     async fn test_handle_rpush_new_list() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let command = RedisCommand::RPUSH(RPushCommand {
             key: "mylist".to_string(),
-            values: vec!["one".to_string(), "two".to_string()],
+            values: vec![Bytes::from("one"), Bytes::from("two")],
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         assert_eq!(result, RespDataType::Integer(2));
-        let list = store.get_list(&"mylist".to_string()).await.unwrap();
+        let list = server.store.get_list(&"mylist".to_string()).await.unwrap();
         assert_eq!(
             *list,
-            VecDeque::from(vec!["one".to_string(), "two".to_string()])
+            VecDeque::from(vec![Bytes::from("one"), Bytes::from("two")])
         );
     }
 
     #[tokio::test]
     async fn test_handle_lpush_new_list() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let command = RedisCommand::LPUSH(LPushCommand {
             key: "mylist".to_string(),
-            values: vec!["one".to_string(), "two".to_string()],
+            values: vec![Bytes::from("one"), Bytes::from("two")],
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         assert_eq!(result, RespDataType::Integer(2));
-        let list = store.get_list(&"mylist".to_string()).await.unwrap();
+        let list = server.store.get_list(&"mylist".to_string()).await.unwrap();
         assert_eq!(
             *list,
-            VecDeque::from(["two".to_string(), "one".to_string()])
+            VecDeque::from([Bytes::from("two"), Bytes::from("one")])
         );
     }
 
     #[tokio::test]
     async fn test_handle_rpush_existing_list() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
-                RedisType::List(VecDeque::from(["zero".to_string()])),
+                RedisType::List(VecDeque::from([Bytes::from("zero")])),
             )
             .await;
 
         let command = RedisCommand::RPUSH(RPushCommand {
             key: "mylist".to_string(),
-            values: vec!["one".to_string(), "two".to_string()],
+            values: vec![Bytes::from("one"), Bytes::from("two")],
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         assert_eq!(result, RespDataType::Integer(3));
-        let list = store.get_list(&"mylist".to_string()).await.unwrap();
+        let list = server.store.get_list(&"mylist".to_string()).await.unwrap();
         assert_eq!(
             *list,
-            VecDeque::from(["zero".to_string(), "one".to_string(), "two".to_string()])
+            VecDeque::from([Bytes::from("zero"), Bytes::from("one"), Bytes::from("two")])
         );
     }
 
     #[tokio::test]
     async fn test_handle_lpush_existing_list() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
-                RedisType::List(VecDeque::from(["zero".to_string()])),
+                RedisType::List(VecDeque::from([Bytes::from("zero")])),
             )
             .await;
 
         let command = RedisCommand::LPUSH(LPushCommand {
             key: "mylist".to_string(),
-            values: vec!["one".to_string(), "two".to_string()],
+            values: vec![Bytes::from("one"), Bytes::from("two")],
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         assert_eq!(result, RespDataType::Integer(3));
-        let list = store.get_list(&"mylist".to_string()).await.unwrap();
+        let list = server.store.get_list(&"mylist".to_string()).await.unwrap();
         assert_eq!(
             *list,
-            VecDeque::from(["two".to_string(), "one".to_string(), "zero".to_string(),])
+            VecDeque::from([Bytes::from("two"), Bytes::from("one"), Bytes::from("zero"),])
         );
     }
 
     #[tokio::test]
     async fn test_handle_lrange_non_existent_key() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let command = RedisCommand::LRANGE(LRangeCommand {
             key: "no-such-list".to_string(),
             start: 0,
             end: 1,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -251,9 +3807,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_lrange_empty_list() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(&"mylist".to_string(), RedisType::List(VecDeque::new()))
             .await;
 
@@ -263,7 +3820,7 @@ mod tests {
             end: 1,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -272,15 +3829,16 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_lrange_full_range() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
                 RedisType::List(VecDeque::from([
-                    "one".to_string(),
-                    "two".to_string(),
-                    "three".to_string(),
+                    Bytes::from("one"),
+                    Bytes::from("two"),
+                    Bytes::from("three"),
                 ])),
             )
             .await;
@@ -291,7 +3849,7 @@ mod tests {
             end: 2,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -307,17 +3865,18 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_lrange_negative_range() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
                 RedisType::List(VecDeque::from([
-                    "a".to_string(),
-                    "b".to_string(),
-                    "c".to_string(),
-                    "d".to_string(),
-                    "e".to_string(),
+                    Bytes::from("a"),
+                    Bytes::from("b"),
+                    Bytes::from("c"),
+                    Bytes::from("d"),
+                    Bytes::from("e"),
                 ])),
             )
             .await;
@@ -327,7 +3886,7 @@ mod tests {
             end: -1,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -345,7 +3904,7 @@ mod tests {
             end: -3,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -364,7 +3923,7 @@ mod tests {
             end: -1,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -382,12 +3941,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_lrange_end_out_of_bounds() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
-                RedisType::List(VecDeque::from(["one".to_string()])),
+                RedisType::List(VecDeque::from([Bytes::from("one")])),
             )
             .await;
 
@@ -397,7 +3957,7 @@ mod tests {
             end: 10,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -409,12 +3969,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_lrange_start_out_of_bounds() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
-                RedisType::List(VecDeque::from(["one".to_string()])),
+                RedisType::List(VecDeque::from([Bytes::from("one")])),
             )
             .await;
 
@@ -424,7 +3985,7 @@ mod tests {
             end: 10,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -433,12 +3994,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_llen() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
-                RedisType::List(VecDeque::from(["one".to_string()])),
+                RedisType::List(VecDeque::from([Bytes::from("one")])),
             )
             .await;
 
@@ -446,7 +4008,7 @@ mod tests {
             key: "mylist".to_string(),
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -455,16 +4017,17 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_lpop() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
                 RedisType::List(VecDeque::from([
-                    "1".to_string(),
-                    "2".to_string(),
-                    "3".to_string(),
-                    "4".to_string(),
+                    Bytes::from("1"),
+                    Bytes::from("2"),
+                    Bytes::from("3"),
+                    Bytes::from("4"),
                 ])),
             )
             .await;
@@ -473,29 +4036,30 @@ mod tests {
             key: "mylist".to_string(),
             count: 1,
         });
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
         assert_eq!(result, RespDataType::bulk_string("1"));
-        let list = store.get_list(&"mylist".to_string()).await.unwrap();
+        let list = server.store.get_list(&"mylist".to_string()).await.unwrap();
         assert_eq!(
             *list,
-            VecDeque::from(["2".to_string(), "3".to_string(), "4".to_string()])
+            VecDeque::from([Bytes::from("2"), Bytes::from("3"), Bytes::from("4")])
         );
     }
 
     #[tokio::test]
     async fn test_handle_lpop_multiple() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"mylist".to_string(),
                 RedisType::List(VecDeque::from([
-                    "1".to_string(),
-                    "2".to_string(),
-                    "3".to_string(),
-                    "4".to_string(),
+                    Bytes::from("1"),
+                    Bytes::from("2"),
+                    Bytes::from("3"),
+                    Bytes::from("4"),
                 ])),
             )
             .await;
@@ -505,7 +4069,7 @@ mod tests {
             count: 2,
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -516,17 +4080,17 @@ mod tests {
                 RespDataType::bulk_string("2")
             ])
         );
-        let list = store.get_list(&"mylist".to_string()).await.unwrap();
-        assert_eq!(*list, VecDeque::from(["3".to_string(), "4".to_string()]));
+        let list = server.store.get_list(&"mylist".to_string()).await.unwrap();
+        assert_eq!(*list, VecDeque::from([Bytes::from("3"), Bytes::from("4")]));
     }
 
     #[tokio::test]
     async fn test_handle_ping() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let command = RedisCommand::PING(PingCommand);
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -535,13 +4099,13 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_echo() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let command = RedisCommand::ECHO(EchoCommand {
-            message: "hello world".to_string(),
+            message: Bytes::from("hello world"),
         });
 
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -550,12 +4114,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_get_non_existent_key_in_server() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let get_command = RedisCommand::GET(GetCommand {
             key: "nonexistent".to_string(),
         });
-        let get_result = RedisServer::handle_command(get_command, &client, &store)
+        let get_result = RedisServer::handle_command(get_command, &mut client, &server)
             .await
             .unwrap();
         assert_eq!(get_result, RespDataType::Null);
@@ -563,21 +4127,22 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_get_existing_key() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let key = "mykey".to_string();
         let value = KeyValue {
-            value: "myvalue".to_string(),
+            value: Bytes::from("myvalue"),
             expired_at_millis: None,
         };
-        store
+        server
+            .store
             .create_or_update_key(&key.clone(), RedisType::String(value))
             .await;
 
         let command = RedisCommand::GET(GetCommand {
             key: key.to_string(),
         });
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -586,21 +4151,22 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_get_expired_key() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let key = "mykey".to_string();
         let value = KeyValue {
-            value: "myvalue".to_string(),
+            value: Bytes::from("myvalue"),
             expired_at_millis: Some(utils::now_millis() - 1),
         };
-        store
+        server
+            .store
             .create_or_update_key(&key.clone(), RedisType::String(value))
             .await;
 
         let command = RedisCommand::GET(GetCommand {
             key: key.to_string(),
         });
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -609,11 +4175,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_set() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let key = "mykey".to_string();
         let value = KeyValue {
-            value: "myvalue".to_string(),
+            value: Bytes::from("myvalue"),
             expired_at_millis: None,
         };
 
@@ -621,23 +4187,103 @@ mod tests {
             key: key.clone(),
             value,
         });
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         assert_eq!(result, RespDataType::ok());
-        let stored_value = store.get_key_value(&key).await.unwrap();
+        let stored_value = server.store.get_key_value(&key).await.unwrap();
         assert_eq!(stored_value.value, "myvalue");
         assert!(stored_value.expired_at_millis.is_none());
     }
 
+    #[tokio::test]
+    async fn test_handle_set_replaces_any_type() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        let key = "mykey".to_string();
+        let command = RedisCommand::RPUSH(RPushCommand {
+            key: key.clone(),
+            values: vec![Bytes::from("a")],
+        });
+        RedisServer::handle_command(command, &mut client, &server).await;
+
+        let command = RedisCommand::SET(SetCommand {
+            key: key.clone(),
+            value: KeyValue {
+                value: Bytes::from("myvalue"),
+                expired_at_millis: None,
+            },
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server)
+            .await
+            .unwrap();
+
+        assert_eq!(result, RespDataType::ok());
+        let stored_value = server.store.get_key_value(&key).await.unwrap();
+        assert_eq!(stored_value.value, "myvalue");
+    }
+
+    #[tokio::test]
+    async fn test_handle_set_get_binary_value() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        let binary = Bytes::from_static(b"\x89PNG\r\n\x1a\n\x00\xff");
+
+        let command = RedisCommand::build(vec![RespDataType::Array(vec![
+            RespDataType::bulk_string("SET"),
+            RespDataType::bulk_string("image"),
+            RespDataType::bulk_string(&binary),
+        ])])
+        .unwrap()
+        .remove(0);
+        RedisServer::handle_command(command, &mut client, &server).await;
+
+        let command = RedisCommand::GET(GetCommand {
+            key: "image".to_string(),
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server)
+            .await
+            .unwrap();
+
+        assert_eq!(result, RespDataType::bulk_string(binary));
+    }
+
+    #[tokio::test]
+    async fn test_handle_rpush_binary_values() {
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        let command = RedisCommand::RPUSH(RPushCommand {
+            key: "mylist".to_string(),
+            values: vec![Bytes::from_static(b"\x00\r\n"), Bytes::from_static(b"\xfe")],
+        });
+        RedisServer::handle_command(command, &mut client, &server).await;
+
+        let command = RedisCommand::LRANGE(LRangeCommand {
+            key: "mylist".to_string(),
+            start: 0,
+            end: -1,
+        });
+        let result = RedisServer::handle_command(command, &mut client, &server)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result,
+            RespDataType::Array(vec![
+                RespDataType::BulkString(Bytes::from_static(b"\x00\r\n")),
+                RespDataType::BulkString(Bytes::from_static(b"\xfe")),
+            ])
+        );
+    }
+
     #[tokio::test]
     async fn test_handle_set_with_px() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let key = "mykey".to_string();
         let value = KeyValue {
-            value: "myvalue".to_string(),
+            value: Bytes::from("myvalue"),
             expired_at_millis: Some(utils::now_millis() + 10000),
         };
 
@@ -645,24 +4291,25 @@ mod tests {
             key: key.clone(),
             value,
         });
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         assert_eq!(result, RespDataType::ok());
-        let stored_value = store.get_key_value(&key).await.unwrap();
+        let stored_value = server.store.get_key_value(&key).await.unwrap();
         assert_eq!(stored_value.value, "myvalue");
         assert!(stored_value.expired_at_millis.is_some()); // Access public field
     }
 
     #[tokio::test]
     async fn test_handle_blpop_item_exists() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
-        store
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
+        server
+            .store
             .create_or_update_key(
                 &"myblist".to_string(),
-                RedisType::List(VecDeque::from(["one".to_string()])),
+                RedisType::List(VecDeque::from([Bytes::from("one")])),
             )
             .await;
 
@@ -670,7 +4317,7 @@ mod tests {
             key: "myblist".to_string(),
             timeout: 0.0,
         });
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
@@ -685,8 +4332,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_blpop_timeout() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let timeout_secs = 0.1; // Timeout curto para o teste
 
         let command = RedisCommand::BLPOP(BLPopCommand {
@@ -694,7 +4341,7 @@ mod tests {
             timeout: timeout_secs,
         });
         let start = tokio::time::Instant::now();
-        let result = RedisServer::handle_command(command, &client, &store)
+        let result = RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
         let duration = start.elapsed();
@@ -706,32 +4353,32 @@ mod tests {
     #[tokio::test]
     async fn test_handle_blpop_waits_for_rpush() {
         // 1. Setup: Store compartilhado e um cliente para o BLPOP
-        let store = Arc::new(RedisStore::new());
-        let (blpop_client, _blpop_stream) = new_client_for_test();
+        let server = new_server_for_test();
+        let mut blpop_client = RedisClient::new();
         let key = "myblist_wait".to_string();
 
         // 2. Task 1: Executa o BLPOP em uma nova task.
         // Ele ficará bloqueado pois a lista 'myblist_wait' está vazia.
-        let store_for_blpop = store.clone();
+        let server_for_blpop = server.clone();
         let key_for_blpop = key.clone();
         let blpop_handle = tokio::spawn(async move {
             let command = RedisCommand::BLPOP(BLPopCommand {
                 key: key_for_blpop,
                 timeout: 2.0,
             }); // Timeout de 2s
-            RedisServer::handle_command(command, &blpop_client, &store_for_blpop).await
+            RedisServer::handle_command(command, &mut blpop_client, &server_for_blpop).await
         });
 
         // 3. Pausa breve para garantir que o BLPOP já começou a esperar.
         tokio::time::sleep(Duration::from_millis(50)).await;
 
         // 4. Task 2: Outro cliente faz um RPUSH, que deve notificar e desbloquear o BLPOP.
-        let (rpush_client, _rpush_stream) = new_client_for_test();
+        let mut rpush_client = RedisClient::new();
         let rpush_command = RedisCommand::RPUSH(RPushCommand {
             key: key.clone(),
-            values: vec!["value1".to_string()],
+            values: vec![Bytes::from("value1")],
         });
-        RedisServer::handle_command(rpush_command, &rpush_client, &store).await;
+        RedisServer::handle_command(rpush_command, &mut rpush_client, &server).await;
 
         // 5. Aguarda o resultado da task do BLPOP e verifica se está correto.
         let blpop_result = blpop_handle.await.unwrap().unwrap();
@@ -745,39 +4392,82 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_handle_blpop_pops_with_exec_lock() {
+        let server = new_server_for_test();
+        let key = "myblist_locked".to_string();
+        let server_for_blpop = server.clone();
+        let key_for_blpop = key.clone();
+        let blpop_handle = tokio::spawn(async move {
+            let command = RedisCommand::BLPOP(BLPopCommand {
+                key: key_for_blpop,
+                timeout: 2.0,
+            });
+            RedisServer::handle_command(command, &mut RedisClient::new(), &server_for_blpop).await
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // Woken while the dataset is being captured, it doesn't pop until that is done.
+        let exclusive = server.exec_lock.write().await;
+        server
+            .store
+            .insert(
+                key.clone(),
+                RedisType::List(VecDeque::from([Bytes::from("1")])),
+            )
+            .await;
+        server.store.notify_key_modified(&key).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blpop_handle.is_finished());
+        assert_eq!(server.store.get_list(&key).await.unwrap().len(), 1);
+        drop(exclusive);
+
+        assert_eq!(
+            blpop_handle.await.unwrap().unwrap(),
+            RespDataType::Array(vec![
+                RespDataType::bulk_string(&key),
+                RespDataType::bulk_string("1")
+            ])
+        );
+        // The pop is propagated once, as LPOP.
+        let lpop = RespDataType::new_array(vec!["LPOP", &key]).encode(ProtocolVersion::Resp2);
+        assert_eq!(server.replication.offset(), lpop.len() as u64);
+        assert_eq!(server.persistence.dirty.load(Ordering::SeqCst), 1);
+    }
+
     #[tokio::test]
     async fn test_handle_zadd() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
 
         let command = RedisCommand::ZADD(ZAddCommand {
             key: "mylist".to_string(),
             options: ZAddOptions::new(),
             values: vec![
                 SortedValue {
-                    member: "1".to_string(),
+                    member: Bytes::from("1"),
                     score: 0.1,
                 },
                 SortedValue {
-                    member: "2".to_string(),
+                    member: Bytes::from("2"),
                     score: 1.0,
                 },
                 SortedValue {
-                    member: "2".to_string(),
+                    member: Bytes::from("2"),
                     score: 2.0,
                 },
             ],
         });
 
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
 
         assert_eq!(result, Some(RespDataType::Integer(2)));
     }
 
     #[tokio::test]
     async fn test_handle_zrank() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
 
         let command = RedisCommand::ZADD(ZAddCommand {
             key: "zset_key".to_string(),
@@ -785,69 +4475,69 @@ mod tests {
             values: vec![
                 SortedValue {
                     score: 100.0,
-                    member: "foo".to_string(),
+                    member: Bytes::from("foo"),
                 },
                 SortedValue {
                     score: 100.0,
-                    member: "bar".to_string(),
+                    member: Bytes::from("bar"),
                 },
                 SortedValue {
                     score: 20.0,
-                    member: "baz".to_string(),
+                    member: Bytes::from("baz"),
                 },
                 SortedValue {
                     score: 30.1,
-                    member: "caz".to_string(),
+                    member: Bytes::from("caz"),
                 },
                 SortedValue {
                     score: 40.2,
-                    member: "paz".to_string(),
+                    member: Bytes::from("paz"),
                 },
             ],
         });
 
-        RedisServer::handle_command(command, &client, &store).await;
+        RedisServer::handle_command(command, &mut client, &server).await;
 
         let command = RedisCommand::ZRANK(ZRankCommand {
             key: "other_key".to_string(),
-            member: "caz".to_string(),
+            member: Bytes::from("caz"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Null));
 
         let command = RedisCommand::ZRANK(ZRankCommand {
             key: "zset_key".to_string(),
-            member: "caz".to_string(),
+            member: Bytes::from("caz"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(1)));
 
         let command = RedisCommand::ZRANK(ZRankCommand {
             key: "zset_key".to_string(),
-            member: "baz".to_string(),
+            member: Bytes::from("baz"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(0)));
 
         let command = RedisCommand::ZRANK(ZRankCommand {
             key: "zset_key".to_string(),
-            member: "foo".to_string(),
+            member: Bytes::from("foo"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(4)));
 
         let command = RedisCommand::ZRANK(ZRankCommand {
             key: "zset_key".to_string(),
-            member: "bar".to_string(),
+            member: Bytes::from("bar"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(3)));
     }
 
     #[tokio::test]
     async fn test_handle_zrange() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
 
         let command: RedisCommand = RedisCommand::ZADD(ZAddCommand {
             key: "zset_key".to_string(),
@@ -855,59 +4545,63 @@ mod tests {
             values: vec![
                 SortedValue {
                     score: 100.0,
-                    member: "foo".to_string(),
+                    member: Bytes::from("foo"),
                 },
                 SortedValue {
                     score: 100.0,
-                    member: "bar".to_string(),
+                    member: Bytes::from("bar"),
                 },
                 SortedValue {
                     score: 20.0,
-                    member: "baz".to_string(),
+                    member: Bytes::from("baz"),
                 },
                 SortedValue {
                     score: 30.1,
-                    member: "caz".to_string(),
+                    member: Bytes::from("caz"),
                 },
                 SortedValue {
                     score: 40.2,
-                    member: "paz".to_string(),
+                    member: Bytes::from("paz"),
                 },
             ],
         });
 
-        RedisServer::handle_command(command, &client, &store).await;
+        RedisServer::handle_command(command, &mut client, &server).await;
 
         let command = RedisCommand::ZRANGE(ZRangeCommand {
             key: "other_key".to_string(),
             start: 0,
             end: 1,
+            with_scores: false,
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Array(vec![])));
 
         let command = RedisCommand::ZRANGE(ZRangeCommand {
             key: "zset_key".to_string(),
             start: 10,
             end: 11,
+            with_scores: false,
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Array(vec![])));
 
         let command = RedisCommand::ZRANGE(ZRangeCommand {
             key: "zset_key".to_string(),
             start: 4,
             end: 2,
+            with_scores: false,
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Array(vec![])));
 
         let command = RedisCommand::ZRANGE(ZRangeCommand {
             key: "zset_key".to_string(),
             start: 0,
             end: 10,
+            with_scores: false,
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(
             result,
             Some(RespDataType::Array(vec![
@@ -923,8 +4617,9 @@ mod tests {
             key: "zset_key".to_string(),
             start: 2,
             end: 4,
+            with_scores: false,
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(
             result,
             Some(RespDataType::Array(vec![
@@ -937,8 +4632,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_zcard() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
 
         let command: RedisCommand = RedisCommand::ZADD(ZAddCommand {
             key: "zset_key".to_string(),
@@ -946,46 +4641,46 @@ mod tests {
             values: vec![
                 SortedValue {
                     score: 100.0,
-                    member: "foo".to_string(),
+                    member: Bytes::from("foo"),
                 },
                 SortedValue {
                     score: 100.0,
-                    member: "bar".to_string(),
+                    member: Bytes::from("bar"),
                 },
                 SortedValue {
                     score: 20.0,
-                    member: "baz".to_string(),
+                    member: Bytes::from("baz"),
                 },
                 SortedValue {
                     score: 30.1,
-                    member: "caz".to_string(),
+                    member: Bytes::from("caz"),
                 },
                 SortedValue {
                     score: 40.2,
-                    member: "paz".to_string(),
+                    member: Bytes::from("paz"),
                 },
             ],
         });
 
-        RedisServer::handle_command(command, &client, &store).await;
+        RedisServer::handle_command(command, &mut client, &server).await;
 
         let command = RedisCommand::ZCARD(ZCardCommand {
             key: "other_key".to_string(),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(0)));
 
         let command = RedisCommand::ZCARD(ZCardCommand {
             key: "zset_key".to_string(),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(5)));
     }
 
     #[tokio::test]
     async fn test_handle_zscore() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
 
         let command: RedisCommand = RedisCommand::ZADD(ZAddCommand {
             key: "zset_key".to_string(),
@@ -993,55 +4688,55 @@ mod tests {
             values: vec![
                 SortedValue {
                     score: 100.0,
-                    member: "foo".to_string(),
+                    member: Bytes::from("foo"),
                 },
                 SortedValue {
                     score: 100.0,
-                    member: "bar".to_string(),
+                    member: Bytes::from("bar"),
                 },
                 SortedValue {
                     score: 20.0,
-                    member: "baz".to_string(),
+                    member: Bytes::from("baz"),
                 },
                 SortedValue {
                     score: 30.1,
-                    member: "caz".to_string(),
+                    member: Bytes::from("caz"),
                 },
                 SortedValue {
                     score: 40.2,
-                    member: "paz".to_string(),
+                    member: Bytes::from("paz"),
                 },
             ],
         });
 
-        RedisServer::handle_command(command, &client, &store).await;
+        RedisServer::handle_command(command, &mut client, &server).await;
 
         let command = RedisCommand::ZSCORE(ZScoreCommand {
             key: "other_key".to_string(),
-            member: "foo".to_string(),
+            member: Bytes::from("foo"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Null));
 
         let command = RedisCommand::ZSCORE(ZScoreCommand {
             key: "zset_key".to_string(),
-            member: "other".to_string(),
+            member: Bytes::from("other"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Null));
 
         let command = RedisCommand::ZSCORE(ZScoreCommand {
             key: "zset_key".to_string(),
-            member: "paz".to_string(),
+            member: Bytes::from("paz"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
-        assert_eq!(result, Some(RespDataType::bulk_string("40.2")));
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
+        assert_eq!(result, Some(RespDataType::Double(40.2)));
     }
 
     #[tokio::test]
     async fn test_handle_zrem() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
 
         let command: RedisCommand = RedisCommand::ZADD(ZAddCommand {
             key: "zset_key".to_string(),
@@ -1049,65 +4744,288 @@ mod tests {
             values: vec![
                 SortedValue {
                     score: 100.0,
-                    member: "foo".to_string(),
+                    member: Bytes::from("foo"),
                 },
                 SortedValue {
                     score: 100.0,
-                    member: "bar".to_string(),
+                    member: Bytes::from("bar"),
                 },
             ],
         });
 
-        RedisServer::handle_command(command, &client, &store).await;
+        RedisServer::handle_command(command, &mut client, &server).await;
 
         let command = RedisCommand::ZREM(ZRemCommand {
             key: "other_key".to_string(),
-            member: "foo".to_string(),
+            member: Bytes::from("foo"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(0)));
 
         let command = RedisCommand::ZREM(ZRemCommand {
             key: "zset_key".to_string(),
-            member: "other".to_string(),
+            member: Bytes::from("other"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(0)));
 
         let command = RedisCommand::ZREM(ZRemCommand {
             key: "zset_key".to_string(),
-            member: "foo".to_string(),
+            member: Bytes::from("foo"),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::Integer(1)));
     }
 
     #[tokio::test]
     async fn test_handle_type() {
-        let (client, _server_stream) = new_client_for_test();
-        let store = Arc::new(RedisStore::new());
+        let mut client = RedisClient::new();
+        let server = new_server_for_test();
         let command = RedisCommand::SET(SetCommand {
             key: "key".to_string(),
             value: KeyValue {
-                value: "Value".to_string(),
+                value: Bytes::from("Value"),
                 expired_at_millis: None,
             },
         });
 
-        RedisServer::handle_command(command, &client, &store)
+        RedisServer::handle_command(command, &mut client, &server)
             .await
             .unwrap();
 
         let command = RedisCommand::TYPE(KeyTypeCommand {
             key: "zset_key".to_string(),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::simple_string("none")));
 
         let command = RedisCommand::TYPE(KeyTypeCommand {
             key: "key".to_string(),
         });
-        let result = RedisServer::handle_command(command, &client, &store).await;
+        let result = RedisServer::handle_command(command, &mut client, &server).await;
         assert_eq!(result, Some(RespDataType::simple_string("string")));
     }
+
+    async fn read_line(stream: &mut DuplexStream) -> String {
+        let mut line = Vec::new();
+        while !line.ends_with(b"\r\n") {
+            line.push(stream.read_u8().await.unwrap());
+        }
+        String::from_utf8(line).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_psync_full_resync_and_propagation() {
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        handle_request(vec!["SET", "before", "1"], &mut client, &server).await;
+        let before = b"*3\r\n$3\r\nSET\r\n$6\r\nbefore\r\n$1\r\n1\r\n".len();
+
+        let (server_stream, mut replica_stream) = tokio::io::duplex(64 * 1024);
+        let handle = tokio::spawn(RedisServer::client_process(
+            Connection::new(server_stream),
+            RedisClient::new(),
+            server.clone(),
+        ));
+        replica_stream
+            .write_all(b"REPLCONF listening-port 6380\r\nREPLCONF capa psync2\r\nPSYNC ? -1\r\n")
+            .await
+            .unwrap();
+        assert_eq!(read_line(&mut replica_stream).await, "+OK\r\n");
+        assert_eq!(read_line(&mut replica_stream).await, "+OK\r\n");
+        assert_eq!(
+            read_line(&mut replica_stream).await,
+            format!("+FULLRESYNC {} {}\r\n", server.replication.replid(), before)
+        );
+        let len = read_line(&mut replica_stream).await;
+        let mut payload = vec![0; len[1..len.len() - 2].parse().unwrap()];
+        replica_stream.read_exact(&mut payload).await.unwrap();
+        let snapshot = rdb::parse(&payload).unwrap();
+        assert_eq!(snapshot.entries.len(), 1);
+        assert_eq!(snapshot.entries[0].key, "before");
+
+        handle_request(vec!["SET", "after", "2"], &mut client, &server).await;
+        handle_request(vec!["GET", "after"], &mut client, &server).await;
+        let expected = b"*3\r\n$3\r\nSET\r\n$5\r\nafter\r\n$1\r\n2\r\n";
+        let mut propagated = vec![0; expected.len()];
+        replica_stream.read_exact(&mut propagated).await.unwrap();
+        assert_eq!(propagated, expected);
+        let offset = before + expected.len();
+        assert_eq!(server.replication.offset(), offset as u64);
+
+        // `WAIT` asks for an ACK right away and returns once it comes.
+        let wait = tokio::spawn({
+            let server = server.clone();
+            async move { handle_request(vec!["WAIT", "1", "0"], &mut client, &server).await }
+        });
+        let getack = b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n";
+        let mut propagated = vec![0; getack.len()];
+        replica_stream.read_exact(&mut propagated).await.unwrap();
+        assert_eq!(propagated, getack);
+        let ack = format!("REPLCONF ACK {}\r\n", offset);
+        replica_stream.write_all(ack.as_bytes()).await.unwrap();
+        assert_eq!(wait.await.unwrap(), Some(RespDataType::Integer(1)));
+
+        let mut client = RedisClient::new();
+        let slave = format!(
+            "slave0:ip=?,port=6380,state=online,offset={},lag=0\r\n",
+            offset
+        );
+        let mut info = String::new();
+        for _ in 0..100 {
+            let Some(RespDataType::VerbatimString(_, text)) =
+                handle_request(vec!["INFO", "replication"], &mut client, &server).await
+            else {
+                panic!("INFO should reply with text");
+            };
+            info = String::from_utf8(text).unwrap();
+            if info.contains(&slave) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(info.starts_with("# Replication\r\nrole:master\r\nconnected_slaves:1\r\n"));
+        assert!(info.contains(&slave), "{}", info);
+
+        drop(replica_stream);
+        handle.await.unwrap();
+        assert!(server.replication.replicas().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_replica_sync_and_stream() {
+        let master = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let master_port = master.local_addr().unwrap().port();
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        handle_request(vec!["SET", "stale", "1"], &mut client, &server).await;
+        server.config_mut().replicaof = Some(("127.0.0.1".to_string(), master_port));
+        server.update_replication();
+
+        let (stream, _) = master.accept().await.unwrap();
+        let mut link = Connection::new(stream);
+        let mut expect = async |args: Vec<&str>, reply: &[u8]| {
+            let request = link.read_request().await.unwrap().unwrap();
+            assert_eq!(request, RespDataType::new_array(args));
+            link.write_bytes(reply).await.unwrap();
+        };
+        expect(vec!["PING"], b"+PONG\r\n").await;
+        expect(vec!["REPLCONF", "listening-port", "0"], b"+OK\r\n").await;
+        expect(vec!["REPLCONF", "capa", "psync2"], b"+OK\r\n").await;
+        let replid = "a".repeat(40);
+        let fullresync = format!("+FULLRESYNC {} 100\r\n", replid);
+        expect(vec!["PSYNC", "?", "-1"], fullresync.as_bytes()).await;
+
+        let mut data = HashMap::new();
+        data.insert(
+            "synced".to_string(),
+            RedisType::String(KeyValue {
+                value: Bytes::from("1"),
+                expired_at_millis: None,
+            }),
+        );
+        let payload = rdb::encode(&data, &[], utils::now_millis());
+        let set = b"*3\r\n$3\r\nSET\r\n$3\r\nnew\r\n$1\r\n2\r\n";
+        let mut stream = format!("\n${}\r\n", payload.len()).into_bytes();
+        stream.extend(payload);
+        stream.extend(set);
+        stream.extend(b"*3\r\n$8\r\nREPLCONF\r\n$6\r\nGETACK\r\n$1\r\n*\r\n");
+        link.write_bytes(&stream).await.unwrap();
+
+        // The periodic ACK may come first, the one answering GETACK counts the SET.
+        let ack = format!("{}", 100 + set.len());
+        loop {
+            let request = link.read_request().await.unwrap().unwrap();
+            if request == RespDataType::new_array(vec!["REPLCONF", "ACK", &ack]) {
+                break;
+            }
+        }
+        assert_eq!(server.replication.replid(), replid);
+        assert_eq!(
+            handle_request(vec!["GET", "new"], &mut client, &server).await,
+            Some(RespDataType::bulk_string("2"))
+        );
+        assert_eq!(
+            handle_request(vec!["GET", "stale"], &mut client, &server).await,
+            Some(RespDataType::Null)
+        );
+        assert_eq!(
+            handle_request(vec!["SET", "new", "3"], &mut client, &server).await,
+            Some(RedisError::ReadOnly.into())
+        );
+        let Some(RespDataType::VerbatimString(_, info)) =
+            handle_request(vec!["INFO", "replication"], &mut client, &server).await
+        else {
+            panic!("INFO should reply with text");
+        };
+        let info = String::from_utf8(info).unwrap();
+        assert!(info.contains("role:slave\r\n"), "{}", info);
+        assert!(info.contains("master_link_status:up\r\n"), "{}", info);
+
+        server.config_mut().replicaof = None;
+        server.update_replication();
+        // Only periodic ACKs may be left before the link closes.
+        while link.read_request().await.unwrap().is_some() {}
+        assert_ne!(server.replication.replid(), replid);
+        assert_eq!(
+            handle_request(vec!["SET", "new", "3"], &mut client, &server).await,
+            Some(RespDataType::ok())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_psync_continue_from_backlog() {
+        let server = new_server_for_test();
+        let mut client = RedisClient::new();
+        let psync = |args: &str| {
+            let (server_stream, mut replica_stream) = tokio::io::duplex(64 * 1024);
+            let handle = tokio::spawn(RedisServer::client_process(
+                Connection::new(server_stream),
+                RedisClient::new(),
+                server.clone(),
+            ));
+            let request = format!("PSYNC {}\r\n", args);
+            async move {
+                replica_stream.write_all(request.as_bytes()).await.unwrap();
+                (replica_stream, handle)
+            }
+        };
+
+        let (mut first, first_handle) = psync("? -1").await;
+        let fullresync = read_line(&mut first).await;
+        let len = read_line(&mut first).await;
+        let mut payload = vec![0; len[1..len.len() - 2].parse().unwrap()];
+        first.read_exact(&mut payload).await.unwrap();
+        let replid = server.replication.replid();
+        assert_eq!(fullresync, format!("+FULLRESYNC {} 0\r\n", replid));
+        drop(first);
+        first_handle.await.unwrap();
+
+        // Missed while disconnected, kept in the backlog.
+        handle_request(vec!["SET", "k", "v"], &mut client, &server).await;
+        let set = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$1\r\nv\r\n";
+        let (mut second, _) = psync(&format!("{} 1", replid)).await;
+        assert_eq!(
+            read_line(&mut second).await,
+            format!("+CONTINUE {}\r\n", replid)
+        );
+        let mut missed = vec![0; set.len()];
+        second.read_exact(&mut missed).await.unwrap();
+        assert_eq!(missed, set);
+
+        // An unknown history or an offset past the backlog needs a full resync.
+        let (mut third, _) = psync("0123 1").await;
+        assert!(read_line(&mut third).await.starts_with("+FULLRESYNC"));
+        let (mut fourth, _) = psync(&format!("{} 100", replid)).await;
+        assert!(read_line(&mut fourth).await.starts_with("+FULLRESYNC"));
+
+        // After a promotion, replicas of the previous history resume from the new one.
+        server.replication.shift_replid(replication::new_replid());
+        let offset = set.len() + 1;
+        let (mut fifth, _) = psync(&format!("{} {}", replid, offset)).await;
+        assert_eq!(
+            read_line(&mut fifth).await,
+            format!("+CONTINUE {}\r\n", server.replication.replid())
+        );
+    }
 }
@@ -1,14 +1,19 @@
 use std::{
-    collections::{hash_map::Entry, HashMap, VecDeque},
-    sync::Arc,
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Weak,
+    },
 };
 
 use anyhow::Ok;
+use bytes::Bytes;
 use tokio::sync::{
     broadcast::{self, Receiver, Sender},
     MappedMutexGuard, Mutex, MutexGuard,
 };
 
+use crate::error::RedisError;
 use crate::types::RedisType;
 use crate::types::{key_value::KeyValue, sorted_set::SortedSet, stream::RedisStream};
 
@@ -16,16 +21,28 @@ use crate::types::{key_value::KeyValue, sorted_set::SortedSet, stream::RedisStre
 pub enum KeyResult {
     /// The key was created sucessfully
     Created,
-    /// The key already exists, has same type and was updated
+    /// The key already exists and its value was replaced, whatever its type
     Updated,
     /// The key already exists and has different type
-    Error(String),
+    Error(RedisError),
 }
 
 #[derive(Debug)]
 pub struct RedisStore {
     data: Mutex<HashMap<String, RedisType>>,
     pub key_notifiers: Mutex<HashMap<String, Sender<()>>>,
+    /// Keys watched by clients with `WATCH`, each with the flags set when it changes. A flag
+    /// goes away with its client.
+    watchers: std::sync::Mutex<HashMap<String, Vec<Weak<AtomicBool>>>>,
+    /// Keys modified while a transaction or a script runs, `None` otherwise: the clients
+    /// blocked on them are only woken up once it is done.
+    deferred_wakeups: std::sync::Mutex<Option<HashSet<String>>>,
+    /// Estimate of the memory used by the keys and values, what `maxmemory` is checked against.
+    used_memory: AtomicUsize,
+}
+
+fn usage(key: &str, value: &RedisType) -> usize {
+    key.len() + value.memory_usage()
 }
 
 impl RedisStore {
@@ -33,6 +50,9 @@ impl RedisStore {
         Self {
             data: Mutex::new(HashMap::new()),
             key_notifiers: Mutex::new(HashMap::new()),
+            watchers: std::sync::Mutex::new(HashMap::new()),
+            deferred_wakeups: std::sync::Mutex::new(None),
+            used_memory: AtomicUsize::new(0),
         }
     }
 
@@ -53,34 +73,68 @@ impl RedisStore {
         let entry = guard.entry(key.to_string());
         match entry {
             Entry::Occupied(mut o) => {
-                if !matches!(o.get(), _value) {
-                    return KeyResult::Error(
-                        "WRONGTYPE Operation against a key holding the wrong kind of value"
-                            .to_string(),
-                    );
-                }
-                o.insert(value);
+                self.grow(usage(key, &value));
+                let old = o.insert(value);
+                self.shrink(usage(key, &old));
                 KeyResult::Updated
             }
             Entry::Vacant(v) => {
+                self.grow(usage(key, &value));
                 v.insert(value);
                 KeyResult::Created
             }
         }
     }
 
+    /// Creates a new key. Callers only get here after failing to fetch the key with the type they
+    /// expect, so an existing key means it holds another type.
     pub async fn create(&self, key: &String, value: RedisType) -> KeyResult {
         let mut guard = self.data.lock().await;
         let entry = guard.entry(key.to_string());
         match entry {
-            Entry::Occupied(_) => KeyResult::Error("Key already exists".to_string()),
+            Entry::Occupied(_) => KeyResult::Error(RedisError::WrongType),
             Entry::Vacant(v) => {
+                self.grow(usage(key, &value));
                 v.insert(value);
                 KeyResult::Created
             }
         }
     }
 
+    /// Sets a key whatever it held before, e.g. when loading a snapshot.
+    pub async fn insert(&self, key: String, value: RedisType) {
+        self.grow(usage(&key, &value));
+        if let Some(old) = self.data.lock().await.insert(key.clone(), value) {
+            self.shrink(usage(&key, &old));
+        }
+    }
+
+    /// Drops every key, e.g. for `FLUSHALL` or before loading a master's snapshot.
+    pub async fn clear(&self) {
+        let mut data = self.data.lock().await;
+        data.clear();
+        self.used_memory.store(0, Ordering::SeqCst);
+        drop(data);
+        let keys: Vec<String> = self.watchers().keys().cloned().collect();
+        for key in keys {
+            self.touch_watched(&key);
+        }
+    }
+
+    pub async fn remove(&self, key: &str) -> Option<RedisType> {
+        let removed = self.data.lock().await.remove(key);
+        if let Some(value) = &removed {
+            self.shrink(usage(key, value));
+        }
+        self.touch_watched(key);
+        removed
+    }
+
+    /// A copy of the whole dataset, to serialize without holding it.
+    pub async fn snapshot(&self) -> HashMap<String, RedisType> {
+        self.data.lock().await.clone()
+    }
+
     pub async fn get_key(&self, key: &String) -> Option<MappedMutexGuard<'_, RedisType>> {
         let guard = self.data.lock().await;
         MutexGuard::try_map(guard, |map| match map.get_mut(key) {
@@ -90,7 +144,7 @@ impl RedisStore {
         .ok()
     }
 
-    pub async fn get_list(&self, key: &String) -> Option<MappedMutexGuard<'_, VecDeque<String>>> {
+    pub async fn get_list(&self, key: &String) -> Option<MappedMutexGuard<'_, VecDeque<Bytes>>> {
         let guard = self.data.lock().await;
         MutexGuard::try_map(guard, |map| match map.get_mut(key) {
             Some(RedisType::List(list)) => Some(list),
@@ -117,6 +171,72 @@ impl RedisStore {
         .ok()
     }
 
+    /// Removes keys chosen by a `maxmemory-policy` until the dataset fits in `maxmemory`.
+    /// Returns the number of evicted keys, or `None` if it still doesn't fit: the policy is
+    /// `noeviction` or there are no candidates left. Access times aren't tracked, so the LRU and
+    /// LFU policies pick keys in arbitrary order like the random ones.
+    pub async fn evict(&self, maxmemory: usize, policy: &str) -> Option<usize> {
+        if self.used_memory() <= maxmemory {
+            return Some(0);
+        }
+        let mut guard = self.data.lock().await;
+        let mut used = self.used_memory();
+        if used <= maxmemory {
+            return Some(0);
+        }
+        if policy == "noeviction" {
+            return None;
+        }
+
+        let volatile = policy.starts_with("volatile-");
+        let mut candidates: Vec<(String, u128)> = guard
+            .iter()
+            .filter_map(|(key, value)| match value {
+                RedisType::String(KeyValue {
+                    expired_at_millis: Some(at),
+                    ..
+                }) => Some((key.clone(), *at)),
+                _ if !volatile => Some((key.clone(), u128::MAX)),
+                _ => None,
+            })
+            .collect();
+        if policy == "volatile-ttl" {
+            // Popped from the end, so the closest expire goes first.
+            candidates.sort_by_key(|(_, at)| std::cmp::Reverse(*at));
+        }
+
+        let mut evicted = 0;
+        while used > maxmemory {
+            let (key, _) = candidates.pop()?;
+            if let Some(value) = guard.remove(&key) {
+                used -= usage(&key, &value);
+                self.shrink(usage(&key, &value));
+                evicted += 1;
+                self.touch_watched(&key);
+            }
+        }
+        Some(evicted)
+    }
+
+    pub fn used_memory(&self) -> usize {
+        self.used_memory.load(Ordering::SeqCst)
+    }
+
+    /// Records that a value grew in place, through the guard of `get_list` or
+    /// `get_sorted_set`.
+    pub fn grow(&self, bytes: usize) {
+        self.used_memory.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    /// Records that a value shrank in place, like `grow`.
+    pub fn shrink(&self, bytes: usize) {
+        let _ = self
+            .used_memory
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used.saturating_sub(bytes))
+            });
+    }
+
     pub async fn subscribe_to_key(&self, key: &String) -> Receiver<()> {
         let mut notifiers_guard = self.key_notifiers.lock().await;
         let sender = notifiers_guard
@@ -125,10 +245,74 @@ impl RedisStore {
         sender.subscribe()
     }
 
+    /// Wakes the clients blocked on `key` and fails the transactions watching it.
     pub async fn notify_key_modified(&self, key: &String) {
+        self.touch_watched(key);
+        if let Some(keys) = self.deferred_wakeups().as_mut() {
+            keys.insert(key.clone());
+            return;
+        }
         let notifiers_guard = self.key_notifiers.lock().await;
         if let Some(sender) = notifiers_guard.get(key) {
             let _ = sender.send(());
         }
     }
+
+    fn deferred_wakeups(&self) -> std::sync::MutexGuard<'_, Option<HashSet<String>>> {
+        self.deferred_wakeups
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Holds back the wakeups of blocked clients until `resume_wakeups`, so that they don't
+    /// run in the middle of a transaction or a script.
+    pub fn defer_wakeups(&self) {
+        self.deferred_wakeups().get_or_insert_with(HashSet::new);
+    }
+
+    /// Wakes the clients blocked on the keys modified since `defer_wakeups`.
+    pub async fn resume_wakeups(&self) {
+        let keys = self.deferred_wakeups().take().unwrap_or_default();
+        for key in keys {
+            self.notify_key_modified(&key).await;
+        }
+    }
+
+    fn watchers(&self) -> std::sync::MutexGuard<'_, HashMap<String, Vec<Weak<AtomicBool>>>> {
+        self.watchers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn watch(&self, key: &str, touched: &Arc<AtomicBool>) {
+        let mut watchers = self.watchers();
+        let flags = watchers.entry(key.to_string()).or_default();
+        flags.retain(|flag| flag.strong_count() > 0);
+        flags.push(Arc::downgrade(touched));
+    }
+
+    pub fn unwatch(&self, key: &str, touched: &Arc<AtomicBool>) {
+        let mut watchers = self.watchers();
+        if let Some(flags) = watchers.get_mut(key) {
+            flags.retain(|flag| flag.strong_count() > 0 && flag.as_ptr() != Arc::as_ptr(touched));
+            if flags.is_empty() {
+                watchers.remove(key);
+            }
+        }
+    }
+
+    fn touch_watched(&self, key: &str) {
+        if let Some(flags) = self.watchers().get(key) {
+            for flag in flags.iter().filter_map(Weak::upgrade) {
+                flag.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Whether `key` holds a value that expired after `since`, in Unix milliseconds: a watched
+    /// key that expired counts as changed.
+    pub async fn expired_since(&self, key: &String, since: u128) -> bool {
+        match self.get_key_value(key).await {
+            Some(kv) => kv.is_expired() && kv.expired_at_millis.is_some_and(|at| at > since),
+            None => false,
+        }
+    }
 }
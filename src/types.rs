@@ -1,4 +1,6 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bytes::Bytes;
 
 use crate::{
     resp::RespDataType,
@@ -9,29 +11,51 @@ pub mod key_value;
 pub mod sorted_set;
 pub mod stream;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum RedisType {
     None,
     String(KeyValue),
-    List(VecDeque<String>),
+    List(VecDeque<Bytes>),
     ZSet(SortedSet),
-    // Set,
-    // Hash,
+    Set(HashSet<Bytes>),
+    Hash(HashMap<Bytes, Bytes>),
     Stream(RedisStream),
     // VectorSet,
 }
 
 impl RedisType {
     pub fn to_type_resp(&self) -> RespDataType {
+        RespDataType::simple_string(self.type_name())
+    }
+
+    /// The name `TYPE` replies with.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            RedisType::None => "none",
+            RedisType::String(_) => "string",
+            RedisType::List(_) => "list",
+            RedisType::ZSet(_) => "zset",
+            RedisType::Stream(_) => "stream",
+            RedisType::Set(_) => "set",
+            RedisType::Hash(_) => "hash",
+            // DataType::VectorSet => "vectorset",
+        }
+    }
+
+    /// Rough number of bytes held by the value. Only meant to compare against `maxmemory`,
+    /// allocator overhead is not accounted for.
+    pub fn memory_usage(&self) -> usize {
         match self {
-            RedisType::None => RespDataType::simple_string("none"),
-            RedisType::String(_) => RespDataType::simple_string("string"),
-            RedisType::List(_) => RespDataType::simple_string("list"),
-            RedisType::ZSet(_) => RespDataType::simple_string("zset"),
-            RedisType::Stream(_) => RespDataType::simple_string("stream"),
-            // DataType::Set => RespDataType::simple_string("set"),
-            // DataType::Hash => RespDataType::simple_string("hash"),
-            // DataType::VectorSet => RespDataType::simple_string("vectorset"),
+            RedisType::None => 0,
+            RedisType::String(kv) => kv.value.len(),
+            RedisType::List(list) => list.iter().map(|item| item.len()).sum(),
+            RedisType::ZSet(set) => set.memory_usage(),
+            RedisType::Stream(stream) => stream.memory_usage(),
+            RedisType::Set(set) => set.iter().map(|member| member.len()).sum(),
+            RedisType::Hash(hash) => hash
+                .iter()
+                .map(|(field, value)| field.len() + value.len())
+                .sum(),
         }
     }
 }
@@ -1,10 +1,12 @@
 use std::vec::IntoIter;
 
-use crate::{commands::traits::ParseableCommand, resp::RespDataType, utils};
+use bytes::Bytes;
+
+use crate::{commands::traits::ParseableCommand, error::RedisError, resp::RespDataType, utils};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct KeyValue {
-    pub value: String,
+    pub value: Bytes,
     pub expired_at_millis: Option<u128>,
 }
 
@@ -18,36 +20,36 @@ impl KeyValue {
 }
 
 impl ParseableCommand for KeyValue {
-    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, String>
+    fn parse(args: &mut IntoIter<RespDataType>) -> Result<Self, RedisError>
     where
         Self: Sized,
     {
-        let value = args
-            .next()
-            .ok_or_else(|| "Expected a value for RedisKeyValue".to_string())?;
+        let value = args.next().ok_or(RedisError::WrongArity("set"))?;
 
         let mut expired_at_millis: Option<u128> = None;
 
         while let Some(prop_name) = args.next().and_then(|p| p.to_string()) {
             match prop_name.to_ascii_uppercase().as_str() {
                 "PX" => {
-                    let ttl_arg = args
-                        .next()
-                        .and_then(|p| p.to_string())
-                        .ok_or("PX option requires a value")?;
-                    let ttl_value: u128 = ttl_arg
-                        .parse()
-                        .map_err(|e| format!("Cannot parse PX value: {}", e))?;
-                    expired_at_millis = Some(utils::now_millis() + ttl_value);
+                    let ttl_value = Self::get_arg_as_int(args, RedisError::Syntax)?;
+                    if ttl_value <= 0 {
+                        return Err(RedisError::InvalidExpireTime("set"));
+                    }
+                    expired_at_millis = Some(utils::now_millis() + ttl_value as u128);
+                }
+                "PXAT" => {
+                    let at = Self::get_arg_as_int(args, RedisError::Syntax)?;
+                    if at <= 0 {
+                        return Err(RedisError::InvalidExpireTime("set"));
+                    }
+                    expired_at_millis = Some(at as u128);
                 }
                 _ => (),
             }
         }
-        let value_str = value
-            .to_string()
-            .ok_or("Expected a string value for RedisKeyValue")?;
+        let value_bytes = value.into_bytes().ok_or(RedisError::Syntax)?;
         Ok(Self {
-            value: value_str,
+            value: value_bytes,
             expired_at_millis,
         })
     }
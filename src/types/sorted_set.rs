@@ -5,9 +5,11 @@ use std::{
     vec::IntoIter,
 };
 
+use bytes::Bytes;
+
 #[derive(Debug, Clone)]
 pub struct SortedValue {
-    pub member: String,
+    pub member: Bytes,
     pub score: f64,
 }
 
@@ -33,10 +35,10 @@ impl Ord for SortedValue {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct SortedSet {
     set: BTreeSet<SortedValue>,
-    map: BTreeMap<String, SortedValue>,
+    map: BTreeMap<Bytes, SortedValue>,
 }
 
 impl SortedSet {
@@ -59,7 +61,7 @@ impl SortedSet {
     }
 
     /// Remove pelo membro, retorna a quantidade de membros removidos
-    pub fn remove_by_member(&mut self, member: &String) -> i64 {
+    pub fn remove_by_member(&mut self, member: &[u8]) -> i64 {
         let mut count = 0;
         if let Some(old_value) = self.map.remove(member) {
             count = 1;
@@ -69,7 +71,7 @@ impl SortedSet {
     }
 
     /// Retorna o lugar no ranking do membro
-    pub fn get_rank_by_member(&self, member: &String) -> Option<i64> {
+    pub fn get_rank_by_member(&self, member: &[u8]) -> Option<i64> {
         if let Some(value) = self.map.get(member) {
             // TODO - não escala muito bem mas não vou mexer para não complicar no momento
             return self
@@ -85,11 +87,29 @@ impl SortedSet {
         return self.map.len() as i64;
     }
 
+    /// Percorre os membros em ordem de score
+    pub fn iter(&self) -> Iter<'_, SortedValue> {
+        return self.set.iter();
+    }
+
     pub fn range(&self, start: usize, end: usize) -> Take<Skip<Iter<'_, SortedValue>>> {
         return self.set.iter().skip(start).take(end - start + 1);
     }
 
-    pub fn get_score_by_member(&self, member: &String) -> Option<f64> {
+    /// Estimativa de bytes usados: cada membro aparece no set e no map
+    pub fn memory_usage(&self) -> usize {
+        self.map
+            .keys()
+            .map(|member| Self::member_usage(member))
+            .sum()
+    }
+
+    /// Estimativa de bytes usados por um membro
+    pub fn member_usage(member: &[u8]) -> usize {
+        2 * (member.len() + std::mem::size_of::<SortedValue>())
+    }
+
+    pub fn get_score_by_member(&self, member: &[u8]) -> Option<f64> {
         if let Some(value) = self.map.get(member) {
             return Some(value.score);
         }
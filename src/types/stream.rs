@@ -9,6 +9,10 @@ impl StreamEntry {
     pub fn new(values: HashMap<String, String>) -> Self {
         Self { values }
     }
+
+    pub fn values(&self) -> &HashMap<String, String> {
+        &self.values
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -26,4 +30,23 @@ impl RedisStream {
     pub fn add_entry(&mut self, key: String, entry: StreamEntry) {
         self.entries.insert(key, entry);
     }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &StreamEntry)> {
+        self.entries.iter()
+    }
+
+    /// Rough number of bytes held by the entries, for `maxmemory` accounting.
+    pub fn memory_usage(&self) -> usize {
+        self.entries
+            .iter()
+            .map(|(id, entry)| {
+                id.len()
+                    + entry
+                        .values
+                        .iter()
+                        .map(|(field, value)| field.len() + value.len())
+                        .sum::<usize>()
+            })
+            .sum()
+    }
 }
@@ -1,4 +1,9 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 pub fn now_millis() -> u128 {
     SystemTime::now()
@@ -6,3 +11,210 @@ pub fn now_millis() -> u128 {
         .expect("Problem with time!")
         .as_millis()
 }
+
+/// Glob-style matching with the same rules as Redis `stringmatchlen`: `*`, `?`, `[...]` classes
+/// (with `^` negation and `a-z` ranges) and `\` to escape the next character.
+///
+/// On a mismatch it only backtracks to the last `*`, letting it match one more character, so
+/// that patterns with many stars take `O(pattern * string)` steps rather than exponential ones.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Where the pattern resumes after the last star, and where the string does.
+    let mut star = None;
+    loop {
+        if pattern.get(p) == Some(&b'*') {
+            while pattern.get(p) == Some(&b'*') {
+                p += 1;
+            }
+            if p == pattern.len() {
+                return true;
+            }
+            star = Some((p, s));
+            continue;
+        }
+        match string.get(s) {
+            Some(&c) if p < pattern.len() => {
+                if let Some(next) = glob_match_one(pattern, p, c, nocase) {
+                    p = next;
+                    s += 1;
+                    continue;
+                }
+            }
+            Some(_) => (),
+            None if p == pattern.len() => return true,
+            None => (),
+        }
+        match star {
+            Some((star_p, star_s)) if star_s < string.len() => {
+                star = Some((star_p, star_s + 1));
+                p = star_p;
+                s = star_s + 1;
+            }
+            _ => return false,
+        }
+    }
+}
+
+/// Matches `c` against the part of the pattern at `p` that isn't a star, returning where the
+/// pattern goes on if it does.
+fn glob_match_one(pattern: &[u8], mut p: usize, c: u8, nocase: bool) -> Option<usize> {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    let matched = match pattern[p] {
+        b'?' => true,
+        b'[' => {
+            p += 1;
+            let negate = pattern.get(p) == Some(&b'^');
+            if negate {
+                p += 1;
+            }
+            let mut matched = false;
+            while p < pattern.len() && pattern[p] != b']' {
+                if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                    p += 1;
+                    matched |= eq(pattern[p], c);
+                } else if pattern.get(p + 1) == Some(&b'-') && p + 2 < pattern.len() {
+                    let (mut start, mut end) = (pattern[p], pattern[p + 2]);
+                    if start > end {
+                        std::mem::swap(&mut start, &mut end);
+                    }
+                    let c = if nocase { c.to_ascii_lowercase() } else { c };
+                    let (start, end) = if nocase {
+                        (start.to_ascii_lowercase(), end.to_ascii_lowercase())
+                    } else {
+                        (start, end)
+                    };
+                    matched |= (start..=end).contains(&c);
+                    p += 2;
+                } else {
+                    matched |= eq(pattern[p], c);
+                }
+                p += 1;
+            }
+            // Like Redis, an unterminated class ends with the pattern.
+            p = p.min(pattern.len() - 1);
+            matched != negate
+        }
+        b'\\' if p + 1 < pattern.len() => {
+            p += 1;
+            eq(pattern[p], c)
+        }
+        other => eq(other, c),
+    };
+    matched.then_some(p + 1)
+}
+
+/// Formats a UTC timestamp the way Redis prefixes its log lines: `16 Oct 2026 14:03:09.512`.
+pub fn format_log_time(millis: u128) -> String {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let seconds = (millis / 1000) as i64;
+    let (days, time) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    // Days since the epoch to a civil date, from Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:02} {} {} {:02}:{:02}:{:02}.{:03}",
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        millis % 1000
+    )
+}
+
+/// Writes `data` to `path` without ever leaving a partial file there: it goes to a temporary
+/// file in the same directory first, synced, then renamed over `path`.
+pub fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!("temp-{}-{}", std::process::id(), name));
+    let result = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_atomically() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let path = dir.join("dump.rdb");
+        fs::write(&path, b"old").unwrap();
+
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+
+        assert!(write_atomically(&dir.join("missing").join("dump.rdb"), b"new").is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b"anything", false));
+        assert!(glob_match(b"z*", b"zadd", false));
+        assert!(!glob_match(b"z*", b"get", false));
+        assert!(glob_match(b"h?llo", b"hello", false));
+        assert!(!glob_match(b"h?llo", b"hllo", false));
+        assert!(glob_match(b"h[ae]llo", b"hallo", false));
+        assert!(!glob_match(b"h[^e]llo", b"hello", false));
+        assert!(glob_match(b"h[a-f]llo", b"hcllo", false));
+        assert!(glob_match(b"max*memory*", b"maxmemory-policy", false));
+        assert!(glob_match(b"a\\*b", b"a*b", false));
+        assert!(!glob_match(b"a\\*b", b"axb", false));
+        assert!(glob_match(b"GET", b"get", true));
+        assert!(!glob_match(b"GET", b"get", false));
+        assert!(glob_match(b"*a*b", b"xaxxb", false));
+        assert!(!glob_match(b"*a*b", b"xaxxbx", false));
+        assert!(glob_match(b"a*", b"a", false));
+        assert!(glob_match(b"[a-c]*[^x]", b"bxxy", false));
+        assert!(glob_match(b"h[ae", b"ha", false));
+        assert!(glob_match(b"a\\", b"a\\", false));
+        assert!(!glob_match(b"?", b"", false));
+
+        // Backtracking to every star would take forever here.
+        let pattern = b"a*".repeat(30);
+        assert!(glob_match(&pattern, &b"a".repeat(100), false));
+        assert!(!glob_match(
+            &[&pattern[..], b"b"].concat(),
+            &b"a".repeat(100),
+            false
+        ));
+    }
+
+    #[test]
+    fn test_format_log_time() {
+        assert_eq!(format_log_time(0), "01 Jan 1970 00:00:00.000");
+        assert_eq!(format_log_time(951_782_400_123), "29 Feb 2000 00:00:00.123");
+        assert_eq!(
+            format_log_time(1_792_159_389_512),
+            "16 Oct 2026 14:03:09.512"
+        );
+    }
+}